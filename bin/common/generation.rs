@@ -0,0 +1,60 @@
+//! Generation of chains from asynchronous servers and bots.
+
+use impersonate::cancel::CancellationToken;
+use impersonate::{Chain, Error};
+use std::panic;
+use std::time::Duration;
+
+/// Time given to a generation before cancelling it.
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Generate a chain on a blocking thread, so that the asynchronous runtime isn’t blocked meanwhile.
+///
+/// The generation is cancelled if it takes longer than `timeout`, such as [`TIMEOUT`], failing
+/// with [`Error::Cancelled`].
+pub async fn generate<F>(timeout: Duration, generate: F) -> Result<Chain, Error>
+where
+  F: FnOnce(&CancellationToken) -> Result<Chain, Error> + Send + 'static,
+{
+  let cancel = CancellationToken::new();
+  let token = cancel.clone();
+  let generation = tokio::task::spawn_blocking(move || generate(&token));
+
+  match tokio::time::timeout(timeout, generation).await {
+    Ok(Ok(chain)) => chain,
+    Ok(Err(err)) => panic::resume_unwind(err.into_panic()),
+    Err(_) => {
+      cancel.cancel();
+      Err(Error::Cancelled)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use impersonate::{ChainParameters, LearningParameters, MarkovChainGenerator};
+
+  #[tokio::test]
+  async fn test_generate() {
+    let learn_param = LearningParameters::new(1).unwrap();
+    let mut generator = MarkovChainGenerator::new();
+    generator.train(&learn_param, "hello world");
+
+    let chain = generate(TIMEOUT, move |cancel| {
+      generator.generate_chain_cancellable(&ChainParameters::new(), cancel)
+    })
+    .await;
+    assert_eq!(chain.unwrap().to_string(), "hello world");
+
+    // a generation that would never end is cancelled
+    let chain = generate(Duration::from_millis(10), |cancel| {
+      while !cancel.is_cancelled() {
+        std::thread::yield_now();
+      }
+      Err(Error::Cancelled)
+    })
+    .await;
+    assert!(matches!(chain, Err(Error::Cancelled)));
+  }
+}
@@ -0,0 +1,45 @@
+//! Inputs of the command line interfaces.
+
+use impersonate::trainers::compressed;
+use impersonate::Error;
+use std::env;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// Open an input, decompressing it if needed; - is the standard input.
+pub fn open(path: &Path) -> Result<Box<dyn Read>, Error> {
+  let reader = if path == Path::new("-") {
+    compressed::reader(io::stdin())?
+  } else {
+    compressed::reader(File::open(path)?)?
+  };
+
+  Ok(reader)
+}
+
+/// Expand the leading ~ and the glob patterns of a path.
+///
+/// Globs are expanded here too so that they work when quoted or on shells that don’t expand them.
+pub fn expand(path: &Path) -> Result<Vec<PathBuf>, Error> {
+  let path = path.to_string_lossy();
+  let path = match (path.strip_prefix("~/"), env::var("HOME")) {
+    (Some(rest), Ok(home)) => format!("{}/{}", home, rest),
+    _ => path.into_owned(),
+  };
+
+  if !path.contains(&['*', '?', '['][..]) {
+    return Ok(vec![PathBuf::from(path)]);
+  }
+
+  let paths = glob::glob(&path)
+    .map_err(|err| Error::InvalidParameters(format!("invalid glob {}: {}", path, err)))?
+    .filter_map(Result::ok)
+    .collect::<Vec<_>>();
+
+  if paths.is_empty() {
+    log::warn!("no file matching {}", path);
+  }
+
+  Ok(paths)
+}
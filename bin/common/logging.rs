@@ -0,0 +1,22 @@
+//! Logging of the command line interfaces.
+
+use log::LevelFilter;
+
+/// Print the logs of the library to stderr.
+///
+/// Only warnings, errors and the main steps are printed by default; every occurrence of
+/// `verbose` shows more details, while `quiet` only shows errors.
+pub fn init(verbose: u8, quiet: bool) {
+  let level = match (quiet, verbose) {
+    (true, _) => LevelFilter::Error,
+    (false, 0) => LevelFilter::Info,
+    (false, 1) => LevelFilter::Debug,
+    (false, _) => LevelFilter::Trace,
+  };
+
+  env_logger::Builder::new()
+    .filter_level(level)
+    .format_timestamp(None)
+    .format_target(false)
+    .init();
+}
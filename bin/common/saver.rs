@@ -0,0 +1,96 @@
+//! Saving of the personas of the bots.
+
+use impersonate::checkpoint::write_atomically;
+use impersonate::persona::PersonaSet;
+use impersonate::Error;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinHandle;
+
+/// Saves snapshots of personas on blocking threads, so that writing large models doesn’t block the
+/// asynchronous runtime.
+///
+/// Snapshots are numbered in the order they are given: a snapshot that finishes being written after
+/// a newer one isn’t written, so that the model always holds the latest snapshot. The model is only
+/// replaced once a snapshot is entirely written, so that a crash while saving doesn’t truncate it.
+pub struct Saver {
+  path: PathBuf,
+  /// Number of the last snapshot given.
+  snapshots: AtomicU64,
+  /// Number of the last snapshot written, locked while writing.
+  written: Arc<Mutex<u64>>,
+}
+
+impl Saver {
+  /// Save personas to `path`.
+  pub fn new(path: PathBuf) -> Self {
+    Self {
+      path,
+      snapshots: AtomicU64::new(0),
+      written: Arc::new(Mutex::new(0)),
+    }
+  }
+
+  /// Save personas, such as a snapshot taken with [`OnlineTrainer::snapshot`].
+  ///
+  /// [`OnlineTrainer::snapshot`]: impersonate::online::OnlineTrainer::snapshot
+  pub fn save(&self, personas: PersonaSet) -> JoinHandle<Result<(), Error>> {
+    let snapshot = self.snapshots.fetch_add(1, Ordering::SeqCst) + 1;
+    let path = self.path.clone();
+    let written = self.written.clone();
+
+    tokio::task::spawn_blocking(move || {
+      let mut written = written.lock().unwrap();
+      if *written > snapshot {
+        return Ok(());
+      }
+
+      write_atomically(&path, |writer| personas.save(writer))?;
+      *written = snapshot;
+      log::info!("saved {} personas", personas.len());
+      Ok(())
+    })
+  }
+
+  /// Save personas in the background, only reporting errors.
+  pub fn save_in_background(&self, personas: PersonaSet) {
+    let saved = self.save(personas);
+
+    tokio::spawn(async move {
+      match saved.await {
+        Ok(Ok(())) => (),
+        Ok(Err(err)) => log::error!("cannot save the model: {}", err),
+        Err(err) => log::error!("cannot save the model: {}", err),
+      }
+    });
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use impersonate::LearningParameters;
+  use std::env;
+  use std::fs::{self, File};
+
+  #[tokio::test]
+  async fn test_saver() {
+    let path = env::temp_dir().join(format!("impersonate-saver-{}.json", std::process::id()));
+    let saver = Saver::new(path.clone());
+    let learn_param = LearningParameters::new(1).unwrap();
+    let mut personas = PersonaSet::new();
+
+    personas.train("alice", &learn_param, "hello");
+    let first = saver.save(personas.clone());
+    personas.train("bob", &learn_param, "hi");
+    let second = saver.save(personas.clone());
+    second.await.unwrap().unwrap();
+    first.await.unwrap().unwrap();
+
+    // whichever snapshot was written last, the latest one is saved
+    let saved = PersonaSet::load(File::open(&path).unwrap()).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(saved, personas);
+  }
+}
@@ -0,0 +1,308 @@
+use impersonate::cleanup::{Cleanup, URL_PLACEHOLDER};
+use impersonate::online::OnlineTrainer;
+use impersonate::persona::PersonaSet;
+use impersonate::{Chain, ChainParameters, Error, LearningParameters};
+use saver::Saver;
+use serenity::all::{
+  ChannelType, Client, CommandInteraction, CommandOptionType, Context, CreateAllowedMentions,
+  CreateCommand, CreateCommandOption, CreateInteractionResponse, CreateInteractionResponseMessage,
+  EventHandler, GatewayIntents, GetMessages, GuildId, Interaction, Message, Ready, ResolvedValue,
+};
+use serenity::async_trait;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, Mutex};
+use structopt::StructOpt;
+
+#[path = "../common/generation.rs"]
+mod generation;
+#[path = "../common/logging.rs"]
+mod logging;
+#[path = "../common/saver.rs"]
+mod saver;
+
+/// Maximum number of characters of a reply; Discord messages are limited to 2000.
+const MAX_REPLY_CHARS: usize = 1900;
+
+/// Number of messages fetched by history requests, the maximum allowed by Discord.
+const HISTORY_PAGE: u8 = 100;
+
+#[derive(Debug, StructOpt)]
+/// A Discord bot learning how the members of a guild talk and impersonating them.
+///
+/// The history of the text channels of the guild is learned at startup, then every new message,
+/// into the persona of its author. The `/impersonate` slash command makes the bot answer with a
+/// string impersonating a member.
+///
+/// The bot requires the message content privileged intent.
+struct CLIOpt {
+  #[structopt(long, env = "DISCORD_TOKEN", hide_env_values = true)]
+  /// Token of the bot.
+  token: String,
+
+  #[structopt(short, long)]
+  /// Identifier of the guild to learn from.
+  guild: u64,
+
+  #[structopt(short, long, default_value = "impersonate-discord.json")]
+  /// Model holding the personas, loaded at startup if it exists and saved regularly.
+  model: PathBuf,
+
+  #[structopt(short, long)]
+  /// Number of words to use to form a wording while learning.
+  ///
+  /// Defaults to the size the personas were trained with, or 2 for a new model.
+  learning_size: Option<usize>,
+
+  #[structopt(long, default_value = "0")]
+  /// Number of past messages to learn from every text channel at startup.
+  history: usize,
+
+  #[structopt(long, default_value = "100")]
+  /// Save the model every that many learned messages.
+  save_every: usize,
+
+  #[structopt(long)]
+  /// Replace URLs with a <url> placeholder.
+  replace_urls: bool,
+
+  #[structopt(short, long, parse(from_occurrences))]
+  /// Show more logs; repeat for even more details.
+  verbose: u8,
+
+  #[structopt(short, long, conflicts_with = "verbose")]
+  /// Only show errors.
+  quiet: bool,
+}
+
+struct Handler {
+  guild: GuildId,
+  saver: Arc<Saver>,
+  history: usize,
+  save_every: usize,
+  trainer: Arc<Mutex<OnlineTrainer>>,
+}
+
+impl Handler {
+  /// Learn a message into the persona of its author, saving the model if needed.
+  fn learn(&self, message: &Message) {
+    if message.author.bot {
+      return;
+    }
+
+    let mut trainer = self.trainer.lock().unwrap();
+    trainer.feed_line(&message.author.id.to_string(), &message.content);
+
+    // the snapshot is taken while locked, so that snapshots are saved in order
+    if trainer.unsaved() >= self.save_every {
+      self.saver.save_in_background(trainer.snapshot());
+    }
+  }
+
+  /// Learn the last messages of every text channel of the guild.
+  async fn learn_history(&self, ctx: &Context) -> Result<(), serenity::Error> {
+    for channel in self.guild.channels(&ctx.http).await?.into_values() {
+      if channel.kind != ChannelType::Text {
+        continue;
+      }
+
+      let mut learned = 0;
+      let mut before = None;
+
+      while learned < self.history {
+        let request = GetMessages::new().limit(HISTORY_PAGE);
+        let request = match before {
+          Some(before) => request.before(before),
+          None => request,
+        };
+
+        let messages = channel.messages(&ctx.http, request).await?;
+        for message in messages.iter().take(self.history - learned) {
+          self.learn(message);
+        }
+
+        learned = (learned + messages.len()).min(self.history);
+        before = match messages.last() {
+          Some(message) if messages.len() == usize::from(HISTORY_PAGE) => Some(message.id),
+          _ => break,
+        };
+      }
+
+      log::info!("learned {} messages from #{}", learned, channel.name);
+    }
+
+    Ok(())
+  }
+
+  /// Answer the `/impersonate` command.
+  async fn impersonate(&self, ctx: &Context, command: &CommandInteraction) {
+    let user = command
+      .data
+      .options()
+      .into_iter()
+      .find_map(|option| match option.value {
+        ResolvedValue::User(user, _) => Some(user.clone()),
+        _ => None,
+      });
+    let user = match user {
+      Some(user) => user,
+      None => return,
+    };
+
+    let chain_param = ChainParameters::new().max_chars(MAX_REPLY_CHARS);
+    let chain = generate(self.trainer.clone(), user.id.to_string(), chain_param).await;
+    let answer = match chain {
+      Ok(chain) => format!("**{}**: {}", user.name, chain),
+      Err(Error::UnknownAuthor(_)) => format!("I don’t know {} yet.", user.name),
+      Err(err) => {
+        log::warn!("cannot impersonate {}: {}", user.name, err);
+        format!("I cannot impersonate {}.", user.name)
+      }
+    };
+
+    // generated text may contain mentions, which must not ping anyone
+    let message = CreateInteractionResponseMessage::new()
+      .content(answer)
+      .allowed_mentions(CreateAllowedMentions::new());
+    if let Err(err) = command
+      .create_response(&ctx.http, CreateInteractionResponse::Message(message))
+      .await
+    {
+      log::warn!("cannot answer: {}", err);
+    }
+  }
+}
+
+#[async_trait]
+impl EventHandler for Handler {
+  async fn ready(&self, ctx: Context, ready: Ready) {
+    log::info!("connected as {}", ready.user.name);
+
+    let command = CreateCommand::new("impersonate")
+      .description("Say something the way a member would")
+      .add_option(
+        CreateCommandOption::new(CommandOptionType::User, "user", "Member to impersonate")
+          .required(true),
+      );
+    if let Err(err) = self.guild.set_commands(&ctx.http, vec![command]).await {
+      log::error!("cannot register the /impersonate command: {}", err);
+    }
+
+    if self.history > 0 {
+      if let Err(err) = self.learn_history(&ctx).await {
+        log::error!("cannot learn the history of the guild: {}", err);
+      }
+    }
+  }
+
+  async fn message(&self, _: Context, message: Message) {
+    // messages fetched from the history have no guild, but live ones do
+    if message.guild_id == Some(self.guild) {
+      self.learn(&message);
+    }
+  }
+
+  async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+    if let Interaction::Command(command) = interaction {
+      if command.data.name == "impersonate" {
+        self.impersonate(&ctx, &command).await;
+      }
+    }
+  }
+}
+
+/// Generate a chain impersonating an author, on a blocking thread.
+///
+/// The persona is copied so that messages are learned meanwhile.
+async fn generate(
+  trainer: Arc<Mutex<OnlineTrainer>>,
+  author: String,
+  chain_param: ChainParameters,
+) -> Result<Chain, Error> {
+  generation::generate(generation::TIMEOUT, move |cancel| {
+    let persona = trainer.lock().unwrap().personas().get(&author).cloned();
+    persona
+      .ok_or(Error::UnknownAuthor(author))?
+      .generate_chain_cancellable(&chain_param, cancel)
+  })
+  .await
+}
+
+/// Load the personas, or create an empty set if the model doesn’t exist yet.
+fn load_or_new(path: &Path) -> Result<PersonaSet, Error> {
+  if path.exists() {
+    PersonaSet::load(BufReader::new(File::open(path)?))
+  } else {
+    Ok(PersonaSet::new())
+  }
+}
+
+/// Learning parameters of the personas, unless another learning size is given.
+fn learning_parameters(
+  personas: &PersonaSet,
+  learning_size: Option<usize>,
+) -> Result<LearningParameters, Error> {
+  match (learning_size, personas.learning_parameters()) {
+    (None, Some(learn_param)) => Ok(learn_param.clone()),
+    (size, _) => LearningParameters::new(size.unwrap_or(2)),
+  }
+}
+
+async fn run(opt: CLIOpt) -> Result<(), Box<dyn std::error::Error>> {
+  let cleanup = if opt.replace_urls {
+    Cleanup::new().replace_urls(URL_PLACEHOLDER)
+  } else {
+    Cleanup::new()
+  };
+  let personas = load_or_new(&opt.model)?;
+  let learn_param = learning_parameters(&personas, opt.learning_size)?;
+  let trainer = Arc::new(Mutex::new(
+    OnlineTrainer::new(personas, learn_param)?.cleanup(cleanup),
+  ));
+
+  let saver = Arc::new(Saver::new(opt.model.clone()));
+
+  let handler = Handler {
+    guild: GuildId::new(opt.guild),
+    saver: saver.clone(),
+    history: opt.history,
+    save_every: opt.save_every,
+    trainer: trainer.clone(),
+  };
+
+  let intents =
+    GatewayIntents::GUILDS | GatewayIntents::GUILD_MESSAGES | GatewayIntents::MESSAGE_CONTENT;
+  let mut client = Client::builder(&opt.token, intents)
+    .event_handler(handler)
+    .await?;
+
+  tokio::select! {
+    result = client.start() => result?,
+    result = tokio::signal::ctrl_c() => result?,
+  }
+
+  // learned messages are saved whatever the reason of the disconnection
+  let personas = trainer.lock().unwrap().snapshot();
+  saver.save(personas).await??;
+  Ok(())
+}
+
+fn main() {
+  let opt = CLIOpt::from_args();
+  logging::init(opt.verbose, opt.quiet);
+
+  let runtime = match tokio::runtime::Runtime::new() {
+    Ok(runtime) => runtime,
+    Err(err) => {
+      log::error!("{}", err);
+      process::exit(1);
+    }
+  };
+
+  if let Err(err) = runtime.block_on(run(opt)) {
+    log::error!("{}", err);
+    process::exit(1);
+  }
+}
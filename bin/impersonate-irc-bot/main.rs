@@ -0,0 +1,369 @@
+use impersonate::checkpoint::write_atomically;
+use impersonate::cleanup::{Cleanup, URL_PLACEHOLDER};
+use impersonate::online::OnlineTrainer;
+use impersonate::persona::PersonaSet;
+use impersonate::{ChainParameters, Error, LearningParameters};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, Instant};
+use structopt::StructOpt;
+
+#[path = "../common/logging.rs"]
+mod logging;
+
+/// Maximum number of characters of a reply, so that it fits in a single IRC message.
+const MAX_REPLY_CHARS: usize = 400;
+
+#[derive(Debug, StructOpt)]
+/// An IRC bot learning how people talk and impersonating them.
+///
+/// Every message of the joined channels is learned into the persona of its author. Sending
+/// `!impersonate <nick>` makes the bot answer with a string impersonating <nick>.
+struct CLIOpt {
+  #[structopt(short, long)]
+  /// Server to connect to, such as irc.libera.chat:6667; TLS is not supported.
+  server: String,
+
+  #[structopt(short, long, default_value = "impersonate")]
+  /// Nick of the bot.
+  nick: String,
+
+  #[structopt(short, long = "channel", number_of_values = 1, required = true)]
+  /// Channel to join; repeat for several channels.
+  channels: Vec<String>,
+
+  #[structopt(short, long, default_value = "impersonate-irc.json")]
+  /// Model holding the personas, loaded at startup if it exists and saved regularly.
+  model: PathBuf,
+
+  #[structopt(short, long)]
+  /// Number of words to use to form a wording while learning.
+  ///
+  /// Defaults to the size the personas were trained with, or 2 for a new model.
+  learning_size: Option<usize>,
+
+  #[structopt(long, default_value = "100")]
+  /// Save the model every that many learned messages.
+  save_every: usize,
+
+  #[structopt(long, default_value = "10")]
+  /// Minimum number of seconds between two answers, to avoid flooding.
+  cooldown: u64,
+
+  #[structopt(long, default_value = "!impersonate")]
+  /// Command asking for an impersonation.
+  command: String,
+
+  #[structopt(long)]
+  /// Replace URLs with a <url> placeholder.
+  replace_urls: bool,
+
+  #[structopt(long)]
+  /// Remove leading mentions (nick: ).
+  strip_mentions: bool,
+
+  #[structopt(short, long, parse(from_occurrences))]
+  /// Show more logs; repeat for even more details.
+  verbose: u8,
+
+  #[structopt(short, long, conflicts_with = "verbose")]
+  /// Only show errors.
+  quiet: bool,
+}
+
+/// A message received from the server.
+#[derive(Debug)]
+struct Message<'a> {
+  /// Nick of the sender, if any.
+  nick: Option<&'a str>,
+  command: &'a str,
+  params: Vec<&'a str>,
+}
+
+impl<'a> Message<'a> {
+  /// Parse a line sent by the server, without its line terminator.
+  fn parse(line: &'a str) -> Option<Self> {
+    let (prefix, rest) = match line.strip_prefix(':') {
+      Some(line) => {
+        let (prefix, rest) = line.split_once(' ')?;
+        (Some(prefix), rest)
+      }
+      None => (None, line),
+    };
+    let nick = prefix.map(|prefix| prefix.split('!').next().unwrap_or(prefix));
+
+    let (rest, trailing) = match rest.split_once(" :") {
+      Some((rest, trailing)) => (rest, Some(trailing)),
+      None => (rest, None),
+    };
+    let mut words = rest.split(' ').filter(|word| !word.is_empty());
+    let command = words.next()?;
+    let params = words.chain(trailing).collect();
+
+    Some(Self {
+      nick,
+      command,
+      params,
+    })
+  }
+}
+
+/// Arguments of a command sent in a message, if the message is this command.
+///
+/// The command must be followed by whitespace or end the message, so that `!impersonated` isn’t
+/// taken for `!impersonate`.
+fn command_arguments<'a>(text: &'a str, command: &str) -> Option<&'a str> {
+  let rest = text.strip_prefix(command)?;
+
+  if rest.is_empty() || rest.starts_with(char::is_whitespace) {
+    Some(rest.trim())
+  } else {
+    None
+  }
+}
+
+/// Connection to the server and state of the bot.
+struct Bot {
+  writer: BufWriter<TcpStream>,
+  opt: CLIOpt,
+  trainer: OnlineTrainer,
+  /// When the bot last answered.
+  last_answer: Option<Instant>,
+}
+
+impl Bot {
+  /// Send a line to the server.
+  fn send(&mut self, line: &str) -> Result<(), Error> {
+    log::trace!("> {}", line);
+    write!(self.writer, "{}\r\n", line)?;
+    self.writer.flush()?;
+    Ok(())
+  }
+
+  /// Save the model.
+  fn save(&mut self) -> Result<(), Error> {
+    let trainer = &mut self.trainer;
+    write_atomically(&self.opt.model, |writer| trainer.save(writer))?;
+    log::info!("saved {} personas", self.trainer.personas().len());
+    Ok(())
+  }
+
+  /// React to a line sent by the server.
+  fn handle(&mut self, line: &str) -> Result<(), Error> {
+    let message = match Message::parse(line) {
+      Some(message) => message,
+      None => return Ok(()),
+    };
+
+    match (message.command, message.params.as_slice()) {
+      ("PING", params) => {
+        let token = params.first().copied().unwrap_or_default();
+        self.send(&format!("PONG :{}", token))
+      }
+
+      // welcome: the bot is registered and can join channels
+      ("001", _) => {
+        let channels = self.opt.channels.join(",");
+        self.send(&format!("JOIN {}", channels))
+      }
+
+      // nick already in use: registering requires another one
+      ("433", _) => {
+        log::warn!("nick {} is already in use", self.opt.nick);
+        self.opt.nick.push('_');
+        let nick = self.opt.nick.clone();
+        self.send(&format!("NICK {}", nick))
+      }
+
+      ("PRIVMSG", [target, text]) => match message.nick {
+        Some(nick) if nick != self.opt.nick => self.privmsg(nick, target, text),
+        _ => Ok(()),
+      },
+
+      _ => Ok(()),
+    }
+  }
+
+  /// Learn a message, or answer it if it’s a command.
+  fn privmsg(&mut self, nick: &str, target: &str, text: &str) -> Result<(), Error> {
+    // answer in private to private messages
+    let reply_to = if target.starts_with(&['#', '&'][..]) {
+      target
+    } else {
+      nick
+    };
+
+    // commands aren’t learned, even without a nick to impersonate
+    if let Some(arguments) = command_arguments(text, &self.opt.command) {
+      return match arguments.split_whitespace().next() {
+        Some(author) => self.answer(reply_to, author),
+        None => Ok(()),
+      };
+    }
+
+    // CTCP requests and actions aren’t learned
+    if text.starts_with('\x01') {
+      return Ok(());
+    }
+
+    // nicks are case-insensitive on IRC
+    self.trainer.feed_line(&nick.to_lowercase(), text);
+
+    if self.trainer.unsaved() >= self.opt.save_every {
+      self.save()?;
+    }
+
+    Ok(())
+  }
+
+  /// Answer with a string impersonating an author, unless the bot answered too recently.
+  fn answer(&mut self, reply_to: &str, author: &str) -> Result<(), Error> {
+    let cooldown = Duration::from_secs(self.opt.cooldown);
+    if self
+      .last_answer
+      .is_some_and(|last_answer| last_answer.elapsed() < cooldown)
+    {
+      log::debug!("not impersonating {}: answered too recently", author);
+      return Ok(());
+    }
+
+    let chain_param = ChainParameters::new().max_chars(MAX_REPLY_CHARS);
+    let answer = match self
+      .trainer
+      .personas()
+      .generate_chain(&author.to_lowercase(), &chain_param)
+    {
+      Ok(chain) => format!("<{}> {}", author, chain),
+      Err(Error::UnknownAuthor(_)) => format!("I don’t know {} yet.", author),
+      Err(err) => {
+        log::warn!("cannot impersonate {}: {}", author, err);
+        return Ok(());
+      }
+    };
+
+    self.last_answer = Some(Instant::now());
+    self.send(&format!("PRIVMSG {} :{}", reply_to, answer))
+  }
+}
+
+/// Load the personas, or create an empty set if the model doesn’t exist yet.
+fn load_or_new(path: &Path) -> Result<PersonaSet, Error> {
+  if path.exists() {
+    PersonaSet::load(BufReader::new(File::open(path)?))
+  } else {
+    Ok(PersonaSet::new())
+  }
+}
+
+/// Learning parameters of the personas, unless another learning size is given.
+fn learning_parameters(
+  personas: &PersonaSet,
+  learning_size: Option<usize>,
+) -> Result<LearningParameters, Error> {
+  match (learning_size, personas.learning_parameters()) {
+    (None, Some(learn_param)) => Ok(learn_param.clone()),
+    (size, _) => LearningParameters::new(size.unwrap_or(2)),
+  }
+}
+
+fn run(opt: CLIOpt) -> Result<(), Error> {
+  let personas = load_or_new(&opt.model)?;
+  let learn_param = learning_parameters(&personas, opt.learning_size)?;
+  let cleanup = Cleanup::new()
+    .strip_formatting(true)
+    .strip_mentions(opt.strip_mentions);
+  let cleanup = if opt.replace_urls {
+    cleanup.replace_urls(URL_PLACEHOLDER)
+  } else {
+    cleanup
+  };
+
+  log::info!("connecting to {}", opt.server);
+  let stream = TcpStream::connect(&opt.server)?;
+  let reader = BufReader::new(stream.try_clone()?);
+
+  let mut bot = Bot {
+    writer: BufWriter::new(stream),
+    opt,
+    trainer: OnlineTrainer::new(personas, learn_param)?.cleanup(cleanup),
+    last_answer: None,
+  };
+
+  let nick = bot.opt.nick.clone();
+  bot.send(&format!("NICK {}", nick))?;
+  bot.send(&format!("USER {} 0 * :impersonate bot", nick))?;
+
+  let result = reader.split(b'\n').try_for_each(|line| {
+    let line = line?;
+    let line = String::from_utf8_lossy(&line);
+    let line = line.trim_end_matches('\r');
+    log::trace!("< {}", line);
+
+    bot.handle(line)
+  });
+
+  // learned messages are saved whatever the reason of the disconnection
+  log::info!("disconnected");
+  if let Err(err) = bot.save() {
+    log::error!("cannot save the model: {}", err);
+  }
+
+  result
+}
+
+fn main() {
+  let opt = CLIOpt::from_args();
+  logging::init(opt.verbose, opt.quiet);
+
+  if let Err(err) = run(opt) {
+    log::error!("{}", err);
+    process::exit(1);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse() {
+    let message = Message::parse(":alice!~alice@host PRIVMSG #rust :hello: world").unwrap();
+    assert_eq!(message.nick, Some("alice"));
+    assert_eq!(message.command, "PRIVMSG");
+    assert_eq!(message.params, vec!["#rust", "hello: world"]);
+
+    let message = Message::parse("PING :irc.example.com").unwrap();
+    assert_eq!(message.nick, None);
+    assert_eq!(message.command, "PING");
+    assert_eq!(message.params, vec!["irc.example.com"]);
+
+    let message =
+      Message::parse(":irc.example.com 433 * impersonate :Nickname is already in use").unwrap();
+    assert_eq!(message.command, "433");
+    assert_eq!(
+      message.params,
+      vec!["*", "impersonate", "Nickname is already in use"]
+    );
+
+    assert!(Message::parse(":prefix-only").is_none());
+  }
+
+  #[test]
+  fn test_command_arguments() {
+    let command = "!impersonate";
+    assert_eq!(
+      command_arguments("!impersonate alice", command),
+      Some("alice")
+    );
+    assert_eq!(
+      command_arguments("!impersonate\tbob  ", command),
+      Some("bob")
+    );
+    assert_eq!(command_arguments("!impersonate", command), Some(""));
+    assert_eq!(command_arguments("!impersonated is cool", command), None);
+    assert_eq!(command_arguments("say !impersonate alice", command), None);
+  }
+}
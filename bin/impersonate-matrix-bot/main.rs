@@ -0,0 +1,314 @@
+use impersonate::cleanup::{Cleanup, URL_PLACEHOLDER};
+use impersonate::online::OnlineTrainer;
+use impersonate::persona::PersonaSet;
+use impersonate::{Chain, ChainParameters, Error, LearningParameters};
+use matrix_sdk::config::SyncSettings;
+use matrix_sdk::room::MessagesOptions;
+use matrix_sdk::ruma::events::room::message::{
+  MessageType, OriginalSyncRoomMessageEvent, RoomMessageEventContent,
+};
+use matrix_sdk::ruma::events::{AnyMessageLikeEvent, AnyTimelineEvent, MessageLikeEvent};
+use matrix_sdk::ruma::{OwnedUserId, RoomOrAliasId, UInt, UserId};
+use matrix_sdk::{Client, Room};
+use saver::Saver;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, Mutex};
+use structopt::StructOpt;
+
+#[path = "../common/generation.rs"]
+mod generation;
+#[path = "../common/logging.rs"]
+mod logging;
+#[path = "../common/saver.rs"]
+mod saver;
+
+/// Maximum number of characters of a reply.
+const MAX_REPLY_CHARS: usize = 1000;
+
+/// Number of events fetched by history requests.
+const HISTORY_PAGE: u32 = 100;
+
+#[derive(Debug, StructOpt)]
+/// A Matrix bot learning how the members of rooms talk and impersonating them.
+///
+/// The history of the joined rooms is learned at startup, then every new message, into the persona
+/// of its sender. Sending `!impersonate @user:server` makes the bot answer with a string
+/// impersonating that user.
+///
+/// Encrypted rooms are not supported.
+struct CLIOpt {
+  #[structopt(short, long)]
+  /// URL of the homeserver, such as https://matrix.org.
+  homeserver: String,
+
+  #[structopt(short, long)]
+  /// User of the bot, such as @impersonate:matrix.org.
+  user: String,
+
+  #[structopt(long, env = "MATRIX_PASSWORD", hide_env_values = true)]
+  /// Password of the bot.
+  password: String,
+
+  #[structopt(short, long = "room", number_of_values = 1)]
+  /// Room to join, by identifier or alias; repeat for several rooms. Rooms already joined are
+  /// learned as well.
+  rooms: Vec<String>,
+
+  #[structopt(short, long, default_value = "impersonate-matrix.json")]
+  /// Model holding the personas, loaded at startup if it exists and saved regularly.
+  model: PathBuf,
+
+  #[structopt(short, long)]
+  /// Number of words to use to form a wording while learning.
+  ///
+  /// Defaults to the size the personas were trained with, or 2 for a new model.
+  learning_size: Option<usize>,
+
+  #[structopt(long, default_value = "0")]
+  /// Number of past events to learn from every joined room at startup.
+  history: usize,
+
+  #[structopt(long, default_value = "100")]
+  /// Save the model every that many learned messages.
+  save_every: usize,
+
+  #[structopt(long, default_value = "!impersonate")]
+  /// Command asking for an impersonation.
+  command: String,
+
+  #[structopt(long)]
+  /// Replace URLs with a <url> placeholder.
+  replace_urls: bool,
+
+  #[structopt(short, long, parse(from_occurrences))]
+  /// Show more logs; repeat for even more details.
+  verbose: u8,
+
+  #[structopt(short, long, conflicts_with = "verbose")]
+  /// Only show errors.
+  quiet: bool,
+}
+
+struct Bot {
+  /// User of the bot, whose messages aren’t learned.
+  user: OwnedUserId,
+  saver: Saver,
+  save_every: usize,
+  command: String,
+  trainer: Arc<Mutex<OnlineTrainer>>,
+}
+
+impl Bot {
+  /// Learn a message into the persona of its sender, saving the model if needed.
+  fn learn(&self, sender: &UserId, body: &str) {
+    if sender == self.user || body.starts_with(&self.command) {
+      return;
+    }
+
+    let mut trainer = self.trainer.lock().unwrap();
+    trainer.feed_line(sender.as_str(), body);
+
+    // the snapshot is taken while locked, so that snapshots are saved in order
+    if trainer.unsaved() >= self.save_every {
+      self.saver.save_in_background(trainer.snapshot());
+    }
+  }
+
+  /// Learn the last events of a room.
+  async fn learn_history(&self, room: &Room, history: usize) -> Result<(), matrix_sdk::Error> {
+    let mut fetched = 0;
+    let mut learned = 0;
+    let mut from = None;
+
+    while fetched < history {
+      let mut options = MessagesOptions::backward().from(from.as_deref());
+      options.limit = UInt::from(HISTORY_PAGE);
+
+      let messages = room.messages(options).await?;
+      for event in messages.chunk.iter().take(history - fetched) {
+        if let Ok(AnyTimelineEvent::MessageLike(AnyMessageLikeEvent::RoomMessage(
+          MessageLikeEvent::Original(event),
+        ))) = event.event.deserialize()
+        {
+          if let MessageType::Text(text) = &event.content.msgtype {
+            self.learn(&event.sender, &text.body);
+            learned += 1;
+          }
+        }
+      }
+
+      fetched = (fetched + messages.chunk.len()).min(history);
+      from = match messages.end {
+        Some(end) if !messages.chunk.is_empty() => Some(end),
+        _ => break,
+      };
+    }
+
+    log::info!("learned {} messages from {}", learned, room.room_id());
+    Ok(())
+  }
+
+  /// Learn a new message, or answer it if it’s a command.
+  async fn on_message(&self, event: OriginalSyncRoomMessageEvent, room: Room) {
+    let body = match &event.content.msgtype {
+      MessageType::Text(text) => &text.body,
+      _ => return,
+    };
+
+    if let Some(rest) = body.strip_prefix(&self.command) {
+      if let Some(user) = rest.split_whitespace().next() {
+        self.answer(&room, user).await;
+        return;
+      }
+    }
+
+    self.learn(&event.sender, body);
+  }
+
+  /// Answer with a string impersonating a user.
+  async fn answer(&self, room: &Room, user: &str) {
+    let chain_param = ChainParameters::new().max_chars(MAX_REPLY_CHARS);
+    let chain = generate(self.trainer.clone(), user.to_owned(), chain_param).await;
+    let answer = match chain {
+      Ok(chain) => format!("<{}> {}", user, chain),
+      Err(Error::UnknownAuthor(_)) => format!("I don’t know {} yet.", user),
+      Err(err) => {
+        log::warn!("cannot impersonate {}: {}", user, err);
+        format!("I cannot impersonate {}.", user)
+      }
+    };
+
+    // notices aren’t meant to be reacted to by bots, and highlight less than plain messages
+    if let Err(err) = room
+      .send(RoomMessageEventContent::notice_plain(answer))
+      .await
+    {
+      log::warn!("cannot answer: {}", err);
+    }
+  }
+}
+
+/// Generate a chain impersonating an author, on a blocking thread.
+///
+/// The persona is copied so that messages are learned meanwhile.
+async fn generate(
+  trainer: Arc<Mutex<OnlineTrainer>>,
+  author: String,
+  chain_param: ChainParameters,
+) -> Result<Chain, Error> {
+  generation::generate(generation::TIMEOUT, move |cancel| {
+    let persona = trainer.lock().unwrap().personas().get(&author).cloned();
+    persona
+      .ok_or(Error::UnknownAuthor(author))?
+      .generate_chain_cancellable(&chain_param, cancel)
+  })
+  .await
+}
+
+/// Load the personas, or create an empty set if the model doesn’t exist yet.
+fn load_or_new(path: &Path) -> Result<PersonaSet, Error> {
+  if path.exists() {
+    PersonaSet::load(BufReader::new(File::open(path)?))
+  } else {
+    Ok(PersonaSet::new())
+  }
+}
+
+/// Learning parameters of the personas, unless another learning size is given.
+fn learning_parameters(
+  personas: &PersonaSet,
+  learning_size: Option<usize>,
+) -> Result<LearningParameters, Error> {
+  match (learning_size, personas.learning_parameters()) {
+    (None, Some(learn_param)) => Ok(learn_param.clone()),
+    (size, _) => LearningParameters::new(size.unwrap_or(2)),
+  }
+}
+
+async fn run(opt: CLIOpt) -> Result<(), Box<dyn std::error::Error>> {
+  let cleanup = if opt.replace_urls {
+    Cleanup::new().replace_urls(URL_PLACEHOLDER)
+  } else {
+    Cleanup::new()
+  };
+
+  let client = Client::builder()
+    .homeserver_url(&opt.homeserver)
+    .build()
+    .await?;
+  client
+    .matrix_auth()
+    .login_username(&opt.user, &opt.password)
+    .initial_device_display_name("impersonate")
+    .send()
+    .await?;
+  let user = client.user_id().ok_or("not logged in")?.to_owned();
+  log::info!("logged in as {}", user);
+
+  for room in &opt.rooms {
+    client
+      .join_room_by_id_or_alias(&RoomOrAliasId::parse(room)?, &[])
+      .await?;
+  }
+
+  let personas = load_or_new(&opt.model)?;
+  let learn_param = learning_parameters(&personas, opt.learning_size)?;
+  let bot = Arc::new(Bot {
+    user,
+    saver: Saver::new(opt.model.clone()),
+    save_every: opt.save_every,
+    command: opt.command,
+    trainer: Arc::new(Mutex::new(
+      OnlineTrainer::new(personas, learn_param)?.cleanup(cleanup),
+    )),
+  });
+
+  // the first sync returns past events, which are learned with --history only
+  let response = client.sync_once(SyncSettings::default()).await?;
+
+  if opt.history > 0 {
+    for room in client.joined_rooms() {
+      if let Err(err) = bot.learn_history(&room, opt.history).await {
+        log::error!("cannot learn the history of {}: {}", room.room_id(), err);
+      }
+    }
+  }
+
+  let handler_bot = bot.clone();
+  client.add_event_handler(move |event: OriginalSyncRoomMessageEvent, room: Room| {
+    let bot = handler_bot.clone();
+    async move { bot.on_message(event, room).await }
+  });
+
+  let settings = SyncSettings::default().token(response.next_batch);
+  tokio::select! {
+    result = client.sync(settings) => result?,
+    result = tokio::signal::ctrl_c() => result?,
+  }
+
+  // learned messages are saved whatever the reason of the disconnection
+  let personas = bot.trainer.lock().unwrap().snapshot();
+  bot.saver.save(personas).await??;
+  Ok(())
+}
+
+fn main() {
+  let opt = CLIOpt::from_args();
+  logging::init(opt.verbose, opt.quiet);
+
+  let runtime = match tokio::runtime::Runtime::new() {
+    Ok(runtime) => runtime,
+    Err(err) => {
+      log::error!("{}", err);
+      process::exit(1);
+    }
+  };
+
+  if let Err(err) = runtime.block_on(run(opt)) {
+    log::error!("{}", err);
+    process::exit(1);
+  }
+}
@@ -0,0 +1,274 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use impersonate::cancel::CancellationToken;
+use impersonate::format;
+use impersonate::frozen::FrozenModel;
+use impersonate::mapped::{self, MappedModel};
+use impersonate::persona::PersonaSet;
+use impersonate::{Chain, ChainParameters, Error, MarkovChainGenerator, Sampling};
+use memmap2::Mmap;
+use rand::rngs::StdRng;
+use rand::SeedableRng as _;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+use structopt::StructOpt;
+
+#[path = "../common/generation.rs"]
+mod generation;
+#[path = "../common/logging.rs"]
+mod logging;
+
+#[derive(Debug, StructOpt)]
+/// Serve models over HTTP.
+///
+/// `GET /personas` lists the personas that can be impersonated, and `POST /generate` generates a
+/// string impersonating one of them. Both endpoints answer with JSON.
+struct CLIOpt {
+  #[structopt(required = true)]
+  /// Models to serve.
+  ///
+  /// Models holding several personas (trained with --all-authors) serve all of them; other models
+  /// are named after their file, without its extension.
+  ///
+  /// Models converted with `impersonate map` are memory-mapped instead of loaded, so that large
+  /// ones take little memory.
+  models: Vec<PathBuf>,
+
+  #[structopt(short, long, default_value = "127.0.0.1:8080")]
+  /// Address to listen on.
+  address: SocketAddr,
+
+  #[structopt(short, long, parse(from_occurrences))]
+  /// Show more logs; repeat for even more details.
+  verbose: u8,
+
+  #[structopt(short, long, conflicts_with = "verbose")]
+  /// Only show errors.
+  quiet: bool,
+}
+
+/// Body of `POST /generate`.
+#[derive(Debug, Deserialize)]
+struct GenerateRequest {
+  /// Persona to impersonate.
+  persona: String,
+  /// Seed of the random generator, for reproducible outputs.
+  seed: Option<u64>,
+  /// Text to start the string from.
+  prompt: Option<String>,
+  /// Word the string must contain.
+  must_contain: Option<String>,
+  /// Maximum number of wordings.
+  max_wordings: Option<usize>,
+  /// Maximum number of characters.
+  max_chars: Option<usize>,
+  /// Minimum number of wordings.
+  min_wordings: Option<usize>,
+  /// Minimum number of characters.
+  min_chars: Option<usize>,
+  /// Number of the last wordings that cannot be repeated.
+  no_repeat: Option<usize>,
+  /// Add-k smoothing.
+  smoothing: Option<f64>,
+  /// Pick the next wordings uniformly instead of according to their frequency.
+  #[serde(default)]
+  uniform: bool,
+}
+
+/// Answer of `POST /generate`.
+#[derive(Debug, Serialize)]
+struct GenerateResponse {
+  persona: String,
+  text: String,
+}
+
+/// Answer of failed requests.
+#[derive(Debug, Serialize)]
+struct ErrorResponse {
+  error: String,
+}
+
+type Failure = (StatusCode, Json<ErrorResponse>);
+
+/// Served personas.
+#[derive(Default)]
+struct Models {
+  /// Personas loaded in memory, frozen once all the models are loaded.
+  personas: PersonaSet,
+  /// Frozen personas, by name, shared by all the requests.
+  frozen: BTreeMap<String, FrozenModel>,
+  /// Memory-mapped personas, by name.
+  mapped: BTreeMap<String, MappedModel<Mmap>>,
+}
+
+impl Models {
+  /// Load a model, as a set of personas or as a single one named after its file.
+  fn load(&mut self, path: &Path) -> Result<(), Error> {
+    let name = path
+      .file_stem()
+      .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+
+    let mut magic = [0; 8];
+    let is_mapped = File::open(path)?.read_exact(&mut magic).is_ok() && &magic == mapped::MAGIC;
+    if is_mapped {
+      self.mapped.insert(name, MappedModel::open(path)?);
+      return Ok(());
+    }
+
+    if format::is_personas(BufReader::new(File::open(path)?))? {
+      let set = PersonaSet::load(BufReader::new(File::open(path)?))?;
+      self.personas.merge(&set);
+      return Ok(());
+    }
+
+    let generator = MarkovChainGenerator::load(BufReader::new(File::open(path)?))?;
+    self.personas.insert(name, generator);
+
+    Ok(())
+  }
+
+  /// Freeze the personas loaded in memory, so that they are generated from without any lock.
+  fn freeze(&mut self) {
+    let personas = std::mem::take(&mut self.personas);
+    self.frozen = personas
+      .into_iter()
+      .map(|(name, generator)| (name, FrozenModel::new(generator)))
+      .collect();
+  }
+
+  /// Generate a chain impersonating a persona, unless `cancel` is cancelled meanwhile.
+  fn generate(
+    &self,
+    persona: &str,
+    chain_param: &ChainParameters,
+    cancel: &CancellationToken,
+  ) -> Result<Chain, Error> {
+    let mut rng = match chain_param.seed {
+      Some(seed) => StdRng::seed_from_u64(seed),
+      None => StdRng::from_entropy(),
+    };
+
+    match (self.mapped.get(persona), self.frozen.get(persona)) {
+      (Some(model), _) => model.generate_chain_cancellable_with_rng(chain_param, &mut rng, cancel),
+      (None, Some(model)) => model.generate_chain_cancellable(chain_param, &mut rng, cancel),
+      (None, None) => Err(Error::UnknownAuthor(persona.to_owned())),
+    }
+  }
+
+  /// Names of all the personas, sorted.
+  fn names(&self) -> Vec<String> {
+    let mut names = self
+      .frozen
+      .keys()
+      .chain(self.mapped.keys())
+      .cloned()
+      .collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+    names
+  }
+}
+
+/// `GET /personas`
+async fn personas(State(models): State<Arc<Models>>) -> Json<Vec<String>> {
+  Json(models.names())
+}
+
+/// `POST /generate`
+async fn generate(
+  State(models): State<Arc<Models>>,
+  Json(request): Json<GenerateRequest>,
+) -> Result<Json<GenerateResponse>, Failure> {
+  let mut chain_param = ChainParameters::new().sampling(if request.uniform {
+    Sampling::Uniform
+  } else {
+    Sampling::Weighted
+  });
+  chain_param.seed = request.seed;
+  chain_param.prompt = request.prompt;
+  chain_param.must_contain = request.must_contain;
+  chain_param.max_state_traversal = request.max_wordings;
+  chain_param.max_chars = request.max_chars;
+  chain_param.min_wordings = request.min_wordings;
+  chain_param.min_chars = request.min_chars;
+  chain_param.no_repeat = request.no_repeat;
+  chain_param.smoothing = request.smoothing;
+
+  // parameters are chosen by clients, so that generating may take long
+  let persona = request.persona.clone();
+  let chain = generation::generate(generation::TIMEOUT, move |cancel| {
+    models.generate(&persona, &chain_param, cancel)
+  })
+  .await;
+
+  match chain {
+    Ok(chain) => Ok(Json(GenerateResponse {
+      persona: request.persona,
+      text: chain.to_string(),
+    })),
+
+    Err(err) => {
+      let status = match err {
+        Error::UnknownAuthor(_) => StatusCode::NOT_FOUND,
+        Error::NoStateMatchingPrompt(_)
+        | Error::NoStateContaining(_)
+        | Error::InvalidParameters(_)
+        | Error::NoAcceptableChain(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        Error::Cancelled => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+      };
+      log::warn!("cannot impersonate {}: {}", request.persona, err);
+
+      Err((
+        status,
+        Json(ErrorResponse {
+          error: err.to_string(),
+        }),
+      ))
+    }
+  }
+}
+
+#[tokio::main]
+async fn main() {
+  let CLIOpt {
+    models,
+    address,
+    verbose,
+    quiet,
+  } = CLIOpt::from_args();
+  logging::init(verbose, quiet);
+
+  let mut served = Models::default();
+  for path in &models {
+    if let Err(err) = served.load(path) {
+      log::error!("cannot load {}: {}", path.display(), err);
+      process::exit(1);
+    }
+  }
+  served.freeze();
+  log::info!("serving {} personas on {}", served.names().len(), address);
+
+  let app = Router::new()
+    .route("/personas", get(self::personas))
+    .route("/generate", post(generate))
+    .with_state(Arc::new(served));
+
+  let served = match tokio::net::TcpListener::bind(address).await {
+    Ok(listener) => axum::serve(listener, app).await,
+    Err(err) => Err(err),
+  };
+
+  if let Err(err) = served {
+    log::error!("{}", err);
+    process::exit(1);
+  }
+}
@@ -1,21 +1,64 @@
-use impersonate::trainers::weechat::WeechatLogTrainer;
-use impersonate::{ChainParameters, LearningParameters, MarkovChainGenerator, Trainer};
-use std::fs;
+use impersonate::cleanup::{Cleanup, URL_PLACEHOLDER};
+use impersonate::trainers::lines::Lines;
+use impersonate::trainers::weechat::{Actions, WeechatLogTrainer};
+use impersonate::{
+  ChainParameters, Error, LearningParameters, MarkovChainGenerator, Sampling, Trainer, Windowing,
+};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
 use std::path::PathBuf;
+use std::process;
 use structopt::StructOpt;
 
+#[path = "../common/input.rs"]
+mod input;
+#[path = "../common/logging.rs"]
+mod logging;
+
 #[derive(Debug, StructOpt)]
 struct CLIOpt {
-  path: PathBuf,
+  #[structopt(required_unless = "load-model")]
+  /// Weechat logs to learn from, optionally compressed with gzip, xz or zstd.
+  ///
+  /// Shell-style globs (such as ~/.weechat/logs/irc.*.#rust.weechatlog) are expanded. Use - to
+  /// read a log from the standard input.
+  paths: Vec<PathBuf>,
 
   #[structopt(short, long)]
   /// Name of the author to mimick.
   author: Option<String>,
 
+  #[structopt(long = "alias", number_of_values = 1)]
+  /// Other nick of the author; repeat for several nicks.
+  ///
+  /// Nick changes found in the logs are followed anyway.
+  aliases: Vec<String>,
+
+  #[structopt(long, default_value = "ignore", parse(try_from_str = parse_actions))]
+  /// How to learn actions (/me) of Weechat logs: ignore, plain (as regular messages) or emote
+  /// (as /me commands).
+  actions: Actions,
+
+  #[structopt(long)]
+  /// Remove mIRC formatting codes (colors, bold, etc.).
+  strip_formatting: bool,
+
+  #[structopt(long)]
+  /// Replace URLs with a <url> placeholder.
+  replace_urls: bool,
+
+  #[structopt(long)]
+  /// Remove leading mentions (nick: ).
+  strip_mentions: bool,
+
   #[structopt(short, long, default_value = "2")]
   /// Number of words to use to form a wording while learning.
   learning_size: usize,
 
+  #[structopt(long)]
+  /// Learn overlapping wordings (n-grams) instead of disjoint chunks of words.
+  sliding: bool,
+
   #[structopt(short, long, default_value = "1")]
   /// Number of random strings to generate.
   output_strings: usize,
@@ -23,35 +66,130 @@ struct CLIOpt {
   #[structopt(short = "s", long)]
   /// Number of maximum wordings to use while generating random strings.
   output_size: Option<usize>,
+
+  #[structopt(short, long)]
+  /// Pick next wordings uniformly instead of weighting them by their number of occurrences.
+  uniform: bool,
+
+  #[structopt(short, long)]
+  /// Start generated strings from the wording matching this prompt.
+  prompt: Option<String>,
+
+  #[structopt(long)]
+  /// Load a previously saved model instead of starting from an empty one.
+  load_model: Option<PathBuf>,
+
+  #[structopt(long)]
+  /// Save the model once trained, so that it can be reused with --load-model.
+  save_model: Option<PathBuf>,
+
+  #[structopt(short, long, parse(from_occurrences))]
+  /// Print more details about what is going on; repeat for even more.
+  verbose: u8,
+
+  #[structopt(short, long, conflicts_with = "verbose")]
+  /// Only print errors.
+  quiet: bool,
+}
+
+/// Cleanup passes enabled on the command line.
+fn cleanup(strip_formatting: bool, replace_urls: bool, strip_mentions: bool) -> Cleanup {
+  let cleanup = Cleanup::new()
+    .strip_formatting(strip_formatting)
+    .strip_mentions(strip_mentions);
+
+  if replace_urls {
+    cleanup.replace_urls(URL_PLACEHOLDER)
+  } else {
+    cleanup
+  }
+}
+
+/// Parse how to learn Weechat actions.
+fn parse_actions(s: &str) -> Result<Actions, String> {
+  match s {
+    "ignore" => Ok(Actions::Ignore),
+    "plain" => Ok(Actions::Plain),
+    "emote" => Ok(Actions::Emote),
+    _ => Err(format!("unknown way to learn actions: {}", s)),
+  }
 }
 
 fn main() {
+  let opt = CLIOpt::from_args();
+  logging::init(opt.verbose, opt.quiet);
+
+  if let Err(err) = run(opt) {
+    log::error!("{}", err);
+    process::exit(1);
+  }
+}
+
+fn run(opt: CLIOpt) -> Result<(), Error> {
   let CLIOpt {
-    path,
+    paths,
     author,
+    aliases,
+    actions,
+    strip_formatting,
+    replace_urls,
+    strip_mentions,
     learning_size,
+    sliding,
     output_strings,
     output_size,
-  } = CLIOpt::from_args();
-  let author = author.unwrap_or(String::new());
-
-  let mut markov_chain_generator = MarkovChainGenerator::new();
-  let mut trainer = WeechatLogTrainer::new(author, fs::read_to_string(path).unwrap());
-
-  trainer
-    .source_train(
-      &mut markov_chain_generator,
-      LearningParameters {
-        wording_size: learning_size,
-      },
-    )
-    .unwrap();
-
-  for _ in 0..output_strings {
-    if let Ok(output) = markov_chain_generator.generate_chain(&ChainParameters {
-      max_state_traversal: output_size,
-    }) {
-      println!("{}", output);
+    uniform,
+    prompt,
+    load_model,
+    save_model,
+    ..
+  } = opt;
+  let author = author.unwrap_or_default();
+  let windowing = if sliding {
+    Windowing::Sliding
+  } else {
+    Windowing::Chunks
+  };
+  let sampling = if uniform {
+    Sampling::Uniform
+  } else {
+    Sampling::Weighted
+  };
+
+  let mut markov_chain_generator = match load_model {
+    Some(model_path) => MarkovChainGenerator::load(BufReader::new(File::open(model_path)?))?,
+    None => MarkovChainGenerator::new(),
+  };
+
+  let cleanup = cleanup(strip_formatting, replace_urls, strip_mentions);
+  let learn_param = LearningParameters::new(learning_size)?.windowing(windowing);
+
+  for path in &paths {
+    for path in input::expand(path)? {
+      let lines = Lines::from_reader(BufReader::new(input::open(&path)?));
+      let mut trainer = WeechatLogTrainer::new(&author, lines)
+        .aliases(aliases.clone())
+        .actions(actions)
+        .cleanup(cleanup.clone());
+
+      trainer.source_train(&mut markov_chain_generator, &learn_param)?;
     }
   }
+
+  if let Some(model_path) = save_model {
+    markov_chain_generator.save(BufWriter::new(File::create(model_path)?))?;
+  }
+
+  let mut chain_param = ChainParameters::new().sampling(sampling);
+  chain_param.max_state_traversal = output_size;
+  chain_param.prompt = prompt;
+
+  for chain in markov_chain_generator
+    .generate_chains(&chain_param)
+    .take(output_strings)
+  {
+    println!("{}", chain?);
+  }
+
+  Ok(())
 }
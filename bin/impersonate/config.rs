@@ -0,0 +1,169 @@
+//! Configuration file of the command line interface.
+//!
+//! A TOML file, given with --config, describes what would otherwise take long command lines:
+//!
+//! ```toml
+//! model = "alice.json"
+//!
+//! [learning]
+//! size = 2
+//! sliding = true
+//!
+//! [[sources]]
+//! paths = ["logs/irc.libera.#rust.weechatlog"]
+//! author = "alice"
+//! aliases = ["alice_", "al1ce"]
+//! strip_mentions = true
+//! since = "2019"
+//! exclude_patterns = ["^!", "(?i)password"]
+//! no_private = true
+//!
+//! [[sources]]
+//! paths = ["mail/alice.mbox"]
+//! format = "mbox"
+//! author = "alice@example.org"
+//!
+//! [generate]
+//! count = 5
+//! max_chars = 280
+//! template = "<{author}> {text}"
+//! ```
+//!
+//! Relative paths are relative to the directory of the file. Options given on the command line
+//! override the ones of the file.
+
+use impersonate::Error;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Settings read from a configuration file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+  /// Model shared by all the commands.
+  pub model: Option<PathBuf>,
+  /// Sources `train` learns from when no path is given.
+  pub sources: Vec<Source>,
+  /// How `train` learns.
+  pub learning: Learning,
+  /// How `generate` generates.
+  pub generate: Generate,
+}
+
+/// Sources of the same format and author.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Source {
+  /// Paths to learn from, which can be globs and directories.
+  pub paths: Vec<PathBuf>,
+  /// Format of the sources; weechat by default.
+  pub format: Option<String>,
+  /// Regular expression matching the lines of the sources, overriding the format.
+  pub format_regex: Option<String>,
+  /// Layout of the lines of IRC logs, overriding the format.
+  pub log_format: Option<String>,
+  /// Author to mimick.
+  pub author: Option<String>,
+  /// Other nicks of the author in Weechat logs.
+  pub aliases: Vec<String>,
+  /// How to learn actions of Weechat logs: ignore, plain or emote.
+  pub actions: Option<String>,
+  /// Remove mIRC formatting codes from Weechat messages.
+  pub strip_formatting: bool,
+  /// Replace URLs with a placeholder in Weechat messages.
+  pub replace_urls: bool,
+  /// Remove leading mentions from Weechat messages.
+  pub strip_mentions: bool,
+  /// Regular expressions matching the lines not to learn, once cleaned up.
+  pub exclude_patterns: Vec<String>,
+  /// Only learn the Weechat messages written since this date.
+  pub since: Option<String>,
+  /// Only learn the Weechat messages written until this date.
+  pub until: Option<String>,
+  /// Only learn these buffers of Weechat logs.
+  pub channels: Vec<String>,
+  /// Don’t learn these buffers of Weechat logs.
+  pub exclude_channels: Vec<String>,
+  /// Don’t learn the private messages of Weechat logs.
+  pub no_private: bool,
+}
+
+/// Learning parameters.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Learning {
+  /// Number of words of a wording.
+  pub size: Option<usize>,
+  /// Learn overlapping wordings.
+  pub sliding: bool,
+  /// Also learn shorter contexts.
+  pub variable_order: bool,
+  /// Learn characters instead of words.
+  pub characters: bool,
+  /// Ignore case while learning.
+  pub case_folding: bool,
+  /// Remember the n-grams of that many words, to reject verbatim copies.
+  pub verbatim_ngram: Option<usize>,
+  /// Also learn which words precede which.
+  pub backward: bool,
+}
+
+/// Generation settings.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Generate {
+  /// Number of random strings to generate.
+  pub count: Option<usize>,
+  /// Maximum number of wordings of the random strings.
+  pub max_wordings: Option<usize>,
+  /// Pick next wordings uniformly.
+  pub uniform: bool,
+  /// Persona to impersonate, for models trained with --all-authors.
+  pub author: Option<String>,
+  /// Number of last wordings that cannot be gone through again.
+  pub no_repeat: Option<usize>,
+  /// Maximum number of characters of the random strings.
+  pub max_chars: Option<usize>,
+  /// Minimum number of wordings of the random strings.
+  pub min_wordings: Option<usize>,
+  /// Minimum number of characters of the random strings.
+  pub min_chars: Option<usize>,
+  /// Number of attempts at generating an acceptable random string.
+  pub attempts: Option<usize>,
+  /// File of the words never to output.
+  pub banned_words_file: Option<PathBuf>,
+  /// Seed of the random generator.
+  pub seed: Option<u64>,
+  /// Template rendering the random strings as lines of a fake chat log.
+  pub template: Option<String>,
+}
+
+impl Config {
+  /// Read a configuration file, resolving its paths.
+  pub fn load(path: &Path) -> Result<Self, Error> {
+    let mut config = toml::from_str::<Config>(&fs::read_to_string(path)?).map_err(|err| {
+      Error::InvalidParameters(format!("invalid configuration {}: {}", path.display(), err))
+    })?;
+
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let resolve = |path: &mut PathBuf| {
+      // the standard input and home directories are left as is
+      if path.is_relative() && !path.starts_with("~") && *path != Path::new("-") {
+        *path = dir.join(&*path);
+      }
+    };
+
+    config.model.iter_mut().for_each(resolve);
+    config
+      .generate
+      .banned_words_file
+      .iter_mut()
+      .for_each(resolve);
+    for source in &mut config.sources {
+      source.paths.iter_mut().for_each(resolve);
+    }
+
+    Ok(config)
+  }
+}
@@ -0,0 +1,1630 @@
+use config::Config;
+use impersonate::banned::BannedWords;
+use impersonate::checkpoint::Checkpointer;
+use impersonate::classify::ModelSet;
+use impersonate::cleanup::{Cleanup, URL_PLACEHOLDER};
+use impersonate::dialogue;
+use impersonate::dot::DotOptions;
+use impersonate::markovify;
+use impersonate::persona::{AuthorTrainer, PersonaSet};
+use impersonate::trainers::compressed;
+use impersonate::trainers::dir::DirectoryTrainer;
+use impersonate::trainers::exclude::ExcludingTrainer;
+use impersonate::trainers::hexchat::HexChatLogTrainer;
+use impersonate::trainers::irclog::IrcLogTrainer;
+use impersonate::trainers::lines::Lines;
+use impersonate::trainers::mbox::MboxTrainer;
+use impersonate::trainers::mirc::MircLogTrainer;
+use impersonate::trainers::plain::PlainTextTrainer;
+use impersonate::trainers::regex::RegexTrainer;
+use impersonate::trainers::srt::SubtitleTrainer;
+use impersonate::trainers::weechat::{Actions, Channels, TimeRange, WeechatLogTrainer};
+use impersonate::trainers::znc::ZncLogTrainer;
+use impersonate::{
+  ChainParameters, Error, Granularity, LearningParameters, MarkovChainGenerator, Sampling, Trainer,
+  Windowing, Wording,
+};
+use indicatif::{ProgressBar, ProgressStyle};
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+use structopt::clap::Shell;
+use structopt::StructOpt;
+use template::{Clock, DateTime, Template};
+
+mod config;
+#[path = "../common/input.rs"]
+mod input;
+#[path = "../common/logging.rs"]
+mod logging;
+mod man;
+mod template;
+mod watch;
+
+#[derive(Debug, StructOpt)]
+/// Learn how people write and impersonate them.
+struct CLIOpt {
+  #[structopt(short, long)]
+  /// Model shared by all the commands.
+  ///
+  /// Defaults to the model of the configuration file, or impersonate.json.
+  model: Option<PathBuf>,
+
+  #[structopt(short, long)]
+  /// TOML configuration file describing the sources, learning parameters and generation
+  /// settings; options given on the command line override it.
+  config: Option<PathBuf>,
+
+  #[structopt(short, long, parse(from_occurrences))]
+  /// Print more details about what is going on; repeat for even more.
+  verbose: u8,
+
+  #[structopt(short, long, conflicts_with = "verbose")]
+  /// Only print errors.
+  quiet: bool,
+
+  #[structopt(subcommand)]
+  command: Command,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+  /// Train the model on some sources, creating it if it doesn’t exist yet.
+  Train {
+    /// Sources to learn from, optionally compressed with gzip, xz or zstd.
+    ///
+    /// Shell-style globs are expanded. Use - to read the standard input. Directories are walked recursively and the format of their files is detected.
+    ///
+    /// Defaults to the sources of the configuration file.
+    paths: Vec<PathBuf>,
+
+    #[structopt(short, long, default_value = "weechat")]
+    /// Format of the sources: weechat, znc, mirc, hexchat, mbox, srt or plain.
+    format: Format,
+
+    #[structopt(long)]
+    /// Regular expression matching the lines of the sources, overriding --format.
+    ///
+    /// The message to learn must be captured by a group named message, such as in
+    /// `^\[.*\] <(?P<author>[^>]+)> (?P<message>.*)$`; a group named author is used to find the
+    /// messages of the author.
+    format_regex: Option<String>,
+
+    #[structopt(long, conflicts_with = "format-regex")]
+    /// Layout of the lines of IRC logs, overriding --format.
+    ///
+    /// The layout is a strftime-like format, where %n is the nick of the author and %m, as last
+    /// directive, the message; for instance, `%Y-%m-%d %H:%M:%S\t%n\t%m`.
+    log_format: Option<String>,
+
+    #[structopt(short, long)]
+    /// Name (or address, for mailboxes) of the author to mimick.
+    author: Option<String>,
+
+    #[structopt(long = "alias", number_of_values = 1)]
+    /// Other nick of the author in Weechat logs; repeat for several nicks.
+    ///
+    /// Nick changes found in the logs are followed anyway.
+    aliases: Vec<String>,
+
+    #[structopt(long, default_value = "ignore", parse(try_from_str = parse_actions))]
+    /// How to learn actions (/me) of Weechat logs: ignore, plain (as regular messages) or emote
+    /// (as /me commands).
+    actions: Actions,
+
+    #[structopt(long)]
+    /// Remove mIRC formatting codes (colors, bold, etc.) from Weechat messages.
+    strip_formatting: bool,
+
+    #[structopt(long)]
+    /// Replace URLs with a <url> placeholder in Weechat messages.
+    replace_urls: bool,
+
+    #[structopt(long)]
+    /// Remove leading mentions (nick: ) from Weechat messages.
+    strip_mentions: bool,
+
+    #[structopt(long = "exclude-pattern", number_of_values = 1)]
+    /// Don’t learn the lines matching this regular expression, once cleaned up; repeat for several
+    /// patterns.
+    ///
+    /// For instance, ^! drops bot commands and (?i)password drops lines mentioning passwords.
+    exclude_patterns: Vec<String>,
+
+    #[structopt(long)]
+    /// Only learn the Weechat messages written since this date, such as 2019, 2019-06 or
+    /// 2019-06-01 12:00; inclusive.
+    since: Option<String>,
+
+    #[structopt(long)]
+    /// Only learn the Weechat messages written until this date, such as 2019, 2019-06 or
+    /// 2019-06-01 12:00; inclusive, so that --until 2019 includes the whole year.
+    until: Option<String>,
+
+    #[structopt(long = "channel", number_of_values = 1)]
+    /// Only learn this buffer of Weechat logs, such as #rust; repeat for several buffers.
+    ///
+    /// Buffers are named after log files, such as irc.libera.#rust.weechatlog, or after the
+    /// ==> file <== headers of logs concatenated with tail.
+    channels: Vec<String>,
+
+    #[structopt(long = "exclude-channel", number_of_values = 1)]
+    /// Don’t learn this buffer of Weechat logs; repeat for several buffers.
+    exclude_channels: Vec<String>,
+
+    #[structopt(long)]
+    /// Don’t learn the private messages of Weechat logs, only channels.
+    no_private: bool,
+
+    #[structopt(long, conflicts_with = "author")]
+    /// Learn every author of Weechat logs in a single pass, into a model holding one persona per
+    /// author.
+    ///
+    /// Generate strings from such a model with --author.
+    all_authors: bool,
+
+    #[structopt(short, long)]
+    /// Number of words to use to form a wording while learning.
+    ///
+    /// Defaults to the size the model was trained with, or 2 for a new model. An existing model
+    /// keeps the learning options it was trained with, and cannot be trained with other ones.
+    learning_size: Option<usize>,
+
+    #[structopt(long)]
+    /// Learn overlapping wordings (n-grams) instead of disjoint chunks of words.
+    sliding: bool,
+
+    #[structopt(long)]
+    /// Also learn shorter contexts, so that generation can back off to them.
+    variable_order: bool,
+
+    #[structopt(long)]
+    /// Learn characters instead of words, to generate nicknames or short handles.
+    characters: bool,
+
+    #[structopt(long)]
+    /// Ignore case while learning, and restore the most frequent one when generating.
+    case_folding: bool,
+
+    #[structopt(long)]
+    /// Remember the n-grams of that many words of the sources, to reject verbatim copies.
+    verbatim_ngram: Option<usize>,
+
+    #[structopt(long)]
+    /// Also learn which words precede which, to speed up --must-contain and --bidirectional.
+    backward: bool,
+
+    #[structopt(short, long)]
+    /// Number of threads to learn with; 0 uses one thread per CPU.
+    ///
+    /// Without this option, sources are learned on a single thread.
+    jobs: Option<usize>,
+
+    #[structopt(long, conflicts_with = "all-authors")]
+    /// Save the partially trained model every that many learned lines, so that a crash doesn’t
+    /// lose the whole training.
+    ///
+    /// With --jobs, the model is saved after whole chunks of lines only.
+    checkpoint_every: Option<usize>,
+
+    #[structopt(long)]
+    /// Don’t train: print the first cleaned lines that would be learned, and how many lines of
+    /// every source are kept and dropped, to check the author, format and cleanup options.
+    dry_run: bool,
+
+    #[structopt(long, default_value = "10")]
+    /// Number of cleaned lines printed by --dry-run.
+    preview_lines: usize,
+  },
+
+  /// Keep training the model on the lines appended to logs, such as the logs of a running IRC
+  /// client, saving it periodically.
+  ///
+  /// Logs are followed from their current end; learn what they already hold with train first.
+  /// Stop with Ctrl-C: the lines learned since the last save are saved before exiting.
+  Watch {
+    /// Logs to follow.
+    ///
+    /// Shell-style globs are expanded when starting. Defaults to the sources of the configuration
+    /// file.
+    paths: Vec<PathBuf>,
+
+    #[structopt(short, long, default_value = "weechat")]
+    /// Format of the logs: weechat, znc, mirc, hexchat, mbox, srt or plain.
+    format: Format,
+
+    #[structopt(short, long)]
+    /// Name of the author to mimick.
+    author: Option<String>,
+
+    #[structopt(long = "alias", number_of_values = 1)]
+    /// Other nick of the author in Weechat logs; repeat for several nicks.
+    aliases: Vec<String>,
+
+    #[structopt(long, default_value = "60")]
+    /// Number of seconds between two saves of the model, when it learned something.
+    save_every: u64,
+  },
+
+  /// Generate random strings out of the model.
+  Generate {
+    #[structopt(short = "n", long)]
+    /// Number of random strings to generate; 1 by default.
+    output_strings: Option<usize>,
+
+    #[structopt(short = "s", long)]
+    /// Number of maximum wordings to use while generating random strings.
+    output_size: Option<usize>,
+
+    #[structopt(short, long)]
+    /// Pick next wordings uniformly instead of weighting them by their number of occurrences.
+    uniform: bool,
+
+    #[structopt(short, long)]
+    /// Start generated strings from the wording matching this prompt.
+    prompt: Option<String>,
+
+    #[structopt(long, requires = "prompt")]
+    /// Grow strings backwards from the prompt too, so that it lands in their middle.
+    bidirectional: bool,
+
+    #[structopt(long, conflicts_with = "prompt")]
+    /// Only generate strings containing this word, which might not be at their start.
+    must_contain: Option<String>,
+
+    #[structopt(short, long)]
+    /// Impersonate this author of a model trained with --all-authors.
+    author: Option<String>,
+
+    #[structopt(long)]
+    /// Back off to shorter contexts from states with less than this number of transitions.
+    ///
+    /// The model must have been trained with --variable-order.
+    back_off: Option<usize>,
+
+    #[structopt(long)]
+    /// Add-k smoothing, giving a small probability to transitions never seen while training.
+    smoothing: Option<f64>,
+
+    #[structopt(long)]
+    /// Reject strings copying the sources, trying again up to this number of times.
+    ///
+    /// The model must have been trained with --verbatim-ngram.
+    reject_verbatim: Option<usize>,
+
+    #[structopt(long)]
+    /// Don’t go through any of this number of last wordings again, to avoid loops.
+    no_repeat: Option<usize>,
+
+    #[structopt(long)]
+    /// Maximum number of characters of the random strings, which are never cut mid-word.
+    max_chars: Option<usize>,
+
+    #[structopt(long)]
+    /// Minimum number of wordings of the random strings; shorter ones are generated again.
+    min_wordings: Option<usize>,
+
+    #[structopt(long)]
+    /// Minimum number of characters of the random strings; shorter ones are generated again.
+    min_chars: Option<usize>,
+
+    #[structopt(long)]
+    /// Number of attempts at generating a long enough or original random string.
+    attempts: Option<usize>,
+
+    #[structopt(long)]
+    /// Only output distinct strings, which might be fewer than asked on small models.
+    distinct: bool,
+
+    #[structopt(long, conflicts_with_all = &["uniform", "distinct", "must-contain"])]
+    /// Output the most likely strings instead of random ones, with their log-likelihood.
+    most_likely: bool,
+
+    #[structopt(long)]
+    /// Never output the words of this file, one regular expression matching whole words per line.
+    banned_words_file: Option<PathBuf>,
+
+    #[structopt(long)]
+    /// Seed of the random generator, so that the same model and seed always generate the same
+    /// strings.
+    seed: Option<u64>,
+
+    #[structopt(long, conflicts_with = "most-likely")]
+    /// Render the strings as lines of a fake chat log, such as `<{author}> {text}`.
+    ///
+    /// {author} is the impersonated author and {text} the string. %Y, %m, %d, %H, %M and %S are
+    /// replaced with the UTC date and time of the line, lines being a few seconds apart from now
+    /// on; \t and \n are tabs and line breaks. For instance, `%Y-%m-%d %H:%M:%S\t{author}\t{text}`
+    /// renders Weechat logs.
+    template: Option<Template>,
+  },
+
+  /// Chat with the model: every typed line is answered with a random string continuing it.
+  ///
+  /// Lines matching nothing in the model are answered with a string starting anywhere. Quit with
+  /// Ctrl-D.
+  Repl {
+    #[structopt(short, long)]
+    /// Impersonate this author of a model trained with --all-authors.
+    author: Option<String>,
+
+    #[structopt(short, long)]
+    /// Pick next wordings uniformly instead of weighting them by their number of occurrences.
+    uniform: bool,
+
+    #[structopt(long)]
+    /// Don’t go through any of this number of last wordings again, to avoid loops.
+    no_repeat: Option<usize>,
+
+    #[structopt(long)]
+    /// Maximum number of characters of the answers, which are never cut mid-word.
+    max_chars: Option<usize>,
+  },
+
+  /// Simulate a conversation between two models, each one answering the other.
+  Converse {
+    /// Model speaking first.
+    first: String,
+
+    /// Model answering.
+    second: String,
+
+    #[structopt(long)]
+    /// Impersonate two authors of the model trained with --all-authors instead of two models.
+    authors: bool,
+
+    #[structopt(short = "n", long, default_value = "10")]
+    /// Number of lines of the conversation.
+    turns: usize,
+
+    #[structopt(short, long)]
+    /// Start the conversation from the wording matching this prompt.
+    prompt: Option<String>,
+
+    #[structopt(long)]
+    /// Seed of the random generator, for reproducible conversations.
+    seed: Option<u64>,
+
+    #[structopt(long)]
+    /// Maximum number of characters of the lines, which are never cut mid-word.
+    max_chars: Option<usize>,
+
+    #[structopt(long, default_value = "<{author}> {text}")]
+    /// Render the lines of the conversation as lines of a fake chat log; see generate --template.
+    template: Template,
+  },
+
+  /// Show statistics about the model, and its most frequent wordings.
+  Stats {
+    #[structopt(short = "n", long, default_value = "10")]
+    /// Number of most frequent wordings to show.
+    top: usize,
+
+    #[structopt(short, long)]
+    /// Persona to show, for models trained with --all-authors.
+    author: Option<String>,
+  },
+
+  /// Export the transitions of the model as CSV rows of from, to and count, to analyse them with
+  /// other tools.
+  ///
+  /// Line starts and ends are empty wordings.
+  Export {
+    /// File to write the transitions to; the standard output if omitted.
+    output: Option<PathBuf>,
+
+    #[structopt(long)]
+    /// Separate fields with tabulations (TSV) instead of commas.
+    tsv: bool,
+
+    #[structopt(short, long)]
+    /// Persona to export, for models trained with --all-authors.
+    author: Option<String>,
+  },
+
+  /// Merge a model trained by markovify (saved with `Text.to_json`) into the model.
+  ImportMarkovify {
+    /// markovify model to import.
+    input: PathBuf,
+  },
+
+  /// Export the model to markovify, to be loaded with `Text.from_json`.
+  ///
+  /// Only models trained over words with --sliding can be exported.
+  ExportMarkovify {
+    /// File to write the markovify model to.
+    output: PathBuf,
+
+    #[structopt(short, long)]
+    /// Persona to export, for models trained with --all-authors.
+    author: Option<String>,
+  },
+
+  /// Merge other models into the model.
+  Merge {
+    #[structopt(required = true)]
+    /// Models to merge.
+    models: Vec<PathBuf>,
+  },
+
+  /// Shrink the model by dropping rare transitions and states.
+  Prune {
+    #[structopt(long, default_value = "2")]
+    /// Drop transitions seen less than this number of times.
+    min_count: usize,
+
+    #[structopt(long)]
+    /// Keep only this number of states, the ones seen the most.
+    max_states: Option<usize>,
+  },
+
+  /// Make old habits fade by scaling down the counts of all transitions.
+  Decay {
+    /// Factor to scale the counts by, between 0 and 1.
+    factor: f64,
+
+    #[structopt(long, default_value = "0.01")]
+    /// Drop the transitions whose scaled count is less than this number.
+    min_count: f64,
+  },
+
+  /// Convert the model to the read-only format impersonate-serve memory-maps, which uses little
+  /// memory.
+  Map {
+    /// File to write the converted model to.
+    output: PathBuf,
+
+    #[structopt(short, long)]
+    /// Persona to convert, for models trained with --all-authors.
+    author: Option<String>,
+  },
+
+  /// Show the wordings that can follow a wording, with their probability, or the whole graph.
+  Inspect {
+    #[structopt(required_unless = "dot")]
+    /// Wording to inspect.
+    wording: Option<String>,
+
+    #[structopt(short = "n", long, conflicts_with = "dot")]
+    /// Only show that many continuations, the most likely ones.
+    top: Option<usize>,
+
+    #[structopt(long, conflicts_with = "wording")]
+    /// Export the whole graph of the model in the GraphViz DOT language instead.
+    dot: bool,
+
+    #[structopt(long, default_value = "1")]
+    /// Only export the transitions seen at least that many times.
+    min_count: usize,
+
+    #[structopt(long, requires = "dot")]
+    /// Only export that many transitions, the most frequent ones.
+    max_edges: Option<usize>,
+
+    #[structopt(short, long)]
+    /// Persona to inspect, for models trained with --all-authors.
+    author: Option<String>,
+  },
+
+  /// Show how likely texts are under the model: their log-likelihood and perplexity.
+  Score {
+    #[structopt(required = true)]
+    /// Texts to score.
+    texts: Vec<String>,
+
+    #[structopt(short, long)]
+    /// Persona to score the texts under, for models trained with --all-authors.
+    author: Option<String>,
+  },
+
+  /// Print the completions of the command for a shell, or its manual page.
+  ///
+  /// For instance, `impersonate completions bash > /etc/bash_completion.d/impersonate` or
+  /// `impersonate completions --man > impersonate.1`.
+  Completions {
+    #[structopt(
+      possible_values = &Shell::variants(),
+      case_insensitive = true,
+      required_unless = "man"
+    )]
+    /// Shell to complete the command in.
+    shell: Option<Shell>,
+
+    #[structopt(long, conflicts_with = "shell")]
+    /// Print the manual page, in roff, instead.
+    man: bool,
+  },
+
+  /// Guess which of several models most likely wrote a text.
+  ///
+  /// Authors are named after the files of their models, and ranked from the most to the least
+  /// likely, with the perplexity of the text under their model.
+  Classify {
+    /// Text to classify.
+    text: String,
+
+    #[structopt(required = true)]
+    /// Models of the authors.
+    models: Vec<PathBuf>,
+
+    #[structopt(short, long)]
+    /// Persona to compare across the models, for models trained with --all-authors.
+    author: Option<String>,
+  },
+}
+
+/// Format of the sources to train on.
+#[derive(Clone, Debug)]
+enum Format {
+  Weechat,
+  Znc,
+  Mirc,
+  HexChat,
+  Mbox,
+  Srt,
+  Plain,
+  /// Lines matched by a regular expression, given with --format-regex.
+  Regex(String),
+  /// IRC logs of the layout given with --log-format.
+  IrcLog(String),
+}
+
+impl FromStr for Format {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "weechat" => Ok(Format::Weechat),
+      "znc" => Ok(Format::Znc),
+      "mirc" => Ok(Format::Mirc),
+      "hexchat" => Ok(Format::HexChat),
+      "mbox" => Ok(Format::Mbox),
+      "srt" => Ok(Format::Srt),
+      "plain" => Ok(Format::Plain),
+      _ => Err(format!("unknown format: {}", s)),
+    }
+  }
+}
+
+impl Format {
+  /// Whether trainers of this format read their log as they learn it, rather than when created.
+  fn is_streamed(&self) -> bool {
+    !matches!(self, Format::Mirc | Format::Plain)
+  }
+}
+
+/// Sources of the same format and author.
+struct Source {
+  paths: Vec<PathBuf>,
+  format: Format,
+  author: String,
+  aliases: Vec<String>,
+  actions: Actions,
+  cleanup: Cleanup,
+  time_range: TimeRange,
+  channels: Channels,
+  exclude_patterns: Vec<String>,
+}
+
+impl Source {
+  /// Sources described by a configuration file.
+  fn from_config(source: config::Source) -> Result<Self, Error> {
+    let format = match (source.format_regex, source.log_format, source.format) {
+      (Some(regex), _, _) => Format::Regex(regex),
+      (None, Some(log_format), _) => Format::IrcLog(log_format),
+      (None, None, Some(format)) => format.parse().map_err(Error::InvalidParameters)?,
+      (None, None, None) => Format::Weechat,
+    };
+    let actions = match source.actions {
+      Some(actions) => parse_actions(&actions).map_err(Error::InvalidParameters)?,
+      None => Actions::Ignore,
+    };
+
+    Ok(Self {
+      paths: source.paths,
+      format,
+      author: source.author.unwrap_or_default(),
+      aliases: source.aliases,
+      actions,
+      cleanup: cleanup(
+        source.strip_formatting,
+        source.replace_urls,
+        source.strip_mentions,
+      ),
+      time_range: TimeRange::new(source.since.as_deref(), source.until.as_deref())?,
+      channels: channels(source.channels, source.exclude_channels, source.no_private),
+      exclude_patterns: source.exclude_patterns,
+    })
+  }
+
+  /// Fail if the sources filter timestamps or buffers that only Weechat log files have.
+  fn check_filters(&self, path: &Path) -> Result<(), Error> {
+    let weechat_file = matches!(self.format, Format::Weechat) && !path.is_dir();
+
+    if weechat_file || (self.time_range.is_unbounded() && self.channels.is_unfiltered()) {
+      Ok(())
+    } else {
+      Err(Error::InvalidParameters(
+        "--since, --until, --channel, --exclude-channel and --no-private require Weechat log files"
+          .to_owned(),
+      ))
+    }
+  }
+
+  /// Weechat trainer of a log file of the sources.
+  fn weechat_trainer(&self, author: &str, path: &Path, reader: Box<dyn Read>) -> WeechatLogTrainer {
+    let trainer = WeechatLogTrainer::new(author, lines(reader))
+      .actions(self.actions)
+      .cleanup(self.cleanup.clone())
+      .time_range(self.time_range.clone())
+      .channels(self.channels.clone());
+
+    match path.file_name().and_then(|name| name.to_str()) {
+      Some(name) => trainer.file_name(name),
+      None => trainer,
+    }
+  }
+
+  /// Create a trainer for a file of the sources.
+  fn trainer(
+    &self,
+    path: &Path,
+    lines_read: Option<&LinesRead>,
+  ) -> Result<Box<dyn Trainer>, Error> {
+    let author = &self.author;
+    self.check_filters(path)?;
+
+    if path.is_dir() {
+      return self.excluding(DirectoryTrainer::new(author, path)?);
+    }
+
+    self.reader_trainer(path, open(path, lines_read)?)
+  }
+
+  /// Create a trainer for the content of a file of the sources, read from `reader`.
+  fn reader_trainer(&self, path: &Path, reader: Box<dyn Read>) -> Result<Box<dyn Trainer>, Error> {
+    let author = &self.author;
+    let trainer: Box<dyn Trainer> = match &self.format {
+      Format::Weechat => Box::new(
+        self
+          .weechat_trainer(author, path, reader)
+          .aliases(self.aliases.iter().cloned()),
+      ),
+      Format::Znc => {
+        let trainer = ZncLogTrainer::new(author, lines(reader));
+        match compressed::uncompressed_path(path)
+          .file_name()
+          .and_then(|name| name.to_str())
+        {
+          Some(name) => Box::new(trainer.file_name(name)),
+          None => Box::new(trainer),
+        }
+      }
+      Format::Mirc => Box::new(MircLogTrainer::from_reader(author, reader)?),
+      Format::HexChat => Box::new(HexChatLogTrainer::new(author, lines(reader))),
+      Format::Mbox => Box::new(MboxTrainer::from_reader(author, reader)?),
+      Format::Srt => Box::new(SubtitleTrainer::from_reader(reader)?),
+      Format::Plain => Box::new(PlainTextTrainer::from_reader(reader)?),
+      Format::Regex(regex) => Box::new(RegexTrainer::new(author, regex, lines(reader))?),
+      Format::IrcLog(format) => Box::new(IrcLogTrainer::new(author, format, lines(reader))?),
+    };
+
+    self.excluding(trainer)
+  }
+
+  /// Wrap a trainer to drop the lines matching the exclude patterns, if any.
+  fn excluding(&self, trainer: impl Trainer + 'static) -> Result<Box<dyn Trainer>, Error> {
+    if self.exclude_patterns.is_empty() {
+      Ok(Box::new(trainer))
+    } else {
+      Ok(Box::new(ExcludingTrainer::new(
+        trainer,
+        &self.exclude_patterns,
+      )?))
+    }
+  }
+
+  /// Create a trainer learning every author of a file of the sources.
+  fn author_trainer(
+    &self,
+    path: &Path,
+    lines_read: Option<&LinesRead>,
+  ) -> Result<Box<dyn AuthorTrainer>, Error> {
+    self.check_filters(path)?;
+
+    let reader = open(path, lines_read)?;
+    let trainer: Box<dyn AuthorTrainer> = match &self.format {
+      Format::Weechat => Box::new(self.weechat_trainer("", path, reader)),
+      Format::Regex(regex) => Box::new(RegexTrainer::new("", regex, lines(reader))?),
+      Format::IrcLog(format) => Box::new(IrcLogTrainer::new("", format, lines(reader))?),
+      _ => {
+        return Err(Error::InvalidParameters(
+          "learning all authors requires Weechat logs, --format-regex or --log-format".to_owned(),
+        ))
+      }
+    };
+
+    if self.exclude_patterns.is_empty() {
+      Ok(trainer)
+    } else {
+      Ok(Box::new(ExcludingTrainer::new(
+        trainer,
+        &self.exclude_patterns,
+      )?))
+    }
+  }
+}
+
+/// Number of lines read from an input, shared with the reader counting them.
+type LinesRead = Rc<Cell<u64>>;
+
+/// Reader counting the lines read through it.
+struct CountingReader {
+  reader: Box<dyn Read>,
+  lines_read: LinesRead,
+}
+
+impl Read for CountingReader {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let read = self.reader.read(buf)?;
+    let lines = buf[..read].iter().filter(|&&byte| byte == b'\n').count();
+    self.lines_read.set(self.lines_read.get() + lines as u64);
+    Ok(read)
+  }
+}
+
+/// Open an input, counting the lines read from it into `lines_read` if any.
+fn open(path: &Path, lines_read: Option<&LinesRead>) -> Result<Box<dyn Read>, Error> {
+  let reader = input::open(path)?;
+
+  match lines_read {
+    Some(lines_read) => Ok(Box::new(CountingReader {
+      reader,
+      lines_read: lines_read.clone(),
+    })),
+    None => Ok(reader),
+  }
+}
+
+/// Stream the lines of an input, so that large logs aren’t loaded in memory.
+fn lines(reader: Box<dyn Read>) -> Lines {
+  Lines::from_reader(BufReader::new(reader))
+}
+
+/// Buffers of Weechat logs to learn, as given on the command line.
+fn channels(channels: Vec<String>, exclude_channels: Vec<String>, no_private: bool) -> Channels {
+  let filter = Channels::new()
+    .except(exclude_channels)
+    .skip_private(no_private);
+
+  if channels.is_empty() {
+    filter
+  } else {
+    filter.only(channels)
+  }
+}
+
+/// Cleanup passes enabled on the command line.
+fn cleanup(strip_formatting: bool, replace_urls: bool, strip_mentions: bool) -> Cleanup {
+  let cleanup = Cleanup::new()
+    .strip_formatting(strip_formatting)
+    .strip_mentions(strip_mentions);
+
+  if replace_urls {
+    cleanup.replace_urls(URL_PLACEHOLDER)
+  } else {
+    cleanup
+  }
+}
+
+/// Parse how to learn Weechat actions.
+fn parse_actions(s: &str) -> Result<Actions, String> {
+  match s {
+    "ignore" => Ok(Actions::Ignore),
+    "plain" => Ok(Actions::Plain),
+    "emote" => Ok(Actions::Emote),
+    _ => Err(format!("unknown way to learn actions: {}", s)),
+  }
+}
+
+/// Print the first `count` cleaned lines of sources, and how many lines of every source are kept,
+/// without training anything.
+fn preview(sources: &[Source], all_authors: bool, count: usize) -> Result<(), Error> {
+  let mut printed = 0;
+
+  for source in sources {
+    for path in expand_all(&source.paths)? {
+      let lines_read = LinesRead::default();
+      let mut kept = 0_u64;
+
+      if all_authors {
+        source
+          .author_trainer(&path, Some(&lines_read))?
+          .for_each_message(&mut |author, message| {
+            if printed < count {
+              println!("<{}> {}", author, message);
+              printed += 1;
+            }
+            kept += 1;
+          })?;
+      } else {
+        source
+          .trainer(&path, Some(&lines_read))?
+          .for_each_line(&mut |line| {
+            if printed < count {
+              println!("{}", line);
+              printed += 1;
+            }
+            kept += 1;
+          })?;
+      }
+
+      // directories are read by their own trainer, which doesn’t count the lines it drops
+      if path.is_dir() {
+        println!("# {}: {} lines kept", path.display(), kept);
+      } else {
+        let read = lines_read.get();
+        println!(
+          "# {}: {} lines kept, {} dropped out of {}",
+          path.display(),
+          kept,
+          read.saturating_sub(kept),
+          read
+        );
+      }
+    }
+  }
+
+  Ok(())
+}
+
+/// Expand the globs of several paths.
+fn expand_all(paths: &[PathBuf]) -> Result<Vec<PathBuf>, Error> {
+  let mut expanded = Vec::new();
+  for path in paths {
+    expanded.extend(input::expand(path)?);
+  }
+
+  Ok(expanded)
+}
+
+/// Name of a model, after its file.
+fn model_name(path: &Path) -> String {
+  path.file_stem().map_or_else(
+    || path.display().to_string(),
+    |stem| stem.to_string_lossy().into_owned(),
+  )
+}
+
+/// Load a model.
+fn load(path: &Path) -> Result<MarkovChainGenerator, Error> {
+  MarkovChainGenerator::load(BufReader::new(File::open(path)?))
+}
+
+/// Load a model, or the persona of an author of a model trained with --all-authors.
+fn load_persona(path: &Path, author: Option<&str>) -> Result<MarkovChainGenerator, Error> {
+  match author {
+    Some(author) => PersonaSet::load(BufReader::new(File::open(path)?))?
+      .remove(author)
+      .ok_or_else(|| Error::UnknownAuthor(author.to_owned())),
+    None => load(path),
+  }
+}
+
+/// Load a model, or create an empty one if it doesn’t exist yet.
+fn load_or_new(path: &Path) -> Result<MarkovChainGenerator, Error> {
+  if path.exists() {
+    load(path)
+  } else {
+    Ok(MarkovChainGenerator::new())
+  }
+}
+
+/// Learning parameters given by options, or else by the parameters `previous` a model was trained
+/// with.
+///
+/// Fails if the options contradict the previous parameters, as mixing wordings of different shapes
+/// would spoil the model.
+fn learning_parameters(
+  learning: &config::Learning,
+  previous: Option<&LearningParameters>,
+) -> Result<LearningParameters, Error> {
+  let was = |trained: fn(&LearningParameters) -> bool| previous.is_some_and(trained);
+
+  let windowing = if learning.sliding || was(|p| p.windowing == Windowing::Sliding) {
+    Windowing::Sliding
+  } else {
+    Windowing::Chunks
+  };
+  let granularity = if learning.characters || was(|p| p.granularity == Granularity::Character) {
+    Granularity::Character
+  } else {
+    Granularity::Word
+  };
+  let wording_size = learning
+    .size
+    .or_else(|| previous.map(|learn_param| learn_param.wording_size))
+    .unwrap_or(2);
+  let learn_param = LearningParameters::new(wording_size)?
+    .windowing(windowing)
+    .variable_order(learning.variable_order || was(|p| p.variable_order))
+    .granularity(granularity)
+    .case_folding(learning.case_folding || was(|p| p.case_folding))
+    .backward(learning.backward || was(|p| p.backward));
+  let learn_param = match learning.verbatim_ngram.or_else(|| previous?.verbatim_ngram) {
+    Some(n) => learn_param.verbatim_ngram(n)?,
+    None => learn_param,
+  };
+
+  if previous.is_some_and(|previous| *previous != learn_param) {
+    return Err(Error::InvalidParameters(
+      "the model was trained with other learning parameters: train a new model to change them"
+        .to_owned(),
+    ));
+  }
+
+  Ok(learn_param)
+}
+
+/// Load a model holding several personas, or create an empty one if it doesn’t exist yet.
+fn load_personas_or_new(path: &Path) -> Result<PersonaSet, Error> {
+  if path.exists() {
+    PersonaSet::load(BufReader::new(File::open(path)?))
+  } else {
+    Ok(PersonaSet::new())
+  }
+}
+
+/// Spinner showing the number of lines learned from a source, unless only errors are shown.
+fn progress_bar(path: &Path) -> ProgressBar {
+  if !log::log_enabled!(log::Level::Info) {
+    return ProgressBar::hidden();
+  }
+
+  let style = ProgressStyle::with_template("{spinner} {msg}: {human_pos} lines ({per_sec})")
+    .expect("valid progress template");
+  ProgressBar::new_spinner()
+    .with_style(style)
+    .with_message(path.display().to_string())
+}
+
+/// Number of lines learned by every persona.
+fn learned_lines(personas: &PersonaSet) -> HashMap<String, u64> {
+  personas
+    .authors()
+    .into_iter()
+    .filter_map(|author| Some((author.to_owned(), personas.get(author)?.info().lines)))
+    .collect()
+}
+
+/// Format a time as a UTC date, such as `2020-05-17 13:37:00 UTC`.
+fn format_time(time: SystemTime) -> String {
+  let date = DateTime::from_time(time);
+
+  format!(
+    "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+    date.year, date.month, date.day, date.hour, date.minute, date.second
+  )
+}
+
+/// Turn a line editing error into an I/O one.
+fn readline_error(err: ReadlineError) -> Error {
+  match err {
+    ReadlineError::Io(err) => Error::Io(err),
+    err => Error::Io(io::Error::other(err.to_string())),
+  }
+}
+
+/// Save a model.
+fn save(markov_chain_generator: &MarkovChainGenerator, path: &Path) -> Result<(), Error> {
+  markov_chain_generator.save(BufWriter::new(File::create(path)?))
+}
+
+fn main() {
+  let CLIOpt {
+    model,
+    config,
+    verbose,
+    quiet,
+    command,
+  } = CLIOpt::from_args();
+  logging::init(verbose, quiet);
+
+  let config = match config.as_deref().map(Config::load).transpose() {
+    Ok(config) => config.unwrap_or_default(),
+    Err(err) => {
+      log::error!("{}", err);
+      process::exit(1);
+    }
+  };
+  let model = model
+    .or_else(|| config.model.clone())
+    .unwrap_or_else(|| PathBuf::from("impersonate.json"));
+
+  if let Err(err) = run(&model, config, command) {
+    log::error!("{}", err);
+    process::exit(1);
+  }
+}
+
+fn run(model: &Path, config: Config, command: Command) -> Result<(), Error> {
+  match command {
+    Command::Train {
+      paths,
+      format,
+      format_regex,
+      log_format,
+      author,
+      aliases,
+      actions,
+      strip_formatting,
+      replace_urls,
+      strip_mentions,
+      exclude_patterns,
+      since,
+      until,
+      channels,
+      exclude_channels,
+      no_private,
+      all_authors,
+      learning_size,
+      sliding,
+      variable_order,
+      characters,
+      case_folding,
+      verbatim_ngram,
+      backward,
+      jobs,
+      checkpoint_every,
+      dry_run,
+      preview_lines,
+    } => {
+      let (mut markov_chain_generator, mut personas) = if all_authors {
+        (MarkovChainGenerator::new(), load_personas_or_new(model)?)
+      } else {
+        (load_or_new(model)?, PersonaSet::new())
+      };
+
+      // parameters the model was trained with are kept unless given, as mixing wordings of
+      // different shapes would spoil it
+      let previous = if all_authors {
+        personas.learning_parameters().cloned()
+      } else {
+        markov_chain_generator.learning_parameters().cloned()
+      };
+      let learning = config::Learning {
+        size: learning_size.or(config.learning.size),
+        sliding: sliding || config.learning.sliding,
+        variable_order: variable_order || config.learning.variable_order,
+        characters: characters || config.learning.characters,
+        case_folding: case_folding || config.learning.case_folding,
+        verbatim_ngram: verbatim_ngram.or(config.learning.verbatim_ngram),
+        backward: backward || config.learning.backward,
+      };
+      let learn_param = learning_parameters(&learning, previous.as_ref())?;
+
+      // sources given on the command line replace the ones of the configuration file
+      let sources = if paths.is_empty() {
+        config
+          .sources
+          .into_iter()
+          .map(Source::from_config)
+          .collect::<Result<Vec<_>, _>>()?
+      } else {
+        let format = match (format_regex, log_format) {
+          (Some(regex), _) => Format::Regex(regex),
+          (None, Some(log_format)) => Format::IrcLog(log_format),
+          (None, None) => format,
+        };
+
+        vec![Source {
+          paths,
+          format,
+          author: author.unwrap_or_default(),
+          aliases,
+          actions,
+          cleanup: cleanup(strip_formatting, replace_urls, strip_mentions),
+          time_range: TimeRange::new(since.as_deref(), until.as_deref())?,
+          channels: self::channels(channels, exclude_channels, no_private),
+          exclude_patterns,
+        }]
+      };
+
+      if sources.iter().all(|source| source.paths.is_empty()) {
+        return Err(Error::InvalidParameters(
+          "no source to train on: give paths, or sources in the configuration file".to_owned(),
+        ));
+      }
+
+      if dry_run {
+        return preview(&sources, all_authors, preview_lines);
+      }
+
+      if all_authors {
+        for source in &sources {
+          for path in expand_all(&source.paths)? {
+            let before = learned_lines(&personas);
+            source
+              .author_trainer(&path, None)?
+              .persona_train(&mut personas, &learn_param)?;
+
+            // only the personas that learned something from the source record it
+            for (author, lines) in learned_lines(&personas) {
+              if before.get(&author) != Some(&lines) {
+                if let Some(generator) = personas.get_mut(&author) {
+                  generator.record_source(path.display().to_string());
+                }
+              }
+            }
+          }
+        }
+
+        return personas.save(BufWriter::new(File::create(model)?));
+      }
+
+      if let Some(jobs) = jobs {
+        rayon::ThreadPoolBuilder::new()
+          .num_threads(jobs)
+          .build_global()
+          .map_err(|err| Error::InvalidParameters(err.to_string()))?;
+      }
+
+      let mut checkpointer = checkpoint_every
+        .map(|every| Checkpointer::new(model, every))
+        .transpose()?;
+
+      for source in &sources {
+        for path in expand_all(&source.paths)? {
+          let mut trainer = source.trainer(&path, None)?;
+          let bar = progress_bar(&path);
+          let mut progress = |lines| bar.set_position(lines as u64);
+          let generator = &mut markov_chain_generator;
+
+          match (&mut checkpointer, jobs.is_some()) {
+            (Some(checkpointer), true) => trainer.source_train_par_with_checkpoints(
+              generator,
+              &learn_param,
+              &mut progress,
+              checkpointer,
+            )?,
+            (Some(checkpointer), false) => trainer.source_train_with_checkpoints(
+              generator,
+              &learn_param,
+              &mut progress,
+              checkpointer,
+            )?,
+            (None, true) => {
+              trainer.source_train_par_with_progress(generator, &learn_param, &mut progress)?
+            }
+            (None, false) => {
+              trainer.source_train_with_progress(generator, &learn_param, &mut progress)?
+            }
+          }
+
+          bar.finish_and_clear();
+          markov_chain_generator.record_source(path.display().to_string());
+        }
+      }
+
+      let author = sources.iter().find(|source| !source.author.is_empty());
+      if let Some(source) = author {
+        markov_chain_generator.set_author(source.author.clone());
+      }
+
+      save(&markov_chain_generator, model)?;
+    }
+
+    Command::Watch {
+      paths,
+      format,
+      author,
+      aliases,
+      save_every,
+    } => {
+      let sources = if paths.is_empty() {
+        config
+          .sources
+          .into_iter()
+          .map(Source::from_config)
+          .collect::<Result<Vec<_>, _>>()?
+      } else {
+        vec![Source {
+          paths,
+          format,
+          author: author.unwrap_or_default(),
+          aliases,
+          actions: Actions::Ignore,
+          cleanup: Cleanup::new(),
+          time_range: TimeRange::default(),
+          channels: Channels::new(),
+          exclude_patterns: Vec::new(),
+        }]
+      };
+
+      let markov_chain_generator = load_or_new(model)?;
+      let learn_param = learning_parameters(
+        &config.learning,
+        markov_chain_generator.learning_parameters(),
+      )?;
+
+      watch::watch(
+        &sources,
+        markov_chain_generator,
+        &learn_param,
+        model,
+        Duration::from_secs(save_every),
+      )?;
+    }
+
+    Command::Generate {
+      output_strings,
+      output_size,
+      uniform,
+      prompt,
+      bidirectional,
+      must_contain,
+      author,
+      back_off,
+      smoothing,
+      reject_verbatim,
+      no_repeat,
+      max_chars,
+      min_wordings,
+      min_chars,
+      attempts,
+      distinct,
+      most_likely,
+      banned_words_file,
+      seed,
+      template,
+    } => {
+      let defaults = config.generate;
+      let output_strings = output_strings.or(defaults.count).unwrap_or(1);
+      let sampling = if uniform || defaults.uniform {
+        Sampling::Uniform
+      } else {
+        Sampling::Weighted
+      };
+      let mut chain_param = ChainParameters::new().sampling(sampling);
+      chain_param.max_state_traversal = output_size.or(defaults.max_wordings);
+      chain_param.prompt = prompt;
+      chain_param.bidirectional = bidirectional;
+      chain_param.must_contain = must_contain;
+      chain_param.back_off = back_off;
+      chain_param.smoothing = smoothing;
+      chain_param.reject_verbatim = reject_verbatim;
+      chain_param.no_repeat = no_repeat.or(defaults.no_repeat);
+      chain_param.max_chars = max_chars.or(defaults.max_chars);
+      chain_param.min_wordings = min_wordings.or(defaults.min_wordings);
+      chain_param.min_chars = min_chars.or(defaults.min_chars);
+      chain_param.attempts = attempts.or(defaults.attempts);
+      chain_param.seed = seed.or(defaults.seed);
+      if let Some(path) = banned_words_file.or(defaults.banned_words_file) {
+        chain_param.banned_words =
+          Some(BannedWords::from_reader(BufReader::new(File::open(path)?))?);
+      }
+
+      let author = author.or(defaults.author);
+      let markov_chain_generator = load_persona(model, author.as_deref())?;
+
+      let template = match template {
+        Some(template) => Some(template),
+        None => defaults
+          .template
+          .map(|template| template.parse().map_err(Error::InvalidParameters))
+          .transpose()?,
+      };
+      let name = author
+        .or_else(|| markov_chain_generator.info().author.map(str::to_owned))
+        .unwrap_or_else(|| model_name(model));
+      let mut clock = Clock::new(chain_param.seed);
+      let mut render = |text: String| match &template {
+        Some(template) => template.render(&name, &text, clock.tick()),
+        None => text,
+      };
+
+      if most_likely {
+        let chains = markov_chain_generator.most_likely_chains(output_strings, &chain_param)?;
+        for chain in chains {
+          println!(
+            "{:.4}\t{}",
+            chain.log_likelihood(),
+            render(chain.to_string())
+          );
+        }
+      } else if distinct {
+        let chains =
+          markov_chain_generator.generate_distinct_chains(output_strings, &chain_param)?;
+        for chain in chains {
+          println!("{}", render(chain.to_string()));
+        }
+      } else {
+        for chain in markov_chain_generator
+          .generate_chains(&chain_param)
+          .take(output_strings)
+        {
+          println!("{}", render(chain?.to_string()));
+        }
+      }
+    }
+
+    Command::Repl {
+      author,
+      uniform,
+      no_repeat,
+      max_chars,
+    } => {
+      let markov_chain_generator = load_persona(model, author.as_deref())?;
+      let sampling = if uniform {
+        Sampling::Uniform
+      } else {
+        Sampling::Weighted
+      };
+      let mut chain_param = ChainParameters::new().sampling(sampling);
+      chain_param.no_repeat = no_repeat;
+      chain_param.max_chars = max_chars;
+
+      let mut editor = DefaultEditor::new().map_err(readline_error)?;
+
+      loop {
+        let line = match editor.readline("> ") {
+          Ok(line) => line,
+          Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => break,
+          Err(err) => return Err(readline_error(err)),
+        };
+        let line = line.trim();
+
+        if !line.is_empty() {
+          let _ = editor.add_history_entry(line);
+          chain_param.prompt = Some(line.to_owned());
+        } else {
+          chain_param.prompt = None;
+        }
+
+        let chain = match markov_chain_generator.generate_chain(&chain_param) {
+          Err(Error::NoStateMatchingPrompt(_)) => {
+            chain_param.prompt = None;
+            markov_chain_generator.generate_chain(&chain_param)
+          }
+          chain => chain,
+        };
+
+        match chain {
+          Ok(chain) => println!("{}", chain),
+          Err(err) => log::warn!("{}", err),
+        }
+      }
+    }
+
+    Command::Converse {
+      first,
+      second,
+      authors,
+      turns,
+      prompt,
+      seed,
+      max_chars,
+      template,
+    } => {
+      let (speakers, names) = if authors {
+        let speakers = [
+          load_persona(model, Some(&first))?,
+          load_persona(model, Some(&second))?,
+        ];
+        (speakers, [first, second])
+      } else {
+        let name = |path: &str| model_name(Path::new(path));
+        let speakers = [load(Path::new(&first))?, load(Path::new(&second))?];
+        (speakers, [name(&first), name(&second)])
+      };
+
+      let mut chain_param = ChainParameters::new();
+      chain_param.prompt = prompt;
+      chain_param.seed = seed;
+      chain_param.max_chars = max_chars;
+
+      let mut clock = Clock::new(seed);
+      let lines = dialogue::converse(&speakers[0], &speakers[1], turns, &chain_param)?;
+      for (turn, line) in lines.into_iter().enumerate() {
+        println!(
+          "{}",
+          template.render(&names[turn % 2], &line.to_string(), clock.tick())
+        );
+      }
+    }
+
+    Command::Stats { top, author } => {
+      let markov_chain_generator = load_persona(model, author.as_deref())?;
+      let stats = markov_chain_generator.stats();
+      let info = markov_chain_generator.info();
+
+      if let Some(author) = info.author {
+        println!("author: {}", author);
+      }
+      for source in info.sources {
+        println!("source: {}", source);
+      }
+      if let Some(trained_at) = info.trained_at {
+        println!("trained at: {}", format_time(trained_at));
+      }
+      println!("lines: {}", info.lines);
+      if let Some(learn_param) = info.learn_param {
+        println!("learning parameters: {:?}", learn_param);
+      }
+
+      println!("states: {}", stats.states);
+      println!("transitions: {}", stats.transitions);
+      println!("occurrences: {}", stats.occurrences);
+      println!("vocabulary: {}", stats.vocabulary);
+      println!("branching factor: {:.2}", stats.branching_factor());
+
+      for (wording, count) in markov_chain_generator.top_wordings(top) {
+        println!("{}\t{}", count, wording);
+      }
+    }
+
+    Command::Prune {
+      min_count,
+      max_states,
+    } => {
+      let mut markov_chain_generator = load(model)?;
+      let before = markov_chain_generator.stats();
+
+      markov_chain_generator.prune(min_count);
+      if let Some(max_states) = max_states {
+        markov_chain_generator.prune_states(max_states);
+      }
+
+      let after = markov_chain_generator.stats();
+      log::info!(
+        "pruned {} states and {} transitions",
+        before.states - after.states,
+        before.transitions - after.transitions
+      );
+
+      save(&markov_chain_generator, model)?;
+    }
+
+    Command::Decay { factor, min_count } => {
+      let mut markov_chain_generator = load(model)?;
+      markov_chain_generator.decay(factor, min_count)?;
+      save(&markov_chain_generator, model)?;
+    }
+
+    Command::Map { output, author } => {
+      let markov_chain_generator = load_persona(model, author.as_deref())?;
+      markov_chain_generator.save_mapped(BufWriter::new(File::create(output)?))?;
+    }
+
+    Command::Export {
+      output,
+      tsv,
+      author,
+    } => {
+      let markov_chain_generator = load_persona(model, author.as_deref())?;
+      let delimiter = if tsv { '\t' } else { ',' };
+
+      match output {
+        Some(output) => markov_chain_generator
+          .export_transitions_delimited(BufWriter::new(File::create(output)?), delimiter)?,
+        None => {
+          markov_chain_generator.export_transitions_delimited(io::stdout().lock(), delimiter)?
+        }
+      }
+    }
+
+    Command::ImportMarkovify { input } => {
+      let mut markov_chain_generator = load_or_new(model)?;
+      markov_chain_generator.merge(&markovify::import(BufReader::new(File::open(&input)?))?);
+      markov_chain_generator.record_source(input.display().to_string());
+      save(&markov_chain_generator, model)?;
+    }
+
+    Command::ExportMarkovify { output, author } => {
+      let markov_chain_generator = load_persona(model, author.as_deref())?;
+      markovify::export(
+        &markov_chain_generator,
+        BufWriter::new(File::create(output)?),
+      )?;
+    }
+
+    Command::Merge { models } => {
+      let mut markov_chain_generator = load_or_new(model)?;
+
+      for path in models {
+        markov_chain_generator.merge(&load(&path)?);
+      }
+
+      save(&markov_chain_generator, model)?;
+    }
+
+    Command::Inspect {
+      wording,
+      top,
+      dot,
+      min_count,
+      max_edges,
+      author,
+    } => {
+      let markov_chain_generator = load_persona(model, author.as_deref())?;
+
+      if dot {
+        let options = DotOptions::new().min_count(min_count);
+        let options = match max_edges {
+          Some(max_edges) => options.max_edges(max_edges),
+          None => options,
+        };
+
+        print!("{}", markov_chain_generator.to_dot(&options));
+        return Ok(());
+      }
+
+      let wording = wording
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(str::to_owned)
+        .collect::<Wording>();
+
+      let continuations = markov_chain_generator.continuations(&wording);
+      if continuations.is_empty() {
+        log::warn!(
+          "no continuation for {}; wordings must be as long as the learned ones",
+          wording
+        );
+      }
+
+      for (next, probability) in continuations.into_iter().take(top.unwrap_or(usize::MAX)) {
+        println!("{:.4}\t{}", probability, next);
+      }
+    }
+
+    Command::Score { texts, author } => {
+      let markov_chain_generator = load_persona(model, author.as_deref())?;
+
+      for text in texts {
+        println!(
+          "{:.4}\t{:.4}\t{}",
+          markov_chain_generator.score(&text),
+          markov_chain_generator.perplexity(&text),
+          text
+        );
+      }
+    }
+
+    Command::Completions { shell, man } => {
+      let mut app = CLIOpt::clap();
+
+      match shell {
+        Some(shell) => app.gen_completions_to("impersonate", shell, &mut io::stdout().lock()),
+        None if man => print!("{}", man::render(&app)),
+        None => (),
+      }
+    }
+
+    Command::Classify {
+      text,
+      models,
+      author,
+    } => {
+      let mut model_set = ModelSet::new();
+
+      for path in models {
+        model_set.insert(model_name(&path), load_persona(&path, author.as_deref())?);
+      }
+
+      for (name, perplexity) in model_set.rank(&text) {
+        println!("{:.4}\t{}", perplexity, name);
+      }
+    }
+  }
+
+  Ok(())
+}
@@ -0,0 +1,99 @@
+//! Manual page of the command line interface, generated from its definition.
+//!
+//! The page holds the long help of the command and of every subcommand, as `--help` prints them,
+//! so that it never gets out of date.
+
+use structopt::clap::{App, ErrorKind};
+
+/// Escape text for roff, in no-fill mode.
+fn escape(text: &str) -> String {
+  text
+    .lines()
+    .map(|line| {
+      let line = line.replace('\\', "\\e");
+
+      // lines starting with a dot or a quote would be requests
+      if line.starts_with(['.', '\'']) {
+        format!("\\&{}", line)
+      } else {
+        line
+      }
+    })
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+/// Long help of a command, or of one of its subcommands.
+fn long_help(app: &App, args: &[&str]) -> String {
+  let args = std::iter::once(app.get_name())
+    .chain(args.iter().copied())
+    .chain(std::iter::once("--help"));
+
+  match app.clone().get_matches_from_safe(args) {
+    Err(err) if err.kind == ErrorKind::HelpDisplayed => err.message,
+    _ => String::new(),
+  }
+}
+
+/// Names of the subcommands listed in the long help of a command, except `help`.
+fn subcommands(help: &str) -> Vec<&str> {
+  help
+    .lines()
+    .skip_while(|line| *line != "SUBCOMMANDS:")
+    .skip(1)
+    .take_while(|line| line.is_empty() || line.starts_with(' '))
+    // subcommands are indented by four spaces, and wrapped descriptions further
+    .filter(|line| line.starts_with("    ") && !line[4..].starts_with(' '))
+    .filter_map(|line| line.split_whitespace().next())
+    .filter(|&subcommand| subcommand != "help")
+    .collect()
+}
+
+/// Render the manual page of a command in roff.
+pub fn render(app: &App) -> String {
+  let name = app.get_name();
+  let help = long_help(app, &[]);
+  let about = help.lines().nth(1).unwrap_or_default();
+
+  let mut page = format!(
+    ".TH {} 1\n.SH NAME\n{} \\- {}\n.SH DESCRIPTION\n.nf\n{}\n.fi\n.SH COMMANDS\n",
+    name.to_uppercase(),
+    name,
+    escape(about),
+    escape(&help)
+  );
+
+  for subcommand in subcommands(&help) {
+    page.push_str(&format!(
+      ".SS {}\n.nf\n{}\n.fi\n",
+      subcommand,
+      escape(&long_help(app, &[subcommand]))
+    ));
+  }
+
+  page
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::CLIOpt;
+  use structopt::StructOpt;
+
+  #[test]
+  fn test_render() {
+    let page = render(&CLIOpt::clap());
+
+    assert!(page.starts_with(".TH IMPERSONATE 1\n"));
+    for subcommand in &["train", "export-markovify", "watch"] {
+      assert!(
+        page.contains(&format!(".SS {}\n", subcommand)),
+        "{}",
+        subcommand
+      );
+    }
+    assert!(!page.contains(".SS help\n"));
+    // wrapped descriptions aren’t taken for subcommands
+    assert!(!page.contains(".SS other\n"));
+  }
+}
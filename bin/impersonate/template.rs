@@ -0,0 +1,250 @@
+//! Templates rendering generated strings as lines of fake chat logs.
+//!
+//! A template is a line where `{author}` and `{text}` are replaced with the impersonated author and
+//! the generated string, and strftime-like directives with the UTC time of the line: `%Y` (year),
+//! `%m` (month), `%d` (day), `%H` (hour), `%M` (minutes) and `%S` (seconds). `{{`, `}}` and `%%`
+//! are literal braces and percent signs, and `\t` and `\n` tabs and line breaks, which are awkward
+//! to pass on a command line. For instance, `%Y-%m-%d %H:%M:%S\t{author}\t{text}` renders lines
+//! of Weechat logs.
+//!
+//! Successive lines are given increasing times, as if they were written a few seconds apart,
+//! starting from now.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Maximum number of seconds between two lines.
+const MAX_GAP_SECS: u64 = 60;
+
+/// A UTC date and time.
+pub struct DateTime {
+  pub year: u64,
+  pub month: u64,
+  pub day: u64,
+  pub hour: u64,
+  pub minute: u64,
+  pub second: u64,
+}
+
+impl DateTime {
+  /// Date and time of a point in time, in UTC.
+  pub fn from_time(time: SystemTime) -> Self {
+    let secs = time
+      .duration_since(UNIX_EPOCH)
+      .map_or(0, |elapsed| elapsed.as_secs());
+    let (days, secs) = (secs / 86_400, secs % 86_400);
+
+    // civil date from the number of days since the epoch, after Howard Hinnant’s algorithm
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    Self {
+      year,
+      month,
+      day,
+      hour: secs / 3600,
+      minute: secs / 60 % 60,
+      second: secs % 60,
+    }
+  }
+}
+
+/// Piece of a template.
+#[derive(Clone, Debug, PartialEq)]
+enum Part {
+  Literal(String),
+  Author,
+  Text,
+  /// strftime-like directive, without its `%`.
+  Time(char),
+}
+
+/// A line template, such as `<{author}> {text}`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+  parts: Vec<Part>,
+}
+
+impl FromStr for Template {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+      let part = match (c, chars.peek().copied()) {
+        ('{', Some('{')) | ('}', Some('}')) | ('%', Some('%')) | ('\\', Some('\\')) => {
+          chars.next();
+          literal.push(c);
+          continue;
+        }
+
+        ('\\', Some('t')) | ('\\', Some('n')) => {
+          let escaped = if chars.next() == Some('t') {
+            '\t'
+          } else {
+            '\n'
+          };
+          literal.push(escaped);
+          continue;
+        }
+
+        ('{', _) => {
+          let mut name = String::new();
+          loop {
+            match chars.next() {
+              Some('}') => break,
+              Some(c) => name.push(c),
+              None => return Err(format!("unterminated placeholder {{{} in template", name)),
+            }
+          }
+
+          match name.as_str() {
+            "author" => Part::Author,
+            "text" => Part::Text,
+            _ => return Err(format!("unknown placeholder {{{}}} in template", name)),
+          }
+        }
+
+        ('%', Some(directive)) if "YmdHMS".contains(directive) => {
+          chars.next();
+          Part::Time(directive)
+        }
+
+        ('%', directive) => {
+          let directive = directive.map(String::from).unwrap_or_default();
+          return Err(format!("unknown directive %{} in template", directive));
+        }
+
+        _ => {
+          literal.push(c);
+          continue;
+        }
+      };
+
+      if !literal.is_empty() {
+        parts.push(Part::Literal(std::mem::take(&mut literal)));
+      }
+      parts.push(part);
+    }
+
+    if !literal.is_empty() {
+      parts.push(Part::Literal(literal));
+    }
+
+    Ok(Self { parts })
+  }
+}
+
+impl Template {
+  /// Render a line written by `author` at `time`.
+  pub fn render(&self, author: &str, text: &str, time: SystemTime) -> String {
+    let date = DateTime::from_time(time);
+    let mut line = String::new();
+
+    for part in &self.parts {
+      match part {
+        Part::Literal(literal) => line.push_str(literal),
+        Part::Author => line.push_str(author),
+        Part::Text => line.push_str(text),
+        Part::Time('Y') => line.push_str(&format!("{:04}", date.year)),
+        Part::Time(directive) => {
+          let value = match directive {
+            'm' => date.month,
+            'd' => date.day,
+            'H' => date.hour,
+            'M' => date.minute,
+            _ => date.second,
+          };
+          line.push_str(&format!("{:02}", value));
+        }
+      }
+    }
+
+    line
+  }
+}
+
+/// Times of successive lines, a few random seconds apart.
+pub struct Clock {
+  time: SystemTime,
+  rng: StdRng,
+}
+
+impl Clock {
+  /// Start from now; the gaps between lines are reproducible with a seed.
+  pub fn new(seed: Option<u64>) -> Self {
+    let rng = match seed {
+      Some(seed) => StdRng::seed_from_u64(seed),
+      None => StdRng::from_entropy(),
+    };
+
+    Self {
+      time: SystemTime::now(),
+      rng,
+    }
+  }
+
+  /// Time of the next line.
+  pub fn tick(&mut self) -> SystemTime {
+    let time = self.time;
+    self.time += Duration::from_secs(self.rng.gen_range(1, MAX_GAP_SECS + 1));
+    time
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_from_time() {
+    let date = DateTime::from_time(UNIX_EPOCH + Duration::from_secs(951_782_400 + 3723));
+    assert_eq!((date.year, date.month, date.day), (2000, 2, 29), "leap day");
+    assert_eq!((date.hour, date.minute, date.second), (1, 2, 3));
+
+    let date = DateTime::from_time(UNIX_EPOCH);
+    assert_eq!(
+      (date.year, date.month, date.day, date.hour),
+      (1970, 1, 1, 0)
+    );
+  }
+
+  #[test]
+  fn test_render() {
+    let time = UNIX_EPOCH + Duration::from_secs(1_580_464_805);
+    let template = "%Y-%m-%d %H:%M:%S\\t{author}\\t{text}"
+      .parse::<Template>()
+      .unwrap();
+    assert_eq!(
+      template.render("alice", "hello", time),
+      "2020-01-31 10:00:05\talice\thello"
+    );
+
+    let template = "{{{author}}} 100%% {text}\\n\\\\t"
+      .parse::<Template>()
+      .unwrap();
+    assert_eq!(
+      template.render("alice", "hello", time),
+      "{alice} 100% hello\n\\t"
+    );
+  }
+
+  #[test]
+  fn test_parse_errors() {
+    for template in &["{author", "<{author> {text}", "{nick}", "%y", "100%"] {
+      assert!(template.parse::<Template>().is_err(), "{}", template);
+    }
+  }
+}
@@ -0,0 +1,378 @@
+//! Continuous training on logs being written.
+//!
+//! Logs are followed like `tail -f` does: the directories holding them are watched for changes and,
+//! whenever a log grows, the complete lines appended to it since it was last read are learned. The
+//! model is saved periodically, if it learned something, rather than after every line.
+//!
+//! A log rotated by renaming it is followed again once a new log is created at its path, from its
+//! start; failing to read a log is reported without stopping to watch the others.
+//!
+//! The new lines of a log are streamed to the same trainer from a batch to the next, so that state
+//! carried from a line to the next one, such as the nick changes Weechat logs record, survives
+//! between batches. Formats whose trainers read their whole log when created are learned by a fresh
+//! trainer for each batch.
+//!
+//! Watching stops on Ctrl-C (or `SIGTERM`), once the lines learned since the last save are saved.
+
+use impersonate::checkpoint::Checkpointer;
+use impersonate::trainers::compressed;
+use impersonate::{Error, LearningParameters, MarkovChainGenerator, Trainer};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use crate::{expand_all, Source};
+
+/// What the watching loop is woken up by.
+enum Wakeup {
+  /// A change of the file system.
+  Event(notify::Result<Event>),
+  /// Ctrl-C, or a termination signal.
+  Interrupted,
+}
+
+/// Bytes read from a log, waiting to be learned.
+///
+/// Reading an empty pipe reaches its end, but more bytes can be read once pushed.
+#[derive(Clone, Default)]
+struct Pipe(Rc<RefCell<VecDeque<u8>>>);
+
+impl Pipe {
+  fn push(&self, bytes: &[u8]) {
+    self.0.borrow_mut().extend(bytes);
+  }
+}
+
+impl Read for Pipe {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    self.0.borrow_mut().read(buf)
+  }
+}
+
+/// A log followed from a given position.
+struct Follower<'a> {
+  /// Sources the log belongs to.
+  source: &'a Source,
+  /// Path of the log, as given.
+  path: PathBuf,
+  /// Number of bytes already read.
+  offset: u64,
+  /// Lines read from the log, streamed to the trainer.
+  pipe: Pipe,
+  /// Trainer learning the log, kept from a batch to the next if its format is streamed.
+  trainer: Option<Box<dyn Trainer>>,
+}
+
+impl<'a> Follower<'a> {
+  /// Follow a log from its current end.
+  fn new(source: &'a Source, path: PathBuf) -> Result<Self, Error> {
+    let offset = File::open(&path)?.metadata()?.len();
+
+    Ok(Self {
+      source,
+      path,
+      offset,
+      pipe: Pipe::default(),
+      trainer: None,
+    })
+  }
+
+  /// Follow the log from its start again, such as a log just created.
+  ///
+  /// The trainer is kept, as the new log continues the previous one.
+  fn rewind(&mut self) {
+    self.offset = 0;
+  }
+
+  /// Complete lines appended to the log since it was last read, if any.
+  ///
+  /// A log shorter than what was read was truncated or rotated, and is read again from its start.
+  fn read_new(&mut self) -> io::Result<Option<Vec<u8>>> {
+    let mut file = File::open(&self.path)?;
+    let len = file.metadata()?.len();
+
+    if len < self.offset {
+      log::info!(
+        "{} was truncated, reading it from its start",
+        self.path.display()
+      );
+      self.offset = 0;
+    }
+
+    file.seek(SeekFrom::Start(self.offset))?;
+    let mut new = Vec::new();
+    file.take(len - self.offset).read_to_end(&mut new)?;
+
+    // a partial last line is read again once it’s complete
+    match new.iter().rposition(|&byte| byte == b'\n') {
+      Some(end) => {
+        new.truncate(end + 1);
+        self.offset += new.len() as u64;
+        Ok(Some(new))
+      }
+      None => Ok(None),
+    }
+  }
+
+  /// Learn the lines appended to the log, returning how many were learned.
+  fn learn(
+    &mut self,
+    markov_chain_generator: &mut MarkovChainGenerator,
+    learn_param: &LearningParameters,
+  ) -> Result<usize, Error> {
+    let new = match self.read_new()? {
+      Some(new) => new,
+      None => return Ok(0),
+    };
+
+    self.pipe.push(&new);
+    let trainer = match &mut self.trainer {
+      Some(trainer) if self.source.format.is_streamed() => trainer,
+      trainer => trainer.insert(
+        self
+          .source
+          .reader_trainer(&self.path, Box::new(self.pipe.clone()))?,
+      ),
+    };
+
+    let mut lines = 0;
+    trainer.for_each_line(&mut |line| {
+      markov_chain_generator.train(learn_param, line);
+      lines += 1;
+    })?;
+
+    Ok(lines)
+  }
+}
+
+/// Train a model on the lines appended to the files of sources, until interrupted.
+///
+/// The model is saved to `model` every `save_every` at most, if it learned something.
+pub fn watch(
+  sources: &[Source],
+  mut markov_chain_generator: MarkovChainGenerator,
+  learn_param: &LearningParameters,
+  model: &Path,
+  save_every: Duration,
+) -> Result<(), Error> {
+  if save_every.is_zero() {
+    return Err(Error::InvalidParameters(
+      "the model must be saved every one second at least".to_owned(),
+    ));
+  }
+
+  // logs are identified by their canonical paths, as notified
+  let mut followers = HashMap::new();
+  for source in sources {
+    for path in expand_all(&source.paths)? {
+      if path == Path::new("-") || path.is_dir() || compressed::uncompressed_path(&path) != path {
+        return Err(Error::InvalidParameters(format!(
+          "cannot watch {}: only uncompressed files can be watched",
+          path.display()
+        )));
+      }
+
+      followers.insert(path.canonicalize()?, Follower::new(source, path)?);
+    }
+  }
+
+  if followers.is_empty() {
+    return Err(Error::InvalidParameters(
+      "no log to watch: give paths, or sources in the configuration file".to_owned(),
+    ));
+  }
+
+  let (sender, receiver) = mpsc::channel();
+  let interrupted = sender.clone();
+  ctrlc::set_handler(move || {
+    let _ = interrupted.send(Wakeup::Interrupted);
+  })
+  .map_err(|err| Error::Io(io::Error::other(err)))?;
+
+  // directories are watched rather than files, so that rotated logs are followed
+  let mut watcher = notify::recommended_watcher(move |event| {
+    let _ = sender.send(Wakeup::Event(event));
+  })
+  .map_err(watch_error)?;
+  let dirs = followers
+    .keys()
+    .filter_map(|path| path.parent())
+    .collect::<BTreeSet<_>>();
+  for dir in dirs {
+    watcher
+      .watch(dir, RecursiveMode::NonRecursive)
+      .map_err(watch_error)?;
+  }
+
+  log::info!("watching {} logs", followers.len());
+
+  let mut checkpointer = Checkpointer::new(model, 1)?;
+  let mut pending = 0;
+  let mut saved = Instant::now();
+
+  loop {
+    let timeout = save_every.saturating_sub(saved.elapsed());
+
+    match receiver.recv_timeout(timeout) {
+      Ok(Wakeup::Event(Ok(event)))
+        if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) =>
+      {
+        for path in event.paths {
+          if let Some(follower) = followers.get_mut(&path) {
+            // a log created anew, for instance once the previous one was rotated
+            if let EventKind::Create(_) = event.kind {
+              log::info!(
+                "{} was created, reading it from its start",
+                follower.path.display()
+              );
+              follower.rewind();
+            }
+
+            match follower.learn(&mut markov_chain_generator, learn_param) {
+              Ok(0) => (),
+              Ok(lines) => {
+                log::debug!("learned {} lines from {}", lines, follower.path.display());
+                pending += lines;
+              }
+              Err(err) => log::warn!("cannot read {}: {}", follower.path.display(), err),
+            }
+          }
+        }
+      }
+      Ok(Wakeup::Event(Ok(_))) | Err(RecvTimeoutError::Timeout) => (),
+      Ok(Wakeup::Event(Err(err))) => log::warn!("{}", err),
+      Ok(Wakeup::Interrupted) | Err(RecvTimeoutError::Disconnected) => break,
+    }
+
+    if saved.elapsed() >= save_every {
+      if pending > 0 {
+        checkpointer.save(&markov_chain_generator)?;
+        log::info!("saved the model, after learning {} lines", pending);
+        pending = 0;
+      }
+
+      saved = Instant::now();
+    }
+  }
+
+  if pending > 0 {
+    checkpointer.save(&markov_chain_generator)?;
+    log::info!("saved the model, after learning {} lines", pending);
+  }
+
+  Ok(())
+}
+
+/// Report a failure of the file system notifications as an I/O error.
+fn watch_error(err: notify::Error) -> Error {
+  Error::Io(io::Error::other(err))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use impersonate::cleanup::Cleanup;
+  use impersonate::trainers::weechat::{Actions, Channels, TimeRange};
+  use std::env;
+  use std::fs::{self, OpenOptions};
+  use std::io::Write;
+
+  use crate::Format;
+
+  #[test]
+  fn test_read_new() {
+    let path = env::temp_dir().join(format!("impersonate-watch-{}.log", std::process::id()));
+    fs::write(&path, "old line\n").unwrap();
+    let source = Source {
+      paths: vec![path.clone()],
+      format: Format::Plain,
+      author: String::new(),
+      aliases: Vec::new(),
+      actions: Actions::Ignore,
+      cleanup: Cleanup::new(),
+      time_range: TimeRange::default(),
+      channels: Channels::new(),
+      exclude_patterns: Vec::new(),
+    };
+    let append = |text: &str| {
+      let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+      file.write_all(text.as_bytes()).unwrap();
+    };
+
+    // lines written before following the log are skipped
+    let mut follower = Follower::new(&source, path.clone()).unwrap();
+    assert_eq!(follower.read_new().unwrap(), None);
+
+    // a partial line is read once complete
+    append("first\nsec");
+    assert_eq!(follower.read_new().unwrap(), Some(b"first\n".to_vec()));
+    assert_eq!(follower.read_new().unwrap(), None);
+    append("ond\n");
+    assert_eq!(follower.read_new().unwrap(), Some(b"second\n".to_vec()));
+
+    // a truncated log is read again from its start
+    fs::write(&path, "new\n").unwrap();
+    assert_eq!(follower.read_new().unwrap(), Some(b"new\n".to_vec()));
+
+    // a rotated log is missing until created anew, and then read from its start
+    fs::remove_file(&path).unwrap();
+    assert!(follower.read_new().is_err());
+    fs::write(&path, "rotated\n").unwrap();
+    follower.rewind();
+    assert_eq!(follower.read_new().unwrap(), Some(b"rotated\n".to_vec()));
+    fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn test_learn_renames() {
+    let path = env::temp_dir().join(format!(
+      "impersonate-watch-renames-{}.weechatlog",
+      std::process::id()
+    ));
+    fs::write(&path, "").unwrap();
+    let source = Source {
+      paths: vec![path.clone()],
+      format: Format::Weechat,
+      author: "bob".to_owned(),
+      aliases: Vec::new(),
+      actions: Actions::Ignore,
+      cleanup: Cleanup::new(),
+      time_range: TimeRange::default(),
+      channels: Channels::new(),
+      exclude_patterns: Vec::new(),
+    };
+    let append = |text: &str| {
+      let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+      file.write_all(text.as_bytes()).unwrap();
+    };
+    let mut markov_chain_generator = MarkovChainGenerator::new();
+    let learn_param = LearningParameters::new(1).unwrap();
+
+    // a nick change read in a batch is still followed in the next ones
+    let mut follower = Follower::new(&source, path.clone()).unwrap();
+    append(
+      "2020-01-31 10:00:00\tbob\thello\n\
+       2020-01-31 10:00:01\t--\tbob is now known as bob_\n",
+    );
+    assert_eq!(
+      follower
+        .learn(&mut markov_chain_generator, &learn_param)
+        .unwrap(),
+      1
+    );
+    append("2020-01-31 10:00:02\tbob_\tstill me\n");
+    assert_eq!(
+      follower
+        .learn(&mut markov_chain_generator, &learn_param)
+        .unwrap(),
+      1
+    );
+    fs::remove_file(&path).unwrap();
+  }
+}
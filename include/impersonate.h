@@ -0,0 +1,41 @@
+/* C interface of impersonate, built with the `ffi` feature. See src/ffi.rs. */
+
+#ifndef IMPERSONATE_H
+#define IMPERSONATE_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* A generator, along with the parameters it learns with. */
+typedef struct Impersonate Impersonate;
+
+/* Create an empty generator learning wordings of `wording_size` words; NULL if it's 0. */
+Impersonate *impersonate_new(size_t wording_size);
+
+/* Load a saved generator; NULL if the model cannot be read. */
+Impersonate *impersonate_load_model(const char *path);
+
+/* Save a generator; 0 on success, -1 on failure. */
+int impersonate_save_model(const Impersonate *impersonate, const char *path);
+
+/* Train a generator on a line; 0 on success, -1 on failure. */
+int impersonate_train_line(Impersonate *impersonate, const char *line);
+
+/* Generate a string, starting from `prompt` unless it's NULL; NULL on failure. The string must be
+ * released with impersonate_string_free. */
+char *impersonate_generate(const Impersonate *impersonate, const char *prompt);
+
+/* Release a string returned by impersonate_generate. */
+void impersonate_string_free(char *string);
+
+/* Release a generator. */
+void impersonate_free(Impersonate *impersonate);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
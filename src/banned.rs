@@ -0,0 +1,119 @@
+//! Words that must never be generated.
+//!
+//! Old logs might hold slurs, passwords pasted in the wrong window or anything else a bot
+//! shouldn’t repeat. A [`BannedWords`] denylist, set with [`ChainParameters::banned_words`], makes
+//! generation avoid the wordings containing any of them, and generate chains again if one slips
+//! through anyway (for instance from a prompt).
+//!
+//! [`ChainParameters::banned_words`]: crate::ChainParameters::banned_words
+
+use regex::RegexSet;
+use std::io::BufRead;
+
+use crate::{Error, Wording};
+
+/// A denylist of words.
+///
+/// Every entry is a regular expression matching whole tokens, ignoring case: `darn` bans `Darn`
+/// but not `darned`, while `darn\w*` bans both.
+#[derive(Clone, Debug)]
+pub struct BannedWords {
+  patterns: Vec<String>,
+  set: RegexSet,
+}
+
+impl BannedWords {
+  /// Ban the words matched by `patterns`.
+  ///
+  /// Fails if any of the patterns isn’t a valid regular expression.
+  pub fn new<I, P>(patterns: I) -> Result<Self, Error>
+  where
+    I: IntoIterator<Item = P>,
+    P: Into<String>,
+  {
+    let patterns = patterns.into_iter().map(Into::into).collect::<Vec<_>>();
+    let set = RegexSet::new(
+      patterns
+        .iter()
+        .map(|pattern| format!("(?i)^(?:{})$", pattern)),
+    )
+    .map_err(|err| Error::InvalidParameters(format!("invalid banned word: {}", err)))?;
+
+    Ok(Self { patterns, set })
+  }
+
+  /// Read the patterns from a denylist, one per line.
+  ///
+  /// Blank lines and lines starting with `#` are ignored.
+  pub fn from_reader(reader: impl BufRead) -> Result<Self, Error> {
+    let mut patterns = Vec::new();
+
+    for line in reader.lines() {
+      let line = line?;
+      let line = line.trim();
+
+      if !line.is_empty() && !line.starts_with('#') {
+        patterns.push(line.to_owned());
+      }
+    }
+
+    Self::new(patterns)
+  }
+
+  /// Patterns of the banned words.
+  pub fn patterns(&self) -> &[String] {
+    &self.patterns
+  }
+
+  /// Whether a token is banned.
+  pub fn is_banned(&self, token: &str) -> bool {
+    self.set.is_match(token)
+  }
+
+  /// Whether a wording contains a banned token.
+  pub(crate) fn bans(&self, wording: &Wording) -> bool {
+    wording.tokens().any(|token| self.is_banned(token))
+  }
+}
+
+impl PartialEq for BannedWords {
+  fn eq(&self, other: &Self) -> bool {
+    self.patterns == other.patterns
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{ChainParameters, LearningParameters, MarkovChainGenerator};
+
+  #[test]
+  fn test_banned_words() {
+    let banned = BannedWords::from_reader("# swearing\ndarn\n\nheck\\w*\n".as_bytes()).unwrap();
+    assert_eq!(banned.patterns(), &["darn", "heck\\w*"]);
+    assert!(banned.is_banned("Darn"));
+    assert!(!banned.is_banned("darned"));
+    assert!(banned.is_banned("heckin"));
+    assert!(BannedWords::new(vec!["(oops"]).is_err());
+
+    let learn_param = LearningParameters::new(1).unwrap();
+    let mut generator = MarkovChainGenerator::new();
+    generator.train(&learn_param, "oh darn it");
+    generator.train(&learn_param, "oh well");
+
+    let chain_param = ChainParameters::new().banned_words(banned);
+    for seed in 0..20 {
+      let chain_param = chain_param.clone().seed(seed);
+      assert_eq!(
+        generator.generate_chain(&chain_param).unwrap().to_string(),
+        "oh well"
+      );
+    }
+
+    let chain_param = chain_param.prompt("darn");
+    assert!(matches!(
+      generator.generate_chain(&chain_param),
+      Err(Error::NoAcceptableChain(_))
+    ));
+  }
+}
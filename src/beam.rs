@@ -0,0 +1,199 @@
+//! Beam search of the most likely chains.
+
+use std::hash::BuildHasher;
+
+use crate::{Chain, ChainParameters, Error, MarkovChainGenerator, Sampling, Wording};
+
+/// Number of wordings chains go through at maximum, unless
+/// [`ChainParameters::max_state_traversal`] is set.
+const MAX_STEPS: usize = 100;
+
+/// A chain being searched.
+struct Hypothesis<'a> {
+  path: Vec<&'a Wording>,
+  log_probability: f64,
+  finished: bool,
+}
+
+impl<'a> Hypothesis<'a> {
+  /// Number of wordings the chain went through, without the line start and end.
+  fn len(&self) -> usize {
+    self
+      .path
+      .iter()
+      .filter(|wording| !wording.is_start() && !wording.is_end())
+      .count()
+  }
+}
+
+impl<S> MarkovChainGenerator<S>
+where
+  S: BuildHasher + Default,
+{
+  /// Find the `n` most likely chains, from the most to the least likely.
+  ///
+  /// This is a deterministic beam search of width `n`: at every step, all the transitions of the
+  /// chains found so far are followed, and only the `n` most likely chains are kept. Chains start
+  /// at the line start, at the states matching [`ChainParameters::prompt`] if set, or at any state
+  /// weighted by how often it was seen if the generator knows no line start.
+  ///
+  /// The probability of a chain includes the one of its line end, so short chains are favoured;
+  /// [`ChainParameters::min_wordings`] discards them. Chains go through
+  /// [`ChainParameters::max_state_traversal`] wordings at maximum (100 by default), and are cut if
+  /// they don’t end by then. Other parameters are ignored.
+  ///
+  /// Chains that cannot be completed, such as too short ones, are dropped from the beam, so fewer
+  /// than `n` chains might be found.
+  pub fn most_likely_chains(
+    &self,
+    n: usize,
+    chain_param: &ChainParameters,
+  ) -> Result<Vec<Chain>, Error> {
+    if self.states.is_empty() {
+      return Err(Error::EmptyModel);
+    }
+
+    let starts = if let Some(prompt) = &chain_param.prompt {
+      let keys = self.prompt_states(prompt)?;
+
+      if keys.is_empty() {
+        return Err(Error::NoStateMatchingPrompt(prompt.clone()));
+      }
+
+      let log_probability = -(keys.len() as f64).ln();
+      keys.into_iter().map(|key| (key, log_probability)).collect()
+    } else if let Some((start, _)) = self.states.get_key_value(&Wording::start()) {
+      vec![(start, 0.)]
+    } else {
+      let weights = self
+        .states
+        .iter()
+        .map(|(wording, state)| (wording, state.total_weight(Sampling::Weighted)))
+        .collect::<Vec<_>>();
+      let total = weights.iter().map(|(_, weight)| weight).sum::<f64>();
+
+      weights
+        .into_iter()
+        .map(|(wording, weight)| (wording, (weight / total).ln()))
+        .collect::<Vec<_>>()
+    };
+
+    let min_wordings = chain_param.min_wordings.unwrap_or(0);
+    let mut beam = starts
+      .into_iter()
+      .map(|(wording, log_probability)| Hypothesis {
+        path: vec![wording],
+        log_probability,
+        finished: false,
+      })
+      .collect::<Vec<_>>();
+    Self::prune_beam(&mut beam, n);
+
+    for _ in 0..chain_param.max_state_traversal.unwrap_or(MAX_STEPS) {
+      if beam.iter().all(|hypothesis| hypothesis.finished) {
+        break;
+      }
+
+      let mut candidates = Vec::new();
+
+      for hypothesis in beam {
+        let last = hypothesis.path[hypothesis.path.len() - 1];
+        let state = match self.states.get(last) {
+          Some(state) if !hypothesis.finished => state,
+          _ => {
+            candidates.push(hypothesis);
+            continue;
+          }
+        };
+
+        let total = state.total_weight(Sampling::Weighted);
+        let len = hypothesis.len();
+
+        for (next, transition) in &state.nexts {
+          if next.is_end() && len < min_wordings {
+            continue;
+          }
+
+          let mut path = hypothesis.path.clone();
+          path.push(next);
+          candidates.push(Hypothesis {
+            path,
+            log_probability: hypothesis.log_probability + (transition.count / total).ln(),
+            finished: next.is_end(),
+          });
+        }
+      }
+
+      beam = candidates;
+      Self::prune_beam(&mut beam, n);
+    }
+
+    let case_folding = self
+      .learn_param
+      .as_ref()
+      .is_some_and(|learn_param| learn_param.case_folding);
+
+    let chains = beam
+      .into_iter()
+      .map(|hypothesis| {
+        let mut chain = self.path_chain(&hypothesis.path);
+        if case_folding {
+          self.restore_case(&mut chain.tokens);
+        }
+        chain
+      })
+      .collect();
+
+    Ok(chains)
+  }
+
+  /// Keep the `n` most likely hypotheses, sorted; ties are broken by the wordings.
+  fn prune_beam(beam: &mut Vec<Hypothesis>, n: usize) {
+    beam.sort_by(|a, b| {
+      b.log_probability
+        .total_cmp(&a.log_probability)
+        .then_with(|| a.path.cmp(&b.path))
+    });
+    beam.truncate(n);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{Chain, ChainParameters, LearningParameters, MarkovChainGenerator};
+
+  #[test]
+  fn test_most_likely_chains() {
+    let learn_param = LearningParameters::new(1).unwrap();
+    let mut generator = MarkovChainGenerator::new();
+    generator.train(&learn_param, "I like cheese");
+    generator.train(&learn_param, "I like cheese");
+    generator.train(&learn_param, "I like bread a lot");
+    generator.train(&learn_param, "you like cheese");
+
+    let chains = generator
+      .most_likely_chains(3, &ChainParameters::new())
+      .unwrap();
+    let texts = chains.iter().map(Chain::to_string).collect::<Vec<_>>();
+    // the last two chains are as likely, and sorted by their wordings
+    assert_eq!(
+      texts,
+      vec!["I like cheese", "I like bread a lot", "you like cheese"]
+    );
+    assert!(chains[0].log_likelihood() > chains[1].log_likelihood());
+
+    let chain_param = ChainParameters::new().prompt("bread");
+    let chains = generator.most_likely_chains(1, &chain_param).unwrap();
+    assert_eq!(chains[0].to_string(), "bread a lot");
+
+    let chain_param = ChainParameters::new().min_wordings(4);
+    let chains = generator.most_likely_chains(2, &chain_param).unwrap();
+    assert_eq!(chains.len(), 1);
+    assert_eq!(chains[0].to_string(), "I like bread a lot");
+
+    assert!(generator
+      .most_likely_chains(0, &ChainParameters::new())
+      .unwrap()
+      .is_empty());
+  }
+}
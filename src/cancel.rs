@@ -0,0 +1,71 @@
+//! Cancellation of running generations.
+//!
+//! Generating a chain usually takes microseconds, but a huge [`ChainParameters::max_state_traversal`]
+//! on a cyclic model, or many rejected attempts, can keep a thread busy for a long time. An async
+//! bot generates in a blocking task and, when it gives up waiting, cancels the
+//! [`CancellationToken`] it passed to
+//! [`MarkovChainGenerator::generate_chain_cancellable`](crate::MarkovChainGenerator::generate_chain_cancellable),
+//! which stops the generation at the next transition.
+//!
+//! [`ChainParameters::max_state_traversal`]: crate::ChainParameters::max_state_traversal
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A flag telling generations to stop, shared between the threads generating and cancelling.
+///
+/// Clones share the same flag.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+  cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+  /// Create a token that isn’t cancelled.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Cancel the generations using this token, or any of its clones.
+  pub fn cancel(&self) {
+    self.cancelled.store(true, Ordering::Relaxed);
+  }
+
+  /// Whether the token was cancelled.
+  pub fn is_cancelled(&self) -> bool {
+    self.cancelled.load(Ordering::Relaxed)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{ChainParameters, Error, LearningParameters, MarkovChainGenerator};
+
+  #[test]
+  fn test_cancellation() {
+    let learn_param = LearningParameters::new(1).unwrap();
+    let mut generator = MarkovChainGenerator::new();
+    generator.train(&learn_param, "round and round and round");
+
+    let chain_param = ChainParameters::new().seed(0);
+    let cancel = CancellationToken::new();
+    assert_eq!(
+      generator
+        .generate_chain_cancellable(&chain_param, &cancel)
+        .unwrap(),
+      generator.generate_chain(&chain_param).unwrap()
+    );
+
+    cancel.clone().cancel();
+    assert!(cancel.is_cancelled());
+    assert!(matches!(
+      generator.generate_chain_cancellable(&chain_param, &cancel),
+      Err(Error::Cancelled)
+    ));
+    assert!(matches!(
+      generator.generate_chain_cancellable(&chain_param.must_contain("and"), &cancel),
+      Err(Error::Cancelled)
+    ));
+  }
+}
@@ -0,0 +1,191 @@
+//! Periodic saving of generators being trained.
+//!
+//! Training a huge corpus can take hours. A [`Checkpointer`], passed to
+//! [`Trainer::source_train_with_checkpoints`], saves the partially trained generator every given
+//! number of learned lines, so that a crash only loses the lines learned since the last checkpoint.
+//!
+//! [`write_atomically`] is also used to save models and personas without ever leaving a truncated
+//! file behind.
+//!
+//! [`Trainer::source_train_with_checkpoints`]: crate::Trainer::source_train_with_checkpoints
+
+use std::fs::{self, File};
+use std::hash::BuildHasher;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use crate::{Error, MarkovChainGenerator};
+
+/// Write a file with `write`, replacing the file at `path` only once it is entirely written.
+///
+/// The file is first written next to the destination and synced to disk, then renamed over it, so
+/// that a crash or a write error doesn’t leave a truncated file behind. The temporary file is
+/// removed on error.
+pub fn write_atomically<F>(path: &Path, write: F) -> Result<(), Error>
+where
+  F: FnOnce(&mut BufWriter<File>) -> Result<(), Error>,
+{
+  let mut temporary = path.to_owned().into_os_string();
+  temporary.push(".tmp");
+  let temporary = PathBuf::from(temporary);
+
+  let result = File::create(&temporary)
+    .map_err(Error::from)
+    .and_then(|file| {
+      let mut writer = BufWriter::new(file);
+      write(&mut writer)?;
+      writer
+        .into_inner()
+        .map_err(|err| err.into_error())?
+        .sync_all()?;
+      Ok(())
+    })
+    .and_then(|_| fs::rename(&temporary, path).map_err(Error::from));
+
+  if result.is_err() {
+    let _ = fs::remove_file(&temporary);
+  }
+
+  result
+}
+
+/// Saves a generator every `every` learned lines.
+///
+/// Checkpoints are written with [`write_atomically`], so that a crash or a write error while saving
+/// doesn’t leave a truncated model behind.
+#[derive(Clone, Debug)]
+pub struct Checkpointer {
+  path: PathBuf,
+  every: usize,
+  pending: usize,
+}
+
+impl Checkpointer {
+  /// Save checkpoints to `path` every `every` learned lines.
+  ///
+  /// Fails if `every` is zero.
+  pub fn new(path: impl Into<PathBuf>, every: usize) -> Result<Self, Error> {
+    if every == 0 {
+      return Err(Error::InvalidParameters(
+        "checkpoints must be saved every one line at least".to_owned(),
+      ));
+    }
+
+    Ok(Self {
+      path: path.into(),
+      every,
+      pending: 0,
+    })
+  }
+
+  /// Path checkpoints are saved to.
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  /// Number of lines learned between two checkpoints.
+  pub fn every(&self) -> usize {
+    self.every
+  }
+
+  /// Record that `lines` more lines were learned, saving a checkpoint if enough were since the
+  /// last one.
+  ///
+  /// Returns whether a checkpoint was saved. A checkpoint that fails to be saved is only tried
+  /// again once enough lines were learned since, instead of at every line.
+  pub fn learned<S>(
+    &mut self,
+    markov_chain_generator: &MarkovChainGenerator<S>,
+    lines: usize,
+  ) -> Result<bool, Error>
+  where
+    S: BuildHasher + Default,
+  {
+    self.pending += lines;
+
+    if self.pending < self.every {
+      return Ok(false);
+    }
+
+    self.pending = 0;
+    self.save(markov_chain_generator)?;
+    Ok(true)
+  }
+
+  /// Save a checkpoint now.
+  pub fn save<S>(&mut self, markov_chain_generator: &MarkovChainGenerator<S>) -> Result<(), Error>
+  where
+    S: BuildHasher + Default,
+  {
+    write_atomically(&self.path, |writer| markov_chain_generator.save(writer))?;
+    self.pending = 0;
+
+    info!("saved a checkpoint to {}", self.path.display());
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{LearningParameters, Trainer};
+  use std::env;
+
+  #[test]
+  fn test_checkpointer() {
+    struct Lines(Vec<&'static str>);
+
+    impl Trainer for Lines {
+      fn for_each_line(&mut self, sink: &mut dyn FnMut(&str)) -> Result<(), Error> {
+        self.0.iter().for_each(|line| sink(line));
+        Ok(())
+      }
+    }
+
+    assert!(Checkpointer::new("model.json", 0).is_err());
+
+    let path = env::temp_dir().join(format!(
+      "impersonate-checkpoint-{}.json",
+      std::process::id()
+    ));
+    let mut checkpointer = Checkpointer::new(&path, 2).unwrap();
+    let learn_param = LearningParameters::new(1).unwrap();
+    let mut generator = MarkovChainGenerator::new();
+    let mut reference = MarkovChainGenerator::new();
+    reference.train(&learn_param, "a b");
+    reference.train(&learn_param, "c d");
+
+    Lines(vec!["a b", "c d", "e f"])
+      .source_train_with_checkpoints(&mut generator, &learn_param, &mut |_| (), &mut checkpointer)
+      .unwrap();
+
+    // the third line is learned after the last checkpoint
+    let saved = MarkovChainGenerator::load(File::open(&path).unwrap()).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(saved, reference);
+    assert_ne!(saved, generator);
+  }
+
+  #[test]
+  fn test_checkpointer_failure() {
+    // a directory cannot be replaced by a checkpoint
+    let path = env::temp_dir().join(format!(
+      "impersonate-checkpoint-failure-{}",
+      std::process::id()
+    ));
+    fs::create_dir_all(&path).unwrap();
+    let mut temporary = path.clone().into_os_string();
+    temporary.push(".tmp");
+
+    let mut checkpointer = Checkpointer::new(&path, 2).unwrap();
+    let generator = MarkovChainGenerator::new();
+    let results = (0..4)
+      .map(|_| checkpointer.learned(&generator, 1).is_err())
+      .collect::<Vec<_>>();
+    fs::remove_dir(&path).unwrap();
+
+    // a failed checkpoint is tried again after as many lines, and leaves nothing behind
+    assert_eq!(results, vec![false, true, false, true]);
+    assert!(!Path::new(&temporary).exists());
+  }
+}
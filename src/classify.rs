@@ -0,0 +1,131 @@
+//! Guess who wrote a text, among several impersonated authors.
+//!
+//! A [`ModelSet`] holds one [`MarkovChainGenerator`] per author. A text is attributed to the author
+//! whose model finds it the most likely, that is, with the lowest perplexity (see
+//! [`MarkovChainGenerator::perplexity`]). Perplexity is used rather than the raw log-likelihood so
+//! that models learned with different wording sizes can be compared.
+
+use std::iter::FromIterator;
+
+use crate::MarkovChainGenerator;
+
+/// Named models, one per author.
+#[derive(Clone, Debug, Default)]
+pub struct ModelSet {
+  models: Vec<(String, MarkovChainGenerator)>,
+}
+
+impl ModelSet {
+  /// Create a model set without any author.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Add the model of an author, replacing the previous one of the same author, if any.
+  pub fn with(mut self, author: impl Into<String>, model: MarkovChainGenerator) -> Self {
+    self.insert(author, model);
+    self
+  }
+
+  /// Add the model of an author, replacing the previous one of the same author, if any.
+  pub fn insert(&mut self, author: impl Into<String>, model: MarkovChainGenerator) {
+    let author = author.into();
+
+    match self.models.iter_mut().find(|(name, _)| *name == author) {
+      Some((_, previous)) => *previous = model,
+      None => self.models.push((author, model)),
+    }
+  }
+
+  /// Model of an author.
+  pub fn get(&self, author: &str) -> Option<&MarkovChainGenerator> {
+    self
+      .models
+      .iter()
+      .find(|(name, _)| name == author)
+      .map(|(_, model)| model)
+  }
+
+  /// Authors of the set, in insertion order.
+  pub fn authors(&self) -> impl Iterator<Item = &str> {
+    self.models.iter().map(|(name, _)| name.as_str())
+  }
+
+  /// Number of authors.
+  pub fn len(&self) -> usize {
+    self.models.len()
+  }
+
+  /// Whether the set has no author.
+  pub fn is_empty(&self) -> bool {
+    self.models.is_empty()
+  }
+
+  /// Perplexity of a text under the model of every author, from the most to the least likely
+  /// author.
+  ///
+  /// Ties are broken by insertion order.
+  pub fn rank(&self, text: impl AsRef<str>) -> Vec<(&str, f64)> {
+    let text = text.as_ref();
+    let mut ranking = self
+      .models
+      .iter()
+      .map(|(name, model)| (name.as_str(), model.perplexity(text)))
+      .collect::<Vec<_>>();
+    ranking.sort_by(|(_, pa), (_, pb)| pa.total_cmp(pb));
+
+    ranking
+  }
+
+  /// Author who most likely wrote a text, with the perplexity of the text under their model.
+  ///
+  /// Returns [`None`] if the set is empty.
+  pub fn classify(&self, text: impl AsRef<str>) -> Option<(&str, f64)> {
+    self.rank(text).into_iter().next()
+  }
+}
+
+impl<A> FromIterator<(A, MarkovChainGenerator)> for ModelSet
+where
+  A: Into<String>,
+{
+  fn from_iter<T>(iter: T) -> Self
+  where
+    T: IntoIterator<Item = (A, MarkovChainGenerator)>,
+  {
+    let mut set = Self::new();
+
+    for (author, model) in iter {
+      set.insert(author, model);
+    }
+
+    set
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::LearningParameters;
+
+  #[test]
+  fn test_classify() {
+    let learn_param = LearningParameters::new(1).unwrap();
+    let model = |lines: &[&str]| {
+      let mut model = MarkovChainGenerator::new();
+      for line in lines {
+        model.train(&learn_param, line);
+      }
+      model
+    };
+
+    let set = ModelSet::new()
+      .with("alice", model(&["I love rust", "rust is great"]))
+      .with("bob", model(&["I hate mondays", "mondays are the worst"]));
+
+    assert_eq!(set.classify("rust is great").unwrap().0, "alice");
+    assert_eq!(set.classify("I hate mondays").unwrap().0, "bob");
+    assert_eq!(set.classify("mondays are the worst").unwrap().0, "bob");
+    assert!(ModelSet::new().classify("hello").is_none());
+  }
+}
@@ -0,0 +1,138 @@
+//! Cleanup passes removing what shouldn’t be learned from chat messages.
+//!
+//! Messages from IRC and other chats often carry formatting control codes, links that will be dead
+//! by the time they’re generated again, and mentions of people (`nick: hello`). The functions of
+//! this module remove them, and [`Cleanup`] bundles them into a configurable pipeline that trainers
+//! can apply to every message.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::borrow::Cow;
+
+lazy_static! {
+  static ref REGEX_FORMATTING: Regex = Regex::new(
+    r"\x03(\d{1,2}(,\d{1,2})?)?|\x04([[:xdigit:]]{6}(,[[:xdigit:]]{6})?)?|[\x02\x0F\x11\x16\x1D\x1E\x1F]"
+  )
+  .unwrap();
+  static ref REGEX_URL: Regex = Regex::new(r"\b(https?|ftp)://\S+|\bwww\.\S+\.\S+").unwrap();
+  static ref REGEX_MENTION: Regex =
+    Regex::new(r"^[A-Za-z\[\]\\`_^{|}][A-Za-z0-9\[\]\\`_^{|}-]*[:,]\s+").unwrap();
+}
+
+/// Placeholder replacing URLs by default.
+pub const URL_PLACEHOLDER: &str = "<url>";
+
+/// Remove mIRC formatting control codes: colors, bold, italics, underline, etc.
+pub fn strip_formatting(text: &str) -> Cow<'_, str> {
+  REGEX_FORMATTING.replace_all(text, "")
+}
+
+/// Replace URLs with a placeholder.
+pub fn replace_urls<'a>(text: &'a str, placeholder: &str) -> Cow<'a, str> {
+  REGEX_URL.replace_all(text, placeholder)
+}
+
+/// Remove a leading mention, such as `nick: ` or `nick, `.
+pub fn strip_mention(text: &str) -> &str {
+  match REGEX_MENTION.find(text) {
+    Some(mention) => &text[mention.end()..],
+    None => text,
+  }
+}
+
+/// Cleanup passes to apply to messages.
+///
+/// No pass is enabled by default.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Cleanup {
+  formatting: bool,
+  urls: Option<String>,
+  mentions: bool,
+}
+
+impl Cleanup {
+  /// Cleanup without any pass.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Remove mIRC formatting control codes (see [`strip_formatting`]).
+  pub fn strip_formatting(mut self, strip: bool) -> Self {
+    self.formatting = strip;
+    self
+  }
+
+  /// Replace URLs with `placeholder` (see [`replace_urls`] and [`URL_PLACEHOLDER`]).
+  pub fn replace_urls(mut self, placeholder: impl Into<String>) -> Self {
+    self.urls = Some(placeholder.into());
+    self
+  }
+
+  /// Remove leading mentions (see [`strip_mention`]).
+  pub fn strip_mentions(mut self, strip: bool) -> Self {
+    self.mentions = strip;
+    self
+  }
+
+  /// Apply the enabled passes to a message.
+  pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+    let mut text = Cow::Borrowed(text);
+
+    if self.formatting {
+      if let Cow::Owned(stripped) = strip_formatting(&text) {
+        text = Cow::Owned(stripped);
+      }
+    }
+
+    if let Some(placeholder) = &self.urls {
+      if let Cow::Owned(replaced) = replace_urls(&text, placeholder) {
+        text = Cow::Owned(replaced);
+      }
+    }
+
+    if self.mentions {
+      text = match text {
+        Cow::Borrowed(text) => Cow::Borrowed(strip_mention(text)),
+        Cow::Owned(text) => Cow::Owned(strip_mention(&text).to_owned()),
+      };
+    }
+
+    text
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_cleanup() {
+    assert_eq!(
+      strip_formatting("\x02bold\x02 \x0304,12red\x03 \x1Ditalic\x0F"),
+      "bold red italic"
+    );
+    assert_eq!(
+      replace_urls(
+        "see https://example.com/a?b=c and www.rust-lang.org",
+        "<url>"
+      ),
+      "see <url> and <url>"
+    );
+    assert_eq!(strip_mention("alice: hello"), "hello");
+    assert_eq!(strip_mention("hello: world"), "world");
+    assert_eq!(strip_mention("hello world"), "hello world");
+
+    let cleanup = Cleanup::new()
+      .strip_formatting(true)
+      .replace_urls(URL_PLACEHOLDER)
+      .strip_mentions(true);
+    assert_eq!(
+      cleanup.apply("\x0303bob\x03: look at http://example.com"),
+      "look at <url>"
+    );
+    assert!(matches!(
+      Cleanup::new().apply("nick: hi"),
+      Cow::Borrowed("nick: hi")
+    ));
+  }
+}
@@ -0,0 +1,127 @@
+//! Fake conversations between two impersonated authors.
+//!
+//! Every reply is seeded with the previous line of the conversation: its words are tried as
+//! prompts, from the last one to the first one, so that a reply picks up where the other author
+//! left off. When the replying model knows none of them, the reply starts anywhere.
+
+use rand::rngs::StdRng;
+use rand::{thread_rng, Rng, SeedableRng};
+use std::hash::BuildHasher;
+
+use crate::{Chain, ChainParameters, Error, MarkovChainGenerator};
+
+impl<S> MarkovChainGenerator<S>
+where
+  S: BuildHasher + Default,
+{
+  /// Generate a random chain answering another one, by using the provided random generator.
+  ///
+  /// [`ChainParameters::prompt`] is ignored: the words of `to` are used as prompts instead.
+  pub fn reply_with_rng<R>(
+    &self,
+    to: &Chain,
+    chain_param: &ChainParameters,
+    rng: &mut R,
+  ) -> Result<Chain, Error>
+  where
+    R: Rng,
+  {
+    let mut chain_param = chain_param.clone();
+
+    let words = to
+      .tokens()
+      .iter()
+      .rev()
+      .filter(|token| token.chars().any(char::is_alphanumeric));
+    for word in words {
+      chain_param.prompt = Some(word.clone());
+
+      match self.generate_chain_with_rng(&chain_param, rng) {
+        Err(Error::NoStateMatchingPrompt(_)) => continue,
+        chain => return chain,
+      }
+    }
+
+    chain_param.prompt = None;
+    self.generate_chain_with_rng(&chain_param, rng)
+  }
+}
+
+/// Generate a conversation of `turns` lines, alternating between `first` and `second`.
+///
+/// The first line is generated by `first`, from [`ChainParameters::prompt`] if set. If
+/// [`ChainParameters::seed`] is set, the whole conversation is deterministic.
+pub fn converse<S>(
+  first: &MarkovChainGenerator<S>,
+  second: &MarkovChainGenerator<S>,
+  turns: usize,
+  chain_param: &ChainParameters,
+) -> Result<Vec<Chain>, Error>
+where
+  S: BuildHasher + Default,
+{
+  match chain_param.seed {
+    Some(seed) => converse_with_rng(
+      first,
+      second,
+      turns,
+      chain_param,
+      &mut StdRng::seed_from_u64(seed),
+    ),
+    None => converse_with_rng(first, second, turns, chain_param, &mut thread_rng()),
+  }
+}
+
+/// Generate a conversation by using the provided random generator.
+///
+/// See [`converse`]; [`ChainParameters::seed`] is ignored.
+pub fn converse_with_rng<S, R>(
+  first: &MarkovChainGenerator<S>,
+  second: &MarkovChainGenerator<S>,
+  turns: usize,
+  chain_param: &ChainParameters,
+  rng: &mut R,
+) -> Result<Vec<Chain>, Error>
+where
+  S: BuildHasher + Default,
+  R: Rng,
+{
+  let mut lines: Vec<Chain> = Vec::with_capacity(turns);
+
+  for turn in 0..turns {
+    let speaker = if turn % 2 == 0 { first } else { second };
+
+    let line = match lines.last() {
+      Some(previous) => speaker.reply_with_rng(previous, chain_param, rng)?,
+      None => speaker.generate_chain_with_rng(chain_param, rng)?,
+    };
+    lines.push(line);
+  }
+
+  Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::LearningParameters;
+
+  #[test]
+  fn test_converse() {
+    let learn_param = LearningParameters::new(1).unwrap();
+    let mut alice = MarkovChainGenerator::new();
+    alice.train(&learn_param, "hello bob");
+    alice.train(&learn_param, "pizza is great");
+    let mut bob = MarkovChainGenerator::new();
+    bob.train(&learn_param, "bob likes pizza");
+
+    let chain_param = ChainParameters::new().seed(42).prompt("hello");
+    let lines = converse(&alice, &bob, 3, &chain_param).unwrap();
+    let lines = lines.iter().map(Chain::to_string).collect::<Vec<_>>();
+
+    assert_eq!(
+      lines,
+      vec!["hello bob", "bob likes pizza", "pizza is great"]
+    );
+  }
+}
@@ -0,0 +1,160 @@
+//! Export of the Markov graph to the GraphViz DOT language.
+//!
+//! Every state of a [`MarkovChainGenerator`] is a node, and every transition an edge labelled with
+//! its probability. The start and the end of learned lines are drawn as a point and a double
+//! circle. Large models have far too many transitions to be drawn; [`DotOptions`] keeps only the
+//! most frequent ones.
+//!
+//! The output can be rendered with `dot -Tsvg` or imported in Gephi.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::hash::BuildHasher;
+use std::iter;
+
+use crate::{MarkovChainGenerator, Wording};
+
+/// Which transitions to export.
+///
+/// Create them with [`DotOptions::new`] and customize them with the builder methods.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub struct DotOptions {
+  /// Minimum number of times a transition must have been seen while training to be exported.
+  pub min_count: usize,
+  /// Maximum number of transitions to export; the most frequent ones are kept.
+  pub max_edges: Option<usize>,
+}
+
+impl DotOptions {
+  /// Export every transition.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Only export transitions seen at least `min_count` times.
+  pub fn min_count(mut self, min_count: usize) -> Self {
+    self.min_count = min_count;
+    self
+  }
+
+  /// Only export the `max_edges` most frequent transitions.
+  pub fn max_edges(mut self, max_edges: usize) -> Self {
+    self.max_edges = Some(max_edges);
+    self
+  }
+}
+
+/// Quote a string as a DOT identifier.
+fn quote(s: &str) -> String {
+  format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl<S> MarkovChainGenerator<S>
+where
+  S: BuildHasher + Default,
+{
+  /// Export the graph of states and transitions in the DOT language.
+  ///
+  /// The output is deterministic: nodes and edges are sorted.
+  pub fn to_dot(&self, options: &DotOptions) -> String {
+    let mut edges = self
+      .states
+      .iter()
+      .flat_map(|(from, state)| {
+        let total = state
+          .nexts
+          .values()
+          .map(|transition| transition.count)
+          .sum::<f64>();
+
+        state
+          .nexts
+          .iter()
+          .filter(move |(_, transition)| transition.count >= options.min_count as f64)
+          .map(move |(to, transition)| (from, to, transition.count, transition.count / total))
+      })
+      .collect::<Vec<_>>();
+
+    if let Some(max_edges) = options.max_edges {
+      edges.sort_by(|(fa, ta, ca, _), (fb, tb, cb, _)| {
+        cb.total_cmp(ca)
+          .then_with(|| fa.cmp(fb))
+          .then_with(|| ta.cmp(tb))
+      });
+      edges.truncate(max_edges);
+    }
+
+    edges.sort_by(|(fa, ta, ..), (fb, tb, ..)| fa.cmp(fb).then_with(|| ta.cmp(tb)));
+
+    // number nodes, as different wordings might be displayed the same way
+    let mut nodes = edges
+      .iter()
+      .flat_map(|(from, to, ..)| iter::once(*from).chain(iter::once(*to)))
+      .collect::<Vec<&Wording>>();
+    nodes.sort();
+    nodes.dedup();
+    let ids = nodes
+      .iter()
+      .enumerate()
+      .map(|(id, wording)| (*wording, id))
+      .collect::<HashMap<_, _>>();
+
+    let mut dot = String::from("digraph markov {\n");
+
+    for (id, wording) in nodes.iter().enumerate() {
+      let attributes = if wording.is_start() {
+        "shape=point".to_owned()
+      } else if wording.is_end() {
+        "shape=doublecircle, label=\"\"".to_owned()
+      } else {
+        format!("label={}", quote(&wording.to_string()))
+      };
+
+      let _ = writeln!(dot, "  n{} [{}];", id, attributes);
+    }
+
+    for (from, to, _, probability) in edges {
+      let _ = writeln!(
+        dot,
+        "  n{} -> n{} [label=\"{:.2}\"];",
+        ids[from], ids[to], probability
+      );
+    }
+
+    dot.push_str("}\n");
+    dot
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::LearningParameters;
+
+  #[test]
+  fn test_dot() {
+    let learn_param = LearningParameters::new(1).unwrap();
+    let mut generator = MarkovChainGenerator::new();
+    generator.train(&learn_param, "say \"hi\"");
+    generator.train(&learn_param, "say \"hi\"");
+    generator.train(&learn_param, "say bye");
+
+    assert_eq!(
+      generator.to_dot(&DotOptions::new().min_count(2)),
+      "digraph markov {\n  \
+         n0 [label=\"\\\"hi\\\"\"];\n  \
+         n1 [label=\"say\"];\n  \
+         n2 [shape=point];\n  \
+         n3 [shape=doublecircle, label=\"\"];\n  \
+         n0 -> n3 [label=\"1.00\"];\n  \
+         n1 -> n0 [label=\"0.67\"];\n  \
+         n2 -> n1 [label=\"1.00\"];\n\
+       }\n"
+    );
+
+    let dot = generator.to_dot(&DotOptions::new().max_edges(1));
+    assert_eq!(dot.matches("->").count(), 1);
+    assert!(dot.contains("[label=\"1.00\"]"));
+  }
+}
@@ -0,0 +1,256 @@
+//! C interface, so that bots written in C, or in any language with a C FFI such as Python, can link
+//! against the library.
+//!
+//! With the `ffi` feature, the library is also built as a shared (`cdylib`) and a static
+//! (`staticlib`) library to link against, along with `include/impersonate.h`:
+//!
+//! ```text
+//! cargo build --lib --release --features ffi
+//! ```
+//!
+//! Generators are opaque pointers, created with [`impersonate_new`] or [`impersonate_load_model`]
+//! and released with [`impersonate_free`]. Strings are NUL-terminated UTF-8; generated ones are
+//! owned by the caller, who releases them with [`impersonate_string_free`]. Functions returning a
+//! pointer return `NULL` on failure, and functions returning an `int` return `0` on success and
+//! `-1` on failure. Panics are caught rather than unwound into the caller, and reported as
+//! failures.
+
+use std::ffi::{CStr, CString};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::os::raw::{c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use crate::{ChainParameters, LearningParameters, MarkovChainGenerator};
+
+/// A generator, along with the parameters it learns with.
+pub struct Impersonate {
+  generator: MarkovChainGenerator,
+  learn_param: LearningParameters,
+}
+
+impl Impersonate {
+  fn into_raw(self) -> *mut Self {
+    Box::into_raw(Box::new(self))
+  }
+}
+
+/// Run `f`, or return `failed` if it panics, as unwinding into C is undefined behavior.
+///
+/// The arguments of a function panicking midway may be left in an inconsistent state, but they are
+/// still safe to release.
+fn catch<T>(failed: T, f: impl FnOnce() -> T) -> T {
+  panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(failed)
+}
+
+/// Borrow a C string as UTF-8, or [`None`] if it’s `NULL` or not UTF-8.
+///
+/// # Safety
+///
+/// `string` must be `NULL` or a valid NUL-terminated string.
+unsafe fn str_arg<'a>(string: *const c_char) -> Option<&'a str> {
+  if string.is_null() {
+    return None;
+  }
+
+  CStr::from_ptr(string).to_str().ok()
+}
+
+/// Create an empty generator learning wordings of `wording_size` words.
+///
+/// Returns `NULL` if `wording_size` is `0`.
+#[no_mangle]
+pub extern "C" fn impersonate_new(wording_size: usize) -> *mut Impersonate {
+  catch(ptr::null_mut(), || {
+    match LearningParameters::new(wording_size) {
+      Ok(learn_param) => Impersonate {
+        generator: MarkovChainGenerator::new(),
+        learn_param,
+      }
+      .into_raw(),
+      Err(_) => ptr::null_mut(),
+    }
+  })
+}
+
+/// Load a generator saved by [`impersonate_save_model`] or the command line.
+///
+/// The generator keeps learning with the parameters it was trained with. Returns `NULL` if the
+/// model cannot be read.
+///
+/// # Safety
+///
+/// `path` must be a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn impersonate_load_model(path: *const c_char) -> *mut Impersonate {
+  catch(ptr::null_mut(), || {
+    let loaded = str_arg(path)
+      .and_then(|path| File::open(path).ok())
+      .and_then(|file| MarkovChainGenerator::load(BufReader::new(file)).ok());
+    let generator = match loaded {
+      Some(generator) => generator,
+      None => return ptr::null_mut(),
+    };
+
+    let learn_param = match generator.learning_parameters() {
+      Some(learn_param) => learn_param.clone(),
+      None => match LearningParameters::new(1) {
+        Ok(learn_param) => learn_param,
+        Err(_) => return ptr::null_mut(),
+      },
+    };
+
+    Impersonate {
+      generator,
+      learn_param,
+    }
+    .into_raw()
+  })
+}
+
+/// Save a generator, to be loaded back with [`impersonate_load_model`].
+///
+/// # Safety
+///
+/// `impersonate` must have been returned by [`impersonate_new`] or [`impersonate_load_model`], and
+/// `path` must be a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn impersonate_save_model(
+  impersonate: *const Impersonate,
+  path: *const c_char,
+) -> c_int {
+  catch(-1, || {
+    let (impersonate, path) = match (impersonate.as_ref(), str_arg(path)) {
+      (Some(impersonate), Some(path)) => (impersonate, path),
+      _ => return -1,
+    };
+
+    let saved = File::create(path)
+      .map_err(Into::into)
+      .and_then(|file| impersonate.generator.save(BufWriter::new(file)));
+
+    match saved {
+      Ok(()) => 0,
+      Err(_) => -1,
+    }
+  })
+}
+
+/// Train a generator on a line.
+///
+/// # Safety
+///
+/// `impersonate` must have been returned by [`impersonate_new`] or [`impersonate_load_model`], and
+/// `line` must be a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn impersonate_train_line(
+  impersonate: *mut Impersonate,
+  line: *const c_char,
+) -> c_int {
+  catch(-1, || match (impersonate.as_mut(), str_arg(line)) {
+    (Some(impersonate), Some(line)) => {
+      impersonate.generator.train(&impersonate.learn_param, line);
+      0
+    }
+
+    _ => -1,
+  })
+}
+
+/// Generate a random string, to be released with [`impersonate_string_free`].
+///
+/// `prompt` is the text to start the string from, or `NULL`. Returns `NULL` if no string can be
+/// generated, such as when the generator was never trained.
+///
+/// # Safety
+///
+/// `impersonate` must have been returned by [`impersonate_new`] or [`impersonate_load_model`], and
+/// `prompt` must be `NULL` or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn impersonate_generate(
+  impersonate: *const Impersonate,
+  prompt: *const c_char,
+) -> *mut c_char {
+  catch(ptr::null_mut(), || {
+    let impersonate = match impersonate.as_ref() {
+      Some(impersonate) => impersonate,
+      None => return ptr::null_mut(),
+    };
+
+    let mut chain_param = ChainParameters::new();
+    chain_param.prompt = str_arg(prompt).map(str::to_owned);
+
+    impersonate
+      .generator
+      .generate_chain(&chain_param)
+      .ok()
+      .and_then(|chain| CString::new(chain.to_string()).ok())
+      .map_or(ptr::null_mut(), CString::into_raw)
+  })
+}
+
+/// Release a string returned by [`impersonate_generate`].
+///
+/// # Safety
+///
+/// `string` must be `NULL` or have been returned by [`impersonate_generate`], and not be released
+/// already.
+#[no_mangle]
+pub unsafe extern "C" fn impersonate_string_free(string: *mut c_char) {
+  if !string.is_null() {
+    catch((), || drop(CString::from_raw(string)));
+  }
+}
+
+/// Release a generator.
+///
+/// # Safety
+///
+/// `impersonate` must be `NULL` or have been returned by [`impersonate_new`] or
+/// [`impersonate_load_model`], and not be released already.
+#[no_mangle]
+pub unsafe extern "C" fn impersonate_free(impersonate: *mut Impersonate) {
+  if !impersonate.is_null() {
+    catch((), || drop(Box::from_raw(impersonate)));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::env;
+
+  #[test]
+  fn test_ffi() {
+    assert!(impersonate_new(0).is_null());
+
+    unsafe {
+      let impersonate = impersonate_new(1);
+      assert!(impersonate_generate(impersonate, ptr::null()).is_null());
+
+      let line = CString::new("hello world").unwrap();
+      assert_eq!(impersonate_train_line(impersonate, line.as_ptr()), 0);
+      assert_eq!(impersonate_train_line(impersonate, ptr::null()), -1);
+
+      let prompt = CString::new("world").unwrap();
+      let generated = impersonate_generate(impersonate, prompt.as_ptr());
+      assert_eq!(CStr::from_ptr(generated).to_str(), Ok("world"));
+      impersonate_string_free(generated);
+
+      let path = env::temp_dir().join(format!("impersonate-ffi-{}.json", std::process::id()));
+      let path = CString::new(path.to_str().unwrap()).unwrap();
+      assert_eq!(impersonate_save_model(impersonate, path.as_ptr()), 0);
+      let loaded = impersonate_load_model(path.as_ptr());
+      std::fs::remove_file(path.to_str().unwrap()).unwrap();
+      assert!(!loaded.is_null());
+      assert_eq!((*loaded).generator, (*impersonate).generator);
+
+      impersonate_free(loaded);
+      impersonate_free(impersonate);
+    }
+
+    // panics are reported as failures instead of unwinding into C
+    assert_eq!(catch(-1, || panic!("unexpected")), -1);
+  }
+}
@@ -0,0 +1,240 @@
+//! Versioned file format of saved models.
+//!
+//! Saved models are JSON objects wrapping the model with a magic string, the version of the format
+//! and the kind of model saved:
+//!
+//! ```json
+//! {"magic": "impersonate", "version": 2, "kind": "generator", "model": {…}}
+//! ```
+//!
+//! Loading checks them first, so that a model saved by a newer version of the crate, or of the
+//! wrong kind, fails with a precise error instead of being misread. Older versions are upgraded
+//! while loading:
+//!
+//! - version 1 is the bare model, saved before the format was versioned; its integer transition
+//!   counts are read as the floating-point counts of version 2.
+
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::{Deserialize, Serialize};
+use std::io;
+
+use crate::Error;
+
+/// Magic string identifying saved models.
+pub const MAGIC: &str = "impersonate";
+
+/// Version of the format written when saving models.
+pub const VERSION: u32 = 2;
+
+/// Kind of a saved model.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Kind {
+  /// A [`MarkovChainGenerator`](crate::MarkovChainGenerator).
+  Generator,
+  /// A [`PersonaSet`](crate::persona::PersonaSet).
+  Personas,
+}
+
+impl Kind {
+  /// Name of the kind, as saved.
+  fn name(self) -> &'static str {
+    match self {
+      Kind::Generator => "generator",
+      Kind::Personas => "personas",
+    }
+  }
+}
+
+/// Saved model, as written.
+#[derive(Serialize)]
+struct Envelope<'a, T> {
+  magic: &'static str,
+  version: u32,
+  kind: Kind,
+  model: &'a T,
+}
+
+/// Fields identifying a saved model, read before the model itself.
+#[derive(Deserialize)]
+struct Header {
+  magic: Option<String>,
+  version: Option<u32>,
+  kind: Option<String>,
+  /// Personas of a bare set, saved with version 1.
+  personas: Option<IgnoredAny>,
+}
+
+/// Saved model, as read once its header is checked.
+#[derive(Deserialize)]
+struct Body<T> {
+  model: T,
+}
+
+/// Error of a model that cannot be read.
+fn invalid(reason: String) -> Error {
+  Error::Serialization(Box::new(io::Error::new(io::ErrorKind::InvalidData, reason)))
+}
+
+/// Save a model of the given kind with the current version of the format.
+///
+/// The writer is flushed, so that failing to write the end of the model isn’t silently ignored.
+pub(crate) fn save<T, W>(kind: Kind, model: &T, mut writer: W) -> Result<(), Error>
+where
+  T: Serialize,
+  W: io::Write,
+{
+  let envelope = Envelope {
+    magic: MAGIC,
+    version: VERSION,
+    kind,
+    model,
+  };
+
+  serde_json::to_writer(&mut writer, &envelope)
+    .map_err(|err| Error::Serialization(Box::new(err)))?;
+  writer.flush()?;
+  Ok(())
+}
+
+/// Whether a saved model is a [`PersonaSet`](crate::persona::PersonaSet) rather than a single
+/// [`MarkovChainGenerator`](crate::MarkovChainGenerator).
+///
+/// Only the fields identifying the model are checked: loading it may still fail.
+pub fn is_personas<R>(reader: R) -> Result<bool, Error>
+where
+  R: io::Read,
+{
+  let header = serde_json::from_reader::<_, Header>(reader)
+    .map_err(|err| Error::Serialization(Box::new(err)))?;
+
+  match header.magic {
+    None => Ok(header.personas.is_some()),
+    Some(_) => Ok(header.kind.as_deref() == Some(Kind::Personas.name())),
+  }
+}
+
+/// Load a model of the given kind, upgrading it if it was saved with an older version of the
+/// format.
+pub(crate) fn load<T, R>(kind: Kind, mut reader: R) -> Result<T, Error>
+where
+  T: DeserializeOwned,
+  R: io::Read,
+{
+  // the header is read from the same bytes as the model, which is skipped meanwhile
+  let mut bytes = Vec::new();
+  reader.read_to_end(&mut bytes)?;
+  let header =
+    serde_json::from_slice::<Header>(&bytes).map_err(|err| Error::Serialization(Box::new(err)))?;
+
+  let version = match header.magic {
+    None => 1,
+    Some(magic) if magic == MAGIC => header.version.unwrap_or(0),
+    Some(magic) => return Err(invalid(format!("not a model: unknown magic {:?}", magic))),
+  };
+
+  match version {
+    1 => {
+      info!("upgrading a model saved with version 1 of the format");
+      serde_json::from_slice(&bytes).map_err(|err| Error::Serialization(Box::new(err)))
+    }
+
+    VERSION => {
+      if header.kind.as_deref() != Some(kind.name()) {
+        return Err(invalid(format!(
+          "expected a model of kind {}, found {:?}",
+          kind.name(),
+          header.kind.unwrap_or_default()
+        )));
+      }
+
+      serde_json::from_slice::<Body<T>>(&bytes)
+        .map(|body| body.model)
+        .map_err(|err| Error::Serialization(Box::new(err)))
+    }
+
+    version => Err(Error::UnsupportedVersion(version)),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::persona::PersonaSet;
+  use crate::{LearningParameters, MarkovChainGenerator};
+  use std::collections::hash_map::RandomState;
+
+  #[test]
+  fn test_versioned_format() {
+    let learn_param = LearningParameters::new(1).unwrap();
+    let mut generator = MarkovChainGenerator::new();
+    generator.train(&learn_param, "hello world");
+
+    let mut saved = Vec::new();
+    generator.save(&mut saved).unwrap();
+    let json = serde_json::from_slice::<serde_json::Value>(&saved).unwrap();
+    assert_eq!(json["magic"], MAGIC);
+    assert_eq!(json["version"], VERSION);
+    assert_eq!(json["kind"], "generator");
+    assert_eq!(
+      MarkovChainGenerator::load(saved.as_slice()).unwrap(),
+      generator
+    );
+
+    // version 1: the bare model, with integer counts
+    let legacy = serde_json::to_string(&generator)
+      .unwrap()
+      .replace("\"count\":1.0", "\"count\":1");
+    assert!(legacy.contains("\"count\":1}"));
+    assert_eq!(
+      MarkovChainGenerator::load(legacy.as_bytes()).unwrap(),
+      generator
+    );
+
+    assert!(matches!(
+      PersonaSet::load(saved.as_slice()),
+      Err(Error::Serialization(_))
+    ));
+    assert!(!is_personas(saved.as_slice()).unwrap());
+    assert!(!is_personas(legacy.as_bytes()).unwrap());
+
+    let mut personas = PersonaSet::new();
+    personas.train("alice", &learn_param, "hello world");
+    let mut saved = Vec::new();
+    personas.save(&mut saved).unwrap();
+    assert!(is_personas(saved.as_slice()).unwrap());
+    let legacy = serde_json::to_string(&personas).unwrap();
+    assert!(is_personas(legacy.as_bytes()).unwrap());
+    assert!(is_personas(&b"{\"kind\": \"personas\""[..]).is_err());
+
+    let newer = r#"{"magic": "impersonate", "version": 3, "kind": "generator", "model": {}}"#;
+    assert!(matches!(
+      MarkovChainGenerator::<RandomState>::load(newer.as_bytes()),
+      Err(Error::UnsupportedVersion(3))
+    ));
+
+    let other = r#"{"magic": "something else", "version": 2}"#;
+    assert!(matches!(
+      MarkovChainGenerator::<RandomState>::load(other.as_bytes()),
+      Err(Error::Serialization(_))
+    ));
+  }
+
+  #[test]
+  fn test_save_flush_error() {
+    struct Full;
+
+    impl io::Write for Full {
+      fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(buf.len())
+      }
+
+      fn flush(&mut self) -> io::Result<()> {
+        Err(io::Error::other("no space left on device"))
+      }
+    }
+
+    let generator = MarkovChainGenerator::new();
+    assert!(matches!(generator.save(Full), Err(Error::Io(_))));
+  }
+}
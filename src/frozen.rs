@@ -0,0 +1,150 @@
+//! Read-only generators shared between threads.
+//!
+//! A trained [`MarkovChainGenerator`] is `Send` and `Sync` as long as its hasher is, which is the
+//! case of the standard and Fx hashers: its tokens are atomically reference-counted, and generating
+//! only borrows it immutably. A [`FrozenModel`] builds on that to let servers
+//! and bots generate from many threads at once: it is compiled once (see
+//! [`MarkovChainGenerator::compile`]), cloned for the price of a reference count, and generates
+//! with a random generator owned by the caller, without any lock.
+
+use rand::Rng;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::Arc;
+
+use crate::cancel::CancellationToken;
+use crate::{Chain, ChainParameters, Error, MarkovChainGenerator, Token};
+
+/// A compiled generator that cannot be trained anymore, cheap to clone and share between threads.
+#[derive(Debug)]
+pub struct FrozenModel<S = RandomState, T = String> {
+  generator: Arc<MarkovChainGenerator<S, T>>,
+}
+
+impl<S, T> Clone for FrozenModel<S, T> {
+  fn clone(&self) -> Self {
+    Self {
+      generator: Arc::clone(&self.generator),
+    }
+  }
+}
+
+impl<S, T> FrozenModel<S, T>
+where
+  S: BuildHasher + Default,
+  T: Token,
+{
+  /// Freeze a generator, compiling it for faster generation.
+  pub fn new(mut generator: MarkovChainGenerator<S, T>) -> Self {
+    generator.compile();
+
+    Self {
+      generator: Arc::new(generator),
+    }
+  }
+
+  /// The frozen generator.
+  pub fn generator(&self) -> &MarkovChainGenerator<S, T> {
+    &self.generator
+  }
+
+  /// Generate a random chain by using the provided random generator.
+  ///
+  /// See [`MarkovChainGenerator::generate_chain_with_rng`].
+  pub fn generate_chain<R>(
+    &self,
+    chain_param: &ChainParameters,
+    rng: &mut R,
+  ) -> Result<Chain, Error>
+  where
+    R: Rng,
+  {
+    self.generator.generate_chain_with_rng(chain_param, rng)
+  }
+
+  /// Generate a random chain by using the provided random generator, unless `cancel` is
+  /// cancelled meanwhile.
+  ///
+  /// See [`MarkovChainGenerator::generate_chain_cancellable`].
+  pub fn generate_chain_cancellable<R>(
+    &self,
+    chain_param: &ChainParameters,
+    rng: &mut R,
+    cancel: &CancellationToken,
+  ) -> Result<Chain, Error>
+  where
+    R: Rng,
+  {
+    self
+      .generator
+      .generate_chain_cancellable_with_rng(chain_param, rng, cancel)
+  }
+
+  /// Generate a random sequence of tokens by using the provided random generator.
+  ///
+  /// See [`MarkovChainGenerator::generate_sequence_with_rng`].
+  pub fn generate_sequence<R>(
+    &self,
+    chain_param: &ChainParameters,
+    rng: &mut R,
+  ) -> Result<Vec<T>, Error>
+  where
+    R: Rng,
+  {
+    self.generator.generate_sequence_with_rng(chain_param, rng)
+  }
+}
+
+impl<S, T> From<MarkovChainGenerator<S, T>> for FrozenModel<S, T>
+where
+  S: BuildHasher + Default,
+  T: Token,
+{
+  fn from(generator: MarkovChainGenerator<S, T>) -> Self {
+    Self::new(generator)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::LearningParameters;
+  use rand::rngs::StdRng;
+  use rand::SeedableRng as _;
+  use std::thread;
+
+  fn assert_send_sync<T: Send + Sync>() {}
+
+  #[test]
+  fn test_frozen_model() {
+    assert_send_sync::<MarkovChainGenerator>();
+    assert_send_sync::<FrozenModel>();
+
+    let learn_param = LearningParameters::new(1).unwrap();
+    let mut generator = MarkovChainGenerator::new();
+    generator.train(&learn_param, "hello world");
+    generator.train(&learn_param, "hello you");
+
+    let chain_param = ChainParameters::new();
+    let model = FrozenModel::new(generator);
+    let expected = model
+      .generate_chain(&chain_param, &mut StdRng::seed_from_u64(0))
+      .unwrap();
+
+    let threads = (0..4)
+      .map(|_| {
+        let model = model.clone();
+        let chain_param = chain_param.clone();
+        thread::spawn(move || {
+          model
+            .generate_chain(&chain_param, &mut StdRng::seed_from_u64(0))
+            .unwrap()
+        })
+      })
+      .collect::<Vec<_>>();
+
+    for thread in threads {
+      assert_eq!(thread.join().unwrap().to_string(), expected.to_string());
+    }
+  }
+}
@@ -1,18 +1,145 @@
+#[macro_use]
+mod logging;
+#[cfg(feature = "banned-words")]
+pub mod banned;
+mod beam;
+pub mod cancel;
+#[cfg(feature = "persistence")]
+pub mod checkpoint;
+pub mod classify;
+#[cfg(feature = "cleanup")]
+pub mod cleanup;
+pub mod dialogue;
+pub mod dot;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "persistence")]
+pub mod format;
+pub mod frozen;
+mod intern;
+#[cfg(feature = "mmap")]
+pub mod mapped;
+#[cfg(feature = "persistence")]
+pub mod markovify;
+#[cfg(feature = "cleanup")]
+pub mod online;
+pub mod persona;
 pub mod trainers;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
+#[cfg(feature = "banned-words")]
+use banned::BannedWords;
+use cancel::CancellationToken;
+#[cfg(feature = "persistence")]
+use checkpoint::Checkpointer;
+use intern::Symbol;
 use itertools::Itertools as _;
-use rand::{thread_rng, Rng as _};
-use std::collections::HashMap;
+use rand::distributions::WeightedIndex;
+use rand::rngs::StdRng;
+use rand::{thread_rng, Rng, SeedableRng as _};
+use std::borrow::Cow;
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::error;
 use std::fmt;
-use std::fmt::Write as _;
-use std::iter::FromIterator;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::io;
+use std::iter::{self, FromIterator};
+use std::marker::PhantomData;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Word marking the start of a learned line.
+///
+/// This is a Unicode noncharacter, so it never appears in regular text.
+const START_MARKER: &str = "\u{FDD0}";
+
+/// Word marking the end of a learned line.
+///
+/// This is a Unicode noncharacter, so it never appears in regular text.
+const END_MARKER: &str = "\u{FDD1}";
+
+/// Number of seconds elapsed since the Unix epoch, if the platform has a clock.
+///
+/// Web pages have no system clock: asking `wasm32-unknown-unknown` for the time panics.
+fn now() -> Option<u64> {
+  if cfg!(all(target_arch = "wasm32", target_os = "unknown")) {
+    return None;
+  }
+
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .ok()
+    .map(|elapsed| elapsed.as_secs())
+}
+
+/// Hash a sequence of tokens with FNV-1a.
+///
+/// Unlike the standard library hashers, the result is stable across platforms and versions, so it
+/// can be persisted.
+fn hash_tokens(tokens: &[String]) -> u64 {
+  let mut hash = 0xCBF2_9CE4_8422_2325_u64;
+
+  for byte in tokens
+    .iter()
+    .flat_map(|token| token.bytes().chain(iter::once(0xFF)))
+  {
+    hash ^= u64::from(byte);
+    hash = hash.wrapping_mul(0x0100_0000_01B3);
+  }
+
+  hash
+}
 
 /// The smallest amount of wording that can be used to represent Markov
 /// states.
 #[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(
+  feature = "serde",
+  derive(serde::Serialize, serde::Deserialize),
+  serde(transparent)
+)]
 pub struct Wording {
+  /// Parts of the words forming the wording, interned.
+  words: Vec<Symbol>,
+}
+
+impl Wording {
+  /// Sentinel wording preceding the first wording of every learned line.
+  fn start() -> Self {
+    Self {
+      words: vec![Symbol::intern(START_MARKER)],
+    }
+  }
+
+  /// Sentinel wording following the last wording of every learned line.
+  fn end() -> Self {
+    Self {
+      words: vec![Symbol::intern(END_MARKER)],
+    }
+  }
+
+  /// Whether this wording is the sentinel starting learned lines.
+  fn is_start(&self) -> bool {
+    self.words.len() == 1 && self.words[0].as_str() == START_MARKER
+  }
+
+  /// Whether this wording is the sentinel ending learned lines.
+  fn is_end(&self) -> bool {
+    self.words.len() == 1 && self.words[0].as_str() == END_MARKER
+  }
+
   /// Parts of the words forming the wording.
-  words: Vec<String>,
+  fn tokens(&self) -> impl Iterator<Item = &str> {
+    self.words.iter().map(|word| word.as_str())
+  }
+
+  /// Wording made of the last `len` words of this wording.
+  fn suffix(&self, len: usize) -> Self {
+    Self {
+      words: self.words[self.words.len() - len..].to_vec(),
+    }
+  }
 }
 
 /// Create a wording based on an iterator.
@@ -22,25 +149,334 @@ impl FromIterator<String> for Wording {
     T: IntoIterator<Item = String>,
   {
     Self {
-      words: iter.into_iter().collect(),
+      words: iter.into_iter().map(|word| Symbol::intern(&word)).collect(),
     }
   }
 }
 
 impl fmt::Display for Wording {
   fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-    if !self.words.is_empty() {
-      f.write_str(&self.words[0])?;
+    if let Some((first, rest)) = self.words.split_first() {
+      f.write_str(first.as_str())?;
+
+      for w in rest {
+        write!(f, " {}", w)?;
+      }
+    }
+
+    Ok(())
+  }
+}
+
+/// An element of the sequences a [`MarkovChainGenerator`] learns.
+///
+/// Generators learn text, cut into [`String`] tokens, by default. They can learn sequences of any
+/// other type of tokens, such as enums, token IDs or emotes, with
+/// [`MarkovChainGenerator::train_sequence`], and generate them back with
+/// [`MarkovChainGenerator::generate_sequence`].
+///
+/// Tokens are stored under a textual key, interned as words are, so that generators of any kind
+/// of tokens are saved, merged and exported as text generators are. Keys must round-trip:
+/// [`Token::from_key`] must give back the token [`Token::to_key`] was called on, so keys of
+/// different tokens must differ. Keys must also be non-empty and hold no whitespace, since saved
+/// and exported wordings are split on whitespace; training on a token whose key breaks that fails.
+pub trait Token: Sized {
+  /// Key the token is stored under.
+  fn to_key(&self) -> Cow<'_, str>;
+
+  /// Token stored under `key`, or [`None`] if `key` isn’t the key of a token.
+  fn from_key(key: &str) -> Option<Self>;
+}
+
+impl Token for String {
+  fn to_key(&self) -> Cow<'_, str> {
+    Cow::Borrowed(self)
+  }
+
+  fn from_key(key: &str) -> Option<Self> {
+    Some(key.to_owned())
+  }
+}
+
+/// Implement [`Token`] for types stored as their [`Display`](fmt::Display) output, and read back
+/// with [`FromStr`](std::str::FromStr).
+macro_rules! impl_token_from_str {
+  ($($t:ty),*) => {
+    $(
+      impl Token for $t {
+        fn to_key(&self) -> Cow<'_, str> {
+          Cow::Owned(self.to_string())
+        }
+
+        fn from_key(key: &str) -> Option<Self> {
+          key.parse().ok()
+        }
+      }
+    )*
+  };
+}
+
+impl_token_from_str!(char, u16, u32, u64, usize);
+
+/// A generated chain.
+///
+/// Its [`Display`](fmt::Display) implementation outputs the chain as text. Words are separated by
+/// spaces, except around punctuation tokens, which are reattached to the surrounding words:
+///
+/// ```ignore
+///   "hello" "," "world" "!" "(" "it" "'s" "me" ")"
+/// ```
+///
+/// is displayed as `hello, world! (it's me)`.
+///
+/// Besides its text, a chain holds the wordings it went through and the probabilities of its
+/// transitions, so that unlikely chains can be told apart. Chains are compared and hashed by their
+/// text only.
+#[derive(Clone, Debug)]
+pub struct Chain {
+  /// Generated tokens.
+  tokens: Vec<String>,
+  /// Granularity of the tokens.
+  granularity: Granularity,
+  /// Wordings gone through, including the one the chain started from.
+  wordings: Vec<Wording>,
+  /// Probabilities of the transitions taken.
+  probabilities: Vec<f64>,
+}
+
+impl Chain {
+  /// Create a chain of tokens, without any wording nor transition.
+  fn new(tokens: Vec<String>, granularity: Granularity) -> Self {
+    Self {
+      tokens,
+      granularity,
+      wordings: Vec::new(),
+      probabilities: Vec::new(),
+    }
+  }
+
+  /// Generated tokens (words or characters).
+  pub fn tokens(&self) -> &[String] {
+    &self.tokens
+  }
+
+  /// Wordings the chain went through, in order, including the one it started from.
+  ///
+  /// Case-folded models yield lowercase wordings, even though the tokens get their case back.
+  pub fn wordings(&self) -> &[Wording] {
+    &self.wordings
+  }
+
+  /// Probabilities of the transitions taken, in order.
+  ///
+  /// Each one is the probability of jumping to the next wording from the state the chain was in, as
+  /// learned, whatever the [`Sampling`] and including [`ChainParameters::smoothing`]. Chains
+  /// starting from a prompt, or from a random state, have one less transition than wordings.
+  pub fn probabilities(&self) -> &[f64] {
+    &self.probabilities
+  }
+
+  /// Log-likelihood (natural logarithm) of the chain: the sum of the logarithms of the
+  /// probabilities of its transitions.
+  ///
+  /// The line end isn’t taken into account, so this differs from
+  /// [`MarkovChainGenerator::score`].
+  pub fn log_likelihood(&self) -> f64 {
+    self.probabilities.iter().map(|p| p.ln()).sum()
+  }
+
+  /// Whether no token was generated.
+  pub fn is_empty(&self) -> bool {
+    self.tokens.is_empty()
+  }
+
+  /// Number of characters of the displayed chain.
+  fn char_count(&self) -> usize {
+    let mut counter = CharCounter::new(self.granularity);
+    for token in &self.tokens {
+      counter.push(token);
     }
 
-    for w in &self.words[1..] {
-      write!(f, " {}", w)?;
+    counter.count()
+  }
+
+  /// Whether a token must be attached to the previous one, such as a comma or a contraction.
+  fn is_closing(token: &str) -> bool {
+    let contraction = token
+      .strip_prefix(&['\'', '’'][..])
+      .is_some_and(|rest| !rest.is_empty() && rest.chars().all(char::is_alphabetic));
+
+    contraction
+      || token == "n't"
+      || token == "n’t"
+      || (!token.is_empty() && token.chars().all(|c| ".,;:!?…)]}»%".contains(c)))
+  }
+
+  /// Whether a token must be attached to the next one, such as an opening parenthesis.
+  fn is_opening(token: &str) -> bool {
+    !token.is_empty() && token.chars().all(|c| "([{«¿¡".contains(c))
+  }
+}
+
+impl PartialEq for Chain {
+  fn eq(&self, other: &Self) -> bool {
+    self.tokens == other.tokens && self.granularity == other.granularity
+  }
+}
+
+impl Eq for Chain {}
+
+impl Hash for Chain {
+  fn hash<H>(&self, state: &mut H)
+  where
+    H: Hasher,
+  {
+    self.tokens.hash(state);
+    self.granularity.hash(state);
+  }
+}
+
+impl fmt::Display for Chain {
+  fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    let mut counter = CharCounter::new(self.granularity);
+
+    for token in &self.tokens {
+      if counter.push(token) {
+        f.write_str(" ")?;
+      }
+
+      f.write_str(token)?;
     }
 
     Ok(())
   }
 }
 
+/// Number of characters of a chain being displayed, counted as its tokens are added, so that long
+/// chains aren’t displayed again after every token.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct CharCounter {
+  granularity: Granularity,
+  /// Characters counted so far.
+  count: usize,
+  /// Whether the next token must be attached to the previous one; true at first so that the chain
+  /// doesn’t start with a space.
+  attach: bool,
+  in_quote: bool,
+}
+
+impl CharCounter {
+  pub(crate) fn new(granularity: Granularity) -> Self {
+    Self {
+      granularity,
+      count: 0,
+      attach: true,
+      in_quote: false,
+    }
+  }
+
+  /// Characters counted so far.
+  pub(crate) fn count(&self) -> usize {
+    self.count
+  }
+
+  /// Add a token, returning whether it’s displayed after a space.
+  pub(crate) fn push(&mut self, token: &str) -> bool {
+    let space = match self.granularity {
+      Granularity::Character => false,
+
+      Granularity::Word => {
+        let quote = token == "\"";
+        let closing = Chain::is_closing(token) || (quote && self.in_quote);
+        let space = !self.attach && !closing;
+
+        self.attach = Chain::is_opening(token) || (quote && !self.in_quote);
+        if quote {
+          self.in_quote = !self.in_quote;
+        }
+
+        space
+      }
+    };
+
+    self.count += usize::from(space) + token.chars().count();
+    space
+  }
+}
+
+/// Tokens of a prompt, matched against the wordings chains can start from by generators and
+/// mapped models alike.
+pub(crate) struct Prompt {
+  tokens: Vec<String>,
+  lowercase_tokens: Vec<String>,
+}
+
+impl Prompt {
+  /// Split a prompt as the wordings of a generator learned with `granularity` and `case_folding`.
+  ///
+  /// Fails if the prompt has no token, as any wording would match it.
+  pub(crate) fn new(
+    prompt: &str,
+    granularity: Granularity,
+    case_folding: bool,
+  ) -> Result<Self, Error> {
+    let mut tokens = match granularity {
+      Granularity::Word => prompt.split_whitespace().map(str::to_owned).collect(),
+      granularity => granularity.tokens(prompt),
+    };
+
+    if tokens.is_empty() {
+      return Err(Error::InvalidParameters(
+        "the prompt must contain a word".to_owned(),
+      ));
+    }
+
+    if case_folding {
+      for token in &mut tokens {
+        *token = token.to_lowercase();
+      }
+    }
+
+    let lowercase_tokens = tokens.iter().map(|token| token.to_lowercase()).collect();
+    Ok(Self {
+      tokens,
+      lowercase_tokens,
+    })
+  }
+
+  /// Tokens of the prompt.
+  #[cfg(feature = "mmap")]
+  pub(crate) fn tokens(&self) -> &[String] {
+    &self.tokens
+  }
+
+  /// Whether a wording is made of the tokens of the prompt.
+  pub(crate) fn is_exact<'a>(&self, wording: impl IntoIterator<Item = &'a str>) -> bool {
+    wording
+      .into_iter()
+      .eq(self.tokens.iter().map(String::as_str))
+  }
+
+  /// Whether a wording starts with the tokens of the prompt, or the prompt with the wording.
+  pub(crate) fn is_prefix<'a>(&self, wording: impl IntoIterator<Item = &'a str>) -> bool {
+    let wording = wording.into_iter().collect::<Vec<_>>();
+    let common = wording
+      .iter()
+      .zip(&self.tokens)
+      .take_while(|(a, b)| **a == b.as_str())
+      .count();
+
+    common == wording.len().min(self.tokens.len())
+  }
+
+  /// Whether a wording contains any token of the prompt, ignoring case.
+  pub(crate) fn is_fuzzy<'a>(&self, wording: impl IntoIterator<Item = &'a str>) -> bool {
+    wording
+      .into_iter()
+      .any(|token| self.lowercase_tokens.contains(&token.to_lowercase()))
+  }
+}
+
 /// An associated [`Wording`] to another [`Wording`], attached with a number of occurrences it
 /// was found as a next wording.
 ///
@@ -52,164 +488,3653 @@ impl fmt::Display for Wording {
 ///
 /// `"quux meh"` appears `1` time after `"foo bar zoo"` here.
 ///
+/// Occurrences are weighted (see [`MarkovChainGenerator::train_weighted`]), so the count is a
+/// floating-point number; it is the plain number of occurrences of unweighted lines.
+///
 /// This type also serves as “arc” in the Markov graph.
-#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Debug, Default, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Transition {
-  count: usize,
+  count: f64,
 }
 
-impl Default for Transition {
-  fn default() -> Self {
-    Self { count: 0 }
+/// A set of Markov transitions.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+  feature = "serde",
+  serde(bound(deserialize = "S: BuildHasher + Default"))
+)]
+pub struct State<S = RandomState> {
+  #[cfg_attr(feature = "serde", serde(with = "map_as_pairs"))]
+  nexts: HashMap<Wording, Transition, S>,
+  /// Sampling table built by [`MarkovChainGenerator::compile`], dropped whenever transitions change.
+  #[cfg_attr(feature = "serde", serde(skip))]
+  table: Option<AliasTable>,
+}
+
+impl<S> PartialEq for State<S>
+where
+  S: BuildHasher,
+{
+  fn eq(&self, other: &Self) -> bool {
+    self.nexts == other.nexts
   }
 }
 
-/// A set of Markov transitions.
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct State {
-  nexts: HashMap<Wording, Transition>,
+/// Alias table (Vose’s method), picking weighted wordings in constant time.
+#[derive(Clone, Debug)]
+struct AliasTable {
+  /// Wordings, sorted by weight, then by wording, as in [`State::pick_next`].
+  wordings: Vec<Wording>,
+  /// Probability to keep the wording of a column rather than its alias.
+  probabilities: Vec<f64>,
+  /// Alternative wording of each column.
+  aliases: Vec<usize>,
+  /// Total weight.
+  total: f64,
 }
 
-impl Default for State {
-  fn default() -> Self {
+impl AliasTable {
+  fn new<'a>(weights: impl IntoIterator<Item = (&'a Wording, f64)>) -> Self {
+    let mut weights = weights.into_iter().collect::<Vec<_>>();
+    weights.sort_by(|(wa, ca), (wb, cb)| ca.total_cmp(cb).then_with(|| wa.cmp(wb)));
+
+    let total = weights.iter().map(|(_, count)| count).sum::<f64>();
+    let len = weights.len();
+
+    // scaled probabilities, averaging to 1
+    let mut probabilities = weights
+      .iter()
+      .map(|(_, count)| count * len as f64 / total)
+      .collect::<Vec<_>>();
+    let mut aliases = (0..len).collect::<Vec<_>>();
+
+    let (mut small, mut large): (Vec<_>, Vec<_>) = (0..len).partition(|&i| probabilities[i] < 1.);
+    while let (Some(s), Some(&l)) = (small.pop(), large.last()) {
+      aliases[s] = l;
+      probabilities[l] -= 1. - probabilities[s];
+
+      if probabilities[l] < 1. {
+        large.pop();
+        small.push(l);
+      }
+    }
+
+    // leftovers are only due to rounding errors
+    for i in small.into_iter().chain(large) {
+      probabilities[i] = 1.;
+    }
+
     Self {
-      nexts: HashMap::default(),
+      wordings: weights
+        .into_iter()
+        .map(|(wording, _)| wording.clone())
+        .collect(),
+      probabilities,
+      aliases,
+      total,
+    }
+  }
+
+  /// Pick a wording.
+  fn pick<R>(&self, sampling: Sampling, rng: &mut R) -> Option<&Wording>
+  where
+    R: Rng,
+  {
+    if self.wordings.is_empty() {
+      return None;
+    }
+
+    let i = rng.gen_range(0, self.wordings.len());
+    let i = match sampling {
+      Sampling::Weighted if rng.gen::<f64>() >= self.probabilities[i] => self.aliases[i],
+      _ => i,
+    };
+
+    Some(&self.wordings[i])
+  }
+}
+
+impl<S> State<S>
+where
+  S: BuildHasher,
+{
+  /// Transitions, for modification; this drops the sampling table.
+  fn nexts_mut(&mut self) -> &mut HashMap<Wording, Transition, S> {
+    self.table = None;
+    &mut self.nexts
+  }
+
+  /// Total weight of the transitions, according to the sampling strategy.
+  fn total_weight(&self, sampling: Sampling) -> f64 {
+    match (sampling, &self.table) {
+      (Sampling::Weighted, Some(table)) => table.total,
+      (Sampling::Weighted, None) => self.nexts.values().map(|transition| transition.count).sum(),
+      (Sampling::Uniform, _) => self.nexts.len() as f64,
+    }
+  }
+
+  /// Pick the next [`Wording`] to jump to, if any, ignoring the `excluded` ones.
+  fn pick_next<R>(&self, sampling: Sampling, excluded: &[&Wording], rng: &mut R) -> Option<&Wording>
+  where
+    R: Rng,
+  {
+    if let (Some(table), true) = (&self.table, excluded.is_empty()) {
+      return table.pick(sampling, rng);
     }
+
+    // sort transitions so that a given random sequence always picks the same ones
+    let mut nexts = self
+      .nexts
+      .iter()
+      .filter(|(wording, _)| !excluded.contains(wording))
+      .collect::<Vec<_>>();
+
+    if nexts.is_empty() {
+      return None;
+    }
+
+    nexts.sort_by(|(wa, ta), (wb, tb)| ta.count.total_cmp(&tb.count).then_with(|| wa.cmp(wb)));
+
+    let i = match sampling {
+      Sampling::Weighted => {
+        let weights =
+          WeightedIndex::new(nexts.iter().map(|(_, transition)| transition.count)).ok()?;
+        rng.sample(weights)
+      }
+
+      Sampling::Uniform => rng.gen_range(0, nexts.len()),
+    };
+
+    Some(nexts[i].0)
   }
 }
 
 /// A set of Markov states.
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct MarkovChainGenerator {
-  states: HashMap<Wording, State>,
+///
+/// States are indexed by hash maps built with `S`, the standard hasher by default. Faster, non
+/// cryptographic hashers (see [`FxMarkovChainGenerator`]) speed both training and generation up on
+/// large models; the hasher doesn’t change the outputs nor the saved models.
+///
+/// Generators learn text by default; `T` is the type of the tokens they learn otherwise (see
+/// [`Token`]).
+///
+/// Generators are `Send` and `Sync` when their hasher is, so that a trained generator can be shared
+/// between threads; see [`frozen::FrozenModel`].
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+  feature = "serde",
+  serde(bound(deserialize = "S: BuildHasher + Default"))
+)]
+pub struct MarkovChainGenerator<S = RandomState, T = String> {
+  #[cfg_attr(feature = "serde", serde(with = "map_as_pairs"))]
+  states: HashMap<Wording, State<S>, S>,
+  /// Lower-order states, keyed by the suffixes of the wordings of [`MarkovChainGenerator::states`].
+  ///
+  /// Only populated when learning with [`LearningParameters::variable_order`].
+  #[cfg_attr(feature = "serde", serde(default, with = "map_as_pairs"))]
+  back_off_states: HashMap<Wording, State<S>, S>,
+  /// Reverse transitions: the wordings preceding every wording, keyed by the wordings they precede.
+  ///
+  /// Only populated when learning with [`LearningParameters::backward`].
+  #[cfg_attr(feature = "serde", serde(default, with = "map_as_pairs"))]
+  backward_states: HashMap<Wording, State<S>, S>,
+  /// Number of occurrences of the original forms of tokens, indexed by their lowercase form.
+  ///
+  /// Only populated when learning with [`LearningParameters::case_folding`].
+  #[cfg_attr(feature = "serde", serde(default))]
+  surface_forms: HashMap<String, HashMap<String, f64>>,
+  /// Hashes of the n-grams of learned lines.
+  ///
+  /// Only populated when learning with [`LearningParameters::verbatim_ngram`].
+  #[cfg_attr(feature = "serde", serde(default))]
+  verbatim_ngrams: HashSet<u64>,
+  /// Learning parameters the generator was last trained with.
+  learn_param: Option<LearningParameters>,
+  /// What the generator was trained on.
+  #[cfg_attr(feature = "serde", serde(default))]
+  metadata: Metadata,
+  /// Index of the states to start chains from when there’s no line start, built by
+  /// [`MarkovChainGenerator::compile`] and dropped whenever states change.
+  #[cfg_attr(feature = "serde", serde(skip))]
+  starts: Option<AliasTable>,
+  /// Type of the learned tokens, only used to convert them from and to their keys.
+  #[cfg_attr(feature = "serde", serde(skip))]
+  token: PhantomData<fn() -> T>,
+}
+
+impl<S, T> Default for MarkovChainGenerator<S, T>
+where
+  S: Default,
+{
+  fn default() -> Self {
+    Self {
+      states: HashMap::default(),
+      back_off_states: HashMap::default(),
+      backward_states: HashMap::default(),
+      surface_forms: HashMap::default(),
+      verbatim_ngrams: HashSet::default(),
+      learn_param: None,
+      metadata: Metadata::default(),
+      starts: None,
+      token: PhantomData,
+    }
+  }
+}
+
+impl<S, T> PartialEq for MarkovChainGenerator<S, T>
+where
+  S: BuildHasher,
+{
+  fn eq(&self, other: &Self) -> bool {
+    self.states == other.states
+      && self.back_off_states == other.back_off_states
+      && self.backward_states == other.backward_states
+      && self.surface_forms == other.surface_forms
+      && self.verbatim_ngrams == other.verbatim_ngrams
+      && self.learn_param == other.learn_param
+      && self.metadata == other.metadata
+  }
 }
 
 impl MarkovChainGenerator {
   /// Create a new empty Markov chain generator.
+  ///
+  /// Use [`Default::default`] to create a generator using another hasher.
   pub fn new() -> Self {
-    Self {
-      states: HashMap::new(),
-    }
+    Self::default()
   }
+}
 
-  /// Split a line into a chunk of [`Wording`].
-  fn chunk_line<L>(learn_param: &LearningParameters, line: L) -> Vec<Wording>
+impl<S> MarkovChainGenerator<S>
+where
+  S: BuildHasher + Default,
+{
+  /// Cut an input string into a set of [`Wording`] and train the generator on it.
+  ///
+  /// The input parameter tells how the cut will be done. The line is surrounded by sentinel
+  /// wordings, so that generated chains begin at a learned line start and end at a learned line
+  /// end.
+  pub fn train<L>(&mut self, learn_param: &LearningParameters, line: L)
   where
     L: AsRef<str>,
   {
-    let LearningParameters { wording_size } = *learn_param;
-    let words = line.as_ref().split(' ').map(|line| line.to_owned());
-
-    words
-      .chunks(wording_size)
-      .into_iter()
-      .map(|chunk| chunk.into_iter().collect::<Wording>())
-      .collect()
+    self.train_weighted(learn_param, line, 1.);
   }
 
-  /// Cut an input string into a set of [`Wording`] and train the generator on it.
+  /// Train the generator on a line counting `weight` times.
   ///
-  /// The input parameter tells how the cut will be done.
-  pub fn train<L>(&mut self, learn_param: &LearningParameters, line: L)
+  /// Recent messages can be given more weight than old ones, and quoted or forwarded content less
+  /// than original messages. [`MarkovChainGenerator::train`] uses a weight of `1`. Lines with a
+  /// weight that isn’t a positive number are ignored.
+  pub fn train_weighted<L>(&mut self, learn_param: &LearningParameters, line: L, weight: f64)
   where
     L: AsRef<str>,
   {
-    let chunks = Self::chunk_line(learn_param, line);
+    let line = line.as_ref();
+
+    if line.is_empty() || !weight.is_finite() || weight <= 0. {
+      return;
+    }
 
-    for (wording1, wording2) in chunks.into_iter().tuple_windows() {
-      let state = self.states.entry(wording1).or_insert(State::default());
+    let mut tokens = learn_param.granularity.tokens(line);
 
-      state.nexts.entry(wording2).or_default().count += 1;
+    if learn_param.case_folding {
+      for token in &mut tokens {
+        let folded = token.to_lowercase();
+        let forms = self.surface_forms.entry(folded.clone()).or_default();
+        *forms.entry(std::mem::replace(token, folded)).or_default() += weight;
+      }
     }
+
+    self.learn(learn_param, tokens, weight);
   }
 
-  /// Generate a random chain.
-  pub fn generate_chain(&self, chain_param: &ChainParameters) -> Result<String, ChainError> {
-    let mut rng = thread_rng();
-    let ChainParameters {
-      max_state_traversal,
-    } = *chain_param;
+  /// Train the generator on every line read from a reader.
+  ///
+  /// Lines are read one at a time, so that large corpora are learned with bounded memory. Returns
+  /// the number of lines read.
+  pub fn train_from_reader<R>(
+    &mut self,
+    learn_param: &LearningParameters,
+    reader: R,
+  ) -> Result<usize, Error>
+  where
+    R: io::BufRead,
+  {
+    let mut count = 0;
 
-    // get the initial state
-    let ri = rng.gen_range(0, self.states.len());
-    let mut key = self
-      .states
-      .keys()
-      .nth(ri)
-      .ok_or_else(|| ChainError::TooFewInitialStates(self.states.len()))?;
+    for line in reader.lines() {
+      self.train(learn_param, line?.trim_end_matches('\r'));
+      count += 1;
+    }
+
+    Ok(count)
+  }
 
-    let mut output = key.to_string();
+  /// Log-likelihood (natural logarithm) of a text under the model.
+  ///
+  /// The text is cut into wordings the same way as learned lines, and the probabilities of all its
+  /// transitions, from the line start to the line end, are multiplied. Transitions never seen while
+  /// training get add-one (Laplace) smoothing, so that the score of any text is finite; it is
+  /// [`f64::NEG_INFINITY`] only if the generator was never trained.
+  ///
+  /// The higher, the more likely the text was written by the impersonated persona. Scores of texts
+  /// of different lengths are not comparable; use [`MarkovChainGenerator::perplexity`] for that.
+  pub fn score(&self, text: impl AsRef<str>) -> f64 {
+    self
+      .transition_log_probabilities(text.as_ref())
+      .map_or(f64::NEG_INFINITY, |log_probs| log_probs.iter().sum())
+  }
 
-    for _ in 0..max_state_traversal.unwrap_or(usize::max_value()) {
-      if let Some(state) = self.states.get(&key) {
-        let mut states = state.nexts.iter().collect::<Vec<_>>();
-        states.sort_by(|(_, a), (_, b)| a.count.cmp(&b.count));
+  /// Perplexity of a text under the model.
+  ///
+  /// This is the exponential of the opposite of the mean log-likelihood of the transitions of the
+  /// text (see [`MarkovChainGenerator::score`]): the lower, the more likely the text, whatever its
+  /// length. It is [`f64::INFINITY`] if the generator was never trained.
+  pub fn perplexity(&self, text: impl AsRef<str>) -> f64 {
+    match self.transition_log_probabilities(text.as_ref()) {
+      Some(log_probs) => (-log_probs.iter().sum::<f64>() / log_probs.len() as f64).exp(),
+      None => f64::INFINITY,
+    }
+  }
 
-        // find the next state to jump to; for this, we need to sort the state by occurrences
-        let ri = rng.gen_range(0, states.len());
-        key = &states[ri].0;
+  /// Smoothed log-probabilities of the transitions of a text, or [`None`] if the generator was
+  /// never trained.
+  fn transition_log_probabilities(&self, text: &str) -> Option<Vec<f64>> {
+    let learn_param = self.learn_param.as_ref()?;
 
-        write!(&mut output, " {}", key.to_string()).unwrap();
-      } else {
-        break;
+    let mut tokens = learn_param.granularity.tokens(text);
+    if learn_param.case_folding {
+      for token in &mut tokens {
+        *token = token.to_lowercase();
       }
     }
 
-    Ok(output)
+    // every wording that can be jumped to, and the line end
+    let vocabulary = self
+      .states
+      .keys()
+      .filter(|wording| !wording.is_start())
+      .count()
+      + 1;
+
+    let wordings = iter::once(Wording::start())
+      .chain(Self::chunk_tokens(learn_param, tokens))
+      .chain(iter::once(Wording::end()));
+    let log_probs = wordings
+      .tuple_windows()
+      .map(|(wording1, wording2)| {
+        let (count, total) = self.states.get(&wording1).map_or((0., 0.), |state| {
+          let count = state
+            .nexts
+            .get(&wording2)
+            .map_or(0., |transition| transition.count);
+          (count, state.total_weight(Sampling::Weighted))
+        });
+
+        ((count + 1.) / (total + vocabulary as f64)).ln()
+      })
+      .collect();
+
+    Some(log_probs)
   }
 }
 
-/// Learning parameters.
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct LearningParameters {
+impl<S, T> MarkovChainGenerator<S, T>
+where
+  S: BuildHasher + Default,
+  T: Token,
+{
+  /// Split the tokens of a line into a chunk of [`Wording`].
+  fn chunk_tokens(learn_param: &LearningParameters, words: Vec<String>) -> Vec<Wording> {
+    let LearningParameters {
+      wording_size,
+      windowing,
+      ..
+    } = *learn_param;
+
+    match windowing {
+      Windowing::Chunks => words
+        .into_iter()
+        .chunks(wording_size)
+        .into_iter()
+        .map(|chunk| chunk.into_iter().collect::<Wording>())
+        .collect(),
+
+      Windowing::Sliding => {
+        if words.len() <= wording_size {
+          return vec![words.into_iter().collect()];
+        }
+
+        words
+          .windows(wording_size)
+          .map(|window| window.iter().cloned().collect::<Wording>())
+          .collect()
+      }
+    }
+  }
+
+  /// Learn the tokens of a line counting `weight` times.
+  fn learn(&mut self, learn_param: &LearningParameters, tokens: Vec<String>, weight: f64) {
+    self.starts = None;
+    self.metadata.lines += 1;
+
+    if let Some(n) = learn_param.verbatim_ngram {
+      if tokens.len() < n {
+        self.verbatim_ngrams.insert(hash_tokens(&tokens));
+      } else {
+        self
+          .verbatim_ngrams
+          .extend(tokens.windows(n).map(hash_tokens));
+      }
+    }
+
+    let chunks = Self::chunk_tokens(learn_param, tokens);
+    let wordings = iter::once(Wording::start())
+      .chain(chunks)
+      .chain(iter::once(Wording::end()));
+    self.learn_param = Some(learn_param.clone());
+
+    for (wording1, wording2) in wordings.tuple_windows() {
+      if learn_param.variable_order {
+        // also learn the transition from every shorter context
+        for len in 1..wording1.words.len() {
+          let state = self
+            .back_off_states
+            .entry(wording1.suffix(len))
+            .or_default();
+          state.nexts_mut().entry(wording2.clone()).or_default().count += weight;
+        }
+      }
+
+      if learn_param.backward {
+        let state = self.backward_states.entry(wording2.clone()).or_default();
+        state.nexts_mut().entry(wording1.clone()).or_default().count += weight;
+      }
+
+      let state = self.states.entry(wording1).or_default();
+
+      state.nexts_mut().entry(wording2).or_default().count += weight;
+    }
+  }
+
+  /// Train the generator on a sequence of tokens.
+  ///
+  /// The sequence is learned as a line is, except that it is already cut into tokens:
+  /// [`LearningParameters::granularity`] and [`LearningParameters::case_folding`] are ignored.
+  ///
+  /// Fails with [`Error::InvalidParameters`], learning nothing, if the key of a token is empty or
+  /// holds whitespace: see [`Token`].
+  pub fn train_sequence<I>(
+    &mut self,
+    learn_param: &LearningParameters,
+    sequence: I,
+  ) -> Result<(), Error>
+  where
+    I: IntoIterator<Item = T>,
+  {
+    self.train_sequence_weighted(learn_param, sequence, 1.)
+  }
+
+  /// Train the generator on a sequence of tokens counting `weight` times.
+  ///
+  /// See [`MarkovChainGenerator::train_weighted`] and [`MarkovChainGenerator::train_sequence`].
+  /// Empty sequences are ignored.
+  pub fn train_sequence_weighted<I>(
+    &mut self,
+    learn_param: &LearningParameters,
+    sequence: I,
+    weight: f64,
+  ) -> Result<(), Error>
+  where
+    I: IntoIterator<Item = T>,
+  {
+    let tokens = sequence
+      .into_iter()
+      .map(|token| {
+        let key = token.to_key().into_owned();
+
+        if key.is_empty()
+          || key.contains(char::is_whitespace)
+          || key == START_MARKER
+          || key == END_MARKER
+        {
+          Err(Error::InvalidParameters(format!(
+            "tokens must be stored under non-empty keys without whitespace, not {:?}",
+            key
+          )))
+        } else {
+          Ok(key)
+        }
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+
+    if !tokens.is_empty() && weight.is_finite() && weight > 0. {
+      self.learn(learn_param, tokens, weight);
+    }
+
+    Ok(())
+  }
+
+  /// Generate a random chain.
+  ///
+  /// If [`ChainParameters::seed`] is set, the output is deterministic; otherwise, the thread-local
+  /// random generator is used.
+  pub fn generate_chain(&self, chain_param: &ChainParameters) -> Result<Chain, Error> {
+    match chain_param.seed {
+      Some(seed) => self.generate_chain_with_rng(chain_param, &mut StdRng::seed_from_u64(seed)),
+      None => self.generate_chain_with_rng(chain_param, &mut thread_rng()),
+    }
+  }
+
+  /// Lazily generate as many random chains as needed.
+  ///
+  /// The iterator never ends. If [`ChainParameters::seed`] is set, the random generator is seeded
+  /// once, so that the sequence of chains is deterministic without every chain being the same.
+  ///
+  /// ```ignore
+  /// for chain in generator.generate_chains(&chain_param).take(10) {
+  ///   println!("{}", chain?);
+  /// }
+  /// ```
+  pub fn generate_chains<'a>(
+    &'a self,
+    chain_param: &'a ChainParameters,
+  ) -> impl Iterator<Item = Result<Chain, Error>> + 'a {
+    let mut rng = match chain_param.seed {
+      Some(seed) => StdRng::seed_from_u64(seed),
+      None => StdRng::from_entropy(),
+    };
+
+    iter::repeat_with(move || self.generate_chain_with_rng(chain_param, &mut rng))
+  }
+
+  /// Generate up to `count` distinct random chains.
+  ///
+  /// Chains already generated are generated again, up to [`ChainParameters::attempts`] times in a
+  /// row ([`DEFAULT_ATTEMPTS`] by default). Small models might not be able to generate that many
+  /// distinct chains, in which case fewer are returned.
+  pub fn generate_distinct_chains(
+    &self,
+    count: usize,
+    chain_param: &ChainParameters,
+  ) -> Result<Vec<Chain>, Error> {
+    let attempts = chain_param.attempts.unwrap_or(DEFAULT_ATTEMPTS).max(1);
+    let mut seen = HashSet::new();
+    let mut chains = Vec::with_capacity(count);
+    let mut duplicates = 0;
+
+    for chain in self.generate_chains(chain_param) {
+      if chains.len() == count {
+        break;
+      }
+
+      let chain = chain?;
+      if seen.contains(&chain) {
+        duplicates += 1;
+        if duplicates == attempts {
+          warn!("only {} distinct chains out of {}", chains.len(), count);
+          break;
+        }
+
+        continue;
+      }
+
+      duplicates = 0;
+      seen.insert(chain.clone());
+      chains.push(chain);
+    }
+
+    Ok(chains)
+  }
+
+  /// Generate a random chain by using the provided random generator.
+  ///
+  /// For a given generator state, the output is deterministic. [`ChainParameters::seed`] is
+  /// ignored.
+  pub fn generate_chain_with_rng<R>(
+    &self,
+    chain_param: &ChainParameters,
+    rng: &mut R,
+  ) -> Result<Chain, Error>
+  where
+    R: Rng,
+  {
+    self.generate_acceptable_chain(chain_param, rng, None)
+  }
+
+  /// Generate a random chain, unless `cancel` is cancelled meanwhile.
+  ///
+  /// The token is checked before every transition, so that a generation stops shortly after its
+  /// cancellation, failing with [`Error::Cancelled`]. Otherwise, this is
+  /// [`MarkovChainGenerator::generate_chain`].
+  pub fn generate_chain_cancellable(
+    &self,
+    chain_param: &ChainParameters,
+    cancel: &CancellationToken,
+  ) -> Result<Chain, Error> {
+    match chain_param.seed {
+      Some(seed) => self.generate_chain_cancellable_with_rng(
+        chain_param,
+        &mut StdRng::seed_from_u64(seed),
+        cancel,
+      ),
+      None => self.generate_chain_cancellable_with_rng(chain_param, &mut thread_rng(), cancel),
+    }
+  }
+
+  /// Generate a random chain by using the provided random generator, unless `cancel` is cancelled
+  /// meanwhile.
+  ///
+  /// See [`MarkovChainGenerator::generate_chain_cancellable`].
+  pub fn generate_chain_cancellable_with_rng<R>(
+    &self,
+    chain_param: &ChainParameters,
+    rng: &mut R,
+    cancel: &CancellationToken,
+  ) -> Result<Chain, Error>
+  where
+    R: Rng,
+  {
+    self.generate_acceptable_chain(chain_param, rng, Some(cancel))
+  }
+
+  /// Generate random chains until one is acceptable, or the generation is cancelled.
+  fn generate_acceptable_chain<R>(
+    &self,
+    chain_param: &ChainParameters,
+    rng: &mut R,
+    cancel: Option<&CancellationToken>,
+  ) -> Result<Chain, Error>
+  where
+    R: Rng,
+  {
+    let verbatim_ngram = self
+      .learn_param
+      .as_ref()
+      .and_then(|learn_param| learn_param.verbatim_ngram);
+    let attempts = match (chain_param.reject_verbatim, verbatim_ngram) {
+      (Some(attempts), Some(_)) => attempts,
+      (Some(_), None) => {
+        return Err(Error::InvalidParameters(
+          "rejecting verbatim chains requires learning with verbatim n-grams".to_owned(),
+        ))
+      }
+      (None, _) => 1,
+    };
+    let min_length = chain_param.min_wordings.is_some() || chain_param.min_chars.is_some();
+    let attempts = match chain_param.attempts {
+      Some(attempts) => attempts,
+      None if min_length || chain_param.has_banned_words() => attempts.max(DEFAULT_ATTEMPTS),
+      None => attempts,
+    }
+    .max(1);
+
+    for _ in 0..attempts {
+      let mut chain = self.generate_tokens(chain_param, rng, cancel)?;
+
+      if chain_param
+        .min_wordings
+        .is_some_and(|min| chain.wordings.len() < min)
+      {
+        continue;
+      }
+
+      if let (Some(_), Some(n)) = (chain_param.reject_verbatim, verbatim_ngram) {
+        if self.is_verbatim(&chain.tokens, n) {
+          continue;
+        }
+      }
+
+      if chain
+        .wordings
+        .iter()
+        .any(|wording| chain_param.bans(wording))
+      {
+        continue;
+      }
+
+      let case_folding = self
+        .learn_param
+        .as_ref()
+        .is_some_and(|learn_param| learn_param.case_folding);
+      if case_folding {
+        self.restore_case(&mut chain.tokens);
+      }
+
+      if chain_param
+        .min_chars
+        .is_some_and(|min| chain.char_count() < min)
+      {
+        continue;
+      }
+
+      return Ok(chain);
+    }
+
+    Err(Error::NoAcceptableChain(attempts))
+  }
+
+  /// Generate a random sequence of tokens.
+  ///
+  /// This is [`MarkovChainGenerator::generate_chain`] for generators learning other tokens than
+  /// text (see [`Token`]). It fails with [`Error::Parse`] if a generated key isn’t the key of a
+  /// token, which only happens if the generator also learned text.
+  pub fn generate_sequence(&self, chain_param: &ChainParameters) -> Result<Vec<T>, Error> {
+    match chain_param.seed {
+      Some(seed) => self.generate_sequence_with_rng(chain_param, &mut StdRng::seed_from_u64(seed)),
+      None => self.generate_sequence_with_rng(chain_param, &mut thread_rng()),
+    }
+  }
+
+  /// Generate a random sequence of tokens by using the provided random generator.
+  ///
+  /// See [`MarkovChainGenerator::generate_chain_with_rng`].
+  pub fn generate_sequence_with_rng<R>(
+    &self,
+    chain_param: &ChainParameters,
+    rng: &mut R,
+  ) -> Result<Vec<T>, Error>
+  where
+    R: Rng,
+  {
+    let chain = self.generate_chain_with_rng(chain_param, rng)?;
+
+    chain
+      .tokens
+      .iter()
+      .map(|key| {
+        T::from_key(key)
+          .ok_or_else(|| Error::Parse(format!("{:?} is not the key of a token", key).into()))
+      })
+      .collect()
+  }
+
+  /// Generate a random chain, before filtering it and restoring its case.
+  fn generate_tokens<R>(
+    &self,
+    chain_param: &ChainParameters,
+    rng: &mut R,
+    cancel: Option<&CancellationToken>,
+  ) -> Result<Chain, Error>
+  where
+    R: Rng,
+  {
+    let ChainParameters {
+      max_state_traversal,
+      sampling,
+      ref prompt,
+      back_off,
+      smoothing,
+      no_repeat,
+      max_chars,
+      ref must_contain,
+      bidirectional,
+      ..
+    } = *chain_param;
+
+    if smoothing.is_some_and(|k| !k.is_finite() || k < 0.) {
+      return Err(Error::InvalidParameters(
+        "smoothing must be a positive number".to_owned(),
+      ));
+    }
+
+    if prompt.is_some() && must_contain.is_some() {
+      return Err(Error::InvalidParameters(
+        "chains cannot both start from a prompt and contain a word".to_owned(),
+      ));
+    }
+
+    if self.states.is_empty() {
+      return Err(Error::EmptyModel);
+    }
+
+    // with smoothing, any wording can follow any state; sorted for determinism
+    let end = Wording::end();
+    let vocabulary = if smoothing.is_some() {
+      let mut vocabulary = self
+        .states
+        .keys()
+        .filter(|wording| !wording.is_start())
+        .chain(iter::once(&end))
+        .collect::<Vec<_>>();
+      vocabulary.sort();
+      vocabulary
+    } else {
+      Vec::new()
+    };
+
+    // with overlapping wordings, only the words that were not already output must be appended
+    let overlap = self
+      .learn_param
+      .as_ref()
+      .map_or(0, LearningParameters::overlap);
+
+    let mut wordings = Vec::new();
+    let mut probabilities = Vec::new();
+
+    // get the initial state
+    let pivots = match (must_contain, prompt) {
+      (Some(word), _) => Some((self.containing_states(word), word)),
+      (None, Some(prompt)) if bidirectional => Some((self.prompt_states(prompt)?, prompt)),
+      _ => None,
+    };
+
+    let (mut key, mut tokens) = if let Some((keys, word)) = pivots {
+      // begin at a state containing the word or matching the prompt, and grow the chain backwards
+      // from it
+      if keys.is_empty() && must_contain.is_some() {
+        return Err(Error::NoStateContaining(word.clone()));
+      } else if keys.is_empty() {
+        return Err(Error::NoStateMatchingPrompt(word.clone()));
+      }
+
+      let pivot = keys[rng.gen_range(0, keys.len())];
+      let path = self.grow_backward(pivot, sampling, max_state_traversal, rng, cancel);
+      let chain = self.path_chain(&path);
+      wordings = chain.wordings;
+      probabilities = chain.probabilities;
+
+      (pivot, chain.tokens)
+    } else if let Some(prompt) = prompt {
+      // begin at a state matching the prompt
+      let keys = self.prompt_states(prompt)?;
+
+      if keys.is_empty() {
+        return Err(Error::NoStateMatchingPrompt(prompt.clone()));
+      }
+
+      let key = keys[rng.gen_range(0, keys.len())];
+      (key, key.tokens().map(str::to_owned).collect())
+    } else if let Some((start, _)) = self.states.get_key_value(&Wording::start()) {
+      // begin at a real line start if the generator knows about them
+      (start, Vec::new())
+    } else {
+      // otherwise, such as once pruning dropped the line start, pick a sentence start
+      let key = self.pick_start(rng)?;
+
+      (key, key.tokens().map(str::to_owned).collect())
+    };
+
+    // states recently gone through, which cannot be jumped to again
+    let mut recent = VecDeque::new();
+    if wordings.is_empty() && !key.is_start() {
+      wordings.push(key.clone());
+    }
+
+    let cancelled = || cancel.is_some_and(CancellationToken::is_cancelled);
+
+    for _ in 0..max_state_traversal.unwrap_or(usize::MAX) {
+      if cancelled() {
+        return Err(Error::Cancelled);
+      }
+
+      if let Some(window) = no_repeat {
+        recent.push_back(key);
+        if recent.len() > window {
+          recent.pop_front();
+        }
+      }
+
+      // find the next state to jump to, picked according to the sampling strategy; reaching a
+      // line end terminates the chain
+      let state = self.states.get(key).map(|state| match back_off {
+        Some(min_transitions) => self.back_off(key, state, min_transitions),
+        None => state,
+      });
+      let state = match state {
+        Some(state) => state,
+        None => break,
+      };
+
+      let banned;
+      let excluded = if chain_param.has_banned_words() {
+        banned = recent
+          .iter()
+          .copied()
+          .chain(state.nexts.keys().filter(|next| chain_param.bans(next)))
+          .collect::<Vec<_>>();
+        &banned[..]
+      } else {
+        recent.make_contiguous()
+      };
+
+      // add-k smoothing: every wording of the vocabulary gets k more occurrences, which is the same
+      // as picking uniformly in the vocabulary with a probability proportional to its total weight
+      let mut smoothed = false;
+      let next = match smoothing {
+        Some(k) if k > 0. => {
+          let total = state.total_weight(sampling);
+          let smoothing_weight = k * vocabulary.len() as f64;
+          smoothed = rng.gen::<f64>() * (total + smoothing_weight) >= total;
+
+          if smoothed {
+            Some(vocabulary[rng.gen_range(0, vocabulary.len())])
+          } else {
+            state.pick_next(sampling, excluded, rng)
+          }
+        }
+
+        _ => state.pick_next(sampling, excluded, rng),
+      };
+
+      // stop early rather than looping when all the transitions lead to recent states
+      let next = match next {
+        Some(next) if !next.is_end() && !excluded.contains(&next) => next,
+        _ => break,
+      };
+
+      // the first wording of a line is always entirely output, as well as unseen wordings picked
+      // by smoothing, which don’t overlap with the previous one
+      let skip = if key.is_start() || smoothed {
+        0
+      } else {
+        overlap
+      };
+
+      let len = tokens.len();
+      tokens.extend(next.tokens().skip(skip).map(str::to_owned));
+
+      // stop at the last wording fitting in the character limit
+      if let Some(max_chars) = max_chars {
+        let chain = Chain::new(tokens, self.granularity());
+        let too_long = chain.char_count() > max_chars;
+        tokens = chain.tokens;
+
+        if too_long {
+          tokens.truncate(len);
+          break;
+        }
+      }
+
+      // probability of the transition as learned, whatever the sampling
+      let count = state
+        .nexts
+        .get(next)
+        .map_or(0., |transition| transition.count);
+      let total = state.total_weight(Sampling::Weighted);
+      let probability = match smoothing {
+        Some(k) if k > 0. => (count + k) / (total + k * vocabulary.len() as f64),
+        _ => count / total,
+      };
+
+      key = next;
+      wordings.push(next.clone());
+      probabilities.push(probability);
+    }
+
+    Ok(Chain {
+      tokens,
+      granularity: self.granularity(),
+      wordings,
+      probabilities,
+    })
+  }
+
+  /// States containing a word, ignoring case, sorted.
+  fn containing_states(&self, word: &str) -> Vec<&Wording> {
+    let word = word.to_lowercase();
+    let mut keys = self
+      .states
+      .keys()
+      .filter(|wording| !wording.is_start())
+      .filter(|wording| wording.tokens().any(|token| token.to_lowercase() == word))
+      .collect::<Vec<_>>();
+    keys.sort();
+    keys
+  }
+
+  /// Walk the Markov graph backwards from `pivot`, picking predecessors according to `sampling`.
+  ///
+  /// The walk stops at a line start, at a state without predecessor or after `max_steps` steps.
+  /// The returned path goes forwards and ends with `pivot`; it starts with the line start if one
+  /// was reached.
+  ///
+  /// Generators trained with [`LearningParameters::backward`] walk their reverse transitions;
+  /// others index the predecessors of all states first, which is linear in the number of
+  /// transitions.
+  fn grow_backward<'a, R>(
+    &'a self,
+    pivot: &'a Wording,
+    sampling: Sampling,
+    max_steps: Option<usize>,
+    rng: &mut R,
+    cancel: Option<&CancellationToken>,
+  ) -> Vec<&'a Wording>
+  where
+    R: Rng,
+  {
+    // a cancelled generation fails as soon as the path is grown
+    let cancelled = || cancel.is_some_and(CancellationToken::is_cancelled);
+
+    if !self.backward_states.is_empty() {
+      let mut path = vec![pivot];
+      let mut current = pivot;
+
+      for _ in 0..max_steps.unwrap_or(usize::MAX) {
+        if cancelled() {
+          break;
+        }
+
+        let previous = self
+          .backward_states
+          .get(current)
+          .and_then(|state| state.pick_next(sampling, &[], rng));
+
+        current = match previous {
+          Some(previous) => previous,
+          None => break,
+        };
+        path.push(current);
+
+        if current.is_start() {
+          break;
+        }
+      }
+
+      path.reverse();
+      return path;
+    }
+
+    // predecessors of every state, sorted so that a given random sequence always picks the same
+    let mut predecessors = HashMap::<&Wording, Vec<(&Wording, f64)>>::new();
+    for (from, state) in &self.states {
+      for (to, transition) in &state.nexts {
+        predecessors
+          .entry(to)
+          .or_default()
+          .push((from, transition.count));
+      }
+    }
+
+    let mut path = vec![pivot];
+    let mut current = pivot;
+
+    for _ in 0..max_steps.unwrap_or(usize::MAX) {
+      if cancelled() {
+        break;
+      }
+
+      let mut previous = match predecessors.get(current) {
+        Some(previous) => previous.clone(),
+        None => break,
+      };
+      previous.sort_by(|(wa, ca), (wb, cb)| ca.total_cmp(cb).then_with(|| wa.cmp(wb)));
+
+      let i = match sampling {
+        Sampling::Weighted => match WeightedIndex::new(previous.iter().map(|(_, count)| *count)) {
+          Ok(weights) => rng.sample(weights),
+          Err(_) => break,
+        },
+        Sampling::Uniform => rng.gen_range(0, previous.len()),
+      };
+
+      current = previous[i].0;
+      path.push(current);
+
+      if current.is_start() {
+        break;
+      }
+    }
+
+    path.reverse();
+    path
+  }
+
+  /// Chain going through a path of wordings, which might start at a line start and end at a line
+  /// end.
+  ///
+  /// The transition to the line end isn’t part of the probabilities of the chain.
+  fn path_chain(&self, path: &[&Wording]) -> Chain {
+    let overlap = self
+      .learn_param
+      .as_ref()
+      .map_or(0, LearningParameters::overlap);
+    let mut tokens = Vec::new();
+    let mut wordings = Vec::new();
+    let mut probabilities = Vec::new();
+
+    for (i, wording) in path.iter().enumerate() {
+      if wording.is_start() || wording.is_end() {
+        continue;
+      }
+
+      match i.checked_sub(1).map(|i| path[i]) {
+        Some(previous) => {
+          probabilities.push(self.transition_probability(previous, wording));
+
+          let skip = if previous.is_start() { 0 } else { overlap };
+          tokens.extend(wording.tokens().skip(skip).map(str::to_owned));
+        }
+
+        None => tokens.extend(wording.tokens().map(str::to_owned)),
+      }
+
+      wordings.push((*wording).clone());
+    }
+
+    Chain {
+      tokens,
+      granularity: self.granularity(),
+      wordings,
+      probabilities,
+    }
+  }
+
+  /// Probability of jumping from a state to a wording, as learned.
+  fn transition_probability(&self, from: &Wording, to: &Wording) -> f64 {
+    self.states.get(from).map_or(0., |state| {
+      let count = state
+        .nexts
+        .get(to)
+        .map_or(0., |transition| transition.count);
+      count / state.total_weight(Sampling::Weighted)
+    })
+  }
+
+  /// Whether generated tokens copy `n` consecutive tokens of a learned line, or a whole learned
+  /// line shorter than that.
+  fn is_verbatim(&self, tokens: &[String], n: usize) -> bool {
+    if tokens.len() < n {
+      return self.verbatim_ngrams.contains(&hash_tokens(tokens));
+    }
+
+    tokens
+      .windows(n)
+      .any(|ngram| self.verbatim_ngrams.contains(&hash_tokens(ngram)))
+  }
+
+  /// Restore the natural case of tokens learned with [`LearningParameters::case_folding`].
+  ///
+  /// Every token gets its most frequent original form back, and sentences start with an
+  /// uppercase letter.
+  fn restore_case(&self, tokens: &mut [String]) {
+    let mut sentence_start = true;
+
+    for token in tokens {
+      let form = self.surface_forms.get(token.as_str()).and_then(|forms| {
+        // on ties, prefer the lowercase form, then the smallest one so that the choice doesn’t
+        // depend on the hash map iteration order
+        forms
+          .iter()
+          .max_by(|(fa, ca), (fb, cb)| {
+            ca.total_cmp(cb)
+              .then_with(|| (*fa == token).cmp(&(*fb == token)))
+              .then_with(|| fb.cmp(fa))
+          })
+          .map(|(form, _)| form.clone())
+      });
+
+      if let Some(form) = form {
+        *token = form;
+      }
+
+      if sentence_start && !token.trim().is_empty() {
+        let mut chars = token.chars();
+        if let Some(first) = chars.next() {
+          *token = first.to_uppercase().chain(chars).collect();
+        }
+
+        sentence_start = false;
+      }
+
+      if self.granularity() == Granularity::Word && token.ends_with(&['.', '!', '?'][..]) {
+        sentence_start = true;
+      }
+    }
+  }
+
+  /// Back off from the state of `key` to the state of a shorter context if it has less than
+  /// `min_transitions` transitions.
+  ///
+  /// Contexts are tried from the longest to the shortest, and the first one with enough transitions
+  /// is used. If none has enough, the one with the most transitions is used.
+  fn back_off<'a>(
+    &'a self,
+    key: &Wording,
+    state: &'a State<S>,
+    min_transitions: usize,
+  ) -> &'a State<S> {
+    let mut best = state;
+
+    for len in (1..key.words.len()).rev() {
+      if best.nexts.len() >= min_transitions {
+        break;
+      }
+
+      if let Some(state) = self.back_off_states.get(&key.suffix(len)) {
+        if state.nexts.len() > best.nexts.len() {
+          best = state;
+        }
+      }
+    }
+
+    best
+  }
+
+  /// Granularity of the tokens the generator was trained with.
+  fn granularity(&self) -> Granularity {
+    self
+      .learn_param
+      .as_ref()
+      .map_or(Granularity::Word, |learn_param| learn_param.granularity)
+  }
+
+  /// Learning parameters the generator was last trained with, if it was trained at all.
+  pub fn learning_parameters(&self) -> Option<&LearningParameters> {
+    self.learn_param.as_ref()
+  }
+
+  /// What the generator was trained on, as saved along with it.
+  pub fn info(&self) -> ModelInfo<'_> {
+    ModelInfo {
+      sources: &self.metadata.sources,
+      author: self.metadata.author.as_deref(),
+      trained_at: self
+        .metadata
+        .trained_at
+        .map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+      lines: self.metadata.lines,
+      learn_param: self.learn_param.as_ref(),
+    }
+  }
+
+  /// Set the name of the author the generator impersonates.
+  pub fn set_author(&mut self, author: impl Into<String>) {
+    self.metadata.author = Some(author.into());
+  }
+
+  /// Record that the generator was just trained on a source, such as a file path.
+  ///
+  /// The description is added to [`ModelInfo::sources`] unless already there, and the training
+  /// time is updated.
+  pub fn record_source(&mut self, description: impl Into<String>) {
+    let description = description.into();
+    if !self.metadata.sources.contains(&description) {
+      self.metadata.sources.push(description);
+    }
+
+    self.metadata.trained_at = now();
+  }
+
+  /// Merge another generator into this one.
+  ///
+  /// States are united and the counts of transitions known by both generators are summed, so that
+  /// merging generators trained on parts of some sources, possibly in parallel, is the same as
+  /// training a single generator on all of them. The learning parameters of this generator are
+  /// kept, if any; merging generators trained with different parameters is allowed but mixes
+  /// incompatible wordings, and is reported with a warning.
+  pub fn merge(&mut self, other: &Self) {
+    self.starts = None;
+
+    if let (Some(learn_param), Some(other_learn_param)) = (&self.learn_param, &other.learn_param) {
+      if learn_param != other_learn_param {
+        warn!(
+          "merging models learned with different parameters: {:?} and {:?}",
+          learn_param, other_learn_param
+        );
+      }
+    }
+
+    for (states, other_states) in [
+      (&mut self.states, &other.states),
+      (&mut self.back_off_states, &other.back_off_states),
+      (&mut self.backward_states, &other.backward_states),
+    ] {
+      for (wording, state) in other_states {
+        let nexts = states.entry(wording.clone()).or_default().nexts_mut();
+
+        for (next, transition) in &state.nexts {
+          nexts.entry(next.clone()).or_default().count += transition.count;
+        }
+      }
+    }
+
+    for (token, forms) in &other.surface_forms {
+      let self_forms = self.surface_forms.entry(token.clone()).or_default();
+
+      for (form, count) in forms {
+        *self_forms.entry(form.clone()).or_default() += count;
+      }
+    }
+
+    self
+      .verbatim_ngrams
+      .extend(other.verbatim_ngrams.iter().copied());
+
+    if self.learn_param.is_none() {
+      self.learn_param = other.learn_param.clone();
+    }
+
+    self.metadata.merge(&other.metadata);
+  }
+
+  /// Pick a state to start a chain from when there’s no line start, weighted by
+  /// [`MarkovChainGenerator::start_weights`].
+  fn pick_start<R>(&self, rng: &mut R) -> Result<&Wording, Error>
+  where
+    R: Rng,
+  {
+    if let Some(starts) = &self.starts {
+      return starts
+        .pick(Sampling::Weighted, rng)
+        .ok_or(Error::EmptyModel);
+    }
+
+    // keys are sorted so that the choice doesn’t depend on the hash map iteration order
+    let mut weights = self.start_weights();
+    weights.sort_by(|(wa, ca), (wb, cb)| wa.cmp(wb).then_with(|| ca.total_cmp(cb)));
+
+    let index = WeightedIndex::new(weights.iter().map(|(_, weight)| *weight))
+      .map_err(|_| Error::EmptyModel)?;
+    Ok(weights[rng.sample(index)].0)
+  }
+
+  /// States chains can start from when there’s no line start, weighted by how often they begin a
+  /// sentence.
+  ///
+  /// A state begins a sentence when it follows a wording ending with `.`, `!` or `?`. If no state
+  /// does, which is the case of character models, every state can start a chain, weighted by the
+  /// number of times it was seen.
+  fn start_weights(&self) -> Vec<(&Wording, f64)> {
+    let mut sentence_starts = HashMap::<&Wording, f64>::new();
+
+    if self.granularity() == Granularity::Word {
+      let sentence_ends = self.states.iter().filter(|(wording, _)| {
+        wording
+          .tokens()
+          .last()
+          .is_some_and(|token| token.ends_with(&['.', '!', '?'][..]))
+      });
+
+      for (_, state) in sentence_ends {
+        for (next, transition) in &state.nexts {
+          if self.states.contains_key(next) {
+            *sentence_starts.entry(next).or_default() += transition.count;
+          }
+        }
+      }
+    }
+
+    if sentence_starts.is_empty() {
+      return self
+        .states
+        .iter()
+        .map(|(wording, state)| (wording, state.total_weight(Sampling::Weighted)))
+        .collect();
+    }
+
+    sentence_starts.into_iter().collect()
+  }
+
+  /// Build sampling tables for every state, so that generation is constant time per step.
+  ///
+  /// This also indexes the states to start chains from when the generator doesn’t know line
+  /// starts, which otherwise requires sorting all the states for every chain.
+  ///
+  /// Without tables, picking the next wording is linear in the number of transitions of a state,
+  /// which becomes noticeable with large models. Tables are built with Vose’s alias method and
+  /// aren’t saved; training, merging, pruning or decaying the generator drops them, so
+  /// [`MarkovChainGenerator::compile`] must be called again afterwards.
+  ///
+  /// A compiled generator still follows the same distribution, but picks differently: a given
+  /// seed doesn’t give the same chains before and after compiling. Steps excluding some wordings,
+  /// such as with [`ChainParameters::no_repeat`], fall back to the linear sampling.
+  pub fn compile(&mut self) {
+    for states in [
+      &mut self.states,
+      &mut self.back_off_states,
+      &mut self.backward_states,
+    ] {
+      for state in states.values_mut() {
+        let weights = state
+          .nexts
+          .iter()
+          .map(|(wording, transition)| (wording, transition.count));
+        state.table = Some(AliasTable::new(weights));
+      }
+    }
+
+    self.starts = Some(AliasTable::new(self.start_weights()));
+  }
+
+  /// Drop the transitions seen less than `min_count` times while training.
+  ///
+  /// States left without any transition, that cannot be reached from a line start anymore, or from
+  /// which no line end can be reached anymore, are dropped as well, along with the transitions
+  /// leading to them.
+  pub fn prune(&mut self, min_count: usize) {
+    for states in [
+      &mut self.states,
+      &mut self.back_off_states,
+      &mut self.backward_states,
+    ] {
+      for state in states.values_mut() {
+        state
+          .nexts_mut()
+          .retain(|_, transition| transition.count >= min_count as f64);
+      }
+    }
+
+    self.remove_orphans();
+  }
+
+  /// Keep only the `max_states` states seen the most while training, and drop the others.
+  ///
+  /// The line start is always kept. As with [`MarkovChainGenerator::prune`], the states that become
+  /// unreachable or without transitions are dropped as well, so that less than `max_states` states
+  /// may remain.
+  pub fn prune_states(&mut self, max_states: usize) {
+    if self.states.len() <= max_states {
+      return;
+    }
+
+    // sorted by decreasing occurrences, then by wording for determinism
+    let mut states = self
+      .states
+      .iter()
+      .map(|(wording, state)| (wording, state.total_weight(Sampling::Weighted)))
+      .collect::<Vec<_>>();
+    states.sort_by(|(wa, ca), (wb, cb)| {
+      wb.is_start()
+        .cmp(&wa.is_start())
+        .then_with(|| cb.total_cmp(ca))
+        .then_with(|| wa.cmp(wb))
+    });
+
+    let dropped = states
+      .into_iter()
+      .skip(max_states)
+      .map(|(wording, _)| wording.clone())
+      .collect::<Vec<_>>();
+    for wording in dropped {
+      self.states.remove(&wording);
+    }
+
+    self.remove_orphans();
+  }
+
+  /// Scale the counts of all transitions by `factor`, so that old habits fade.
+  ///
+  /// Applied regularly to a generator that keeps learning, for instance from live chat, this
+  /// implements exponential forgetting. Transitions whose count drops below `min_count` are
+  /// removed, along with the states that become orphans (see [`MarkovChainGenerator::prune`]). A
+  /// small `min_count`, such as `0.01`, only forgets transitions that haven’t been seen again for a
+  /// long time, while a `min_count` of `1` drops every transition seen once at the first decay.
+  ///
+  /// The counts of the original forms of case-folded tokens are scaled as well.
+  ///
+  /// Fails if `factor` is not between `0` and `1`, or if `min_count` is negative.
+  pub fn decay(&mut self, factor: f64, min_count: f64) -> Result<(), Error> {
+    if !(0. ..=1.).contains(&factor) {
+      return Err(Error::InvalidParameters(
+        "decay factor must be between 0 and 1".to_owned(),
+      ));
+    }
+
+    if !(min_count >= 0. && min_count.is_finite()) {
+      return Err(Error::InvalidParameters(
+        "minimum count must be a non-negative number".to_owned(),
+      ));
+    }
+
+    for states in [
+      &mut self.states,
+      &mut self.back_off_states,
+      &mut self.backward_states,
+    ] {
+      for state in states.values_mut() {
+        for transition in state.nexts_mut().values_mut() {
+          transition.count *= factor;
+        }
+
+        state
+          .nexts
+          .retain(|_, transition| transition.count > 0. && transition.count >= min_count);
+      }
+    }
+
+    for forms in self.surface_forms.values_mut() {
+      for count in forms.values_mut() {
+        *count *= factor;
+      }
+
+      forms.retain(|_, count| *count > 0.);
+    }
+    self.surface_forms.retain(|_, forms| !forms.is_empty());
+
+    self.remove_orphans();
+
+    Ok(())
+  }
+
+  /// Remove the states without transitions, the transitions to removed states and the states that
+  /// cannot be reached from the line start, or that cannot reach the line end.
+  fn remove_orphans(&mut self) {
+    self.starts = None;
+
+    // removing transitions may leave other states empty, hence the fixed point
+    loop {
+      let before = self.states.len();
+      self.states.retain(|_, state| !state.nexts.is_empty());
+
+      let known = self.states.keys().cloned().collect::<HashSet<_>>();
+      for state in self.states.values_mut() {
+        state
+          .nexts_mut()
+          .retain(|next, _| next.is_end() || known.contains(next));
+      }
+
+      if self.states.len() == before {
+        break;
+      }
+    }
+
+    // chains going through states from which the line end cannot be reached would never end, so
+    // the states that can reach it are found by walking transitions backwards from the line end
+    let mut preceding = HashMap::<_, Vec<_>>::new();
+    for (wording, state) in &self.states {
+      for next in state.nexts.keys() {
+        preceding.entry(next).or_default().push(wording);
+      }
+    }
+
+    let end = Wording::end();
+    let mut ending = HashSet::new();
+    let mut pending = vec![&end];
+
+    while let Some(wording) = pending.pop() {
+      for previous in preceding.get(wording).into_iter().flatten() {
+        if ending.insert((*previous).clone()) {
+          pending.push(*previous);
+        }
+      }
+    }
+
+    self.states.retain(|wording, _| ending.contains(wording));
+    for state in self.states.values_mut() {
+      state
+        .nexts_mut()
+        .retain(|next, _| next.is_end() || ending.contains(next));
+    }
+
+    // models without line starts can start anywhere, so every state is reachable
+    if self.states.contains_key(&Wording::start()) {
+      let mut reachable = HashSet::new();
+      let mut pending = vec![Wording::start()];
+
+      while let Some(wording) = pending.pop() {
+        if let Some(state) = self.states.get(&wording) {
+          pending.extend(
+            state
+              .nexts
+              .keys()
+              .filter(|next| !reachable.contains(*next))
+              .cloned(),
+          );
+        }
+
+        reachable.insert(wording);
+      }
+
+      self.states.retain(|wording, _| reachable.contains(wording));
+    }
+
+    let states = &self.states;
+    for state in self.back_off_states.values_mut() {
+      state
+        .nexts_mut()
+        .retain(|next, _| next.is_end() || states.contains_key(next));
+    }
+    self
+      .back_off_states
+      .retain(|_, state| !state.nexts.is_empty());
+
+    // reverse transitions mirror the remaining ones exactly
+    if !self.backward_states.is_empty() {
+      self.backward_states.clear();
+
+      for (from, state) in &self.states {
+        for (to, transition) in &state.nexts {
+          self
+            .backward_states
+            .entry(to.clone())
+            .or_default()
+            .nexts_mut()
+            .entry(from.clone())
+            .or_default()
+            .count += transition.count;
+        }
+      }
+    }
+  }
+
+  /// Statistics about the generator.
+  pub fn stats(&self) -> Stats {
+    let transitions = self.states.values().map(|state| state.nexts.len()).sum();
+    let occurrences = self
+      .states
+      .values()
+      .flat_map(|state| state.nexts.values())
+      .map(|transition| transition.count)
+      .sum();
+
+    let vocabulary = self
+      .states
+      .keys()
+      .filter(|wording| !wording.is_start())
+      .flat_map(|wording| wording.words.iter())
+      .collect::<HashSet<_>>()
+      .len();
+
+    Stats {
+      states: self.states.len(),
+      transitions,
+      occurrences,
+      vocabulary,
+    }
+  }
+
+  /// The `n` wordings seen the most while training, with the number of times they were seen.
+  ///
+  /// Wordings are sorted by decreasing number of occurrences. Line starts are not reported.
+  pub fn top_wordings(&self, n: usize) -> Vec<(Wording, f64)> {
+    let mut wordings = self
+      .states
+      .iter()
+      .filter(|(wording, _)| !wording.is_start())
+      .map(|(wording, state)| (wording, state.total_weight(Sampling::Weighted)))
+      .collect::<Vec<_>>();
+    wordings.sort_by(|(wa, ca), (wb, cb)| cb.total_cmp(ca).then_with(|| wa.cmp(wb)));
+
+    wordings
+      .into_iter()
+      .take(n)
+      .map(|(wording, count)| (wording.clone(), count))
+      .collect()
+  }
+
+  /// Wordings following a given wording, with the probability to jump to them.
+  ///
+  /// Continuations are sorted by decreasing probability. The end of a learned line is not
+  /// reported as a continuation.
+  pub fn continuations(&self, wording: &Wording) -> Vec<(Wording, f64)> {
+    let nexts = match self.states.get(wording) {
+      Some(state) => &state.nexts,
+      None => return Vec::new(),
+    };
+    let total = nexts
+      .values()
+      .map(|transition| transition.count)
+      .sum::<f64>();
+
+    let mut continuations = nexts
+      .iter()
+      .filter(|(next, _)| !next.is_end())
+      .map(|(next, transition)| (next.clone(), transition.count / total))
+      .collect::<Vec<_>>();
+    continuations.sort_by(|(wa, pa), (wb, pb)| pb.total_cmp(pa).then_with(|| wa.cmp(wb)));
+
+    continuations
+  }
+
+  /// Write every transition as a CSV row of `from,to,count`, after a header row.
+  ///
+  /// Line starts are written as an empty `from` and line ends as an empty `to`. Rows are sorted by
+  /// wordings, so that exports of the same model are identical.
+  pub fn export_transitions<W>(&self, writer: W) -> Result<(), Error>
+  where
+    W: io::Write,
+  {
+    self.export_transitions_delimited(writer, ',')
+  }
+
+  /// Write every transition as rows of `from`, `to` and `count` separated by `delimiter`, such as
+  /// `'\t'` for TSV.
+  ///
+  /// See [`MarkovChainGenerator::export_transitions`]. Fields containing the delimiter, quotes or
+  /// line breaks are quoted.
+  pub fn export_transitions_delimited<W>(&self, mut writer: W, delimiter: char) -> Result<(), Error>
+  where
+    W: io::Write,
+  {
+    let field = |wording: &Wording| {
+      let text = if wording.is_start() || wording.is_end() {
+        String::new()
+      } else {
+        wording.to_string()
+      };
+
+      if text.contains([delimiter, '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+      } else {
+        text
+      }
+    };
+
+    let mut rows = self
+      .states
+      .iter()
+      .flat_map(|(from, state)| {
+        state
+          .nexts
+          .iter()
+          .map(move |(to, transition)| (field(from), field(to), transition.count))
+      })
+      .collect::<Vec<_>>();
+    rows.sort_by(|(fa, ta, _), (fb, tb, _)| fa.cmp(fb).then_with(|| ta.cmp(tb)));
+
+    writeln!(writer, "from{}to{}count", delimiter, delimiter)?;
+    for (from, to, count) in rows {
+      writeln!(writer, "{}{}{}{}{}", from, delimiter, to, delimiter, count)?;
+    }
+
+    Ok(())
+  }
+
+  /// Find the states matching a prompt, sorted.
+  ///
+  /// States which wording is exactly the prompt are preferred. If none exists, states with a
+  /// wording starting with the prompt (or the other way around) are used. As last resort, states
+  /// containing any of the words of the prompt, ignoring case, are returned.
+  ///
+  /// Fails if the prompt has no words, as any state would match it.
+  fn prompt_states(&self, prompt: &str) -> Result<Vec<&Wording>, Error> {
+    let case_folding = self
+      .learn_param
+      .as_ref()
+      .is_some_and(|learn_param| learn_param.case_folding);
+    let prompt = Prompt::new(prompt, self.granularity(), case_folding)?;
+    let keys = self
+      .states
+      .keys()
+      .filter(|wording| !wording.is_start())
+      .collect::<Vec<_>>();
+
+    let find = |matches: &dyn Fn(&Wording) -> bool| {
+      let mut found = keys
+        .iter()
+        .copied()
+        .filter(|wording| matches(wording))
+        .collect::<Vec<_>>();
+      found.sort();
+      found
+    };
+
+    let found = find(&|wording| prompt.is_exact(wording.tokens()));
+    if !found.is_empty() {
+      return Ok(found);
+    }
+
+    let found = find(&|wording| prompt.is_prefix(wording.tokens()));
+    if !found.is_empty() {
+      return Ok(found);
+    }
+
+    Ok(find(&|wording| prompt.is_fuzzy(wording.tokens())))
+  }
+}
+
+#[cfg(feature = "persistence")]
+impl<S, T> MarkovChainGenerator<S, T>
+where
+  S: BuildHasher + Default,
+{
+  /// Save the generator into a writer.
+  ///
+  /// The generator is stored as versioned JSON (see [`format`]) and can be read back with
+  /// [`MarkovChainGenerator::load`].
+  pub fn save<W>(&self, writer: W) -> Result<(), Error>
+  where
+    W: io::Write,
+  {
+    format::save(format::Kind::Generator, self, writer)
+  }
+
+  /// Load a generator previously saved with [`MarkovChainGenerator::save`] from a reader.
+  ///
+  /// Generators saved with older versions of the format are upgraded; newer versions fail with
+  /// [`Error::UnsupportedVersion`].
+  pub fn load<R>(reader: R) -> Result<Self, Error>
+  where
+    R: io::Read,
+  {
+    format::load(format::Kind::Generator, reader)
+  }
+}
+
+#[cfg(feature = "rayon")]
+impl<S> MarkovChainGenerator<S>
+where
+  S: BuildHasher + Default + Send,
+{
+  /// Train on lines in parallel.
+  ///
+  /// Lines are split across the threads of the current [rayon] pool, each training a partial
+  /// generator; partial generators are then merged into this one (see
+  /// [`MarkovChainGenerator::merge`]). The result is the same as training on every line in turn.
+  pub fn train_par<I, L>(&mut self, learn_param: &LearningParameters, lines: I)
+  where
+    I: rayon::iter::IntoParallelIterator<Item = L>,
+    L: AsRef<str>,
+  {
+    use rayon::iter::ParallelIterator as _;
+
+    let trained = lines
+      .into_par_iter()
+      .fold(Self::default, |mut generator, line| {
+        generator.train(learn_param, line);
+        generator
+      })
+      .reduce(Self::default, |mut generator, other| {
+        generator.merge(&other);
+        generator
+      });
+
+    self.merge(&trained);
+  }
+}
+
+impl<S, T> Extend<MarkovChainGenerator<S, T>> for MarkovChainGenerator<S, T>
+where
+  S: BuildHasher + Default,
+  T: Token,
+{
+  fn extend<I>(&mut self, iter: I)
+  where
+    I: IntoIterator<Item = MarkovChainGenerator<S, T>>,
+  {
+    for other in iter {
+      self.merge(&other);
+    }
+  }
+}
+
+impl<'a, S, T> Extend<&'a MarkovChainGenerator<S, T>> for MarkovChainGenerator<S, T>
+where
+  S: BuildHasher + Default + 'a,
+  T: Token + 'a,
+{
+  fn extend<I>(&mut self, iter: I)
+  where
+    I: IntoIterator<Item = &'a MarkovChainGenerator<S, T>>,
+  {
+    for other in iter {
+      self.merge(other);
+    }
+  }
+}
+
+impl<S, T> FromIterator<MarkovChainGenerator<S, T>> for MarkovChainGenerator<S, T>
+where
+  S: BuildHasher + Default,
+  T: Token,
+{
+  /// Merge generators into a new one.
+  fn from_iter<I>(iter: I) -> Self
+  where
+    I: IntoIterator<Item = MarkovChainGenerator<S, T>>,
+  {
+    let mut generator = Self::default();
+    generator.extend(iter);
+    generator
+  }
+}
+
+/// A [`MarkovChainGenerator`] hashing its states with the Fx hash function.
+///
+/// Fx is much faster than the standard SipHash on the small keys of states, but offers no
+/// protection against collision attacks; only train it on data you trust.
+#[cfg(feature = "fxhash")]
+pub type FxMarkovChainGenerator = MarkovChainGenerator<fxhash::FxBuildHasher>;
+
+/// Statistics about a [`MarkovChainGenerator`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stats {
+  /// Number of states, including line starts.
+  pub states: usize,
+  /// Number of distinct transitions between states.
+  pub transitions: usize,
+  /// Number of times transitions were seen while training, weighted.
+  pub occurrences: f64,
+  /// Number of distinct tokens (words or characters, depending on the granularity) learned.
+  pub vocabulary: usize,
+}
+
+/// What a [`MarkovChainGenerator`] was trained on, to know what a model file holds long after it
+/// was saved.
+///
+/// Line counts are maintained by training; sources, training time and author are recorded with
+/// [`MarkovChainGenerator::record_source`] and [`MarkovChainGenerator::set_author`].
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct ModelInfo<'a> {
+  /// Descriptions of the sources the generator was trained on, such as file paths.
+  pub sources: &'a [String],
+  /// Author the generator impersonates, if known.
+  pub author: Option<&'a str>,
+  /// Last time the generator was trained on a recorded source.
+  pub trained_at: Option<SystemTime>,
+  /// Number of lines learned.
+  pub lines: u64,
+  /// Learning parameters the generator was last trained with.
+  pub learn_param: Option<&'a LearningParameters>,
+}
+
+/// Saved part of [`ModelInfo`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Metadata {
+  sources: Vec<String>,
+  author: Option<String>,
+  /// Seconds since the Unix epoch.
+  trained_at: Option<u64>,
+  lines: u64,
+}
+
+impl Metadata {
+  /// Merge the metadata of another generator.
+  ///
+  /// The author is kept if known, and the latest training time wins.
+  fn merge(&mut self, other: &Metadata) {
+    for source in &other.sources {
+      if !self.sources.contains(source) {
+        self.sources.push(source.clone());
+      }
+    }
+
+    if self.author.is_none() {
+      self.author = other.author.clone();
+    }
+
+    self.trained_at = self.trained_at.max(other.trained_at);
+    self.lines += other.lines;
+  }
+}
+
+impl Stats {
+  /// Average number of transitions per state.
+  ///
+  /// The higher, the more varied the generated chains; a factor close to 1 means that the corpus
+  /// is too small and that chains mostly copy learned lines.
+  pub fn branching_factor(&self) -> f64 {
+    if self.states == 0 {
+      0.
+    } else {
+      self.transitions as f64 / self.states as f64
+    }
+  }
+}
+
+/// Learning parameters.
+///
+/// Create them with [`LearningParameters::new`] and customize them with the builder methods.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct LearningParameters {
   /// Size (in words) of wordings to learn.
   ///
   /// Minimum value is `1` and is will generate sentences that makes very little sense. Higher
   /// values will generate more sense but a too high value will make the Markov states “poor”.
   pub wording_size: usize,
+  /// How wordings are cut out of the lines.
+  pub windowing: Windowing,
+  /// Also learn transitions from the shorter contexts (suffixes) of wordings, so that generation
+  /// can back off to them; see [`ChainParameters::back_off`].
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub variable_order: bool,
+  /// What the tokens forming wordings are.
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub granularity: Granularity,
+  /// Lowercase tokens while learning, so that `Hello` and `hello` share the same states.
+  ///
+  /// The most frequent original form of every token is remembered and restored when generating
+  /// chains, which also start with an uppercase letter.
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub case_folding: bool,
+  /// Remember (hashes of) the n-grams of that many tokens of learned lines, so that chains copying
+  /// them can be rejected; see [`ChainParameters::reject_verbatim`].
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub verbatim_ngram: Option<usize>,
+  /// Also learn the reverse transitions, so that chains are grown backwards quickly; see
+  /// [`ChainParameters::must_contain`] and [`ChainParameters::bidirectional`].
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub backward: bool,
+}
+
+impl LearningParameters {
+  /// Learn wordings of `wording_size` words, cut in disjoint chunks.
+  ///
+  /// Fails if `wording_size` is `0`.
+  pub fn new(wording_size: usize) -> Result<Self, Error> {
+    if wording_size == 0 {
+      return Err(Error::InvalidParameters(
+        "wordings must have at least one word".to_owned(),
+      ));
+    }
+
+    Ok(Self {
+      wording_size,
+      windowing: Windowing::default(),
+      variable_order: false,
+      granularity: Granularity::default(),
+      case_folding: false,
+      verbatim_ngram: None,
+      backward: false,
+    })
+  }
+
+  /// Change how wordings are cut out of the lines.
+  pub fn windowing(mut self, windowing: Windowing) -> Self {
+    self.windowing = windowing;
+    self
+  }
+
+  /// Also learn the shorter contexts of wordings, from `1` word to the wording size.
+  pub fn variable_order(mut self, variable_order: bool) -> Self {
+    self.variable_order = variable_order;
+    self
+  }
+
+  /// Change what the tokens forming wordings are.
+  pub fn granularity(mut self, granularity: Granularity) -> Self {
+    self.granularity = granularity;
+    self
+  }
+
+  /// Lowercase tokens while learning, and restore their natural case when generating chains.
+  pub fn case_folding(mut self, case_folding: bool) -> Self {
+    self.case_folding = case_folding;
+    self
+  }
+
+  /// Also learn the reverse transitions.
+  pub fn backward(mut self, backward: bool) -> Self {
+    self.backward = backward;
+    self
+  }
+
+  /// Remember the n-grams of `n` tokens of learned lines.
+  ///
+  /// Fails if `n` is `0`.
+  pub fn verbatim_ngram(mut self, n: usize) -> Result<Self, Error> {
+    if n == 0 {
+      return Err(Error::InvalidParameters(
+        "verbatim n-grams must have at least one token".to_owned(),
+      ));
+    }
+
+    self.verbatim_ngram = Some(n);
+    Ok(self)
+  }
+
+  /// Number of words two consecutive wordings have in common.
+  fn overlap(&self) -> usize {
+    match self.windowing {
+      Windowing::Chunks => 0,
+      Windowing::Sliding => self.wording_size.saturating_sub(1),
+    }
+  }
+}
+
+/// Way lines are cut into [`Wording`].
+///
+/// Examples, with a wording size of `2`:
+///
+/// ```ignore
+///   "foo bar zoo quux"
+/// ```
+///
+/// - [`Windowing::Chunks`] yields `"foo bar" "zoo quux"`.
+/// - [`Windowing::Sliding`] yields `"foo bar" "bar zoo" "zoo quux"`.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Windowing {
+  /// Disjoint chunks of words.
+  #[default]
+  Chunks,
+  /// Overlapping n-grams, shifted by one word each time.
+  Sliding,
+}
+
+/// Tokens forming [`Wording`]s.
+///
+/// With [`Granularity::Character`], the wording size is a number of characters, and generated
+/// chains are made of character n-grams, which works well to generate fake nicknames, channel
+/// names or short handles.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Granularity {
+  /// Words, separated by spaces.
+  #[default]
+  Word,
+  /// Characters, including spaces.
+  Character,
+}
+
+impl Granularity {
+  /// Split a text into tokens.
+  fn tokens(self, text: &str) -> Vec<String> {
+    match self {
+      Granularity::Word => text.split(' ').map(str::to_owned).collect(),
+      Granularity::Character => text.chars().map(String::from).collect(),
+    }
+  }
+}
+
+/// Number of attempts made to generate a chain long enough, unless [`ChainParameters::attempts`]
+/// is set.
+pub const DEFAULT_ATTEMPTS: usize = 100;
+
+/// Chain generation parameters.
+///
+/// Create them with [`ChainParameters::new`] and customize them with the builder methods.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct ChainParameters {
+  /// Number of states to go through at maximum.
+  pub max_state_traversal: Option<usize>,
+  /// How to pick the next wording when jumping from a state to another.
+  pub sampling: Sampling,
+  /// Seed of the random generator, for reproducible outputs.
+  pub seed: Option<u64>,
+  /// Text to start the chain from.
+  ///
+  /// The chain starts at a state matching the prompt instead of a random one.
+  pub prompt: Option<String>,
+  /// Minimum number of transitions a state must have not to back off to a shorter context.
+  ///
+  /// This requires the generator to have been trained with [`LearningParameters::variable_order`].
+  pub back_off: Option<usize>,
+  /// Add-k smoothing: every wording gets `k` more occurrences after every state.
+  ///
+  /// This gives a small probability to transitions never seen while training, which avoids
+  /// parroting the training data verbatim on sparse models.
+  pub smoothing: Option<f64>,
+  /// Reject chains copying an n-gram of a learned line, and try again up to that many times.
+  ///
+  /// This requires the generator to have been trained with [`LearningParameters::verbatim_ngram`];
+  /// the n-gram size is the one used while learning.
+  pub reject_verbatim: Option<usize>,
+  /// Number of the last states the chain cannot go through again.
+  ///
+  /// This prevents chains from bouncing between the same wordings on cyclic graphs; the chain
+  /// stops early when all the transitions of a state lead to recent states.
+  pub no_repeat: Option<usize>,
+  /// Maximum number of characters of the displayed chain.
+  ///
+  /// The chain stops at the last wording fitting in the limit, so that words are never cut. The
+  /// wording matching the [`ChainParameters::prompt`] is always output, though.
+  pub max_chars: Option<usize>,
+  /// Minimum number of wordings of the chain.
+  pub min_wordings: Option<usize>,
+  /// Minimum number of characters of the displayed chain.
+  pub min_chars: Option<usize>,
+  /// Number of chains to generate at maximum until one is acceptable.
+  ///
+  /// Chains are rejected when they are too short ([`ChainParameters::min_wordings`] and
+  /// [`ChainParameters::min_chars`]), copy learned lines ([`ChainParameters::reject_verbatim`]) or
+  /// contain banned words ([`ChainParameters::banned_words`]). Defaults to the number of attempts
+  /// of [`ChainParameters::reject_verbatim`], or [`DEFAULT_ATTEMPTS`] with a minimum length or
+  /// banned words.
+  pub attempts: Option<usize>,
+  /// Words the chain must not contain.
+  ///
+  /// Wordings containing any of them are never jumped to, and chains starting with one are
+  /// generated again.
+  #[cfg(feature = "banned-words")]
+  pub banned_words: Option<BannedWords>,
+  /// Word the chain must contain, ignoring case.
+  ///
+  /// The chain is grown backwards from a state containing the word, up to a line start, then
+  /// forwards as usual. It cannot be combined with [`ChainParameters::prompt`].
+  pub must_contain: Option<String>,
+  /// Grow chains backwards from the state matching [`ChainParameters::prompt`] as well, so that
+  /// the prompt lands in the middle of the chain rather than at its start.
+  ///
+  /// This is much faster with generators trained with [`LearningParameters::backward`].
+  pub bidirectional: bool,
+}
+
+impl ChainParameters {
+  /// Weighted sampling of unbounded chains, starting at the beginning of a learned line.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Go through `max_state_traversal` states at maximum.
+  ///
+  /// With `0`, no transition is made: the chain is empty, or only holds the prompt if any.
+  pub fn max_state_traversal(mut self, max_state_traversal: usize) -> Self {
+    self.max_state_traversal = Some(max_state_traversal);
+    self
+  }
+
+  /// Change how the next wording is picked.
+  pub fn sampling(mut self, sampling: Sampling) -> Self {
+    self.sampling = sampling;
+    self
+  }
+
+  /// Seed the random generator, for reproducible outputs.
+  pub fn seed(mut self, seed: u64) -> Self {
+    self.seed = Some(seed);
+    self
+  }
+
+  /// Back off to shorter contexts from states with less than `min_transitions` transitions.
+  pub fn back_off(mut self, min_transitions: usize) -> Self {
+    self.back_off = Some(min_transitions);
+    self
+  }
+
+  /// Apply add-k smoothing to the transitions of states.
+  ///
+  /// Generating a chain fails if `k` is negative.
+  pub fn smoothing(mut self, k: f64) -> Self {
+    self.smoothing = Some(k);
+    self
+  }
+
+  /// Reject chains copying learned lines, making at most `attempts` attempts.
+  pub fn reject_verbatim(mut self, attempts: usize) -> Self {
+    self.reject_verbatim = Some(attempts);
+    self
+  }
+
+  /// Don’t go through any of the last `window` states again.
+  pub fn no_repeat(mut self, window: usize) -> Self {
+    self.no_repeat = Some(window);
+    self
+  }
+
+  /// Stop the chain before it gets longer than `max_chars` characters.
+  pub fn max_chars(mut self, max_chars: usize) -> Self {
+    self.max_chars = Some(max_chars);
+    self
+  }
+
+  /// Reject chains of less than `min_wordings` wordings.
+  pub fn min_wordings(mut self, min_wordings: usize) -> Self {
+    self.min_wordings = Some(min_wordings);
+    self
+  }
+
+  /// Reject chains of less than `min_chars` characters.
+  pub fn min_chars(mut self, min_chars: usize) -> Self {
+    self.min_chars = Some(min_chars);
+    self
+  }
+
+  /// Generate chains going through a state containing `word`.
+  pub fn must_contain(mut self, word: impl Into<String>) -> Self {
+    self.must_contain = Some(word.into());
+    self
+  }
+
+  /// Grow chains backwards from the prompt as well as forwards.
+  pub fn bidirectional(mut self, bidirectional: bool) -> Self {
+    self.bidirectional = bidirectional;
+    self
+  }
+
+  /// Never generate any of the `banned_words`.
+  #[cfg(feature = "banned-words")]
+  pub fn banned_words(mut self, banned_words: BannedWords) -> Self {
+    self.banned_words = Some(banned_words);
+    self
+  }
+
+  /// Make at most `attempts` attempts at generating an acceptable chain.
+  pub fn attempts(mut self, attempts: usize) -> Self {
+    self.attempts = Some(attempts);
+    self
+  }
+
+  /// Start the chain from a state matching `prompt`.
+  pub fn prompt(mut self, prompt: impl Into<String>) -> Self {
+    self.prompt = Some(prompt.into());
+    self
+  }
+
+  /// Whether some words are banned.
+  #[cfg(feature = "banned-words")]
+  fn has_banned_words(&self) -> bool {
+    self.banned_words.is_some()
+  }
+
+  #[cfg(not(feature = "banned-words"))]
+  fn has_banned_words(&self) -> bool {
+    false
+  }
+
+  /// Whether the chain must not contain `wording`.
+  #[cfg(feature = "banned-words")]
+  fn bans(&self, wording: &Wording) -> bool {
+    self
+      .banned_words
+      .as_ref()
+      .is_some_and(|banned_words| banned_words.bans(wording))
+  }
+
+  #[cfg(not(feature = "banned-words"))]
+  fn bans(&self, _: &Wording) -> bool {
+    false
+  }
+}
+
+/// Strategy used to pick the next [`Wording`] among the transitions of a [`State`].
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Sampling {
+  /// Pick transitions proportionally to the number of times they were seen while training.
+  #[default]
+  Weighted,
+  /// Pick any transition with the same probability, regardless of its number of occurrences.
+  Uniform,
+}
+
+/// Errors that can occur while training, generating or persisting a [`MarkovChainGenerator`].
+#[derive(Debug)]
+pub enum Error {
+  /// A source or a model couldn’t be read or written.
+  Io(io::Error),
+  /// A source couldn’t be parsed or queried.
+  Parse(Box<dyn error::Error + Send + Sync>),
+  /// The generator has no state to generate from.
+  EmptyModel,
+  /// No state matches the prompt.
+  NoStateMatchingPrompt(String),
+  /// No state contains the word chains must contain.
+  NoStateContaining(String),
+  /// Parameters are invalid.
+  InvalidParameters(String),
+  /// No generated chain was acceptable after that many attempts.
+  NoAcceptableChain(usize),
+  /// No persona is known for this author.
+  UnknownAuthor(String),
+  /// A model couldn’t be serialized or deserialized.
+  Serialization(Box<dyn error::Error + Send + Sync>),
+  /// A model was saved with a version of the format that isn’t supported, such as a newer one.
+  UnsupportedVersion(u32),
+  /// The generation was cancelled (see [`cancel`]).
+  Cancelled,
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    match self {
+      Error::Io(err) => write!(f, "I/O error: {}", err),
+      Error::Parse(err) => write!(f, "cannot parse source: {}", err),
+      Error::EmptyModel => f.write_str("the model is empty"),
+      Error::NoStateMatchingPrompt(prompt) => {
+        write!(f, "nothing matches the prompt {:?}", prompt)
+      }
+      Error::NoStateContaining(word) => write!(f, "nothing contains {:?}", word),
+      Error::InvalidParameters(reason) => write!(f, "invalid parameters: {}", reason),
+      Error::NoAcceptableChain(attempts) => {
+        write!(f, "no acceptable chain after {} attempts", attempts)
+      }
+      Error::UnknownAuthor(author) => write!(f, "unknown author {:?}", author),
+      Error::Serialization(err) => write!(f, "cannot (de)serialize model: {}", err),
+      Error::UnsupportedVersion(version) => {
+        write!(f, "model format version {} is not supported", version)
+      }
+      Error::Cancelled => f.write_str("the generation was cancelled"),
+    }
+  }
+}
+
+impl error::Error for Error {
+  fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+    match self {
+      Error::Io(err) => Some(err),
+      Error::Parse(err) | Error::Serialization(err) => Some(err.as_ref()),
+      _ => None,
+    }
+  }
+}
+
+impl From<io::Error> for Error {
+  fn from(err: io::Error) -> Self {
+    Error::Io(err)
+  }
+}
+
+#[cfg(feature = "serde_json")]
+impl From<serde_json::Error> for Error {
+  fn from(err: serde_json::Error) -> Self {
+    Error::Parse(Box::new(err))
+  }
+}
+
+#[cfg(feature = "csv")]
+impl From<csv::Error> for Error {
+  fn from(err: csv::Error) -> Self {
+    Error::Parse(Box::new(err))
+  }
 }
 
-/// Chain generation parameters.
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct ChainParameters {
-  /// Number of states to go through at maximum.
-  pub max_state_traversal: Option<usize>,
-}
+#[cfg(feature = "rusqlite")]
+impl From<rusqlite::Error> for Error {
+  fn from(err: rusqlite::Error) -> Self {
+    Error::Parse(Box::new(err))
+  }
+}
+
+/// A way to train a Markov chain generator based on a source.
+///
+/// This trait allows to adapt the way a Markov chain generator can learn without having to know
+/// the format of the input source.
+pub trait Trainer {
+  /// Adapt to the source and pass every cleaned line to `sink`, one at a time.
+  ///
+  /// Lines are meant to be produced lazily, so that the whole cleaned corpus never has to be held
+  /// in memory.
+  fn for_each_line(&mut self, sink: &mut dyn FnMut(&str)) -> Result<(), Error>;
+
+  /// Adapt to the source and train the input [`MarkovChainGenerator`].
+  fn source_train<S>(
+    &mut self,
+    markov_chain_generator: &mut MarkovChainGenerator<S>,
+    learn_params: &LearningParameters,
+  ) -> Result<(), Error>
+  where
+    Self: Sized,
+    S: BuildHasher + Default,
+  {
+    self.source_train_with_progress(markov_chain_generator, learn_params, &mut |_| ())
+  }
+
+  /// Adapt to the source and train the input [`MarkovChainGenerator`], reporting the progress to
+  /// `progress`.
+  fn source_train_with_progress<S>(
+    &mut self,
+    markov_chain_generator: &mut MarkovChainGenerator<S>,
+    learn_params: &LearningParameters,
+    progress: &mut dyn ProgressSink,
+  ) -> Result<(), Error>
+  where
+    Self: Sized,
+    S: BuildHasher + Default,
+  {
+    let mut count = 0;
+
+    self.for_each_line(&mut |line| {
+      markov_chain_generator.train(learn_params, line);
+      count += 1;
+
+      if count % PROGRESS_LINES == 0 {
+        progress.progress(count);
+      }
+    })?;
+
+    progress.progress(count);
+    info!("learned from {} lines", count);
+    Ok(())
+  }
+
+  /// Adapt to the source and train the input [`MarkovChainGenerator`] on several threads.
+  ///
+  /// Lines are read in chunks of [`PAR_CHUNK_LINES`], each of them learned in parallel with
+  /// [`MarkovChainGenerator::train_par`].
+  #[cfg(feature = "rayon")]
+  fn source_train_par<S>(
+    &mut self,
+    markov_chain_generator: &mut MarkovChainGenerator<S>,
+    learn_params: &LearningParameters,
+  ) -> Result<(), Error>
+  where
+    Self: Sized,
+    S: BuildHasher + Default + Send,
+  {
+    self.source_train_par_with_progress(markov_chain_generator, learn_params, &mut |_| ())
+  }
+
+  /// Adapt to the source and train the input [`MarkovChainGenerator`] on several threads,
+  /// reporting the progress to `progress` after every chunk.
+  #[cfg(feature = "rayon")]
+  fn source_train_par_with_progress<S>(
+    &mut self,
+    markov_chain_generator: &mut MarkovChainGenerator<S>,
+    learn_params: &LearningParameters,
+    progress: &mut dyn ProgressSink,
+  ) -> Result<(), Error>
+  where
+    Self: Sized,
+    S: BuildHasher + Default + Send,
+  {
+    let mut count = 0;
+    let mut chunk = Vec::with_capacity(PAR_CHUNK_LINES);
+
+    self.for_each_line(&mut |line| {
+      chunk.push(line.to_owned());
+      count += 1;
+
+      if chunk.len() == PAR_CHUNK_LINES {
+        markov_chain_generator.train_par(learn_params, std::mem::take(&mut chunk));
+        progress.progress(count);
+      }
+    })?;
+
+    markov_chain_generator.train_par(learn_params, chunk);
+    progress.progress(count);
+
+    info!("learned from {} lines", count);
+    Ok(())
+  }
+
+  /// Adapt to the source and train the input [`MarkovChainGenerator`], reporting the progress to
+  /// `progress` and saving checkpoints with `checkpointer`.
+  ///
+  /// A checkpoint that cannot be saved doesn’t stop the training; the error is logged, and saving
+  /// is tried again at the next checkpoint.
+  #[cfg(feature = "persistence")]
+  fn source_train_with_checkpoints<S>(
+    &mut self,
+    markov_chain_generator: &mut MarkovChainGenerator<S>,
+    learn_params: &LearningParameters,
+    progress: &mut dyn ProgressSink,
+    checkpointer: &mut Checkpointer,
+  ) -> Result<(), Error>
+  where
+    Self: Sized,
+    S: BuildHasher + Default,
+  {
+    let mut count = 0;
+
+    self.for_each_line(&mut |line| {
+      markov_chain_generator.train(learn_params, line);
+      count += 1;
+
+      if let Err(err) = checkpointer.learned(markov_chain_generator, 1) {
+        warn!("cannot save a checkpoint: {}", err);
+      }
+
+      if count % PROGRESS_LINES == 0 {
+        progress.progress(count);
+      }
+    })?;
+
+    progress.progress(count);
+    info!("learned from {} lines", count);
+    Ok(())
+  }
+
+  /// Adapt to the source and train the input [`MarkovChainGenerator`] on several threads,
+  /// reporting the progress to `progress` and saving checkpoints with `checkpointer` after every
+  /// chunk.
+  ///
+  /// See [`Trainer::source_train_with_checkpoints`].
+  #[cfg(all(feature = "persistence", feature = "rayon"))]
+  fn source_train_par_with_checkpoints<S>(
+    &mut self,
+    markov_chain_generator: &mut MarkovChainGenerator<S>,
+    learn_params: &LearningParameters,
+    progress: &mut dyn ProgressSink,
+    checkpointer: &mut Checkpointer,
+  ) -> Result<(), Error>
+  where
+    Self: Sized,
+    S: BuildHasher + Default + Send,
+  {
+    let mut count = 0;
+    let mut chunk = Vec::with_capacity(PAR_CHUNK_LINES);
+
+    let mut train_chunk = |markov_chain_generator: &mut MarkovChainGenerator<S>,
+                           chunk: Vec<String>| {
+      let lines = chunk.len();
+      markov_chain_generator.train_par(learn_params, chunk);
+
+      if let Err(err) = checkpointer.learned(markov_chain_generator, lines) {
+        warn!("cannot save a checkpoint: {}", err);
+      }
+    };
+
+    self.for_each_line(&mut |line| {
+      chunk.push(line.to_owned());
+      count += 1;
+
+      if chunk.len() == PAR_CHUNK_LINES {
+        train_chunk(markov_chain_generator, std::mem::take(&mut chunk));
+        progress.progress(count);
+      }
+    })?;
+
+    train_chunk(markov_chain_generator, chunk);
+    progress.progress(count);
+
+    info!("learned from {} lines", count);
+    Ok(())
+  }
+}
+
+impl<T> Trainer for Box<T>
+where
+  T: Trainer + ?Sized,
+{
+  fn for_each_line(&mut self, sink: &mut dyn FnMut(&str)) -> Result<(), Error> {
+    (**self).for_each_line(sink)
+  }
+}
+
+/// Number of lines learned between two reports of the progress of [`Trainer`]s.
+pub const PROGRESS_LINES: usize = 10_000;
+
+/// Observer of the progress of a training.
+///
+/// Closures taking the number of lines learned so far are progress sinks.
+pub trait ProgressSink {
+  /// Called every [`PROGRESS_LINES`] learned lines, and once the source is exhausted, with the
+  /// number of lines learned so far.
+  fn progress(&mut self, lines: usize);
+}
+
+impl<F> ProgressSink for F
+where
+  F: FnMut(usize),
+{
+  fn progress(&mut self, lines: usize) {
+    self(lines)
+  }
+}
+
+/// Number of lines read at once by [`Trainer::source_train_par`].
+#[cfg(feature = "rayon")]
+pub const PAR_CHUNK_LINES: usize = 100_000;
+
+/// (De)serialize maps as sequences of key-value pairs, as most formats only accept string keys.
+#[cfg(feature = "serde")]
+mod map_as_pairs {
+  use serde::{Deserialize, Deserializer, Serialize, Serializer};
+  use std::collections::HashMap;
+  use std::hash::{BuildHasher, Hash};
+
+  pub fn serialize<K, V, H, S>(map: &HashMap<K, V, H>, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    K: Serialize,
+    V: Serialize,
+    S: Serializer,
+  {
+    serializer.collect_seq(map.iter())
+  }
+
+  pub fn deserialize<'de, K, V, H, D>(deserializer: D) -> Result<HashMap<K, V, H>, D::Error>
+  where
+    K: Deserialize<'de> + Eq + Hash,
+    V: Deserialize<'de>,
+    H: BuildHasher + Default,
+    D: Deserializer<'de>,
+  {
+    Vec::<(K, V)>::deserialize(deserializer).map(|pairs| pairs.into_iter().collect())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_simple() {
+    let mut generator = MarkovChainGenerator::new();
+    let learn_param = LearningParameters::new(3).unwrap();
+    let chain_param = ChainParameters::new();
+
+    generator.train(&learn_param, "foo bar zoo quux hello, world!");
+    let result = generator.generate_chain(&chain_param);
+
+    eprintln!("result: {:?}", result);
+  }
+
+  #[test]
+  fn test_sliding_window() {
+    let mut generator = MarkovChainGenerator::new();
+    let learn_param = LearningParameters::new(2)
+      .unwrap()
+      .windowing(Windowing::Sliding);
+    let chain_param = ChainParameters::new();
+
+    generator.train(&learn_param, "foo bar zoo quux");
+    assert_eq!(generator.states.len(), 4);
+
+    // the only possible chain is the learned line
+    let result = generator.generate_chain(&chain_param);
+    assert_eq!(result.unwrap().to_string(), "foo bar zoo quux");
+  }
+
+  #[test]
+  fn test_seeded() {
+    let learn_param = LearningParameters::new(1).unwrap();
+    let chain_param = ChainParameters::new().max_state_traversal(20).seed(42);
+    let train = || {
+      let mut generator = MarkovChainGenerator::new();
+      generator.train(&learn_param, "a b a c b c a a b b c c a");
+      generator.train(&learn_param, "c b a b c a c b b a");
+      generator
+    };
+
+    let expected = train().generate_chain(&chain_param).unwrap().to_string();
+
+    for _ in 0..10 {
+      assert_eq!(
+        train().generate_chain(&chain_param).unwrap().to_string(),
+        expected
+      );
+    }
+  }
+
+  #[test]
+  fn test_prompt() {
+    let mut generator = MarkovChainGenerator::new();
+    let learn_param = LearningParameters::new(1).unwrap();
+    let mut chain_param = ChainParameters::new().prompt("hello");
+
+    generator.train(&learn_param, "hello world");
+    generator.train(&learn_param, "well, Hello there");
+    assert_eq!(
+      generator.generate_chain(&chain_param).unwrap().to_string(),
+      "hello world"
+    );
+
+    chain_param.prompt = Some("HELLO".to_owned());
+    let result = generator.generate_chain(&chain_param).unwrap().to_string();
+    assert!(result == "hello world" || result == "Hello there");
+
+    chain_param.prompt = Some("goodbye".to_owned());
+    assert!(matches!(
+      generator.generate_chain(&chain_param),
+      Err(Error::NoStateMatchingPrompt(prompt)) if prompt == "goodbye"
+    ));
+
+    for prompt in ["", " \t"] {
+      chain_param.prompt = Some(prompt.to_owned());
+      assert!(matches!(
+        generator.generate_chain(&chain_param),
+        Err(Error::InvalidParameters(_))
+      ));
+    }
+  }
 
-/// Chain generation error.
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub enum ChainError {
-  TooFewInitialStates(usize),
-}
+  #[test]
+  fn test_parameters() {
+    assert!(matches!(
+      LearningParameters::new(0),
+      Err(Error::InvalidParameters(_))
+    ));
 
-/// A way to train a Markov chain generator based on a source.
-///
-/// This trait allows to adapt the way a Markov chain generator can learn without having to know
-/// the format of the input source.
-pub trait Trainer {
-  /// Adapt to the source and train the input [`MarkovChainGenerator`].
-  fn source_train(
-    &mut self,
-    markov_chain_generator: &mut MarkovChainGenerator,
-    learn_params: LearningParameters,
-  ) -> Result<(), ChainError>;
-}
+    let mut generator = MarkovChainGenerator::new();
+    let learn_param = LearningParameters::new(2)
+      .unwrap()
+      .windowing(Windowing::Sliding);
+    generator.train(&learn_param, "foo bar zoo");
 
-#[cfg(test)]
-mod tests {
-  use super::*;
+    let chain_param = ChainParameters::new().seed(42).prompt("foo");
+    assert_eq!(
+      generator.generate_chain(&chain_param).unwrap().to_string(),
+      "foo bar zoo"
+    );
+
+    let chain_param = chain_param.max_state_traversal(0);
+    assert_eq!(
+      generator.generate_chain(&chain_param).unwrap().to_string(),
+      "foo bar"
+    );
+
+    let chain_param = ChainParameters::new().max_state_traversal(0);
+    assert_eq!(
+      generator.generate_chain(&chain_param).unwrap().to_string(),
+      ""
+    );
+  }
 
   #[test]
-  fn test_simple() {
+  fn test_back_off() {
     let mut generator = MarkovChainGenerator::new();
-    let learn_param = LearningParameters { wording_size: 3 };
-    let chain_param = ChainParameters {
-      max_state_traversal: None,
+    let learn_param = LearningParameters::new(3)
+      .unwrap()
+      .windowing(Windowing::Sliding)
+      .variable_order(true);
+    generator.train(&learn_param, "a b c d");
+    generator.train(&learn_param, "x b c e");
+
+    let outputs = |chain_param: ChainParameters| {
+      (0..50)
+        .map(|seed| {
+          generator
+            .generate_chain(&chain_param.clone().seed(seed))
+            .unwrap()
+            .to_string()
+        })
+        .collect::<std::collections::BTreeSet<_>>()
     };
 
-    generator.train(&learn_param, "foo bar zoo quux hello, world!");
-    let result = generator.generate_chain(&chain_param);
+    let chain_param = ChainParameters::new().prompt("a b c");
+    assert_eq!(outputs(chain_param.clone()).len(), 1);
+    assert_eq!(
+      outputs(chain_param.back_off(2))
+        .into_iter()
+        .collect::<Vec<_>>(),
+      vec!["a b c d", "a b c e"]
+    );
+  }
 
-    eprintln!("result: {:?}", result);
+  #[test]
+  fn test_smoothing() {
+    let mut generator = MarkovChainGenerator::new();
+    let learn_param = LearningParameters::new(1).unwrap();
+    generator.train(&learn_param, "a b c");
+
+    let chain_param = ChainParameters::new().max_state_traversal(10);
+    let smoothed_param = chain_param.clone().smoothing(10.);
+
+    for seed in 0..20 {
+      let output = generator
+        .generate_chain(&chain_param.clone().seed(seed))
+        .unwrap()
+        .to_string();
+      assert_eq!(output, "a b c");
+    }
+
+    assert!((0..20).any(|seed| {
+      generator
+        .generate_chain(&smoothed_param.clone().seed(seed))
+        .unwrap()
+        .to_string()
+        != "a b c"
+    }));
+
+    assert!(matches!(
+      generator.generate_chain(&chain_param.smoothing(-1.)),
+      Err(Error::InvalidParameters(_))
+    ));
+  }
+
+  #[test]
+  fn test_characters() {
+    let mut generator = MarkovChainGenerator::new();
+    let learn_param = LearningParameters::new(2)
+      .unwrap()
+      .windowing(Windowing::Sliding)
+      .granularity(Granularity::Character);
+    generator.train(&learn_param, "phaazon");
+
+    let chain_param = ChainParameters::new();
+    assert_eq!(
+      generator.generate_chain(&chain_param).unwrap().to_string(),
+      "phaazon"
+    );
+
+    let chain_param = chain_param.prompt("zo");
+    assert_eq!(
+      generator.generate_chain(&chain_param).unwrap().to_string(),
+      "zon"
+    );
+  }
+
+  #[test]
+  fn test_generate_chains() {
+    let learn_param = LearningParameters::new(1).unwrap();
+    let mut generator = MarkovChainGenerator::new();
+    generator.train(&learn_param, "a b");
+    generator.train(&learn_param, "a c");
+
+    let chain_param = ChainParameters::new().seed(42);
+    let chains = generator
+      .generate_chains(&chain_param)
+      .take(20)
+      .collect::<Result<Vec<_>, _>>()
+      .unwrap();
+    assert_eq!(chains.len(), 20);
+    assert!(chains.iter().any(|chain| chain.to_string() == "a b"));
+    assert!(chains.iter().any(|chain| chain.to_string() == "a c"));
+
+    let again = generator.generate_chains(&chain_param).take(20);
+    assert!(again.map(Result::unwrap).eq(chains));
+
+    let empty = MarkovChainGenerator::new();
+    assert!(matches!(
+      empty.generate_chains(&chain_param).next(),
+      Some(Err(Error::EmptyModel))
+    ));
+  }
+
+  #[test]
+  fn test_generate_distinct_chains() {
+    let learn_param = LearningParameters::new(1).unwrap();
+    let mut generator = MarkovChainGenerator::new();
+    generator.train(&learn_param, "a b");
+    generator.train(&learn_param, "a c");
+
+    let chain_param = ChainParameters::new().seed(42);
+    let mut chains = generator
+      .generate_distinct_chains(5, &chain_param)
+      .unwrap()
+      .iter()
+      .map(Chain::to_string)
+      .collect::<Vec<_>>();
+    chains.sort();
+    assert_eq!(chains, vec!["a b", "a c"]);
+
+    assert_eq!(
+      generator
+        .generate_distinct_chains(1, &chain_param)
+        .unwrap()
+        .len(),
+      1
+    );
+  }
+
+  #[test]
+  fn test_must_contain() {
+    let learn_param = LearningParameters::new(1).unwrap();
+    let mut generator = MarkovChainGenerator::new();
+    generator.train(&learn_param, "I like cheese a lot");
+    generator.train(&learn_param, "you like bread");
+    generator.train(&learn_param, "bread is nice");
+
+    for seed in 0..20 {
+      let chain_param = ChainParameters::new().seed(seed).must_contain("CHEESE");
+      let chain = generator.generate_chain(&chain_param).unwrap();
+
+      assert!(chain.tokens().iter().any(|token| token == "cheese"));
+      assert!(chain.to_string().ends_with("cheese a lot"));
+      assert!(chain.to_string().starts_with("I like") || chain.to_string().starts_with("you like"));
+      assert_eq!(chain.probabilities().len(), chain.wordings().len());
+    }
+
+    let chain_param = ChainParameters::new().must_contain("ham");
+    assert!(matches!(
+      generator.generate_chain(&chain_param),
+      Err(Error::NoStateContaining(word)) if word == "ham"
+    ));
+
+    let chain_param = chain_param.prompt("I");
+    assert!(matches!(
+      generator.generate_chain(&chain_param),
+      Err(Error::InvalidParameters(_))
+    ));
+  }
+
+  #[test]
+  fn test_backward() {
+    let learn_param = LearningParameters::new(1).unwrap();
+    let backward_param = learn_param.clone().backward(true);
+    let lines = ["I like cheese a lot", "you like bread", "bread is nice"];
+
+    let mut generator = MarkovChainGenerator::new();
+    let mut backward = MarkovChainGenerator::new();
+    for line in &lines {
+      generator.train(&learn_param, line);
+      backward.train(&backward_param, line);
+    }
+    assert_eq!(backward.backward_states.len(), generator.states.len());
+    assert_eq!(
+      backward.backward_states[&Wording::from_iter(vec!["like".to_owned()])]
+        .nexts
+        .len(),
+      2
+    );
+
+    for seed in 0..20 {
+      let chain_param = ChainParameters::new()
+        .seed(seed)
+        .prompt("cheese")
+        .bidirectional(true);
+      let chain = backward.generate_chain(&chain_param).unwrap().to_string();
+      assert!(chain == "I like cheese a lot" || chain == "you like cheese a lot");
+
+      let chain = generator.generate_chain(&chain_param).unwrap().to_string();
+      assert!(chain == "I like cheese a lot" || chain == "you like cheese a lot");
+    }
+
+    backward.prune(1);
+    assert_eq!(backward.backward_states.len(), generator.states.len());
+  }
+
+  #[test]
+  fn test_chain_metadata() {
+    let learn_param = LearningParameters::new(1).unwrap();
+    let mut generator = MarkovChainGenerator::new();
+    generator.train(&learn_param, "a b");
+    generator.train(&learn_param, "a c");
+
+    let chain = generator
+      .generate_chain(&ChainParameters::new().seed(42))
+      .unwrap();
+    let last = chain.tokens()[1].clone();
+    assert_eq!(
+      chain.wordings(),
+      &[
+        Wording::from_iter(vec!["a".to_owned()]),
+        Wording::from_iter(vec![last])
+      ][..]
+    );
+    assert_eq!(chain.probabilities(), &[1., 0.5][..]);
+    assert_eq!(chain.log_likelihood(), 0.5f64.ln());
+
+    let chain = generator
+      .generate_chain(&ChainParameters::new().prompt("a").smoothing(1.))
+      .unwrap();
+    assert_eq!(chain.wordings()[0].to_string(), "a");
+    assert_eq!(chain.probabilities().len(), chain.wordings().len() - 1);
+  }
+
+  #[test]
+  fn test_detokenize() {
+    let chain = |tokens: &[&str]| {
+      Chain::new(
+        tokens.iter().map(|&token| token.to_owned()).collect(),
+        Granularity::Word,
+      )
+    };
+
+    assert_eq!(
+      chain(&["hello", ",", "world", "!", "(", "it", "'s", "me", ")"]).to_string(),
+      "hello, world! (it's me)"
+    );
+    assert_eq!(
+      chain(&["he", "said", "\"", "don", "'t", "\"", "..."]).to_string(),
+      "he said \"don't\"..."
+    );
+  }
+
+  #[test]
+  fn test_case_folding() {
+    let mut generator = MarkovChainGenerator::new();
+    let learn_param = LearningParameters::new(1).unwrap().case_folding(true);
+    generator.train(&learn_param, "the NASA rocks. yes");
+    generator.train(&learn_param, "The NASA Rocks. yes");
+    generator.train(&learn_param, "the nasa rocks. yes");
+    assert_eq!(generator.states.len(), 5);
+
+    let chain_param = ChainParameters::new().prompt("THE");
+    assert_eq!(
+      generator.generate_chain(&chain_param).unwrap().to_string(),
+      "The NASA rocks. Yes"
+    );
+  }
+
+  #[test]
+  fn test_no_repeat() {
+    let mut generator = MarkovChainGenerator::new();
+    let learn_param = LearningParameters::new(1).unwrap();
+    generator.train(&learn_param, "a b a b a b a b c");
+
+    let chain_param = ChainParameters::new().no_repeat(2);
+    for seed in 0..20 {
+      let output = generator
+        .generate_chain(&chain_param.clone().seed(seed))
+        .unwrap()
+        .to_string();
+      assert_eq!(output, "a b c");
+    }
+
+    // without any other transition, the chain stops instead of going back
+    let mut generator = MarkovChainGenerator::new();
+    generator.train(&learn_param, "a b a b a b");
+    let output = generator.generate_chain(&chain_param).unwrap().to_string();
+    assert_eq!(output, "a b");
+  }
+
+  #[test]
+  fn test_max_chars() {
+    let mut generator = MarkovChainGenerator::new();
+    let learn_param = LearningParameters::new(2).unwrap();
+    generator.train(&learn_param, "hello world, how are you doing today?");
+
+    let output = |max_chars| {
+      generator
+        .generate_chain(&ChainParameters::new().max_chars(max_chars))
+        .unwrap()
+        .to_string()
+    };
+    assert_eq!(output(100), "hello world, how are you doing today?");
+    assert_eq!(output(20), "hello world, how are");
+    assert_eq!(output(19), "hello world,");
+    assert_eq!(output(5), "");
+  }
+
+  #[test]
+  fn test_min_length() {
+    let mut generator = MarkovChainGenerator::new();
+    let learn_param = LearningParameters::new(1).unwrap();
+    generator.train(&learn_param, "hi");
+    generator.train(&learn_param, "hello there, how are you?");
+
+    let chain_param = ChainParameters::new().min_wordings(2);
+    for seed in 0..20 {
+      let output = generator
+        .generate_chain(&chain_param.clone().seed(seed))
+        .unwrap()
+        .to_string();
+      assert_eq!(output, "hello there, how are you?");
+    }
+
+    let chain_param = ChainParameters::new().min_chars(3);
+    for seed in 0..20 {
+      let output = generator
+        .generate_chain(&chain_param.clone().seed(seed))
+        .unwrap()
+        .to_string();
+      assert_eq!(output, "hello there, how are you?");
+    }
+
+    let chain_param = ChainParameters::new().min_wordings(10).attempts(5);
+    assert!(matches!(
+      generator.generate_chain(&chain_param),
+      Err(Error::NoAcceptableChain(5))
+    ));
+  }
+
+  #[test]
+  fn test_score() {
+    let mut generator = MarkovChainGenerator::new();
+    assert_eq!(generator.score("hello"), f64::NEG_INFINITY);
+
+    let learn_param = LearningParameters::new(1).unwrap();
+    generator.train(&learn_param, "hello world");
+    generator.train(&learn_param, "hello there");
+
+    // vocabulary: hello, world, there and the line end
+    let expected = (3. / 6f64).ln() + (2. / 6f64).ln() + (2. / 5f64).ln();
+    assert!((generator.score("hello world") - expected).abs() < 1e-9);
+    assert!(generator.score("hello world") > generator.score("world hello"));
+    assert!(generator.perplexity("hello there") < generator.perplexity("there hello there"));
+  }
+
+  #[test]
+  fn test_reject_verbatim() {
+    let mut generator = MarkovChainGenerator::new();
+    let learn_param = LearningParameters::new(1)
+      .unwrap()
+      .verbatim_ngram(4)
+      .unwrap();
+    generator.train(&learn_param, "a b c d");
+    generator.train(&learn_param, "x b c y");
+
+    let chain_param = ChainParameters::new().reject_verbatim(100);
+    for seed in 0..20 {
+      let output = generator
+        .generate_chain(&chain_param.clone().seed(seed))
+        .unwrap()
+        .to_string();
+      assert!(output == "a b c y" || output == "x b c d");
+    }
+  }
+
+  #[test]
+  fn test_merge() {
+    let learn_param = LearningParameters::new(1).unwrap();
+    let mut generator = MarkovChainGenerator::new();
+    generator.train(&learn_param, "a b");
+    let mut other = MarkovChainGenerator::new();
+    other.train(&learn_param, "a b");
+    other.train(&learn_param, "a c");
+
+    generator.merge(&other);
+    assert_eq!(generator.stats().occurrences, 9.);
+
+    let a = iter::once("a".to_owned()).collect::<Wording>();
+    let continuations = generator.continuations(&a);
+    let continuations = continuations
+      .iter()
+      .map(|(next, probability)| (next.to_string(), *probability))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      continuations,
+      vec![("b".to_owned(), 2. / 3.), ("c".to_owned(), 1. / 3.)]
+    );
+
+    // merging generators trained separately is the same as training a single one
+    let lines = ["a b", "a c", "b c a"];
+    let mut trained = MarkovChainGenerator::new();
+    for line in &lines {
+      trained.train(&learn_param, line);
+    }
+    let merged = lines
+      .iter()
+      .map(|line| {
+        let mut generator = MarkovChainGenerator::new();
+        generator.train(&learn_param, line);
+        generator
+      })
+      .collect::<MarkovChainGenerator>();
+    assert_eq!(merged, trained);
+  }
+
+  #[test]
+  fn test_stats() {
+    let learn_param = LearningParameters::new(1).unwrap();
+    let mut generator = MarkovChainGenerator::new();
+    generator.train(&learn_param, "a b");
+    generator.train(&learn_param, "a b");
+    generator.train(&learn_param, "a c d");
+
+    let stats = generator.stats();
+    assert_eq!(stats.vocabulary, 4);
+    assert_eq!(stats.branching_factor(), 6. / 5.);
+
+    let a = iter::once("a".to_owned()).collect::<Wording>();
+    let b = iter::once("b".to_owned()).collect::<Wording>();
+    assert_eq!(generator.top_wordings(2), vec![(a, 3.), (b, 2.)]);
+    assert_eq!(generator.top_wordings(10).len(), 4);
+  }
+
+  #[test]
+  fn test_prune() {
+    let learn_param = LearningParameters::new(1).unwrap();
+    let mut generator = MarkovChainGenerator::new();
+    generator.train(&learn_param, "a b");
+    generator.train(&learn_param, "a b");
+    generator.train(&learn_param, "a c d");
+
+    generator.prune(2);
+    assert_eq!(
+      generator.stats(),
+      Stats {
+        states: 3,
+        transitions: 3,
+        occurrences: 7.,
+        vocabulary: 2,
+      }
+    );
+
+    // only "a b" remains
+    for seed in 0..10 {
+      let chain_param = ChainParameters::new().seed(seed);
+      let output = generator.generate_chain(&chain_param).unwrap().to_string();
+      assert_eq!(output, "a b");
+    }
+
+    let mut generator = MarkovChainGenerator::new();
+    generator.train(&learn_param, "a b");
+    generator.train(&learn_param, "a b");
+    generator.train(&learn_param, "a c d");
+    generator.prune_states(3);
+    assert_eq!(generator.stats().states, 3);
+    assert_eq!(
+      generator
+        .continuations(&iter::once("a".to_owned()).collect())
+        .len(),
+      1
+    );
+
+    // only the cycle between "a" and "b" remains, which never reaches the line end
+    let mut generator = MarkovChainGenerator::new();
+    generator.train(&learn_param, "a b a b a b");
+    generator.prune(2);
+    assert_eq!(generator.stats().states, 0);
+    assert!(matches!(
+      generator.generate_chain(&ChainParameters::new()),
+      Err(Error::EmptyModel)
+    ));
+  }
+
+  #[test]
+  fn test_train_weighted() {
+    let learn_param = LearningParameters::new(1).unwrap();
+    let mut generator = MarkovChainGenerator::new();
+    generator.train(&learn_param, "a b");
+    generator.train_weighted(&learn_param, "a c", 3.);
+    generator.train_weighted(&learn_param, "a d", 0.);
+    generator.train_weighted(&learn_param, "a d", f64::NAN);
+
+    let a = iter::once("a".to_owned()).collect::<Wording>();
+    let continuations = generator.continuations(&a);
+    assert_eq!(continuations.len(), 2);
+    assert_eq!(continuations[0].0.to_string(), "c");
+    assert_eq!(continuations[0].1, 0.75);
+    assert_eq!(generator.stats().occurrences, 3. * (1. + 3.));
+
+    // the surface forms of case-folded tokens are weighted too
+    let learn_param = learn_param.case_folding(true);
+    let mut generator = MarkovChainGenerator::new();
+    generator.train(&learn_param, "the NASA");
+    generator.train(&learn_param, "the NASA");
+    generator.train_weighted(&learn_param, "the Nasa", 3.);
+    let chain_param = ChainParameters::new().prompt("the");
+    assert_eq!(
+      generator.generate_chain(&chain_param).unwrap().to_string(),
+      "The Nasa"
+    );
+  }
+
+  #[test]
+  fn test_model_info() {
+    let learn_param = LearningParameters::new(1).unwrap();
+    let mut generator = MarkovChainGenerator::new();
+    assert_eq!(generator.info().lines, 0);
+    assert!(generator.info().trained_at.is_none());
+
+    generator.train(&learn_param, "hello world");
+    generator.train(&learn_param, "");
+    generator.record_source("hello.log");
+    generator.set_author("alice");
+
+    let mut other = MarkovChainGenerator::new();
+    other.train(&learn_param, "hello there");
+    other.record_source("hello.log");
+    other.record_source("there.log");
+    generator.merge(&other);
+
+    let info = generator.info();
+    assert_eq!(info.sources, &["hello.log", "there.log"]);
+    assert_eq!(info.author, Some("alice"));
+    assert_eq!(info.lines, 2);
+    assert!(info.trained_at.is_some());
+    assert_eq!(info.learn_param, Some(&learn_param));
+  }
+
+  #[test]
+  fn test_export_transitions() {
+    let learn_param = LearningParameters::new(1).unwrap();
+    let mut generator = MarkovChainGenerator::new();
+    generator.train(&learn_param, "hi, \"you\"");
+    generator.train(&learn_param, "hi");
+
+    let mut csv = Vec::new();
+    generator.export_transitions(&mut csv).unwrap();
+    assert_eq!(
+      String::from_utf8(csv).unwrap(),
+      "from,to,count\n,\"hi,\",1\n,hi,1\n\"\"\"you\"\"\",,1\n\"hi,\",\"\"\"you\"\"\",1\nhi,,1\n"
+    );
+
+    let mut tsv = Vec::new();
+    generator
+      .export_transitions_delimited(&mut tsv, '\t')
+      .unwrap();
+    assert!(String::from_utf8(tsv)
+      .unwrap()
+      .starts_with("from\tto\tcount\n\thi\t1\n\thi,\t1\n"));
+  }
+
+  #[test]
+  fn test_train_sequence() {
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Emote {
+      Kappa,
+      PogChamp,
+    }
+
+    impl Token for Emote {
+      fn to_key(&self) -> Cow<'_, str> {
+        Cow::Borrowed(match self {
+          Emote::Kappa => "Kappa",
+          Emote::PogChamp => "PogChamp",
+        })
+      }
+
+      fn from_key(key: &str) -> Option<Self> {
+        match key {
+          "Kappa" => Some(Emote::Kappa),
+          "PogChamp" => Some(Emote::PogChamp),
+          _ => None,
+        }
+      }
+    }
+
+    let learn_param = LearningParameters::new(1).unwrap();
+    let mut generator = MarkovChainGenerator::<RandomState, Emote>::default();
+    generator
+      .train_sequence(&learn_param, vec![Emote::Kappa, Emote::PogChamp])
+      .unwrap();
+    generator.train_sequence(&learn_param, Vec::new()).unwrap();
+    assert_eq!(generator.info().lines, 1);
+
+    let chain_param = ChainParameters::new().seed(0);
+    assert_eq!(
+      generator.generate_sequence(&chain_param).unwrap(),
+      vec![Emote::Kappa, Emote::PogChamp]
+    );
+
+    let mut ids = MarkovChainGenerator::<RandomState, u32>::default();
+    ids.train_sequence(&learn_param, vec![4, 8, 15]).unwrap();
+    assert_eq!(ids.generate_sequence(&chain_param).unwrap(), vec![4, 8, 15]);
+
+    // keys that wouldn’t round-trip through a saved model are rejected
+    let mut strings = MarkovChainGenerator::<RandomState, String>::default();
+    for key in &["a b", "", "\u{FDD1}"] {
+      assert!(matches!(
+        strings.train_sequence(&learn_param, vec!["x".to_owned(), key.to_string()]),
+        Err(Error::InvalidParameters(_))
+      ));
+    }
+    assert_eq!(strings.info().lines, 0);
+  }
+
+  #[test]
+  fn test_decay() {
+    let learn_param = LearningParameters::new(1).unwrap();
+    let mut generator = MarkovChainGenerator::new();
+    for _ in 0..4 {
+      generator.train(&learn_param, "a b");
+    }
+    generator.train(&learn_param, "a c");
+
+    let mut decayed = generator.clone();
+    decayed.decay(0.5, 0.01).unwrap();
+    let a = iter::once("a".to_owned()).collect::<Wording>();
+    assert_eq!(decayed.continuations(&a).len(), 2);
+    assert_eq!(decayed.stats().occurrences, 2.5 + 2.5 + 2.5);
+
+    generator.decay(0.5, 1.).unwrap();
+    assert_eq!(generator.continuations(&a).len(), 1);
+    assert_eq!(generator.stats().occurrences, 2.5 + 2. + 2.);
+
+    assert!(matches!(
+      generator.decay(1.5, 1.),
+      Err(Error::InvalidParameters(_))
+    ));
+    assert!(matches!(
+      generator.decay(0.5, -1.),
+      Err(Error::InvalidParameters(_))
+    ));
+    generator.decay(0., 0.).unwrap();
+    assert_eq!(generator.stats().states, 0);
+
+    // dropping the line start and end leaves a cycle that never ends, which is dropped too
+    let mut generator = MarkovChainGenerator::new();
+    generator.train(&learn_param, "a b a b a b");
+    generator.decay(0.9, 1.).unwrap();
+    assert!(matches!(
+      generator.generate_chain(&ChainParameters::new()),
+      Err(Error::EmptyModel)
+    ));
+
+    // tokens seen once keep their case
+    let learn_param = LearningParameters::new(1).unwrap().case_folding(true);
+    let mut generator = MarkovChainGenerator::new();
+    generator.train(&learn_param, "hello NASA");
+    generator.train(&learn_param, "hello world");
+    generator.decay(0.9, 0.01).unwrap();
+    let mut tokens = vec!["hello".to_owned(), "nasa".to_owned()];
+    generator.restore_case(&mut tokens);
+    assert_eq!(tokens, ["Hello", "NASA"]);
+  }
+
+  #[test]
+  fn test_compile() {
+    let learn_param = LearningParameters::new(1).unwrap();
+    let mut generator = MarkovChainGenerator::new();
+    for _ in 0..3 {
+      generator.train(&learn_param, "a b");
+    }
+    generator.train(&learn_param, "a c");
+
+    let trained = generator.clone();
+    generator.compile();
+    assert_eq!(generator, trained);
+
+    let mut rng = StdRng::seed_from_u64(0);
+    let chain_param = ChainParameters::new();
+    let mut b = 0;
+    for _ in 0..1000 {
+      let chain = generator
+        .generate_chain_with_rng(&chain_param, &mut rng)
+        .unwrap()
+        .to_string();
+      match chain.as_str() {
+        "a b" => b += 1,
+        "a c" => (),
+        _ => panic!("unexpected chain {}", chain),
+      }
+    }
+    assert!((700..800).contains(&b));
+
+    generator.train(&learn_param, "a d");
+    let a = iter::once("a".to_owned()).collect::<Wording>();
+    assert!(generator.states[&a].table.is_none());
+    assert_eq!(generator.continuations(&a).len(), 3);
+  }
+
+  #[test]
+  fn test_start_without_line_start() {
+    let learn_param = LearningParameters::new(1).unwrap();
+    let mut generator = MarkovChainGenerator::new();
+    generator.train(&learn_param, "one. yes. how are you");
+    generator.train(&learn_param, "two. yes. how are you");
+
+    // pruning drops the line start, and the sentence start that follows "yes." remains
+    generator.prune(2);
+    assert!(!generator.states.contains_key(&Wording::start()));
+
+    for seed in 0..20 {
+      let chain_param = ChainParameters::new().seed(seed);
+      let chain = generator.generate_chain(&chain_param).unwrap();
+      assert_eq!(chain.to_string(), "how are you");
+    }
+
+    generator.compile();
+    let chain = generator.generate_chain(&ChainParameters::new()).unwrap();
+    assert_eq!(chain.to_string(), "how are you");
+  }
+
+  #[test]
+  fn test_train_from_reader() {
+    let learn_param = LearningParameters::new(2).unwrap();
+    let mut streamed = MarkovChainGenerator::new();
+    let count = streamed
+      .train_from_reader(
+        &learn_param,
+        io::Cursor::new(
+          "hello world
+how are you?
+",
+        ),
+      )
+      .unwrap();
+    assert_eq!(count, 2);
+
+    let mut trained = MarkovChainGenerator::new();
+    trained.train(&learn_param, "hello world");
+    trained.train(&learn_param, "how are you?");
+    assert_eq!(streamed, trained);
+  }
+
+  #[test]
+  fn test_source_train_with_progress() {
+    struct Repeat(usize);
+
+    impl Trainer for Repeat {
+      fn for_each_line(&mut self, sink: &mut dyn FnMut(&str)) -> Result<(), Error> {
+        (0..self.0).for_each(|_| sink("hello world"));
+        Ok(())
+      }
+    }
+
+    let learn_param = LearningParameters::new(1).unwrap();
+    let mut generator = MarkovChainGenerator::new();
+    let mut reports = Vec::new();
+    Repeat(2 * PROGRESS_LINES + 1)
+      .source_train_with_progress(&mut generator, &learn_param, &mut |lines| {
+        reports.push(lines)
+      })
+      .unwrap();
+
+    assert_eq!(
+      reports,
+      vec![PROGRESS_LINES, 2 * PROGRESS_LINES, 2 * PROGRESS_LINES + 1]
+    );
+  }
+
+  #[cfg(feature = "rayon")]
+  #[test]
+  fn test_train_par() {
+    let learn_param = LearningParameters::new(2).unwrap();
+    let lines = (0..1000)
+      .map(|i| format!("line {} of {} lines", i % 17, i % 5))
+      .collect::<Vec<_>>();
+
+    let mut trained = MarkovChainGenerator::new();
+    for line in &lines {
+      trained.train(&learn_param, line);
+    }
+
+    let mut trained_par = MarkovChainGenerator::new();
+    trained_par.train_par(&learn_param, lines);
+    assert_eq!(trained_par, trained);
+  }
+
+  #[cfg(feature = "fxhash")]
+  #[test]
+  fn test_fxhash() {
+    let learn_param = LearningParameters::new(1).unwrap();
+    let mut generator = MarkovChainGenerator::new();
+    let mut fx_generator = FxMarkovChainGenerator::default();
+    for line in &["a b c", "a c b", "b a c", "c a b a"] {
+      generator.train(&learn_param, line);
+      fx_generator.train(&learn_param, line);
+    }
+
+    assert_eq!(fx_generator.stats(), generator.stats());
+    for seed in 0..10 {
+      let chain_param = ChainParameters::new().seed(seed);
+      assert_eq!(
+        fx_generator
+          .generate_chain(&chain_param)
+          .unwrap()
+          .to_string(),
+        generator.generate_chain(&chain_param).unwrap().to_string()
+      );
+    }
+
+    // trainers train generators of any hasher
+    struct Lines(&'static [&'static str]);
+
+    impl Trainer for Lines {
+      fn for_each_line(&mut self, sink: &mut dyn FnMut(&str)) -> Result<(), Error> {
+        self.0.iter().for_each(|line| sink(line));
+        Ok(())
+      }
+    }
+
+    let mut fx_trained = FxMarkovChainGenerator::default();
+    Lines(&["a b c", "a c b", "b a c", "c a b a"])
+      .source_train(&mut fx_trained, &learn_param)
+      .unwrap();
+    assert_eq!(fx_trained, fx_generator);
+  }
+
+  #[cfg(feature = "persistence")]
+  #[test]
+  fn test_save_load() {
+    let mut generator = MarkovChainGenerator::new();
+    let learn_param = LearningParameters::new(2)
+      .unwrap()
+      .windowing(Windowing::Sliding);
+
+    generator.train(&learn_param, "foo bar zoo quux");
+    generator.train(&learn_param, "foo bar quux zoo");
+
+    let mut saved = Vec::new();
+    generator.save(&mut saved).unwrap();
+
+    assert_eq!(
+      MarkovChainGenerator::load(saved.as_slice()).unwrap(),
+      generator
+    );
   }
 }
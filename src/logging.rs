@@ -0,0 +1,39 @@
+//! Logging macros.
+//!
+//! With the `log` feature, these forward to the [log](https://crates.io/crates/log) facade, so
+//! that applications decide what gets printed. Without it, they compile to nothing.
+
+#[cfg(feature = "log")]
+#[allow(unused_macros)]
+macro_rules! trace {
+  ($($arg:tt)*) => { log::trace!($($arg)*) };
+}
+
+#[cfg(feature = "log")]
+macro_rules! info {
+  ($($arg:tt)*) => { log::info!($($arg)*) };
+}
+
+#[cfg(feature = "log")]
+macro_rules! warn {
+  ($($arg:tt)*) => { log::warn!($($arg)*) };
+}
+
+#[cfg(not(feature = "log"))]
+macro_rules! trace {
+  ($($arg:tt)*) => {
+    if false {
+      let _ = format_args!($($arg)*);
+    }
+  };
+}
+
+#[cfg(not(feature = "log"))]
+macro_rules! info {
+  ($($arg:tt)*) => { trace!($($arg)*) };
+}
+
+#[cfg(not(feature = "log"))]
+macro_rules! warn {
+  ($($arg:tt)*) => { trace!($($arg)*) };
+}
@@ -0,0 +1,767 @@
+//! Read-only models queried in place, without being deserialized.
+//!
+//! Loading a model with [`MarkovChainGenerator::load`] builds hash maps that often take several
+//! times the size of the file in memory. A server impersonating dozens of large personas rather
+//! converts them once with [`MarkovChainGenerator::save_mapped`], then memory-maps them with
+//! [`MappedModel::open`]: only the pages touched by generation are ever read, and the operating
+//! system can share and evict them at will.
+//!
+//! The format is made of fixed-size little-endian tables, so that no parsing is needed:
+//!
+//! - a header, starting with [`MAGIC`] and [`VERSION`], holding the sizes of the tables;
+//! - the offsets of the interned strings in the string blob;
+//! - the offsets of the words of every wording in the word table;
+//! - the word table, made of string identifiers;
+//! - the offsets of the transitions of every wording in the transition table;
+//! - the transition table, made of wording identifiers and cumulative weights, so that weighted
+//!   picks are binary searches;
+//! - the string blob.
+//!
+//! Wordings are sorted by their tokens, so that prompts are found by binary search as well.
+//!
+//! [`MarkovChainGenerator::load`]: crate::MarkovChainGenerator::load
+
+use memmap2::Mmap;
+use rand::rngs::StdRng;
+use rand::{thread_rng, Rng, SeedableRng as _};
+use std::collections::{BTreeSet, HashMap};
+use std::convert::TryFrom;
+use std::fs::File;
+use std::hash::BuildHasher;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+use std::str;
+
+use crate::cancel::CancellationToken;
+use crate::{
+  Chain, ChainParameters, CharCounter, Error, Granularity, MarkovChainGenerator, Prompt, Sampling,
+  Wording, END_MARKER, START_MARKER,
+};
+
+/// Bytes starting every mapped model.
+pub const MAGIC: &[u8; 8] = b"IMPMMAP\0";
+
+/// Version of the format written by [`MarkovChainGenerator::save_mapped`].
+pub const VERSION: u32 = 1;
+
+/// Size of the header, in bytes.
+const HEADER_LEN: usize = 40;
+
+/// Size of a transition, in bytes: the identifier of the next wording and a cumulative weight.
+const TRANSITION_LEN: usize = 12;
+
+/// Identifier meaning that a wording doesn’t exist.
+const NONE: u32 = u32::MAX;
+
+/// Error of a file that isn’t a valid mapped model.
+fn invalid(reason: &str) -> Error {
+  Error::Serialization(Box::new(io::Error::new(
+    io::ErrorKind::InvalidData,
+    format!("invalid mapped model: {}", reason),
+  )))
+}
+
+/// Convert a size to its on-disk representation.
+fn to_u32(n: usize) -> Result<u32, Error> {
+  u32::try_from(n).map_err(|_| invalid("too many entries"))
+}
+
+impl<S> MarkovChainGenerator<S>
+where
+  S: BuildHasher + Default,
+{
+  /// Save the generator in the read-only format of [`MappedModel`].
+  ///
+  /// Only what generation needs is saved: back-off states, backward transitions, the surface forms
+  /// of case-folded tokens and verbatim n-grams are dropped. A generator that doesn’t know line
+  /// starts, such as once pruned, is saved with the sentence starts it would begin chains with as
+  /// line starts.
+  pub fn save_mapped<W>(&self, mut writer: W) -> Result<(), Error>
+  where
+    W: io::Write,
+  {
+    let start = Wording::start();
+    let start_nexts = if self.states.is_empty() || self.states.contains_key(&start) {
+      None
+    } else {
+      Some(self.start_weights())
+    };
+
+    // every wording, including the ones only appearing as transitions such as the line end
+    let mut wordings = self
+      .states
+      .iter()
+      .flat_map(|(wording, state)| std::iter::once(wording).chain(state.nexts.keys()))
+      .chain(start_nexts.as_ref().map(|_| &start))
+      .map(|wording| (wording.tokens().collect::<Vec<_>>(), wording))
+      .collect::<Vec<_>>();
+    wordings.sort();
+    wordings.dedup_by(|a, b| a.0 == b.0);
+
+    let strings = wordings
+      .iter()
+      .flat_map(|(tokens, _)| tokens.iter().copied())
+      .collect::<BTreeSet<_>>()
+      .into_iter()
+      .collect::<Vec<_>>();
+    let string_ids = strings
+      .iter()
+      .enumerate()
+      .map(|(id, string)| (*string, id as u32))
+      .collect::<HashMap<_, _>>();
+    let wording_ids = wordings
+      .iter()
+      .enumerate()
+      .map(|(id, (_, wording))| (*wording, id as u32))
+      .collect::<HashMap<_, _>>();
+
+    let mut string_offsets = vec![0];
+    let mut blob = Vec::new();
+    for string in &strings {
+      blob.extend_from_slice(string.as_bytes());
+      string_offsets.push(to_u32(blob.len())?);
+    }
+
+    let mut wording_offsets = vec![0];
+    let mut words = Vec::new();
+    let mut state_offsets = vec![0];
+    let mut transitions = Vec::new();
+    for (tokens, wording) in &wordings {
+      words.extend(tokens.iter().map(|token| string_ids[token]));
+      wording_offsets.push(to_u32(words.len())?);
+
+      let mut nexts = match (self.states.get(wording), &start_nexts) {
+        (Some(state), _) => state
+          .nexts
+          .iter()
+          .map(|(next, transition)| (wording_ids[next], transition.count))
+          .collect(),
+        (None, Some(start_nexts)) if wording.is_start() => start_nexts
+          .iter()
+          .map(|(next, weight)| (wording_ids[next], *weight))
+          .collect(),
+        (None, _) => Vec::new(),
+      };
+      nexts.sort_by_key(|(id, _)| *id);
+
+      let mut cumulative = 0.;
+      for (id, count) in nexts {
+        cumulative += count;
+        transitions.push((id, cumulative));
+      }
+      state_offsets.push(to_u32(transitions.len())?);
+    }
+
+    let id_of = |marker: &str| {
+      let marker = std::iter::once(marker.to_owned()).collect::<Wording>();
+      wording_ids.get(&marker).copied().unwrap_or(NONE)
+    };
+    let (overlap, case_folding) = self.learn_param.as_ref().map_or((0, false), |learn_param| {
+      (learn_param.overlap(), learn_param.case_folding)
+    });
+
+    let mut bytes = Vec::with_capacity(
+      HEADER_LEN
+        + 4 * (string_offsets.len() + wording_offsets.len() + words.len() + state_offsets.len())
+        + TRANSITION_LEN * transitions.len()
+        + blob.len(),
+    );
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.push(match self.granularity() {
+      Granularity::Word => 0,
+      Granularity::Character => 1,
+    });
+    bytes.push(u8::try_from(overlap).map_err(|_| invalid("wordings too large"))?);
+    bytes.push(u8::from(case_folding));
+    bytes.push(0);
+    for n in [
+      to_u32(strings.len())?,
+      to_u32(wordings.len())?,
+      to_u32(words.len())?,
+      to_u32(transitions.len())?,
+      id_of(START_MARKER),
+      id_of(END_MARKER),
+    ] {
+      bytes.extend_from_slice(&n.to_le_bytes());
+    }
+
+    for n in string_offsets
+      .iter()
+      .chain(&wording_offsets)
+      .chain(&words)
+      .chain(&state_offsets)
+    {
+      bytes.extend_from_slice(&n.to_le_bytes());
+    }
+    for (id, cumulative) in transitions {
+      bytes.extend_from_slice(&id.to_le_bytes());
+      bytes.extend_from_slice(&cumulative.to_le_bytes());
+    }
+    bytes.extend_from_slice(&blob);
+
+    writer.write_all(&bytes)?;
+    Ok(())
+  }
+}
+
+/// A read-only model, queried in place.
+///
+/// The model is backed by any bytes, usually a memory-mapped file (see [`MappedModel::open`]).
+/// Only the header is checked when the model is opened; the other tables are checked as they are
+/// read, so that opening a model doesn’t touch all of its pages, and a corrupted model fails
+/// generation instead of panicking.
+///
+/// Generation supports [`ChainParameters::prompt`], [`ChainParameters::sampling`],
+/// [`ChainParameters::max_state_traversal`], [`ChainParameters::max_chars`] and
+/// [`ChainParameters::seed`]; other parameters fail generation with
+/// [`Error::InvalidParameters`]. Case-folded models generate lowercase strings.
+#[derive(Debug)]
+pub struct MappedModel<B> {
+  bytes: B,
+  granularity: Granularity,
+  overlap: usize,
+  case_folding: bool,
+  wording_count: u32,
+  start: u32,
+  end: u32,
+  string_offsets: usize,
+  wording_offsets: usize,
+  words: usize,
+  state_offsets: usize,
+  transitions: usize,
+  blob: usize,
+}
+
+impl MappedModel<Mmap> {
+  /// Memory-map a model saved with [`MarkovChainGenerator::save_mapped`].
+  ///
+  /// The file must not be modified while it is mapped: truncating it makes reading the model
+  /// crash the process.
+  pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+    let file = File::open(path)?;
+    // SAFETY: the mapping is only read, and the documentation forbids modifying the file meanwhile
+    let mmap = unsafe { Mmap::map(&file)? };
+
+    Self::from_bytes(mmap)
+  }
+}
+
+impl<B> MappedModel<B>
+where
+  B: AsRef<[u8]>,
+{
+  /// Read a model from bytes written by [`MarkovChainGenerator::save_mapped`].
+  pub fn from_bytes(bytes: B) -> Result<Self, Error> {
+    let data = bytes.as_ref();
+
+    if data.len() < HEADER_LEN || &data[..8] != MAGIC {
+      return Err(invalid("not a mapped model"));
+    }
+
+    let u32_at =
+      |pos: usize| u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]);
+
+    let version = u32_at(8);
+    if version != VERSION {
+      return Err(invalid(&format!("unsupported version {}", version)));
+    }
+
+    let granularity = match data[12] {
+      0 => Granularity::Word,
+      1 => Granularity::Character,
+      _ => return Err(invalid("unknown granularity")),
+    };
+    let overlap = usize::from(data[13]);
+    let case_folding = data[14] != 0;
+
+    let string_count = u32_at(16) as usize;
+    let wording_count = u32_at(20);
+    let word_count = u32_at(24) as usize;
+    let transition_count = u32_at(28) as usize;
+    let start = u32_at(32);
+    let end = u32_at(36);
+
+    let string_offsets = HEADER_LEN;
+    let wording_offsets = string_offsets + 4 * (string_count + 1);
+    let words = wording_offsets + 4 * (wording_count as usize + 1);
+    let state_offsets = words + 4 * word_count;
+    let transitions = state_offsets + 4 * (wording_count as usize + 1);
+    let blob = transitions + TRANSITION_LEN * transition_count;
+
+    if data.len() < blob {
+      return Err(invalid("truncated"));
+    }
+
+    Ok(Self {
+      bytes,
+      granularity,
+      overlap,
+      case_folding,
+      wording_count,
+      start,
+      end,
+      string_offsets,
+      wording_offsets,
+      words,
+      state_offsets,
+      transitions,
+      blob,
+    })
+  }
+
+  /// Number of wordings, including the line start and end.
+  pub fn wording_count(&self) -> usize {
+    self.wording_count as usize
+  }
+
+  /// Read `len` bytes at `pos`.
+  fn slice(&self, pos: usize, len: usize) -> Result<&[u8], Error> {
+    self
+      .bytes
+      .as_ref()
+      .get(pos..pos + len)
+      .ok_or_else(|| invalid("truncated"))
+  }
+
+  /// Read the `index`-th number of a table of `u32` starting at `table`.
+  fn u32_at(&self, table: usize, index: usize) -> Result<u32, Error> {
+    let bytes = self.slice(table + 4 * index, 4)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+  }
+
+  /// Range of the `index`-th entry of an offset table starting at `table`.
+  fn range(&self, table: usize, index: u32) -> Result<Range<usize>, Error> {
+    let index = index as usize;
+    let start = self.u32_at(table, index)? as usize;
+    let end = self.u32_at(table, index + 1)? as usize;
+
+    if start > end {
+      return Err(invalid("decreasing offsets"));
+    }
+
+    Ok(start..end)
+  }
+
+  /// Interned string of an identifier.
+  fn string(&self, id: u32) -> Result<&str, Error> {
+    let range = self.range(self.string_offsets, id)?;
+    let bytes = self.slice(self.blob + range.start, range.len())?;
+
+    str::from_utf8(bytes).map_err(|_| invalid("invalid string"))
+  }
+
+  /// Tokens of a wording.
+  fn tokens(&self, wording: u32) -> Result<Vec<&str>, Error> {
+    self
+      .range(self.wording_offsets, wording)?
+      .map(|i| self.string(self.u32_at(self.words, i)?))
+      .collect()
+  }
+
+  /// Next wording and cumulative weight of the `index`-th transition.
+  fn transition(&self, index: usize) -> Result<(u32, f64), Error> {
+    let bytes = self.slice(self.transitions + TRANSITION_LEN * index, TRANSITION_LEN)?;
+    let mut id = [0; 4];
+    let mut cumulative = [0; 8];
+    id.copy_from_slice(&bytes[..4]);
+    cumulative.copy_from_slice(&bytes[4..]);
+
+    Ok((u32::from_le_bytes(id), f64::from_le_bytes(cumulative)))
+  }
+
+  /// Index of the first wording whose tokens aren’t less than `tokens`.
+  fn lower_bound(&self, tokens: &[String]) -> Result<u32, Error> {
+    let (mut low, mut high) = (0, self.wording_count);
+
+    while low < high {
+      let middle = low + (high - low) / 2;
+
+      if self
+        .tokens(middle)?
+        .iter()
+        .copied()
+        .lt(tokens.iter().map(String::as_str))
+      {
+        low = middle + 1;
+      } else {
+        high = middle;
+      }
+    }
+
+    Ok(low)
+  }
+
+  /// Wordings matching a prompt, sorted, as [`MarkovChainGenerator`] finds them.
+  ///
+  /// The wording made of the tokens of the prompt, and the wordings the prompt starts with, are
+  /// found by binary search, and the wordings starting with the prompt follow them. Only the last
+  /// resort, the wordings containing any token of the prompt, reads every wording.
+  fn prompt_wordings(&self, prompt: &str) -> Result<Vec<u32>, Error> {
+    let prompt = Prompt::new(prompt, self.granularity, self.case_folding)?;
+    let tokens = prompt.tokens();
+    let first = self.lower_bound(tokens)?;
+
+    if first < self.wording_count && prompt.is_exact(self.tokens(first)?) {
+      return Ok(vec![first]);
+    }
+
+    let mut wordings = Vec::new();
+    for len in 1..tokens.len() {
+      let wording = self.lower_bound(&tokens[..len])?;
+
+      if wording < self.wording_count && self.tokens(wording)? == tokens[..len] {
+        wordings.push(wording);
+      }
+    }
+
+    for wording in first..self.wording_count {
+      let candidate = self.tokens(wording)?;
+
+      if candidate.len() < tokens.len() || !prompt.is_prefix(candidate) {
+        break;
+      }
+
+      wordings.push(wording);
+    }
+
+    if !wordings.is_empty() {
+      return Ok(wordings);
+    }
+
+    let mut wordings = Vec::new();
+    for wording in 0..self.wording_count {
+      if wording != self.start && wording != self.end && prompt.is_fuzzy(self.tokens(wording)?) {
+        wordings.push(wording);
+      }
+    }
+
+    Ok(wordings)
+  }
+
+  /// Pick the wording following another one, if any.
+  fn pick_next<R>(
+    &self,
+    wording: u32,
+    sampling: Sampling,
+    rng: &mut R,
+  ) -> Result<Option<u32>, Error>
+  where
+    R: Rng,
+  {
+    let range = self.range(self.state_offsets, wording)?;
+    if range.is_empty() {
+      return Ok(None);
+    }
+
+    let index = match sampling {
+      Sampling::Uniform => rng.gen_range(range.start, range.end),
+      Sampling::Weighted => {
+        let (_, total) = self.transition(range.end - 1)?;
+        let target = rng.gen::<f64>() * total;
+        let (mut low, mut high) = (range.start, range.end - 1);
+
+        // first transition whose cumulative weight exceeds the target
+        while low < high {
+          let middle = low + (high - low) / 2;
+
+          if self.transition(middle)?.1 <= target {
+            low = middle + 1;
+          } else {
+            high = middle;
+          }
+        }
+
+        low
+      }
+    };
+
+    self.transition(index).map(|(next, _)| Some(next))
+  }
+
+  /// Generate a random chain.
+  ///
+  /// If [`ChainParameters::seed`] is set, the chain is always the same.
+  pub fn generate_chain(&self, chain_param: &ChainParameters) -> Result<Chain, Error> {
+    match chain_param.seed {
+      Some(seed) => self.generate_chain_with_rng(chain_param, &mut StdRng::seed_from_u64(seed)),
+      None => self.generate_chain_with_rng(chain_param, &mut thread_rng()),
+    }
+  }
+
+  /// Generate a random chain by using the provided random generator.
+  ///
+  /// [`ChainParameters::seed`] is ignored.
+  pub fn generate_chain_with_rng<R>(
+    &self,
+    chain_param: &ChainParameters,
+    rng: &mut R,
+  ) -> Result<Chain, Error>
+  where
+    R: Rng,
+  {
+    self.generate(chain_param, rng, None)
+  }
+
+  /// Generate a random chain by using the provided random generator, unless `cancel` is cancelled
+  /// meanwhile.
+  ///
+  /// See [`MarkovChainGenerator::generate_chain_cancellable`].
+  pub fn generate_chain_cancellable_with_rng<R>(
+    &self,
+    chain_param: &ChainParameters,
+    rng: &mut R,
+    cancel: &CancellationToken,
+  ) -> Result<Chain, Error>
+  where
+    R: Rng,
+  {
+    self.generate(chain_param, rng, Some(cancel))
+  }
+
+  /// Fail if a parameter that mapped models don’t support is set.
+  fn check_supported(chain_param: &ChainParameters) -> Result<(), Error> {
+    let ChainParameters {
+      back_off,
+      smoothing,
+      reject_verbatim,
+      no_repeat,
+      min_wordings,
+      min_chars,
+      attempts,
+      ref must_contain,
+      bidirectional,
+      ..
+    } = *chain_param;
+
+    #[cfg(feature = "banned-words")]
+    let banned_words = chain_param.banned_words.is_some();
+    #[cfg(not(feature = "banned-words"))]
+    let banned_words = false;
+
+    let unsupported = [
+      ("back_off", back_off.is_some()),
+      ("smoothing", smoothing.is_some()),
+      ("reject_verbatim", reject_verbatim.is_some()),
+      ("no_repeat", no_repeat.is_some()),
+      ("min_wordings", min_wordings.is_some()),
+      ("min_chars", min_chars.is_some()),
+      ("attempts", attempts.is_some()),
+      ("banned_words", banned_words),
+      ("must_contain", must_contain.is_some()),
+      ("bidirectional", bidirectional),
+    ]
+    .iter()
+    .filter(|(_, set)| *set)
+    .map(|(name, _)| *name)
+    .collect::<Vec<_>>();
+
+    if unsupported.is_empty() {
+      Ok(())
+    } else {
+      Err(Error::InvalidParameters(format!(
+        "mapped models don’t support {}",
+        unsupported.join(", ")
+      )))
+    }
+  }
+
+  /// Generate a random chain, checking for cancellation before every transition.
+  fn generate<R>(
+    &self,
+    chain_param: &ChainParameters,
+    rng: &mut R,
+    cancel: Option<&CancellationToken>,
+  ) -> Result<Chain, Error>
+  where
+    R: Rng,
+  {
+    Self::check_supported(chain_param)?;
+
+    if self.wording_count == 0 {
+      return Err(Error::EmptyModel);
+    }
+
+    let (mut key, mut tokens) = if let Some(prompt) = &chain_param.prompt {
+      let keys = self.prompt_wordings(prompt)?;
+
+      if keys.is_empty() {
+        return Err(Error::NoStateMatchingPrompt(prompt.clone()));
+      }
+
+      let key = keys[rng.gen_range(0, keys.len())];
+      (key, self.tokens(key)?)
+    } else if self.start != NONE {
+      (self.start, Vec::new())
+    } else {
+      return Err(invalid("no line start"));
+    };
+
+    let mut counter = CharCounter::new(self.granularity);
+    for token in &tokens {
+      counter.push(token);
+    }
+
+    for _ in 0..chain_param.max_state_traversal.unwrap_or(usize::MAX) {
+      if cancel.is_some_and(CancellationToken::is_cancelled) {
+        return Err(Error::Cancelled);
+      }
+
+      let next = match self.pick_next(key, chain_param.sampling, rng)? {
+        Some(next) if next != self.end => next,
+        _ => break,
+      };
+
+      let skip = if key == self.start { 0 } else { self.overlap };
+      let next_tokens = self
+        .tokens(next)?
+        .into_iter()
+        .skip(skip)
+        .collect::<Vec<_>>();
+
+      // stop at the last wording fitting in the character limit; the count is only kept if it does
+      let mut next_counter = counter;
+      for token in &next_tokens {
+        next_counter.push(token);
+      }
+
+      if chain_param
+        .max_chars
+        .is_some_and(|max_chars| next_counter.count() > max_chars)
+      {
+        break;
+      }
+
+      tokens.extend(next_tokens);
+      counter = next_counter;
+
+      key = next;
+    }
+
+    let tokens = tokens.into_iter().map(str::to_owned).collect();
+    Ok(Chain::new(tokens, self.granularity))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{LearningParameters, Windowing};
+
+  #[test]
+  fn test_mapped_model() {
+    let learn_param = LearningParameters::new(2)
+      .unwrap()
+      .windowing(Windowing::Sliding);
+    let mut generator = MarkovChainGenerator::new();
+    generator.train(&learn_param, "the cat sat on the mat");
+    generator.train(&learn_param, "the dog sat on the rug");
+
+    let mut bytes = Vec::new();
+    generator.save_mapped(&mut bytes).unwrap();
+    let model = MappedModel::from_bytes(bytes).unwrap();
+
+    let lines = [
+      "the cat sat on the mat",
+      "the dog sat on the rug",
+      "the cat sat on the rug",
+      "the dog sat on the mat",
+    ];
+    for seed in 0..20 {
+      let chain_param = ChainParameters::new().seed(seed);
+      let chain = model.generate_chain(&chain_param).unwrap().to_string();
+      assert!(lines.contains(&chain.as_str()), "{}", chain);
+    }
+
+    let chain_param = ChainParameters::new().prompt("dog sat").seed(1);
+    assert!(model
+      .generate_chain(&chain_param)
+      .unwrap()
+      .to_string()
+      .starts_with("dog sat on the"));
+
+    let chain_param = ChainParameters::new().prompt("on");
+    assert!(model.generate_chain(&chain_param).is_ok());
+
+    let chain_param = ChainParameters::new().max_state_traversal(0);
+    assert_eq!(model.generate_chain(&chain_param).unwrap().to_string(), "");
+
+    // prompts the model has no wording for match as with generators
+    let chain_param = ChainParameters::new().prompt("cat sat on").seed(1);
+    assert!(model
+      .generate_chain(&chain_param)
+      .unwrap()
+      .to_string()
+      .starts_with("cat sat on the"));
+
+    let chain_param = ChainParameters::new().prompt("big DOG").seed(1);
+    assert!(model
+      .generate_chain(&chain_param)
+      .unwrap()
+      .to_string()
+      .contains("dog"));
+
+    let chain_param = ChainParameters::new().prompt("bird");
+    assert!(matches!(
+      model.generate_chain(&chain_param),
+      Err(Error::NoStateMatchingPrompt(_))
+    ));
+
+    let chain_param = ChainParameters::new().prompt(" ");
+    assert!(matches!(
+      model.generate_chain(&chain_param),
+      Err(Error::InvalidParameters(_))
+    ));
+
+    for (chain, max_chars) in [("the cat", 3), ("the cat sat", 11), ("the cat sat", 13)] {
+      let chain_param = ChainParameters::new()
+        .prompt("the cat")
+        .max_chars(max_chars);
+      assert_eq!(
+        model.generate_chain(&chain_param).unwrap().to_string(),
+        chain
+      );
+    }
+
+    // parameters that mapped models don’t support aren’t silently ignored
+    let chain_param = ChainParameters::new().must_contain("dog");
+    assert!(matches!(
+      model.generate_chain(&chain_param),
+      Err(Error::InvalidParameters(_))
+    ));
+
+    let cancel = CancellationToken::new();
+    cancel.cancel();
+    assert!(matches!(
+      model.generate_chain_cancellable_with_rng(
+        &ChainParameters::new(),
+        &mut thread_rng(),
+        &cancel
+      ),
+      Err(Error::Cancelled)
+    ));
+
+    assert!(MappedModel::from_bytes(&b"not a model"[..]).is_err());
+  }
+
+  #[test]
+  fn test_start_without_line_start() {
+    let learn_param = LearningParameters::new(1).unwrap();
+    let mut generator = MarkovChainGenerator::new();
+    generator.train(&learn_param, "one. yes. how are you");
+    generator.train(&learn_param, "two. yes. how are you");
+    generator.prune(2);
+
+    // chains begin at the sentence start the generator would begin them with
+    let mut bytes = Vec::new();
+    generator.save_mapped(&mut bytes).unwrap();
+    let model = MappedModel::from_bytes(bytes).unwrap();
+
+    for seed in 0..20 {
+      let chain_param = ChainParameters::new().seed(seed);
+      let chain = model.generate_chain(&chain_param).unwrap();
+      assert_eq!(chain.to_string(), "how are you");
+    }
+  }
+}
@@ -0,0 +1,256 @@
+//! Interoperability with [markovify](https://github.com/jsvine/markovify) models.
+//!
+//! Many bots are built on Python’s markovify, and their communities keep trained models rather
+//! than the corpora they came from. Those models can be imported into a [`MarkovChainGenerator`],
+//! and generators exported to them.
+//!
+//! markovify saves a model, with `Text.to_json`, as an object holding the state size and the chain:
+//! a JSON-encoded list of `[state, {next word: count}]` pairs, where states are tuples of
+//! `state_size` words, padded with `___BEGIN__` at line starts, and `___END__` ends lines. This
+//! is a generator learned with [`Windowing::Sliding`] over words: the padded states are line
+//! starts, rebuilt from the first wording of every line.
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::hash::BuildHasher;
+use std::io;
+use std::iter;
+
+use crate::{Error, Granularity, LearningParameters, MarkovChainGenerator, Windowing, Wording};
+
+/// Word padding the states of line starts.
+const BEGIN: &str = "___BEGIN__";
+
+/// Word ending lines.
+const END: &str = "___END__";
+
+/// A markovify model, as saved by `Text.to_json`.
+#[derive(Deserialize)]
+struct Text {
+  state_size: usize,
+  chain: ChainJson,
+}
+
+/// A markovify chain, JSON-encoded as a string by `Text.to_json`, or inline.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ChainJson {
+  Encoded(String),
+  Inline(Vec<(Vec<String>, BTreeMap<String, f64>)>),
+}
+
+/// Create a wording out of words.
+fn wording(words: &[String]) -> Wording {
+  words.iter().cloned().collect()
+}
+
+/// Import a model saved by markovify’s `Text.to_json`.
+///
+/// The generator is learned with [`Windowing::Sliding`] wordings of `state_size` words. The
+/// original sentences markovify might have kept are ignored.
+pub fn import<S, R>(reader: R) -> Result<MarkovChainGenerator<S>, Error>
+where
+  S: BuildHasher + Default,
+  R: io::Read,
+{
+  let text = serde_json::from_reader::<_, Text>(reader)?;
+  let chain = match text.chain {
+    ChainJson::Encoded(json) => serde_json::from_str(&json)?,
+    ChainJson::Inline(chain) => chain,
+  };
+
+  let n = text.state_size;
+  let learn_param = LearningParameters::new(n)?.windowing(Windowing::Sliding);
+  let mut generator = MarkovChainGenerator::default();
+  let mut add = |from: Wording, to: Wording, count: f64| {
+    generator
+      .states
+      .entry(from)
+      .or_default()
+      .nexts_mut()
+      .entry(to)
+      .or_default()
+      .count += count;
+  };
+
+  for (state, nexts) in chain {
+    if state.len() != n {
+      return Err(Error::Parse(
+        format!("state {:?} is not made of {} words", state, n).into(),
+      ));
+    }
+
+    let padding = state.iter().take_while(|word| *word == BEGIN).count();
+
+    for (next, count) in nexts {
+      match (padding, next == END) {
+        // a complete state
+        (0, true) => add(wording(&state), Wording::end(), count),
+        (0, false) => {
+          let words = state[1..].iter().cloned().chain(iter::once(next));
+          add(wording(&state), words.collect(), count);
+        }
+
+        // the first complete state of a line
+        (1, false) => {
+          let words = state[1..].iter().cloned().chain(iter::once(next));
+          add(Wording::start(), words.collect(), count);
+        }
+
+        // a line shorter than a state, learned as a single wording
+        (padding, true) if padding < n => {
+          let words = wording(&state[padding..]);
+          add(Wording::start(), words.clone(), count);
+          add(words, Wording::end(), count);
+        }
+
+        // other padded states are prefixes of the states above
+        _ => (),
+      }
+    }
+  }
+
+  generator.learn_param = Some(learn_param);
+  Ok(generator)
+}
+
+/// Export a generator as a model markovify’s `Text.from_json` reads.
+///
+/// Only generators learned over words with [`Windowing::Sliding`] (or with wordings of a single
+/// word) can be exported. Back-off states and other extensions are dropped.
+pub fn export<S, W>(generator: &MarkovChainGenerator<S>, writer: W) -> Result<(), Error>
+where
+  S: BuildHasher + Default,
+  W: io::Write,
+{
+  let learn_param = generator.learn_param.as_ref().ok_or(Error::EmptyModel)?;
+  let n = learn_param.wording_size;
+
+  if learn_param.granularity != Granularity::Word || learn_param.overlap() + 1 != n {
+    return Err(Error::InvalidParameters(
+      "only models of sliding wordings of words can be exported to markovify".to_owned(),
+    ));
+  }
+
+  let mut chain = BTreeMap::<Vec<String>, BTreeMap<String, f64>>::new();
+  let mut add = |state: Vec<String>, next: &str, count: f64| {
+    *chain
+      .entry(state)
+      .or_default()
+      .entry(next.to_owned())
+      .or_default() += count;
+  };
+
+  for (from, state) in &generator.states {
+    let words = from.tokens().map(str::to_owned).collect::<Vec<_>>();
+
+    for (to, transition) in &state.nexts {
+      let count = transition.count;
+
+      if from.is_start() {
+        // spell the line start out, one padded state per word of the first wording
+        let first = to.tokens().map(str::to_owned).collect::<Vec<_>>();
+        let padded = |i: usize| {
+          iter::repeat_n(BEGIN.to_owned(), n - i)
+            .chain(first[..i].iter().cloned())
+            .collect::<Vec<_>>()
+        };
+
+        for (i, word) in first.iter().enumerate() {
+          add(padded(i), word, count);
+        }
+
+        if first.len() < n {
+          add(padded(first.len()), END, count);
+        }
+      } else if words.len() == n {
+        match to.tokens().last() {
+          _ if to.is_end() => add(words.clone(), END, count),
+          Some(word) => add(words.clone(), word, count),
+          None => (),
+        }
+      }
+    }
+  }
+
+  let chain = chain
+    .into_iter()
+    .map(|(state, nexts)| {
+      let nexts = nexts
+        .into_iter()
+        .map(|(word, count)| {
+          // markovify counts occurrences; only weighted training leads to fractions
+          let count = if count.fract() == 0. {
+            serde_json::Value::from(count as u64)
+          } else {
+            serde_json::Value::from(count)
+          };
+          (word, count)
+        })
+        .collect::<serde_json::Map<_, _>>();
+      (state, nexts)
+    })
+    .collect::<Vec<_>>();
+
+  let chain = serde_json::to_string(&chain).map_err(|err| Error::Serialization(Box::new(err)))?;
+  let text = serde_json::json!({
+    "state_size": n,
+    "chain": chain,
+    "parsed_sentences": null,
+  });
+
+  serde_json::to_writer(writer, &text).map_err(|err| Error::Serialization(Box::new(err)))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::ChainParameters;
+
+  #[test]
+  fn test_markovify() {
+    // Text("the cat sat\nthe dog", state_size=2).to_json(), with retain_original=False
+    let chain = r#"[[["___BEGIN__", "___BEGIN__"], {"the": 2}], [["___BEGIN__", "the"], {"cat": 1, "dog": 1}], [["the", "cat"], {"sat": 1}], [["cat", "sat"], {"___END__": 1}], [["the", "dog"], {"___END__": 1}]]"#;
+    let json = serde_json::json!({
+      "state_size": 2,
+      "chain": chain,
+      "parsed_sentences": null,
+    })
+    .to_string();
+
+    let imported = import::<std::collections::hash_map::RandomState, _>(json.as_bytes()).unwrap();
+    let learn_param = LearningParameters::new(2)
+      .unwrap()
+      .windowing(Windowing::Sliding);
+    let mut trained = MarkovChainGenerator::new();
+    trained.train(&learn_param, "the cat sat");
+    trained.train(&learn_param, "the dog");
+    // imported models know nothing about the lines they were learned from
+    trained.metadata = Default::default();
+    assert_eq!(imported, trained);
+
+    // a line shorter than a state
+    trained.train(&learn_param, "hi");
+    trained.metadata = Default::default();
+
+    let mut exported = Vec::new();
+    export(&trained, &mut exported).unwrap();
+    let text = serde_json::from_slice::<serde_json::Value>(&exported).unwrap();
+    assert_eq!(text["state_size"], 2);
+    let chain = serde_json::from_str::<serde_json::Value>(text["chain"].as_str().unwrap()).unwrap();
+    assert_eq!(chain[0][0], serde_json::json!([BEGIN, BEGIN]));
+    assert_eq!(chain[0][1], serde_json::json!({"hi": 1, "the": 2}));
+
+    let reimported =
+      import::<std::collections::hash_map::RandomState, _>(exported.as_slice()).unwrap();
+    assert_eq!(reimported, trained);
+    assert!(reimported.generate_chain(&ChainParameters::new()).is_ok());
+
+    let mut chunks = MarkovChainGenerator::new();
+    chunks.train(&LearningParameters::new(2).unwrap(), "the cat sat");
+    assert!(matches!(
+      export(&chunks, Vec::new()),
+      Err(Error::InvalidParameters(_))
+    ));
+  }
+}
@@ -0,0 +1,189 @@
+//! Learning from live streams of messages.
+//!
+//! [`Trainer`](crate::Trainer)s and [`AuthorTrainer`](crate::persona::AuthorTrainer)s learn whole
+//! files. Bots receive messages one at a time instead, and learn each of them as it arrives. An
+//! [`OnlineTrainer`] applies to every message the same filtering as trainers do to the lines of a
+//! file — [`Cleanup`] passes, then exclude patterns — before learning it into the persona of its
+//! author, and counts the messages learned since the personas were last saved.
+
+use regex::RegexSet;
+use std::borrow::Cow;
+
+use crate::cleanup::Cleanup;
+use crate::persona::PersonaSet;
+use crate::trainers::exclude;
+use crate::{Error, LearningParameters};
+
+/// Personas learning messages as they are received.
+#[derive(Clone, Debug)]
+pub struct OnlineTrainer {
+  personas: PersonaSet,
+  learn_param: LearningParameters,
+  /// Cleanup passes applied to messages.
+  cleanup: Cleanup,
+  /// Patterns of the messages not to learn, once cleaned up.
+  exclude: Option<RegexSet>,
+  /// Messages learned since the personas were last saved.
+  unsaved: usize,
+}
+
+impl OnlineTrainer {
+  /// Learn into `personas`, such as a set loaded from a model, with `learn_param`.
+  ///
+  /// Fails if the personas were trained with other learning parameters, as mixing wordings of
+  /// different shapes would spoil them.
+  pub fn new(personas: PersonaSet, learn_param: LearningParameters) -> Result<Self, Error> {
+    if personas
+      .learning_parameters()
+      .is_some_and(|trained| *trained != learn_param)
+    {
+      return Err(Error::InvalidParameters(
+        "the personas were trained with other learning parameters".to_owned(),
+      ));
+    }
+
+    Ok(Self {
+      personas,
+      learn_param,
+      cleanup: Cleanup::default(),
+      exclude: None,
+      unsaved: 0,
+    })
+  }
+
+  /// Clean up messages before learning them.
+  pub fn cleanup(mut self, cleanup: Cleanup) -> Self {
+    self.cleanup = cleanup;
+    self
+  }
+
+  /// Don’t learn the messages matching any of `patterns` once cleaned up, as with
+  /// [`ExcludingTrainer`](crate::trainers::exclude::ExcludingTrainer).
+  pub fn exclude_patterns<P>(mut self, patterns: P) -> Result<Self, Error>
+  where
+    P: IntoIterator,
+    P::Item: AsRef<str>,
+  {
+    self.exclude = Some(exclude::compile(patterns)?);
+    Ok(self)
+  }
+
+  /// Learn a message into the persona of its author, creating it if needed.
+  ///
+  /// Returns whether the message was learned: messages left empty by the cleanup, or excluded,
+  /// aren’t.
+  pub fn feed_line(&mut self, author: &str, text: &str) -> bool {
+    let text = match self.cleanup.apply(text) {
+      Cow::Borrowed(text) => Cow::Borrowed(text.trim()),
+      Cow::Owned(text) => Cow::Owned(text.trim().to_owned()),
+    };
+
+    let excluded = self
+      .exclude
+      .as_ref()
+      .is_some_and(|patterns| exclude::excludes(patterns, &text));
+    if text.is_empty() || excluded {
+      return false;
+    }
+
+    self.personas.train(author, &self.learn_param, &*text);
+    self.unsaved += 1;
+    true
+  }
+
+  /// Personas learned so far.
+  pub fn personas(&self) -> &PersonaSet {
+    &self.personas
+  }
+
+  /// Personas learned so far, mutably.
+  pub fn personas_mut(&mut self) -> &mut PersonaSet {
+    &mut self.personas
+  }
+
+  /// Unwrap the personas.
+  pub fn into_personas(self) -> PersonaSet {
+    self.personas
+  }
+
+  /// Number of messages learned since the personas were last saved with
+  /// [`OnlineTrainer::save`], or since the trainer was created.
+  pub fn unsaved(&self) -> usize {
+    self.unsaved
+  }
+
+  /// Copy of the personas learned so far, to be saved elsewhere, such as on another thread.
+  ///
+  /// The number of unsaved messages is reset, as if the personas were saved.
+  pub fn snapshot(&mut self) -> PersonaSet {
+    self.unsaved = 0;
+    self.personas.clone()
+  }
+}
+
+#[cfg(feature = "persistence")]
+impl OnlineTrainer {
+  /// Save the personas (see [`PersonaSet::save`]), resetting the number of unsaved messages.
+  pub fn save<W>(&mut self, writer: W) -> Result<(), Error>
+  where
+    W: std::io::Write,
+  {
+    self.personas.save(writer)?;
+    self.unsaved = 0;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_feed_line() {
+    let learn_param = LearningParameters::new(1).unwrap();
+    let mut trainer = OnlineTrainer::new(PersonaSet::new(), learn_param)
+      .unwrap()
+      .cleanup(Cleanup::new().strip_mentions(true))
+      .exclude_patterns(&["^!"])
+      .unwrap();
+
+    assert!(trainer.feed_line("alice", "bob: hello world "));
+    assert!(!trainer.feed_line("alice", "bob: "));
+    assert!(!trainer.feed_line("bob", "!quote alice"));
+    assert!(trainer.feed_line("bob", "hi"));
+    assert_eq!(trainer.unsaved(), 2);
+    assert_eq!(trainer.personas().authors(), vec!["alice", "bob"]);
+    assert_eq!(&trainer.clone().snapshot(), trainer.personas());
+
+    let chain_param = crate::ChainParameters::new().prompt("hello");
+    assert_eq!(
+      trainer
+        .personas()
+        .generate_chain("alice", &chain_param)
+        .unwrap()
+        .to_string(),
+      "hello world"
+    );
+
+    // the personas keep the parameters they were trained with
+    let personas = trainer.into_personas();
+    assert!(OnlineTrainer::new(personas.clone(), LearningParameters::new(2).unwrap()).is_err());
+    assert!(OnlineTrainer::new(personas, LearningParameters::new(1).unwrap()).is_ok());
+  }
+
+  #[cfg(feature = "persistence")]
+  #[test]
+  fn test_save() {
+    let learn_param = LearningParameters::new(1).unwrap();
+    let mut trainer = OnlineTrainer::new(PersonaSet::new(), learn_param).unwrap();
+    trainer.feed_line("alice", "hello world");
+
+    let mut saved = Vec::new();
+    trainer.save(&mut saved).unwrap();
+    assert_eq!(trainer.unsaved(), 0);
+    assert_eq!(
+      PersonaSet::load(saved.as_slice()).unwrap(),
+      trainer.into_personas()
+    );
+  }
+}
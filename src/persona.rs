@@ -0,0 +1,246 @@
+//! Several authors impersonated at once.
+//!
+//! A [`PersonaSet`] maps author names to independent [`MarkovChainGenerator`]s. It can be trained
+//! from a single pass over a source knowing the authors of its messages (see [`AuthorTrainer`]),
+//! saved as a single file and queried by author at generation time.
+
+use std::collections::HashMap;
+
+use crate::cancel::CancellationToken;
+#[cfg(feature = "persistence")]
+use crate::format;
+use crate::{Chain, ChainParameters, Error, LearningParameters, MarkovChainGenerator};
+
+/// Generators indexed by the name of the author they impersonate.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PersonaSet {
+  personas: HashMap<String, MarkovChainGenerator>,
+}
+
+impl PersonaSet {
+  /// Create a set without any persona.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Train the generator of `author` on a line, creating it if it doesn’t exist yet.
+  pub fn train(&mut self, author: &str, learn_param: &LearningParameters, line: impl AsRef<str>) {
+    match self.personas.get_mut(author) {
+      Some(generator) => generator.train(learn_param, line),
+      None => {
+        let mut generator = MarkovChainGenerator::new();
+        generator.set_author(author);
+        generator.train(learn_param, line);
+        self.personas.insert(author.to_owned(), generator);
+      }
+    }
+  }
+
+  /// Add the generator of an author, returning the previous one, if any.
+  pub fn insert(
+    &mut self,
+    author: impl Into<String>,
+    generator: MarkovChainGenerator,
+  ) -> Option<MarkovChainGenerator> {
+    self.personas.insert(author.into(), generator)
+  }
+
+  /// Remove the generator of an author.
+  pub fn remove(&mut self, author: &str) -> Option<MarkovChainGenerator> {
+    self.personas.remove(author)
+  }
+
+  /// Generator of an author.
+  pub fn get(&self, author: &str) -> Option<&MarkovChainGenerator> {
+    self.personas.get(author)
+  }
+
+  /// Generator of an author, mutably.
+  pub fn get_mut(&mut self, author: &str) -> Option<&mut MarkovChainGenerator> {
+    self.personas.get_mut(author)
+  }
+
+  /// Authors of the set, sorted.
+  pub fn authors(&self) -> Vec<&str> {
+    let mut authors = self.personas.keys().map(String::as_str).collect::<Vec<_>>();
+    authors.sort_unstable();
+    authors
+  }
+
+  /// Learning parameters the personas were trained with, if any was trained at all.
+  ///
+  /// Personas are all trained with the same parameters: these are the ones of the first trained
+  /// persona.
+  pub fn learning_parameters(&self) -> Option<&LearningParameters> {
+    self
+      .authors()
+      .into_iter()
+      .find_map(|author| self.personas[author].learning_parameters())
+  }
+
+  /// Number of personas.
+  pub fn len(&self) -> usize {
+    self.personas.len()
+  }
+
+  /// Whether the set has no persona.
+  pub fn is_empty(&self) -> bool {
+    self.personas.is_empty()
+  }
+
+  /// Generate a random chain impersonating an author.
+  pub fn generate_chain(
+    &self,
+    author: &str,
+    chain_param: &ChainParameters,
+  ) -> Result<Chain, Error> {
+    self
+      .personas
+      .get(author)
+      .ok_or_else(|| Error::UnknownAuthor(author.to_owned()))?
+      .generate_chain(chain_param)
+  }
+
+  /// Generate a random chain impersonating `author`, unless `cancel` is cancelled meanwhile.
+  ///
+  /// See [`MarkovChainGenerator::generate_chain_cancellable`].
+  pub fn generate_chain_cancellable(
+    &self,
+    author: &str,
+    chain_param: &ChainParameters,
+    cancel: &CancellationToken,
+  ) -> Result<Chain, Error> {
+    self
+      .personas
+      .get(author)
+      .ok_or_else(|| Error::UnknownAuthor(author.to_owned()))?
+      .generate_chain_cancellable(chain_param, cancel)
+  }
+
+  /// Merge another set into this one, author by author.
+  ///
+  /// See [`MarkovChainGenerator::merge`].
+  pub fn merge(&mut self, other: &PersonaSet) {
+    for (author, generator) in &other.personas {
+      self
+        .personas
+        .entry(author.clone())
+        .or_default()
+        .merge(generator);
+    }
+  }
+}
+
+impl IntoIterator for PersonaSet {
+  type Item = (String, MarkovChainGenerator);
+  type IntoIter = std::collections::hash_map::IntoIter<String, MarkovChainGenerator>;
+
+  /// Authors and their generators, in no particular order.
+  fn into_iter(self) -> Self::IntoIter {
+    self.personas.into_iter()
+  }
+}
+
+#[cfg(feature = "persistence")]
+impl PersonaSet {
+  /// Save the set into a writer.
+  ///
+  /// The set is stored as versioned JSON (see [`format`](crate::format)) and can be read back
+  /// with [`PersonaSet::load`].
+  pub fn save<W>(&self, writer: W) -> Result<(), Error>
+  where
+    W: std::io::Write,
+  {
+    format::save(format::Kind::Personas, self, writer)
+  }
+
+  /// Load a set previously saved with [`PersonaSet::save`] from a reader.
+  ///
+  /// As with [`MarkovChainGenerator::load`], older versions of the format are upgraded.
+  pub fn load<R>(reader: R) -> Result<Self, Error>
+  where
+    R: std::io::Read,
+  {
+    format::load(format::Kind::Personas, reader)
+  }
+}
+
+/// A way to train several personas based on a source knowing the author of every message.
+///
+/// This is the multi-author counterpart of [`Trainer`](crate::Trainer): a log is read and
+/// cleaned once, whatever the number of people impersonated.
+pub trait AuthorTrainer {
+  /// Adapt to the source and pass every cleaned message to `sink`, along with its author.
+  fn for_each_message(&mut self, sink: &mut dyn FnMut(&str, &str)) -> Result<(), Error>;
+
+  /// Adapt to the source and train the personas of the input [`PersonaSet`].
+  fn persona_train(
+    &mut self,
+    personas: &mut PersonaSet,
+    learn_params: &LearningParameters,
+  ) -> Result<(), Error> {
+    let mut count = 0;
+
+    self.for_each_message(&mut |author, message| {
+      personas.train(author, learn_params, message);
+      count += 1;
+    })?;
+
+    info!("learned from {} messages", count);
+    Ok(())
+  }
+}
+
+impl<T> AuthorTrainer for Box<T>
+where
+  T: AuthorTrainer + ?Sized,
+{
+  fn for_each_message(&mut self, sink: &mut dyn FnMut(&str, &str)) -> Result<(), Error> {
+    (**self).for_each_message(sink)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  struct Messages(Vec<(&'static str, &'static str)>);
+
+  impl AuthorTrainer for Messages {
+    fn for_each_message(&mut self, sink: &mut dyn FnMut(&str, &str)) -> Result<(), Error> {
+      for (author, message) in &self.0 {
+        sink(author, message);
+      }
+
+      Ok(())
+    }
+  }
+
+  #[test]
+  fn test_personas() {
+    let learn_param = LearningParameters::new(2).unwrap();
+    let mut personas = PersonaSet::new();
+    Messages(vec![
+      ("alice", "hello everyone"),
+      ("bob", "hi alice"),
+      ("alice", "how are you?"),
+    ])
+    .persona_train(&mut personas, &learn_param)
+    .unwrap();
+
+    assert_eq!(personas.authors(), vec!["alice", "bob"]);
+    let chain_param = ChainParameters::new().seed(0);
+    assert_eq!(
+      personas
+        .generate_chain("bob", &chain_param)
+        .unwrap()
+        .to_string(),
+      "hi alice"
+    );
+    assert!(matches!(
+      personas.generate_chain("carol", &chain_param),
+      Err(Error::UnknownAuthor(_))
+    ));
+  }
+}
@@ -1,3 +1,59 @@
 //! Available trainers.
 
+pub mod composite;
+pub mod compressed;
+#[cfg(feature = "csv")]
+pub mod csv;
+#[cfg(feature = "dir")]
+pub mod dir;
+#[cfg(feature = "discord")]
+pub mod discord;
+#[cfg(feature = "cleanup")]
+pub mod exclude;
+#[cfg(feature = "hexchat")]
+pub mod hexchat;
+#[cfg(feature = "irclog")]
+pub mod irclog;
+#[cfg(feature = "jsonl")]
+pub mod jsonl;
+#[cfg(any(
+  feature = "hexchat",
+  feature = "irclog",
+  feature = "regex-trainer",
+  feature = "weechat",
+  feature = "znc"
+))]
+pub mod lines;
+#[cfg(feature = "mastodon")]
+pub mod mastodon;
+#[cfg(feature = "mbox")]
+pub mod mbox;
+#[cfg(feature = "messenger")]
+pub mod messenger;
+#[cfg(feature = "mirc")]
+pub mod mirc;
+pub mod plain;
+#[cfg(feature = "reddit")]
+pub mod reddit;
+#[cfg(feature = "regex-trainer")]
+pub mod regex;
+#[cfg(feature = "rusqlite")]
+pub mod sqlite;
+#[cfg(feature = "srt")]
+pub mod srt;
+#[cfg(feature = "telegram")]
+pub mod telegram;
+#[cfg(feature = "weechat")]
 pub mod weechat;
+#[cfg(feature = "znc")]
+pub mod znc;
+
+/// Lines learned by a trainer.
+#[cfg(test)]
+pub(crate) fn learned_lines(trainer: &mut dyn crate::Trainer) -> Vec<String> {
+  let mut lines = Vec::new();
+  trainer
+    .for_each_line(&mut |line| lines.push(line.to_owned()))
+    .unwrap();
+  lines
+}
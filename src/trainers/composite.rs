@@ -0,0 +1,117 @@
+//! A [`Trainer`] combining several other trainers.
+//!
+//! This allows to learn a single persona from several sources, such as IRC logs, tweets and commit
+//! messages, in a single training pass.
+
+use std::iter::FromIterator;
+
+use crate::{Error, Trainer};
+
+/// A set of trainers, used in order.
+#[derive(Default)]
+pub struct CompositeTrainer {
+  trainers: Vec<Box<dyn Trainer>>,
+}
+
+impl CompositeTrainer {
+  /// Create a composite trainer without any source.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Add a trainer, used after the ones already added.
+  pub fn with(mut self, trainer: impl Trainer + 'static) -> Self {
+    self.push(trainer);
+    self
+  }
+
+  /// Add a trainer, used after the ones already added.
+  pub fn push(&mut self, trainer: impl Trainer + 'static) {
+    self.trainers.push(Box::new(trainer));
+  }
+}
+
+impl From<Vec<Box<dyn Trainer>>> for CompositeTrainer {
+  fn from(trainers: Vec<Box<dyn Trainer>>) -> Self {
+    Self { trainers }
+  }
+}
+
+impl FromIterator<Box<dyn Trainer>> for CompositeTrainer {
+  fn from_iter<T>(iter: T) -> Self
+  where
+    T: IntoIterator<Item = Box<dyn Trainer>>,
+  {
+    Self {
+      trainers: iter.into_iter().collect(),
+    }
+  }
+}
+
+impl Trainer for CompositeTrainer {
+  fn for_each_line(&mut self, sink: &mut dyn FnMut(&str)) -> Result<(), Error> {
+    for trainer in &mut self.trainers {
+      trainer.for_each_line(sink)?;
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::trainers::learned_lines;
+  use std::io;
+
+  struct Lines(&'static [&'static str]);
+
+  impl Trainer for Lines {
+    fn for_each_line(&mut self, sink: &mut dyn FnMut(&str)) -> Result<(), Error> {
+      self.0.iter().for_each(|line| sink(line));
+      Ok(())
+    }
+  }
+
+  struct Failing;
+
+  impl Trainer for Failing {
+    fn for_each_line(&mut self, _: &mut dyn FnMut(&str)) -> Result<(), Error> {
+      Err(io::Error::new(io::ErrorKind::NotFound, "failing").into())
+    }
+  }
+
+  #[test]
+  fn test_order() {
+    let mut trainer = CompositeTrainer::new()
+      .with(Lines(&["a", "b"]))
+      .with(Lines(&[]))
+      .with(Lines(&["c"]));
+    assert_eq!(learned_lines(&mut trainer), vec!["a", "b", "c"]);
+
+    let mut trainer = vec![
+      Box::new(Lines(&["d"])) as Box<dyn Trainer>,
+      Box::new(Lines(&["e"])),
+    ]
+    .into_iter()
+    .collect::<CompositeTrainer>();
+    trainer.push(Lines(&["f"]));
+    assert_eq!(learned_lines(&mut trainer), vec!["d", "e", "f"]);
+
+    assert!(learned_lines(&mut CompositeTrainer::new()).is_empty());
+  }
+
+  #[test]
+  fn test_error() {
+    // trainers after a failing one aren’t used
+    let mut trainer = CompositeTrainer::from(vec![
+      Box::new(Lines(&["a"])) as Box<dyn Trainer>,
+      Box::new(Failing),
+      Box::new(Lines(&["b"])),
+    ]);
+    let mut lines = Vec::new();
+    let result = trainer.for_each_line(&mut |line| lines.push(line.to_owned()));
+    assert!(matches!(result, Err(Error::Io(_))));
+    assert_eq!(lines, vec!["a"]);
+  }
+}
@@ -0,0 +1,163 @@
+//! Transparent decompression of trainer inputs.
+//!
+//! Logs are routinely rotated into `.gz`, `.xz` or `.zst` files. The functions of this module
+//! detect the compression of an input from its first bytes and decompress it before it’s handed
+//! to a trainer; uncompressed inputs are passed through untouched.
+//!
+//! Decompression requires the `compression` feature. Without it, compressed inputs are detected
+//! but yield an error.
+
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+/// Compression format of an input.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Compression {
+  /// Not compressed.
+  None,
+  /// `gzip` (`.gz`).
+  Gzip,
+  /// `xz` (`.xz`).
+  Xz,
+  /// Zstandard (`.zst`).
+  Zstd,
+}
+
+impl Compression {
+  /// Detect the compression of an input from its first bytes.
+  pub fn detect(header: &[u8]) -> Self {
+    if header.starts_with(&[0x1F, 0x8B]) {
+      Compression::Gzip
+    } else if header.starts_with(&[0xFD, b'7', b'z', b'X', b'Z', 0x00]) {
+      Compression::Xz
+    } else if header.starts_with(&[0x28, 0xB5, 0x2F, 0xFD]) {
+      Compression::Zstd
+    } else {
+      Compression::None
+    }
+  }
+
+  /// Extension of files using this compression, if any.
+  pub fn extension(self) -> Option<&'static str> {
+    match self {
+      Compression::None => None,
+      Compression::Gzip => Some("gz"),
+      Compression::Xz => Some("xz"),
+      Compression::Zstd => Some("zst"),
+    }
+  }
+
+  /// Wrap a reader so that it yields decompressed data.
+  #[cfg(feature = "compression")]
+  fn decoder<'a>(self, reader: impl BufRead + 'a) -> io::Result<Box<dyn Read + 'a>> {
+    match self {
+      Compression::None => Ok(Box::new(reader)),
+      Compression::Gzip => Ok(Box::new(flate2::bufread::MultiGzDecoder::new(reader))),
+      Compression::Xz => Ok(Box::new(xz2::bufread::XzDecoder::new_multi_decoder(reader))),
+      Compression::Zstd => Ok(Box::new(zstd::stream::read::Decoder::with_buffer(reader)?)),
+    }
+  }
+
+  /// Wrap a reader so that it yields decompressed data.
+  #[cfg(not(feature = "compression"))]
+  fn decoder<'a>(self, reader: impl BufRead + 'a) -> io::Result<Box<dyn Read + 'a>> {
+    match self {
+      Compression::None => Ok(Box::new(reader)),
+      _ => Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+          "{:?} compressed input; enable the compression feature to read it",
+          self
+        ),
+      )),
+    }
+  }
+}
+
+/// Wrap a reader so that it yields decompressed data, detecting its compression.
+pub fn reader<'a>(reader: impl Read + 'a) -> io::Result<Box<dyn Read + 'a>> {
+  let mut reader = BufReader::new(reader);
+  let compression = Compression::detect(reader.fill_buf()?);
+  compression.decoder(reader)
+}
+
+/// Decompress some bytes, if they are compressed.
+pub fn decompress(bytes: &[u8]) -> io::Result<Vec<u8>> {
+  match Compression::detect(bytes) {
+    Compression::None => Ok(bytes.to_owned()),
+    compression => {
+      let mut decompressed = Vec::new();
+      compression.decoder(bytes)?.read_to_end(&mut decompressed)?;
+      Ok(decompressed)
+    }
+  }
+}
+
+/// Read the whole content of a file, decompressing it if needed.
+pub fn read(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+  decompress(&fs::read(path)?)
+}
+
+/// Read the whole content of a file as a string, decompressing it if needed.
+pub fn read_to_string(path: impl AsRef<Path>) -> io::Result<String> {
+  String::from_utf8(read(path)?).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Path of a file without its compression extension (`.gz`, `.xz` or `.zst`), if any.
+pub fn uncompressed_path(path: &Path) -> PathBuf {
+  let compressed = path
+    .extension()
+    .and_then(|extension| extension.to_str())
+    .is_some_and(|extension| {
+      [Compression::Gzip, Compression::Xz, Compression::Zstd]
+        .iter()
+        .any(|compression| compression.extension() == Some(extension))
+    });
+
+  if compressed {
+    path.with_extension("")
+  } else {
+    path.to_owned()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_uncompressed() {
+    assert_eq!(Compression::detect(b"hello"), Compression::None);
+    assert_eq!(decompress(b"hello").unwrap(), b"hello");
+    assert_eq!(
+      uncompressed_path(Path::new("logs/#rust.weechatlog.gz")),
+      Path::new("logs/#rust.weechatlog")
+    );
+    assert_eq!(
+      uncompressed_path(Path::new("logs/notes.txt")),
+      Path::new("logs/notes.txt")
+    );
+  }
+
+  #[cfg(feature = "compression")]
+  #[test]
+  fn test_gzip() {
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(b"hello, world").unwrap();
+    let compressed = encoder.finish().unwrap();
+
+    assert_eq!(Compression::detect(&compressed), Compression::Gzip);
+    assert_eq!(decompress(&compressed).unwrap(), b"hello, world");
+
+    let mut content = String::new();
+    reader(&compressed[..])
+      .unwrap()
+      .read_to_string(&mut content)
+      .unwrap();
+    assert_eq!(content, "hello, world");
+  }
+}
@@ -0,0 +1,147 @@
+//! A [`Trainer`] that can learn from any CSV (or TSV) file.
+//!
+//! The column containing the text to learn is selected by name, from the header line. Rows can
+//! also be filtered by author, if the file has an author column.
+
+use std::io;
+
+use crate::{Error, Trainer};
+
+/// Columns to read from a CSV file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CsvColumns {
+  /// Name of the column containing the text to learn.
+  pub text: String,
+  /// Name of the column containing authors, along with the author we are interested in.
+  pub author: Option<(String, String)>,
+  /// Field delimiter; `b','` for CSV, `b'\t'` for TSV.
+  pub delimiter: u8,
+}
+
+impl CsvColumns {
+  /// Read the `text` column of a comma-separated file, without filtering authors.
+  pub fn new(text: impl Into<String>) -> Self {
+    Self {
+      text: text.into(),
+      author: None,
+      delimiter: b',',
+    }
+  }
+}
+
+/// The content of a CSV file.
+pub struct CsvTrainer {
+  lines: Vec<String>,
+}
+
+impl CsvTrainer {
+  /// Parse a CSV file and keep the text of the rows matching the author, if any.
+  ///
+  /// Fails if the file is malformed or if one of the columns doesn’t exist.
+  pub fn new(columns: &CsvColumns, content: impl AsRef<str>) -> Result<Self, Error> {
+    let mut reader = ::csv::ReaderBuilder::new()
+      .delimiter(columns.delimiter)
+      .from_reader(content.as_ref().as_bytes());
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| {
+      headers
+        .iter()
+        .position(|header| header == name)
+        .ok_or_else(|| {
+          io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("no column named {}", name),
+          )
+        })
+    };
+
+    let text_column = column(&columns.text)?;
+    let author_column = match columns.author {
+      Some((ref name, ref author)) => Some((column(name)?, author)),
+      None => None,
+    };
+
+    let mut lines = Vec::new();
+    for record in reader.records() {
+      let record = record?;
+
+      if let Some((author_column, author)) = author_column {
+        if record.get(author_column) != Some(author.as_str()) {
+          continue;
+        }
+      }
+
+      if let Some(text) = record.get(text_column) {
+        lines.extend(
+          text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_owned),
+        );
+      }
+    }
+
+    Ok(Self { lines })
+  }
+}
+
+impl Trainer for CsvTrainer {
+  fn for_each_line(&mut self, sink: &mut dyn FnMut(&str)) -> Result<(), Error> {
+    for line in &self.lines {
+      sink(line);
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::trainers::learned_lines;
+
+  #[test]
+  fn test_columns() {
+    let content = "date,author,text\n\
+                   2020-01-31,alice,hello\n\
+                   2020-01-31,bob,\"hi, alice\"\n\
+                   2020-02-01,alice,\"how are you?\n  fine?\"\n\
+                   2020-02-01,alice,\n";
+
+    let trainer = CsvTrainer::new(&CsvColumns::new("text"), content).unwrap();
+    assert_eq!(
+      trainer.lines,
+      vec!["hello", "hi, alice", "how are you?", "fine?"]
+    );
+
+    let columns = CsvColumns {
+      author: Some(("author".to_owned(), "alice".to_owned())),
+      ..CsvColumns::new("text")
+    };
+    let mut trainer = CsvTrainer::new(&columns, content).unwrap();
+    let lines = learned_lines(&mut trainer);
+    assert_eq!(lines, vec!["hello", "how are you?", "fine?"]);
+
+    let columns = CsvColumns {
+      delimiter: b'\t',
+      ..CsvColumns::new("text")
+    };
+    let trainer = CsvTrainer::new(&columns, "author\ttext\nalice\ta, b\n").unwrap();
+    assert_eq!(trainer.lines, vec!["a, b"]);
+  }
+
+  #[test]
+  fn test_errors() {
+    assert!(CsvTrainer::new(&CsvColumns::new("message"), "author,text\nalice,hello\n").is_err());
+
+    let columns = CsvColumns {
+      author: Some(("user".to_owned(), "alice".to_owned())),
+      ..CsvColumns::new("text")
+    };
+    assert!(CsvTrainer::new(&columns, "author,text\nalice,hello\n").is_err());
+
+    // rows of different lengths are malformed
+    assert!(CsvTrainer::new(&CsvColumns::new("text"), "author,text\nalice,hello,world\n").is_err());
+  }
+}
@@ -0,0 +1,351 @@
+//! A [`Trainer`] that can learn from every file of a directory, recursively.
+//!
+//! The format of each file is detected with a registry of [`Sniffer`]s, which look at the path and
+//! the beginning of the content of files and build the appropriate trainer. Files that no sniffer
+//! recognizes are ignored. Compressed files are decompressed first (see [`compressed`]).
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::trainers::compressed;
+use crate::trainers::hexchat::HexChatLogTrainer;
+use crate::trainers::mbox::MboxTrainer;
+use crate::trainers::mirc::MircLogTrainer;
+use crate::trainers::plain::PlainTextTrainer;
+use crate::trainers::srt::SubtitleTrainer;
+use crate::trainers::weechat::WeechatLogTrainer;
+use crate::trainers::znc::ZncLogTrainer;
+use crate::{Error, Trainer};
+
+lazy_static! {
+  static ref REGEX_WEECHAT: Regex =
+    Regex::new(r"(?m)^\d{4}-\d{2}-\d{2} \d{2}:\d{2}:\d{2}\t").unwrap();
+  static ref REGEX_ZNC: Regex = Regex::new(r"(?m)^\[\d{2}:\d{2}:\d{2}\] ").unwrap();
+  static ref REGEX_MIRC: Regex = Regex::new(r"(?m)^(Session Start: |\[\d{2}:\d{2}\] )").unwrap();
+  static ref REGEX_HEXCHAT: Regex =
+    Regex::new(r"(?m)^(\*\*\*\* BEGIN LOGGING AT |[A-Z][a-z]{2} \d{2} \d{2}:\d{2}:\d{2} )")
+      .unwrap();
+  static ref REGEX_SRT: Regex =
+    Regex::new(r"^\u{FEFF}?\d+\r?\n\d{2}:\d{2}:\d{2},\d{3} --> ").unwrap();
+}
+
+/// Size of the beginning of files passed to sniffers.
+const SAMPLE_SIZE: usize = 64 * 1024;
+
+/// Extensions of files considered as plain text.
+const PLAIN_TEXT_EXTENSIONS: [&str; 2] = ["txt", "md"];
+
+/// Format detector.
+pub trait Sniffer {
+  /// Check whether a file is in a format handled by this sniffer, and if so, build a trainer
+  /// learning the messages of `author` from its content.
+  ///
+  /// `sample` is the beginning of the content.
+  fn sniff(
+    &self,
+    path: &Path,
+    sample: &str,
+    author: &str,
+    content: &str,
+  ) -> Option<Box<dyn Trainer>>;
+}
+
+impl<F> Sniffer for F
+where
+  F: Fn(&Path, &str, &str, &str) -> Option<Box<dyn Trainer>>,
+{
+  fn sniff(
+    &self,
+    path: &Path,
+    sample: &str,
+    author: &str,
+    content: &str,
+  ) -> Option<Box<dyn Trainer>> {
+    self(path, sample, author, content)
+  }
+}
+
+fn sniff_weechat(_: &Path, sample: &str, author: &str, content: &str) -> Option<Box<dyn Trainer>> {
+  if REGEX_WEECHAT.is_match(sample) {
+    Some(Box::new(WeechatLogTrainer::new(author, content)))
+  } else {
+    None
+  }
+}
+
+fn sniff_znc(path: &Path, sample: &str, author: &str, content: &str) -> Option<Box<dyn Trainer>> {
+  if REGEX_ZNC.is_match(sample) {
+    let trainer = ZncLogTrainer::new(author, content);
+    let trainer = match path.file_name().and_then(|name| name.to_str()) {
+      Some(name) => trainer.file_name(name),
+      None => trainer,
+    };
+
+    Some(Box::new(trainer))
+  } else {
+    None
+  }
+}
+
+fn sniff_mirc(_: &Path, sample: &str, author: &str, content: &str) -> Option<Box<dyn Trainer>> {
+  if REGEX_MIRC.is_match(sample) {
+    Some(Box::new(MircLogTrainer::new(author, content)))
+  } else {
+    None
+  }
+}
+
+fn sniff_hexchat(_: &Path, sample: &str, author: &str, content: &str) -> Option<Box<dyn Trainer>> {
+  if REGEX_HEXCHAT.is_match(sample) {
+    Some(Box::new(HexChatLogTrainer::new(author, content)))
+  } else {
+    None
+  }
+}
+
+fn sniff_srt(_: &Path, sample: &str, _: &str, content: &str) -> Option<Box<dyn Trainer>> {
+  if REGEX_SRT.is_match(sample) {
+    Some(Box::new(SubtitleTrainer::new(content)))
+  } else {
+    None
+  }
+}
+
+fn sniff_mbox(_: &Path, sample: &str, author: &str, content: &str) -> Option<Box<dyn Trainer>> {
+  if sample.starts_with("From ") {
+    Some(Box::new(MboxTrainer::new(author, content)))
+  } else {
+    None
+  }
+}
+
+#[cfg(feature = "discord")]
+fn sniff_discord(_: &Path, sample: &str, author: &str, content: &str) -> Option<Box<dyn Trainer>> {
+  use crate::trainers::discord::{DiscordAuthor, DiscordExportTrainer};
+
+  if sample.contains("\"author\"") || sample.contains("\"Contents\"") {
+    let author = DiscordAuthor::Name(author.to_owned());
+    DiscordExportTrainer::new(author, content)
+      .ok()
+      .map(|trainer| Box::new(trainer) as Box<dyn Trainer>)
+  } else {
+    None
+  }
+}
+
+#[cfg(feature = "telegram")]
+fn sniff_telegram(_: &Path, sample: &str, author: &str, content: &str) -> Option<Box<dyn Trainer>> {
+  use crate::trainers::telegram::{TelegramAuthor, TelegramExportTrainer};
+
+  if sample.contains("\"from_id\"") {
+    let author = TelegramAuthor::Name(author.to_owned());
+    TelegramExportTrainer::new(author, content)
+      .ok()
+      .map(|trainer| Box::new(trainer) as Box<dyn Trainer>)
+  } else {
+    None
+  }
+}
+
+#[cfg(feature = "mastodon")]
+fn sniff_mastodon(_: &Path, sample: &str, _: &str, content: &str) -> Option<Box<dyn Trainer>> {
+  use crate::trainers::mastodon::MastodonOutboxTrainer;
+
+  if sample.contains("\"orderedItems\"") {
+    MastodonOutboxTrainer::new(content)
+      .ok()
+      .map(|trainer| Box::new(trainer) as Box<dyn Trainer>)
+  } else {
+    None
+  }
+}
+
+#[cfg(feature = "messenger")]
+fn sniff_messenger(
+  _: &Path,
+  sample: &str,
+  author: &str,
+  content: &str,
+) -> Option<Box<dyn Trainer>> {
+  use crate::trainers::messenger::MessengerExportTrainer;
+
+  if sample.contains("\"sender_name\"") {
+    MessengerExportTrainer::new(author, content)
+      .ok()
+      .map(|trainer| Box::new(trainer) as Box<dyn Trainer>)
+  } else {
+    None
+  }
+}
+
+fn sniff_plain(path: &Path, _: &str, _: &str, content: &str) -> Option<Box<dyn Trainer>> {
+  let extension = path.extension()?.to_str()?.to_lowercase();
+
+  if PLAIN_TEXT_EXTENSIONS.contains(&extension.as_str()) {
+    Some(Box::new(PlainTextTrainer::new(content)))
+  } else {
+    None
+  }
+}
+
+/// Sniffers of all the available trainers.
+///
+/// Plain text is only detected with file extensions (`.txt` and `.md`), and as a last resort.
+pub fn default_sniffers() -> Vec<Box<dyn Sniffer>> {
+  let mut sniffers: Vec<Box<dyn Sniffer>> = vec![
+    Box::new(sniff_mbox),
+    Box::new(sniff_srt),
+    Box::new(sniff_weechat),
+    Box::new(sniff_znc),
+    Box::new(sniff_mirc),
+    Box::new(sniff_hexchat),
+  ];
+
+  #[cfg(feature = "discord")]
+  sniffers.push(Box::new(sniff_discord));
+  #[cfg(feature = "messenger")]
+  sniffers.push(Box::new(sniff_messenger));
+  #[cfg(feature = "telegram")]
+  sniffers.push(Box::new(sniff_telegram));
+  #[cfg(feature = "mastodon")]
+  sniffers.push(Box::new(sniff_mastodon));
+
+  sniffers.push(Box::new(sniff_plain));
+
+  sniffers
+}
+
+/// Collect the files of a directory, recursively and sorted.
+///
+/// Symbolic links are followed, but every directory is only walked once, so that links looping
+/// back to a parent directory don’t recurse forever.
+fn walk(dir: &Path, visited: &mut HashSet<PathBuf>, files: &mut Vec<PathBuf>) -> io::Result<()> {
+  if !visited.insert(dir.canonicalize()?) {
+    return Ok(());
+  }
+
+  let mut entries = fs::read_dir(dir)?
+    .map(|entry| entry.map(|entry| entry.path()))
+    .collect::<Result<Vec<_>, _>>()?;
+  entries.sort();
+
+  for path in entries {
+    if path.is_dir() {
+      walk(&path, visited, files)?;
+    } else {
+      files.push(path);
+    }
+  }
+
+  Ok(())
+}
+
+/// Every file of a directory.
+pub struct DirectoryTrainer {
+  files: Vec<PathBuf>,
+  /// The author we are interested in
+  author: String,
+  sniffers: Vec<Box<dyn Sniffer>>,
+}
+
+impl DirectoryTrainer {
+  /// List the files of a directory, recursively, using the [`default_sniffers`].
+  pub fn new(author: impl Into<String>, dir: impl AsRef<Path>) -> Result<Self, Error> {
+    let mut files = Vec::new();
+    walk(dir.as_ref(), &mut HashSet::new(), &mut files)?;
+
+    Ok(Self {
+      files,
+      author: author.into(),
+      sniffers: default_sniffers(),
+    })
+  }
+
+  /// Add a sniffer, tried before the ones already registered.
+  pub fn sniffer(mut self, sniffer: impl Sniffer + 'static) -> Self {
+    self.sniffers.insert(0, Box::new(sniffer));
+    self
+  }
+
+  /// Find a trainer for a file.
+  fn trainer(&self, path: &Path, content: &str) -> Option<Box<dyn Trainer>> {
+    let mut sample_end = content.len().min(SAMPLE_SIZE);
+    while !content.is_char_boundary(sample_end) {
+      sample_end -= 1;
+    }
+
+    let sample = &content[..sample_end];
+    self
+      .sniffers
+      .iter()
+      .find_map(|sniffer| sniffer.sniff(path, sample, &self.author, content))
+  }
+}
+
+impl Trainer for DirectoryTrainer {
+  fn for_each_line(&mut self, sink: &mut dyn FnMut(&str)) -> Result<(), Error> {
+    for path in &self.files {
+      let content = match compressed::read(path) {
+        Ok(content) => content,
+        Err(err) => {
+          warn!("cannot read {}: {}", path.display(), err);
+          continue;
+        }
+      };
+
+      // binary files
+      if content.contains(&0) {
+        continue;
+      }
+
+      let content = String::from_utf8_lossy(&content);
+      if let Some(mut trainer) = self.trainer(&compressed::uncompressed_path(path), &content) {
+        info!("learning from {}", path.display());
+        trainer.for_each_line(sink)?;
+      }
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::trainers::learned_lines;
+
+  #[test]
+  fn test_detection() {
+    let dir = std::env::temp_dir().join(format!("impersonate-dir-{}", std::process::id()));
+    fs::create_dir_all(dir.join("irc")).unwrap();
+    fs::write(
+      dir.join("irc/#rust_20200131.log"),
+      "[10:00:01] <alice> hello from znc\n[10:00:02] <bob> hi\n",
+    )
+    .unwrap();
+    fs::write(
+      dir.join("irc/weechat.log"),
+      "2020-01-31 10:00:00\talice\thello from weechat\n2020-01-31 10:00:01\tbob\thi\n",
+    )
+    .unwrap();
+    fs::write(dir.join("notes.txt"), "Some notes.").unwrap();
+    fs::write(dir.join("unknown.dat"), "not a log").unwrap();
+    fs::write(dir.join("binary.log"), b"\0\x01\x02").unwrap();
+    // a link looping back to the directory itself
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(&dir, dir.join("irc/loop")).unwrap();
+
+    let mut trainer = DirectoryTrainer::new("alice", &dir).unwrap();
+    let lines = learned_lines(&mut trainer);
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(
+      lines,
+      vec!["hello from znc", "hello from weechat", "Some notes."]
+    );
+  }
+}
@@ -0,0 +1,195 @@
+//! A [`Trainer`] that can learn from a Discord export.
+//!
+//! Two formats are supported:
+//!
+//! - The JSON produced by [DiscordChatExporter], containing the messages of everyone in a channel.
+//! - The `messages.json` files found in Discord’s own data package, containing only the messages
+//!   of the person who requested it.
+//!
+//! [DiscordChatExporter]: https://github.com/Tyrrrz/DiscordChatExporter
+
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use serde::Deserialize;
+
+use crate::{Error, Trainer};
+
+lazy_static! {
+  static ref REGEX_MENTION: Regex = Regex::new(r"<(@!?|@&|#)(\d+)>").unwrap();
+  static ref REGEX_EMOJI: Regex = Regex::new(r"<a?:(\w+):\d+>").unwrap();
+}
+
+/// The author we are interested in.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DiscordAuthor {
+  /// Discord user ID (snowflake).
+  Id(String),
+  /// Username or server nickname.
+  Name(String),
+}
+
+/// A DiscordChatExporter JSON export.
+#[derive(Debug, Deserialize)]
+struct ChatExport {
+  messages: Vec<ChatMessage>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ChatMessage {
+  #[serde(rename = "type")]
+  ty: String,
+  content: String,
+  author: ChatUser,
+  #[serde(default)]
+  mentions: Vec<ChatUser>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ChatUser {
+  id: String,
+  name: String,
+  #[serde(default)]
+  nickname: Option<String>,
+}
+
+impl ChatUser {
+  fn is(&self, author: &DiscordAuthor) -> bool {
+    match author {
+      DiscordAuthor::Id(id) => self.id == *id,
+      DiscordAuthor::Name(name) => self.name == *name || self.nickname.as_ref() == Some(name),
+    }
+  }
+}
+
+/// A message from Discord’s data package.
+#[derive(Debug, Deserialize)]
+struct PackageMessage {
+  #[serde(rename = "Contents")]
+  contents: String,
+}
+
+/// Any supported export.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Export {
+  Chat(ChatExport),
+  Package(Vec<PackageMessage>),
+}
+
+/// The content of a Discord export.
+pub struct DiscordExportTrainer {
+  lines: Vec<String>,
+}
+
+impl DiscordExportTrainer {
+  /// Parse a Discord export and keep the messages of `author`.
+  ///
+  /// Data packages only contain messages from a single person, so `author` is ignored for them.
+  pub fn new(author: DiscordAuthor, content: impl AsRef<str>) -> Result<Self, Error> {
+    let contents: Vec<String> = match serde_json::from_str(content.as_ref())? {
+      Export::Chat(export) => export
+        .messages
+        .into_iter()
+        // only keep regular messages; joins, pins, calls etc. are system messages
+        .filter(|msg| (msg.ty == "Default" || msg.ty == "Reply") && msg.author.is(&author))
+        .map(|msg| Self::replace_mentions(&msg.content, &msg.mentions))
+        .collect(),
+
+      Export::Package(messages) => messages
+        .into_iter()
+        .map(|msg| Self::replace_mentions(&msg.contents, &[]))
+        .collect(),
+    };
+
+    // attachments and embeds don’t have any content, so messages made only of them end up empty
+    let lines = contents
+      .iter()
+      .flat_map(|content| content.lines())
+      .map(|line| line.trim().to_owned())
+      .filter(|line| !line.is_empty())
+      .collect();
+
+    Ok(Self { lines })
+  }
+
+  /// Replace raw user mentions by the name of the mentioned users, and custom emojis by their
+  /// names.
+  ///
+  /// Role and channel mentions, as well as unknown users, are removed.
+  fn replace_mentions(content: &str, mentions: &[ChatUser]) -> String {
+    let content = REGEX_MENTION.replace_all(content, |captures: &Captures| match &captures[1] {
+      "@" | "@!" => mentions
+        .iter()
+        .find(|user| user.id == captures[2])
+        .map(|user| format!("@{}", user.nickname.as_ref().unwrap_or(&user.name)))
+        .unwrap_or_default(),
+      _ => String::new(),
+    });
+
+    REGEX_EMOJI.replace_all(&content, ":$1:").into_owned()
+  }
+}
+
+impl Trainer for DiscordExportTrainer {
+  fn for_each_line(&mut self, sink: &mut dyn FnMut(&str)) -> Result<(), Error> {
+    for line in &self.lines {
+      sink(line);
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_chat_export() {
+    let content = r#"{
+      "guild": { "id": "1", "name": "guild" },
+      "channel": { "id": "2", "name": "general" },
+      "messages": [
+        {
+          "id": "10",
+          "type": "Default",
+          "content": "hey <@21>, look at this <:pog:1234>",
+          "author": { "id": "20", "name": "alice", "nickname": "Alice" },
+          "attachments": [],
+          "embeds": [],
+          "mentions": [{ "id": "21", "name": "bob", "nickname": "Bob" }]
+        },
+        {
+          "id": "11",
+          "type": "Default",
+          "content": "",
+          "author": { "id": "20", "name": "alice", "nickname": "Alice" },
+          "attachments": [{ "id": "3", "url": "https://cdn.discordapp.com/cat.png" }]
+        },
+        {
+          "id": "12",
+          "type": "GuildMemberJoin",
+          "content": "Joined the server.",
+          "author": { "id": "20", "name": "alice" }
+        },
+        {
+          "id": "13",
+          "type": "Reply",
+          "content": "nice\nvery nice",
+          "author": { "id": "21", "name": "bob", "nickname": "Bob" }
+        }
+      ]
+    }"#;
+
+    let trainer = DiscordExportTrainer::new(DiscordAuthor::Name("Alice".to_owned()), content);
+    assert_eq!(
+      trainer.unwrap().lines,
+      vec!["hey @Bob, look at this :pog:".to_owned()]
+    );
+
+    let trainer = DiscordExportTrainer::new(DiscordAuthor::Id("21".to_owned()), content);
+    assert_eq!(trainer.unwrap().lines, vec!["nice", "very nice"]);
+  }
+}
@@ -0,0 +1,132 @@
+//! A [`Trainer`] dropping the lines of another one that match patterns.
+//!
+//! Cleanup passes rewrite messages; some messages shouldn’t be learned at all, such as passwords
+//! typed in the wrong window, bot commands (`!help`) or pasted stack traces. [`ExcludingTrainer`]
+//! wraps any [`Trainer`] or [`AuthorTrainer`] and drops the lines matching any of a set of regular
+//! expressions. As it sees the lines the wrapped trainer produces, patterns apply after its
+//! cleanup.
+
+use regex::RegexSet;
+
+use crate::persona::AuthorTrainer;
+use crate::{Error, Trainer};
+
+/// A trainer dropping the lines matching any of a set of patterns.
+pub struct ExcludingTrainer<T> {
+  trainer: T,
+  patterns: RegexSet,
+}
+
+impl<T> ExcludingTrainer<T> {
+  /// Wrap a trainer, dropping the lines matching any of `patterns`.
+  ///
+  /// Patterns are searched anywhere in lines; anchor them with `^` and `$` to match whole lines.
+  pub fn new<P>(trainer: T, patterns: P) -> Result<Self, Error>
+  where
+    P: IntoIterator,
+    P::Item: AsRef<str>,
+  {
+    Ok(Self {
+      trainer,
+      patterns: compile(patterns)?,
+    })
+  }
+
+  /// Unwrap the trainer.
+  pub fn into_inner(self) -> T {
+    self.trainer
+  }
+}
+
+/// Compile exclude patterns.
+pub(crate) fn compile<P>(patterns: P) -> Result<RegexSet, Error>
+where
+  P: IntoIterator,
+  P::Item: AsRef<str>,
+{
+  RegexSet::new(patterns)
+    .map_err(|err| Error::InvalidParameters(format!("invalid exclude pattern: {}", err)))
+}
+
+/// Whether a line matches any of the patterns.
+pub(crate) fn excludes(patterns: &RegexSet, line: &str) -> bool {
+  let excluded = patterns.is_match(line);
+
+  if excluded {
+    trace!("excluding {}", line);
+  }
+
+  excluded
+}
+
+impl<T> Trainer for ExcludingTrainer<T>
+where
+  T: Trainer,
+{
+  fn for_each_line(&mut self, sink: &mut dyn FnMut(&str)) -> Result<(), Error> {
+    let patterns = &self.patterns;
+
+    self.trainer.for_each_line(&mut |line| {
+      if !excludes(patterns, line) {
+        sink(line);
+      }
+    })
+  }
+}
+
+impl<T> AuthorTrainer for ExcludingTrainer<T>
+where
+  T: AuthorTrainer,
+{
+  fn for_each_message(&mut self, sink: &mut dyn FnMut(&str, &str)) -> Result<(), Error> {
+    let patterns = &self.patterns;
+
+    self.trainer.for_each_message(&mut |author, message| {
+      if !excludes(patterns, message) {
+        sink(author, message);
+      }
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::trainers::learned_lines;
+
+  /// Trainer passing its lines as is, all written by the same author.
+  struct Lines(&'static [&'static str]);
+
+  impl Trainer for Lines {
+    fn for_each_line(&mut self, sink: &mut dyn FnMut(&str)) -> Result<(), Error> {
+      self.0.iter().for_each(|line| sink(line));
+      Ok(())
+    }
+  }
+
+  impl AuthorTrainer for Lines {
+    fn for_each_message(&mut self, sink: &mut dyn FnMut(&str, &str)) -> Result<(), Error> {
+      self.0.iter().for_each(|line| sink("alice", line));
+      Ok(())
+    }
+  }
+
+  #[test]
+  fn test_exclude_patterns() {
+    let content = Lines(&["hello", "!help", "my Password is hunter2", "bye"]);
+    let patterns = ["^!", "(?i)password"];
+
+    let trainer: Box<dyn Trainer> = Box::new(Lines(content.0));
+    let mut trainer = ExcludingTrainer::new(trainer, &patterns).unwrap();
+    assert_eq!(learned_lines(&mut trainer), vec!["hello", "bye"]);
+
+    let mut messages = Vec::new();
+    ExcludingTrainer::new(content, &patterns)
+      .unwrap()
+      .for_each_message(&mut |author, message| messages.push(format!("{}: {}", author, message)))
+      .unwrap();
+    assert_eq!(messages, vec!["alice: hello", "alice: bye"]);
+
+    assert!(ExcludingTrainer::new(Lines(&[]), &["("]).is_err());
+  }
+}
@@ -0,0 +1,110 @@
+//! A [`Trainer`] that can learn from a HexChat (or XChat) log.
+//!
+//! HexChat logs look like this, with a tab between the nickname column and the message:
+//!
+//! ```ignore
+//! **** BEGIN LOGGING AT Fri Jan 31 10:00:00 2020
+//!
+//! Jan 31 10:00:01 *  Now talking on #channel
+//! Jan 31 10:00:01 *  Topic for #channel is: topic
+//! Jan 31 10:00:02 -->  nick (ident@host) has joined #channel
+//! Jan 31 10:00:03 <@nick>  message
+//! Jan 31 10:00:04 <--  nick (ident@host) has quit (Quit: bye)
+//! **** ENDING LOGGING AT Fri Jan 31 18:00:00 2020
+//! ```
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::io::{BufReader, Read};
+use std::mem;
+
+use crate::trainers::lines::Lines;
+use crate::{Error, Trainer};
+
+lazy_static! {
+  static ref REGEX_LINE: Regex =
+    Regex::new(r"^[A-Z][a-z]{2} \d{2} \d{2}:\d{2}:\d{2} (.*)").unwrap();
+  static ref REGEX_MESSAGE: Regex = Regex::new(r"^<[~&@%+]*([^>\s]+)>\t(.*)").unwrap();
+}
+
+/// The content of a HexChat log.
+pub struct HexChatLogTrainer {
+  content: Lines,
+  /// The author we are interested in
+  author: String,
+}
+
+impl HexChatLogTrainer {
+  /// Create a trainer from a log, either loaded as a string or streamed with
+  /// [`Lines::from_reader`].
+  pub fn new(author: impl Into<String>, content: impl Into<Lines>) -> Self {
+    let content = content.into();
+    let author = author.into();
+
+    Self { content, author }
+  }
+
+  /// Stream a log from a reader, such as the standard input.
+  pub fn from_reader(
+    author: impl Into<String>,
+    reader: impl Read + 'static,
+  ) -> Result<Self, Error> {
+    let content = Lines::from_reader(BufReader::new(reader));
+
+    Ok(Self::new(author, content))
+  }
+
+  /// Remove the timestamp and nickname of a line, leaving only the content of the message.
+  ///
+  /// Returns [`None`] for logging markers, events (joins, quits, topics, etc.) and messages from
+  /// other people.
+  fn cleanup<'a>(&self, line: &'a str) -> Option<&'a str> {
+    let captures = REGEX_LINE.captures(line)?;
+    let captures = REGEX_MESSAGE.captures(captures.get(1)?.as_str())?;
+
+    if captures.get(1)?.as_str() == self.author {
+      Some(captures.get(2)?.as_str().trim()).filter(|content| !content.is_empty())
+    } else {
+      None
+    }
+  }
+}
+
+impl Trainer for HexChatLogTrainer {
+  fn for_each_line(&mut self, sink: &mut dyn FnMut(&str)) -> Result<(), Error> {
+    let mut lines = mem::take(&mut self.content);
+
+    let result = lines.for_each(|line| {
+      if let Some(content) = self.cleanup(line.trim_end_matches('\r')) {
+        sink(content);
+      }
+    });
+
+    self.content = lines;
+    result
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::trainers::learned_lines;
+
+  #[test]
+  fn test_cleanup() {
+    let content = "**** BEGIN LOGGING AT Fri Jan 31 10:00:00 2020\n\
+                   \n\
+                   Jan 31 10:00:01 *\tNow talking on #rust\n\
+                   Jan 31 10:00:01 *\tTopic for #rust is: alice rules\n\
+                   Jan 31 10:00:02 -->\talice (~alice@host) has joined #rust\n\
+                   Jan 31 10:00:03 <@alice>\thello everyone\n\
+                   Jan 31 10:00:04 <+alicia>\tnot alice\n\
+                   Jan 31 10:00:05 <alice>\tbye\n\
+                   Jan 31 10:00:06 <--\talice (~alice@host) has quit (Quit: bye)\n\
+                   **** ENDING LOGGING AT Fri Jan 31 18:00:00 2020\n";
+    let mut trainer = HexChatLogTrainer::new("alice", content);
+    let lines = learned_lines(&mut trainer);
+
+    assert_eq!(lines, vec!["hello everyone", "bye"]);
+  }
+}
@@ -0,0 +1,159 @@
+//! A [`Trainer`] that can learn from IRC logs of any layout, described by a format string.
+//!
+//! Many clients let users customize the layout of log lines. Instead of a trainer per client, the
+//! layout is described with a strftime-like format string, such as `%Y-%m-%d %H:%M:%S\t%n\t%m`,
+//! which is compiled into a parser. The following directives are supported:
+//!
+//! - `%n`: the nick of the author, without its mode sigils (`@`, `+`, etc.).
+//! - `%m`: the message, when it’s the last directive of the format; the month otherwise.
+//! - date and time directives: `%Y`, `%y`, `%m`, `%d`, `%e`, `%j`, `%H`, `%I`, `%M`, `%S`, `%f`,
+//!   `%p`, `%a`, `%A`, `%b`, `%h`, `%B`, `%z`, `%Z`, `%s`, `%F`, `%T`, `%D` and `%R`, as with
+//!   strftime.
+//! - `%t` and `%%`: a tab and a `%`.
+//!
+//! Any other character is matched as is. Lines not matching the format are ignored.
+
+use regex::escape;
+use std::io::{BufReader, Read};
+
+use crate::persona::AuthorTrainer;
+use crate::trainers::lines::Lines;
+use crate::trainers::regex::RegexTrainer;
+use crate::{Error, Trainer};
+
+/// Compile a format string into a regular expression with `author` and `message` groups.
+fn compile(format: &str) -> Result<String, Error> {
+  let mut regex = String::from("^");
+  let mut chars = format.chars().peekable();
+  let mut author = false;
+
+  while let Some(c) = chars.next() {
+    if c != '%' {
+      regex.push_str(&escape(c.encode_utf8(&mut [0; 4])));
+      continue;
+    }
+
+    let directive = chars
+      .next()
+      .ok_or_else(|| Error::InvalidParameters("format ends with a lone %".to_owned()))?;
+    let last = chars.peek().is_none();
+
+    let pattern = match directive {
+      'n' if author => {
+        return Err(Error::InvalidParameters(
+          "the format has several %n".to_owned(),
+        ))
+      }
+      'n' => {
+        author = true;
+        r"[~&@%+!]*(?P<author>\S+?)"
+      }
+      'm' if last => r"(?P<message>.*)",
+      'Y' => r"\d{4}",
+      'y' | 'm' | 'd' | 'H' | 'I' | 'M' | 'S' => r"\d{2}",
+      'e' => r"[ \d]\d",
+      'j' => r"\d{3}",
+      'f' | 's' => r"\d+",
+      'p' => r"[AaPp][Mm]",
+      'a' | 'b' | 'h' => r"[[:alpha:]]{3}",
+      'A' | 'B' | 'Z' => r"[[:alpha:]]+",
+      'z' => r"[+-]\d{2}:?\d{2}",
+      'F' => r"\d{4}-\d{2}-\d{2}",
+      'T' => r"\d{2}:\d{2}:\d{2}",
+      'D' => r"\d{2}/\d{2}/\d{2}",
+      'R' => r"\d{2}:\d{2}",
+      't' => r"\t",
+      '%' => "%",
+      _ => {
+        return Err(Error::InvalidParameters(format!(
+          "unknown directive %{} in format",
+          directive
+        )))
+      }
+    };
+
+    regex.push_str(pattern);
+  }
+
+  regex.push('$');
+  Ok(regex)
+}
+
+/// The content of an IRC log, parsed with a format string.
+pub struct IrcLogTrainer {
+  trainer: RegexTrainer,
+}
+
+impl IrcLogTrainer {
+  /// Create a trainer from the format of the lines of the log.
+  ///
+  /// The log is either loaded as a string or streamed with [`Lines::from_reader`]. Fails if the
+  /// format has unknown directives or doesn’t end with a message (`%m`).
+  pub fn new(
+    author: impl Into<String>,
+    format: &str,
+    content: impl Into<Lines>,
+  ) -> Result<Self, Error> {
+    let regex = compile(format)?;
+    let trainer = RegexTrainer::new(author, &regex, content).map_err(|_| {
+      Error::InvalidParameters("the format must end with a message (%m)".to_owned())
+    })?;
+
+    Ok(Self { trainer })
+  }
+
+  /// Stream a log from a reader, such as the standard input.
+  pub fn from_reader(
+    author: impl Into<String>,
+    format: &str,
+    reader: impl Read + 'static,
+  ) -> Result<Self, Error> {
+    let content = Lines::from_reader(BufReader::new(reader));
+
+    Self::new(author, format, content)
+  }
+}
+
+impl Trainer for IrcLogTrainer {
+  fn for_each_line(&mut self, sink: &mut dyn FnMut(&str)) -> Result<(), Error> {
+    self.trainer.for_each_line(sink)
+  }
+}
+
+impl AuthorTrainer for IrcLogTrainer {
+  fn for_each_message(&mut self, sink: &mut dyn FnMut(&str, &str)) -> Result<(), Error> {
+    self.trainer.for_each_message(sink)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::trainers::learned_lines;
+
+  #[test]
+  fn test_format() {
+    let content = "2020-01-31 10:00:00\t@alice\thello\n\
+                   2020-01-31 10:00:01\t-->\tbob (~bob@host) has joined\n\
+                   2020-01-31 10:00:02\tbob\thi alice\n\
+                   2020-01-31 10:00:03\t+alice\thow are you?\n";
+
+    let lines = learned_lines(
+      &mut IrcLogTrainer::new("alice", "%Y-%m-%d %H:%M:%S\t%n\t%m", content).unwrap(),
+    );
+    assert_eq!(lines, vec!["hello", "how are you?"]);
+
+    let lines = learned_lines(
+      &mut IrcLogTrainer::new(
+        "bob",
+        "[%T] <%n> %m",
+        "[10:00:00] <bob> hey\n[10:00:01] * bob waves\n",
+      )
+      .unwrap(),
+    );
+    assert_eq!(lines, vec!["hey"]);
+
+    assert!(IrcLogTrainer::new("bob", "%T %n %q %m", "").is_err());
+    assert!(IrcLogTrainer::new("bob", "%T %m %n", "").is_err());
+  }
+}
@@ -0,0 +1,133 @@
+//! A [`Trainer`] that can learn from JSON Lines (NDJSON) files, where each line is a JSON object.
+//!
+//! Fields are selected with [JSON pointers] (`/user/name`) or dotted paths (`user.name`).
+//!
+//! [JSON pointers]: https://tools.ietf.org/html/rfc6901
+
+use serde_json::Value;
+
+use crate::{Error, Trainer};
+
+/// Convert a field selector to a JSON pointer.
+fn to_pointer(selector: &str) -> String {
+  if selector.is_empty() || selector.starts_with('/') {
+    selector.to_owned()
+  } else {
+    selector
+      .split('.')
+      .map(|key| format!("/{}", key.replace('~', "~0").replace('/', "~1")))
+      .collect()
+  }
+}
+
+/// Whether a JSON value matches an expected value.
+///
+/// Strings are compared with their content and other values with their JSON representation, so
+/// that `42` matches `"42"`.
+fn matches(value: &Value, expected: &str) -> bool {
+  match value {
+    Value::String(s) => s == expected,
+    value => serde_json::from_str::<Value>(expected).is_ok_and(|expected| *value == expected),
+  }
+}
+
+/// Fields to read from a JSON Lines file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct JsonLinesFields {
+  /// Selector of the field containing the text to learn.
+  pub text: String,
+  /// Selector of a field, along with the value it must have for the line to be learned.
+  pub filter: Option<(String, String)>,
+}
+
+impl JsonLinesFields {
+  /// Read the field selected by `text`, without filtering.
+  pub fn new(text: impl Into<String>) -> Self {
+    Self {
+      text: text.into(),
+      filter: None,
+    }
+  }
+}
+
+/// The content of a JSON Lines file.
+pub struct JsonLinesTrainer {
+  lines: Vec<String>,
+}
+
+impl JsonLinesTrainer {
+  /// Parse a JSON Lines file and keep the text of the objects passing the filter, if any.
+  ///
+  /// Objects without a string at the text selector are ignored.
+  pub fn new(fields: &JsonLinesFields, content: impl AsRef<str>) -> Result<Self, Error> {
+    let text_pointer = to_pointer(&fields.text);
+    let filter = fields
+      .filter
+      .as_ref()
+      .map(|(selector, expected)| (to_pointer(selector), expected));
+
+    let mut lines = Vec::new();
+    for line in content
+      .as_ref()
+      .lines()
+      .filter(|line| !line.trim().is_empty())
+    {
+      let object: Value = serde_json::from_str(line)?;
+
+      if let Some((ref pointer, expected)) = filter {
+        if !object
+          .pointer(pointer)
+          .is_some_and(|value| matches(value, expected))
+        {
+          continue;
+        }
+      }
+
+      if let Some(text) = object.pointer(&text_pointer).and_then(Value::as_str) {
+        lines.extend(
+          text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_owned),
+        );
+      }
+    }
+
+    Ok(Self { lines })
+  }
+}
+
+impl Trainer for JsonLinesTrainer {
+  fn for_each_line(&mut self, sink: &mut dyn FnMut(&str)) -> Result<(), Error> {
+    for line in &self.lines {
+      sink(line);
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_fields() {
+    let content = r#"{"user": {"name": "alice", "id": 1}, "text": "hello"}
+                     {"user": {"name": "bob", "id": 2}, "text": "hi"}
+
+                     {"user": {"name": "alice", "id": 1}, "text": "how are you?\nfine?"}
+                     {"user": {"name": "alice", "id": 1}, "attachment": "cat.png"}"#;
+
+    let mut fields = JsonLinesFields::new("/text");
+    fields.filter = Some(("/user/name".to_owned(), "alice".to_owned()));
+    let trainer = JsonLinesTrainer::new(&fields, content).unwrap();
+    assert_eq!(trainer.lines, vec!["hello", "how are you?", "fine?"]);
+
+    let mut fields = JsonLinesFields::new("text");
+    fields.filter = Some(("user.id".to_owned(), "2".to_owned()));
+    let trainer = JsonLinesTrainer::new(&fields, content).unwrap();
+    assert_eq!(trainer.lines, vec!["hi"]);
+  }
+}
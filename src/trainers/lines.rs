@@ -0,0 +1,96 @@
+//! Sources of lines, held in memory or streamed from a reader.
+//!
+//! Line-based trainers learn from a [`Lines`], which is either a whole log loaded as a string or a
+//! [`BufRead`], read line by line so that multi-gigabyte logs are learned with bounded memory.
+//! Lines that aren’t valid UTF-8 are decoded lossily when streamed.
+//!
+//! A streamed log can only be read once: learning it a second time yields no line.
+
+use std::io::BufRead;
+
+use crate::Error;
+
+/// Lines of a log.
+pub struct Lines {
+  source: Source,
+}
+
+enum Source {
+  Text(String),
+  Reader(Box<dyn BufRead>),
+}
+
+impl Lines {
+  /// Stream lines from a reader, such as a [`BufReader`](std::io::BufReader) over a file.
+  pub fn from_reader(reader: impl BufRead + 'static) -> Self {
+    Self {
+      source: Source::Reader(Box::new(reader)),
+    }
+  }
+
+  /// Pass every line, without its line feed, to `sink`.
+  pub(crate) fn for_each(&mut self, mut sink: impl FnMut(&str)) -> Result<(), Error> {
+    match &mut self.source {
+      Source::Text(text) => text.split_terminator('\n').for_each(sink),
+
+      Source::Reader(reader) => {
+        let mut line = Vec::new();
+
+        while reader.read_until(b'\n', &mut line)? != 0 {
+          if line.last() == Some(&b'\n') {
+            line.pop();
+          }
+
+          sink(&String::from_utf8_lossy(&line));
+          line.clear();
+        }
+      }
+    }
+
+    Ok(())
+  }
+}
+
+impl Default for Lines {
+  fn default() -> Self {
+    Self::from(String::new())
+  }
+}
+
+impl From<String> for Lines {
+  fn from(text: String) -> Self {
+    Self {
+      source: Source::Text(text),
+    }
+  }
+}
+
+impl From<&str> for Lines {
+  fn from(text: &str) -> Self {
+    Self::from(text.to_owned())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::io::Cursor;
+
+  #[test]
+  fn test_lines() {
+    let mut text = Vec::new();
+    Lines::from("foo\nbar\r\n\nzoo")
+      .for_each(|line| text.push(line.to_owned()))
+      .unwrap();
+    assert_eq!(text, vec!["foo", "bar\r", "", "zoo"]);
+
+    let mut streamed = Vec::new();
+    let mut lines = Lines::from_reader(Cursor::new(b"foo\nbar\r\n\nzoo\xFF".to_vec()));
+    lines
+      .for_each(|line| streamed.push(line.to_owned()))
+      .unwrap();
+    assert_eq!(streamed, vec!["foo", "bar\r", "", "zoo\u{FFFD}"]);
+
+    lines.for_each(|_| panic!("read twice")).unwrap();
+  }
+}
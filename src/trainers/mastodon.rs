@@ -0,0 +1,154 @@
+//! A [`Trainer`] that can learn from the `outbox.json` of a Mastodon account export.
+//!
+//! Only the author’s own posts are learned: boosts are skipped. The HTML content of posts is
+//! converted to plain text.
+
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{Error, Trainer};
+
+lazy_static! {
+  static ref REGEX_LINE_BREAK: Regex = Regex::new(r"(?i)<br\s*/?>|</p>").unwrap();
+  static ref REGEX_TAG: Regex = Regex::new(r"<[^>]*>").unwrap();
+  static ref REGEX_ENTITY: Regex = Regex::new(r"&(#x[0-9a-fA-F]+|#\d+|\w+);").unwrap();
+}
+
+/// An ActivityPub outbox.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Outbox {
+  ordered_items: Vec<Activity>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Activity {
+  #[serde(rename = "type")]
+  ty: String,
+  /// Posts for creations, but only an URL for boosts.
+  object: Value,
+}
+
+impl Activity {
+  /// HTML content of the post, if this activity is the creation of a post.
+  fn post_content(&self) -> Option<&str> {
+    if self.ty != "Create" {
+      return None;
+    }
+
+    self.object.get("content")?.as_str()
+  }
+}
+
+/// Convert HTML to plain text.
+fn html_to_text(html: &str) -> String {
+  let text = REGEX_LINE_BREAK.replace_all(html, "\n");
+  let text = REGEX_TAG.replace_all(&text, "");
+
+  REGEX_ENTITY
+    .replace_all(&text, |captures: &Captures| {
+      let entity = &captures[1];
+      let c = match entity {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some(' '),
+        _ if entity.starts_with("#x") => u32::from_str_radix(&entity[2..], 16)
+          .ok()
+          .and_then(char::from_u32),
+        _ if entity.starts_with('#') => entity[1..].parse().ok().and_then(char::from_u32),
+        _ => None,
+      };
+
+      c.map_or_else(|| captures[0].to_owned(), String::from)
+    })
+    .into_owned()
+}
+
+/// The content of a Mastodon outbox.
+pub struct MastodonOutboxTrainer {
+  lines: Vec<String>,
+}
+
+impl MastodonOutboxTrainer {
+  /// Parse the content of an `outbox.json` file.
+  pub fn new(content: impl AsRef<str>) -> Result<Self, Error> {
+    let outbox: Outbox = serde_json::from_str(content.as_ref())?;
+    let lines = outbox
+      .ordered_items
+      .iter()
+      .filter_map(Activity::post_content)
+      .flat_map(|html| {
+        html_to_text(html)
+          .lines()
+          .map(|line| line.trim().to_owned())
+          .collect::<Vec<_>>()
+      })
+      .filter(|line| !line.is_empty())
+      .collect();
+
+    Ok(Self { lines })
+  }
+}
+
+impl Trainer for MastodonOutboxTrainer {
+  fn for_each_line(&mut self, sink: &mut dyn FnMut(&str)) -> Result<(), Error> {
+    for line in &self.lines {
+      sink(line);
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::trainers::learned_lines;
+
+  #[test]
+  fn test_html_to_text() {
+    assert_eq!(
+      html_to_text(
+        "<p>fish &amp; chips<br/>at <a href=\"x\">home</a></p><p>&#233;t&#xE9; &foo;</p>"
+      ),
+      "fish & chips\nat home\nété &foo;\n"
+    );
+  }
+
+  #[test]
+  fn test_outbox() {
+    let content = r#"{
+      "@context": "https://www.w3.org/ns/activitystreams",
+      "type": "OrderedCollection",
+      "totalItems": 3,
+      "orderedItems": [
+        {
+          "type": "Create",
+          "object": {
+            "type": "Note",
+            "content": "<p>hello <span class=\"h-card\"><a href=\"https://example.com/@bob\">@<span>bob</span></a></span></p><p>how are you?</p>"
+          }
+        },
+        {
+          "type": "Announce",
+          "object": "https://example.com/@carol/1"
+        },
+        {
+          "type": "Create",
+          "object": { "type": "Note", "content": "<p>I&apos;m &lt;fine&gt;</p>" }
+        }
+      ]
+    }"#;
+
+    let mut trainer = MastodonOutboxTrainer::new(content).unwrap();
+    let lines = learned_lines(&mut trainer);
+    assert_eq!(lines, vec!["hello @bob", "how are you?", "I'm <fine>"]);
+
+    assert!(MastodonOutboxTrainer::new("[]").is_err());
+  }
+}
@@ -0,0 +1,229 @@
+//! A [`Trainer`] that can learn from emails stored in an mbox file.
+//!
+//! Messages are separated by `From ` lines. Only the messages sent from a given address are kept;
+//! their headers, quoted reply lines (`> …`), reply introductions (`On …, … wrote:`) and
+//! signatures (after a `-- ` line) are removed.
+//!
+//! MIME multipart messages and encoded bodies are not decoded: only plain text bodies are
+//! learned properly.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::io::{BufReader, Read};
+use std::mem;
+
+use crate::trainers::lines::Lines;
+use crate::{Error, Trainer};
+
+lazy_static! {
+  static ref REGEX_REPLY_INTRO: Regex =
+    Regex::new(r"^(On|Le|Am) .*(wrote|a écrit|schrieb)\s?:$").unwrap();
+}
+
+/// A parsed email.
+struct Email<'a> {
+  /// Headers, with continuation lines unfolded.
+  headers: Vec<(String, String)>,
+  body: Vec<&'a str>,
+}
+
+impl<'a> Email<'a> {
+  fn parse(lines: &[&'a str]) -> Self {
+    let mut headers: Vec<(String, String)> = Vec::new();
+    let mut lines = lines.iter();
+
+    for line in lines.by_ref() {
+      if line.is_empty() {
+        break;
+      }
+
+      if line.starts_with([' ', '\t']) {
+        // folded header
+        if let Some((_, value)) = headers.last_mut() {
+          value.push(' ');
+          value.push_str(line.trim());
+        }
+      } else if let Some(colon) = line.find(':') {
+        headers.push((
+          line[..colon].to_lowercase(),
+          line[colon + 1..].trim().to_owned(),
+        ));
+      }
+    }
+
+    Self {
+      headers,
+      body: lines.copied().collect(),
+    }
+  }
+
+  fn header(&self, name: &str) -> Option<&str> {
+    self
+      .headers
+      .iter()
+      .find(|(header, _)| header == name)
+      .map(|(_, value)| value.as_str())
+  }
+
+  /// Whether the email was sent from `address`, either the address of the sender or their display
+  /// name, ignoring case.
+  fn is_from(&self, address: &str) -> bool {
+    let from = match self.header("from") {
+      Some(from) => from,
+      None => return false,
+    };
+
+    // `Name <address>`, or the older `address (Name)`
+    let (name, sender) = match (from.rfind('<'), from.rfind('>')) {
+      (Some(start), Some(end)) if start < end => (&from[..start], &from[start + 1..end]),
+      _ => match from.split_once('(') {
+        Some((sender, name)) => (name.trim_end_matches(')'), sender),
+        None => ("", from),
+      },
+    };
+    let name = name.trim().trim_matches('"').trim();
+    let address = address.trim().to_lowercase();
+
+    sender.trim().to_lowercase() == address || (!name.is_empty() && name.to_lowercase() == address)
+  }
+
+  /// Body lines written by the sender.
+  fn own_lines(&self) -> impl Iterator<Item = &'a str> + '_ {
+    self
+      .body
+      .iter()
+      // everything after the signature delimiter is the signature
+      .take_while(|line| **line != "-- ")
+      // mbox escapes body lines starting with `From ` as `>From `
+      .map(|line| {
+        line
+          .strip_prefix('>')
+          .filter(|line| line.starts_with("From "))
+          .unwrap_or(line)
+      })
+      .filter(|line| !line.starts_with('>') && !REGEX_REPLY_INTRO.is_match(line.trim()))
+      .map(|line| line.trim())
+      .filter(|line| !line.is_empty())
+  }
+}
+
+/// The content of an mbox file.
+pub struct MboxTrainer {
+  content: Lines,
+  /// Address of the sender we are interested in.
+  address: String,
+}
+
+impl MboxTrainer {
+  /// Create a trainer learning from the emails sent from `address`, in a mailbox either loaded as
+  /// a string or streamed with [`Lines::from_reader`].
+  ///
+  /// The address is matched case-insensitively against the address of the `From:` header, or
+  /// against its display name.
+  pub fn new(address: impl Into<String>, content: impl Into<Lines>) -> Self {
+    let content = content.into();
+    let address = address.into();
+
+    Self { content, address }
+  }
+
+  /// Stream a mailbox from a reader, such as the standard input.
+  pub fn from_reader(
+    address: impl Into<String>,
+    reader: impl Read + 'static,
+  ) -> Result<Self, Error> {
+    let content = Lines::from_reader(BufReader::new(reader));
+
+    Ok(Self::new(address, content))
+  }
+
+  /// Learn the lines of a message if it was sent from the address.
+  fn learn(&self, message: &[String], sink: &mut dyn FnMut(&str)) {
+    let lines = message.iter().map(String::as_str).collect::<Vec<_>>();
+    let email = Email::parse(&lines);
+
+    if email.is_from(&self.address) {
+      email.own_lines().for_each(sink);
+    }
+  }
+}
+
+impl Trainer for MboxTrainer {
+  fn for_each_line(&mut self, sink: &mut dyn FnMut(&str)) -> Result<(), Error> {
+    let mut content = mem::take(&mut self.content);
+    // lines of the current message; the content before the first separator is not a message
+    let mut message: Option<Vec<String>> = None;
+
+    let result = content.for_each(|line| {
+      let line = line.trim_end_matches('\r');
+
+      if line.starts_with("From ") {
+        if let Some(message) = message.replace(Vec::new()) {
+          self.learn(&message, sink);
+        }
+      } else if let Some(message) = &mut message {
+        message.push(line.to_owned());
+      }
+    });
+
+    if let Some(message) = message {
+      self.learn(&message, sink);
+    }
+
+    self.content = content;
+    result
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::trainers::learned_lines;
+  use std::io::Cursor;
+
+  const MBOX: &str = "From alice@example.com Fri Jan 31 10:00:00 2020\r\n\
+                      From: Alice <alice@example.com>\r\n\
+                      Subject: hello\r\n\
+                      \tworld\r\n\
+                      \r\n\
+                      Hi Bob,\r\n\
+                      >From the start, it worked.\r\n\
+                      \r\n\
+                      On Fri, Jan 31, 2020, Bob wrote:\r\n\
+                      > how is it going?\r\n\
+                      -- \r\n\
+                      Alice, on her phone\r\n\
+                      From notalice@example.com Fri Jan 31 11:00:00 2020\r\n\
+                      From: notalice@example.com\r\n\
+                      \r\n\
+                      I am not Alice.\r\n\
+                      From alice@example.com Fri Jan 31 12:00:00 2020\r\n\
+                      From: ALICE@example.com (Alice)\r\n\
+                      \r\n\
+                      Bye.\r\n";
+
+  #[test]
+  fn test_own_lines() {
+    let expected = vec!["Hi Bob,", "From the start, it worked.", "Bye."];
+    assert_eq!(
+      learned_lines(&mut MboxTrainer::new("alice@example.com", MBOX)),
+      expected
+    );
+    assert_eq!(
+      learned_lines(&mut MboxTrainer::new("alice", MBOX)),
+      expected
+    );
+
+    let reader = Cursor::new(MBOX.as_bytes().to_vec());
+    assert_eq!(
+      learned_lines(&mut MboxTrainer::from_reader("Alice@Example.com", reader).unwrap()),
+      expected
+    );
+
+    assert_eq!(
+      learned_lines(&mut MboxTrainer::new("notalice@example.com", MBOX)),
+      vec!["I am not Alice."]
+    );
+    assert!(learned_lines(&mut MboxTrainer::new("example.com", MBOX)).is_empty());
+  }
+}
@@ -0,0 +1,112 @@
+//! A [`Trainer`] that can learn from a Facebook Messenger export (`message_1.json` files).
+//!
+//! Facebook writes the UTF-8 bytes of every string as if they were Latin-1 characters, so `é`
+//! appears as `Ã©`; the encoding is fixed while parsing.
+
+use serde::Deserialize;
+use std::convert::TryFrom;
+
+use crate::{Error, Trainer};
+
+/// A Messenger conversation.
+#[derive(Debug, Deserialize)]
+struct Conversation {
+  messages: Vec<Message>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Message {
+  sender_name: String,
+  #[serde(rename = "type", default)]
+  ty: Option<String>,
+  #[serde(default)]
+  content: Option<String>,
+  #[serde(default)]
+  sticker: Option<serde_json::Value>,
+  #[serde(default)]
+  photos: Option<serde_json::Value>,
+}
+
+impl Message {
+  /// Whether the message is a regular text message: stickers and photos come with a generated
+  /// content (such as `Alice sent a photo.`) that must not be learned.
+  fn is_text(&self) -> bool {
+    self.ty.as_deref().is_none_or(|ty| ty == "Generic")
+      && self.sticker.is_none()
+      && self.photos.is_none()
+  }
+}
+
+/// Fix a string which UTF-8 bytes were encoded as Latin-1 characters.
+///
+/// Strings that cannot be fixed are returned unchanged.
+fn fix_mojibake(s: &str) -> String {
+  let bytes = s
+    .chars()
+    .map(|c| u8::try_from(u32::from(c)).ok())
+    .collect::<Option<Vec<_>>>();
+
+  bytes
+    .and_then(|bytes| String::from_utf8(bytes).ok())
+    .unwrap_or_else(|| s.to_owned())
+}
+
+/// The content of a Messenger export.
+pub struct MessengerExportTrainer {
+  lines: Vec<String>,
+}
+
+impl MessengerExportTrainer {
+  /// Parse a Messenger conversation and keep the messages sent by `sender_name`.
+  pub fn new(sender_name: impl AsRef<str>, content: impl AsRef<str>) -> Result<Self, Error> {
+    let sender_name = sender_name.as_ref();
+    let conversation: Conversation = serde_json::from_str(content.as_ref())?;
+
+    let lines = conversation
+      .messages
+      .iter()
+      .filter(|msg| msg.is_text() && fix_mojibake(&msg.sender_name) == sender_name)
+      .filter_map(|msg| msg.content.as_deref())
+      .flat_map(|content| {
+        fix_mojibake(content)
+          .lines()
+          .map(|line| line.trim().to_owned())
+          .collect::<Vec<_>>()
+      })
+      .filter(|line| !line.is_empty())
+      .collect();
+
+    Ok(Self { lines })
+  }
+}
+
+impl Trainer for MessengerExportTrainer {
+  fn for_each_line(&mut self, sink: &mut dyn FnMut(&str)) -> Result<(), Error> {
+    for line in &self.lines {
+      sink(line);
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_messages() {
+    let content = r#"{
+      "participants": [{ "name": "ZoÃ©" }, { "name": "Bob" }],
+      "messages": [
+        { "sender_name": "ZoÃ©", "timestamp_ms": 3, "content": "Ã§a va ? ð\u009f\u0098\u0080", "type": "Generic" },
+        { "sender_name": "ZoÃ©", "timestamp_ms": 2, "content": "ZoÃ© sent a photo.", "photos": [{ "uri": "a.jpg" }], "type": "Generic" },
+        { "sender_name": "ZoÃ©", "timestamp_ms": 1, "sticker": { "uri": "s.png" }, "type": "Generic" },
+        { "sender_name": "Bob", "timestamp_ms": 0, "content": "salut", "type": "Generic" }
+      ]
+    }"#;
+    let trainer = MessengerExportTrainer::new("Zoé", content).unwrap();
+
+    assert_eq!(trainer.lines, vec!["ça va ? 😀"]);
+  }
+}
@@ -0,0 +1,183 @@
+//! A [`Trainer`] that can learn from a mIRC log.
+//!
+//! mIRC logs look like this:
+//!
+//! ```ignore
+//! Session Start: Fri Jan 31 10:00:00 2020
+//! Session Ident: #channel
+//! [10:00] * Now talking in #channel
+//! [10:01] <@nick> message
+//! [10:02] * nick does something
+//! Session Close: Fri Jan 31 18:00:00 2020
+//! ```
+//!
+//! Older logs are frequently encoded in Windows-1252 instead of UTF-8, so the trainer can be built
+//! from raw bytes with [`MircLogTrainer::from_bytes`].
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::io::Read;
+
+use crate::{Error, Trainer};
+
+lazy_static! {
+  static ref REGEX_LINE: Regex = Regex::new(r"^\[\d{2}:\d{2}(:\d{2})?\]\s+(.*)").unwrap();
+  static ref REGEX_MESSAGE: Regex = Regex::new(r"^<[~&@%+]*([^>\s]+)>\s?(.*)").unwrap();
+  static ref REGEX_ACTION: Regex = Regex::new(r"^\*\s+[~&@%+]*(\S+)\s+(.*)").unwrap();
+  static ref REGEX_EVENT: Regex = Regex::new(
+    r"has (joined|left|quit)|sets mode:|is now known as|changes topic to|was kicked by|^Now talking in|^Topic is|^Set by"
+  )
+  .unwrap();
+}
+
+/// Characters of Windows-1252 in the `0x80..=0x9F` range; undefined bytes map to the matching C1
+/// control character, like most decoders do.
+const CP1252_HIGH: [char; 32] = [
+  '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8D}', 'Ž', '\u{8F}',
+  '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9D}', 'ž', 'Ÿ',
+];
+
+/// Decode Windows-1252 bytes.
+fn decode_cp1252(bytes: &[u8]) -> String {
+  bytes
+    .iter()
+    .map(|&byte| match byte {
+      0x80..=0x9F => CP1252_HIGH[usize::from(byte - 0x80)],
+      // the rest of Windows-1252 matches Latin-1, hence the first Unicode code points
+      _ => char::from(byte),
+    })
+    .collect()
+}
+
+/// The content of a mIRC log.
+pub struct MircLogTrainer {
+  content: String,
+  /// The author we are interested in
+  author: String,
+  /// Whether actions (`/me`) should be learned.
+  actions: bool,
+}
+
+impl MircLogTrainer {
+  pub fn new(author: impl Into<String>, content: impl Into<String>) -> Self {
+    let content = content.into();
+    let author = author.into();
+
+    Self {
+      content,
+      author,
+      actions: false,
+    }
+  }
+
+  /// Create a trainer from the raw bytes of a log.
+  ///
+  /// The bytes are decoded as UTF-8 if valid, and as Windows-1252 otherwise.
+  pub fn from_bytes(author: impl Into<String>, content: impl AsRef<[u8]>) -> Self {
+    let content = content.as_ref();
+    let content = match std::str::from_utf8(content) {
+      Ok(content) => content.to_owned(),
+      Err(_) => decode_cp1252(content),
+    };
+
+    Self::new(author, content)
+  }
+
+  /// Read a log from a reader, such as the standard input.
+  ///
+  /// The log is decoded as with [`MircLogTrainer::from_bytes`].
+  pub fn from_reader(author: impl Into<String>, mut reader: impl Read) -> Result<Self, Error> {
+    let mut content = Vec::new();
+    reader.read_to_end(&mut content)?;
+
+    Ok(Self::from_bytes(author, content))
+  }
+
+  /// Also learn from actions (`* nick does something`), without the nickname.
+  pub fn actions(mut self, actions: bool) -> Self {
+    self.actions = actions;
+    self
+  }
+
+  /// Remove the timestamp and nickname of a line, leaving only the content of the message.
+  ///
+  /// Returns [`None`] for session headers, events and messages from other people.
+  fn cleanup<'a>(&self, line: &'a str) -> Option<&'a str> {
+    // session headers don’t have timestamps
+    let content = REGEX_LINE.captures(line)?.get(2)?.as_str();
+
+    let captures = if let Some(captures) = REGEX_MESSAGE.captures(content) {
+      captures
+    } else if self.actions && !REGEX_EVENT.is_match(content) {
+      REGEX_ACTION.captures(content)?
+    } else {
+      return None;
+    };
+
+    if captures.get(1)?.as_str() == self.author {
+      Some(captures.get(2)?.as_str().trim()).filter(|content| !content.is_empty())
+    } else {
+      None
+    }
+  }
+}
+
+impl Trainer for MircLogTrainer {
+  fn for_each_line(&mut self, sink: &mut dyn FnMut(&str)) -> Result<(), Error> {
+    for line in self.content.split_terminator('\n') {
+      if let Some(content) = self.cleanup(line.trim_end_matches('\r')) {
+        sink(content);
+      }
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::trainers::learned_lines;
+
+  #[test]
+  fn test_cleanup() {
+    let content = "Session Start: Fri Jan 31 10:00:00 2020\r\n\
+                   Session Ident: #rust\r\n\
+                   [10:00] * Now talking in #rust\r\n\
+                   [10:01] <@alice> hello everyone\r\n\
+                   [10:02] <bob> hi alice\r\n\
+                   [10:03] * alice waves\r\n\
+                   [10:04] * alice has quit IRC (Quit: bye)\r\n\
+                   [10:05:30] <+alice> how are you?\r\n\
+                   Session Close: Fri Jan 31 18:00:00 2020\r\n";
+
+    assert_eq!(
+      learned_lines(&mut MircLogTrainer::new("alice", content)),
+      vec!["hello everyone", "how are you?"]
+    );
+    assert_eq!(
+      learned_lines(&mut MircLogTrainer::new("alice", content).actions(true)),
+      vec!["hello everyone", "waves", "how are you?"]
+    );
+  }
+
+  #[test]
+  fn test_from_bytes() {
+    // “café” and – in Windows-1252
+    let content = b"[10:00] <alice> \x93caf\xE9\x94 \x96 ok\r\n";
+    assert_eq!(
+      learned_lines(&mut MircLogTrainer::from_bytes("alice", &content[..])),
+      vec!["\u{201C}café\u{201D} \u{2013} ok"]
+    );
+
+    let content = "[10:00] <alice> “café” – ok\n";
+    assert_eq!(
+      learned_lines(&mut MircLogTrainer::from_bytes("alice", content)),
+      vec!["“café” – ok"]
+    );
+
+    let mut trainer =
+      MircLogTrainer::from_reader("alice", &b"[10:00] <alice> \x80 ok\n"[..]).unwrap();
+    assert_eq!(learned_lines(&mut trainer), vec!["€ ok"]);
+  }
+}
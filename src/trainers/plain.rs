@@ -0,0 +1,129 @@
+//! A [`Trainer`] that can learn from arbitrary text, such as books or articles.
+//!
+//! The text is split into sentences, which are learned independently. Line breaks inside a
+//! paragraph are treated as regular spaces, while empty lines end sentences.
+
+use std::io::Read;
+
+use crate::{Error, Trainer};
+
+/// Characters ending sentences by default.
+const DEFAULT_DELIMITERS: [char; 4] = ['.', '!', '?', '…'];
+
+/// Characters kept at the end of a sentence when found right after its delimiters.
+const CLOSING_CHARS: [char; 6] = ['"', '\'', ')', ']', '”', '’'];
+
+/// Plain text content.
+pub struct PlainTextTrainer {
+  content: String,
+  /// Characters ending sentences.
+  delimiters: Vec<char>,
+}
+
+impl PlainTextTrainer {
+  pub fn new(content: impl Into<String>) -> Self {
+    Self {
+      content: content.into(),
+      delimiters: DEFAULT_DELIMITERS.to_vec(),
+    }
+  }
+
+  /// Read text from a reader, such as the standard input.
+  pub fn from_reader(mut reader: impl Read) -> Result<Self, Error> {
+    let mut content = String::new();
+    reader.read_to_string(&mut content)?;
+
+    Ok(Self::new(content))
+  }
+
+  /// Change the characters ending sentences (by default, `.`, `!`, `?` and `…`).
+  pub fn delimiters(mut self, delimiters: impl IntoIterator<Item = char>) -> Self {
+    self.delimiters = delimiters.into_iter().collect();
+    self
+  }
+
+  /// Split the content into sentences, passed one at a time to `sink`.
+  fn sentences(&self, sink: &mut dyn FnMut(&str)) {
+    for paragraph in self.content.split("\n\n") {
+      let mut sentence = String::new();
+      let mut chars = paragraph.split_whitespace().flat_map(|word| {
+        // normalize all whitespaces to single spaces
+        word.chars().chain(Some(' '))
+      });
+
+      while let Some(c) = chars.next() {
+        if c == ' ' && sentence.is_empty() {
+          continue;
+        }
+
+        sentence.push(c);
+
+        if self.delimiters.contains(&c) {
+          // group delimiters (e.g. `?!` or `...`) and closing quotes / parens with the sentence
+          let mut next = chars.next();
+
+          while let Some(c) =
+            next.filter(|c| self.delimiters.contains(c) || CLOSING_CHARS.contains(c))
+          {
+            sentence.push(c);
+            next = chars.next();
+          }
+
+          if next == Some(' ') || next.is_none() {
+            sink(sentence.trim());
+            sentence.clear();
+          } else if let Some(c) = next {
+            // delimiter inside a word, such as in `3.14` or `example.org`
+            sentence.push(c);
+          }
+        }
+      }
+
+      if !sentence.trim().is_empty() {
+        sink(sentence.trim());
+      }
+    }
+  }
+}
+
+impl Trainer for PlainTextTrainer {
+  fn for_each_line(&mut self, sink: &mut dyn FnMut(&str)) -> Result<(), Error> {
+    self.sentences(sink);
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_sentences() {
+    let sentences = |trainer: PlainTextTrainer| {
+      let mut sentences = Vec::new();
+      trainer.sentences(&mut |sentence| sentences.push(sentence.to_owned()));
+      sentences
+    };
+
+    let trainer = PlainTextTrainer::new(
+      "It was a dark and stormy night; the rain fell in\n\
+       torrents. “Who’s there?!” said   Bob. Pi is 3.14...\n\
+       \n\
+       A new paragraph without delimiter\n",
+    );
+
+    assert_eq!(
+      sentences(trainer),
+      vec![
+        "It was a dark and stormy night; the rain fell in torrents.",
+        "“Who’s there?!”",
+        "said Bob.",
+        "Pi is 3.14...",
+        "A new paragraph without delimiter"
+      ]
+    );
+
+    let trainer = PlainTextTrainer::new("first; second; third").delimiters(vec![';']);
+    assert_eq!(sentences(trainer), vec!["first;", "second;", "third"]);
+  }
+}
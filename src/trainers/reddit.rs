@@ -0,0 +1,146 @@
+//! A [`Trainer`] that can learn from the data Reddit provides in user data requests.
+//!
+//! Both the CSV files (`comments.csv` and `posts.csv`) and JSON dumps (an array of comment or post
+//! objects, or one object per line) are supported. Markdown is stripped and quoted lines are
+//! removed, so that only the author’s own words are learned.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_json::Value;
+
+use crate::{Error, Trainer};
+
+lazy_static! {
+  static ref REGEX_LINK: Regex = Regex::new(r"\[([^\]]*)\]\([^)]*\)").unwrap();
+  static ref REGEX_LIST_ITEM: Regex = Regex::new(r"^\s*([*+-]|\d+\.)\s+").unwrap();
+  static ref REGEX_HEADING: Regex = Regex::new(r"^#+\s*").unwrap();
+  static ref REGEX_EMPHASIS: Regex = Regex::new(r"(\*\*|__|~~|\*|`|\^)").unwrap();
+}
+
+/// Columns / fields containing text written by the author.
+const TEXT_FIELDS: [&str; 3] = ["title", "body", "selftext"];
+
+/// Bodies of deleted content.
+const DELETED_BODIES: [&str; 2] = ["[deleted]", "[removed]"];
+
+/// Remove Markdown markup from a comment or post, as well as quoted lines.
+fn strip_markdown(text: &str) -> Vec<String> {
+  text
+    .replace("&amp;", "&")
+    .replace("&lt;", "<")
+    .replace("&gt;", ">")
+    .lines()
+    .map(str::trim)
+    .filter(|line| !line.starts_with('>'))
+    .map(|line| {
+      let line = REGEX_HEADING.replace(line, "");
+      let line = REGEX_LIST_ITEM.replace(&line, "");
+      let line = REGEX_LINK.replace_all(&line, "$1");
+      REGEX_EMPHASIS.replace_all(&line, "").trim().to_owned()
+    })
+    .filter(|line| !line.is_empty())
+    .collect()
+}
+
+/// Content of a Reddit export.
+pub struct RedditExportTrainer {
+  lines: Vec<String>,
+}
+
+impl RedditExportTrainer {
+  /// Parse a CSV export, such as `comments.csv` or `posts.csv`.
+  pub fn from_csv(content: impl AsRef<str>) -> Result<Self, Error> {
+    let mut reader = csv::Reader::from_reader(content.as_ref().as_bytes());
+    let columns = reader
+      .headers()?
+      .iter()
+      .enumerate()
+      .filter(|(_, header)| TEXT_FIELDS.contains(header))
+      .map(|(i, _)| i)
+      .collect::<Vec<_>>();
+
+    let mut texts = Vec::new();
+    for record in reader.records() {
+      let record = record?;
+      texts.extend(
+        columns
+          .iter()
+          .filter_map(|&i| record.get(i).map(str::to_owned)),
+      );
+    }
+
+    Ok(Self::from_texts(texts))
+  }
+
+  /// Parse a JSON export, either as an array of objects or as one object per line.
+  pub fn from_json(content: impl AsRef<str>) -> Result<Self, Error> {
+    let content = content.as_ref();
+    let items = match serde_json::from_str(content) {
+      Ok(Value::Array(items)) => items,
+      _ => serde_json::Deserializer::from_str(content)
+        .into_iter::<Value>()
+        .collect::<Result<_, _>>()?,
+    };
+
+    let texts = items
+      .iter()
+      .flat_map(|item| {
+        TEXT_FIELDS
+          .iter()
+          .filter_map(move |field| item.get(field)?.as_str())
+      })
+      .map(str::to_owned);
+
+    Ok(Self::from_texts(texts))
+  }
+
+  fn from_texts(texts: impl IntoIterator<Item = String>) -> Self {
+    let lines = texts
+      .into_iter()
+      .filter(|text| !DELETED_BODIES.contains(&text.as_str()))
+      .flat_map(|text| strip_markdown(&text))
+      .collect();
+
+    Self { lines }
+  }
+}
+
+impl Trainer for RedditExportTrainer {
+  fn for_each_line(&mut self, sink: &mut dyn FnMut(&str)) -> Result<(), Error> {
+    for line in &self.lines {
+      sink(line);
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_csv() {
+    let content = "id,permalink,date,ip,subreddit,gildings,link,parent,body,media\n\
+                   a1,/r/rust/a1,2020-01-31,,rust,0,/r/rust/x,,\"> you said rust is hard\n\
+                   \n\
+                   It **really** isn't, see [the book](https://doc.rust-lang.org/book).\",\n\
+                   a2,/r/rust/a2,2020-01-31,,rust,0,/r/rust/y,,[deleted],\n\
+                   a3,/r/rust/a3,2020-01-31,,rust,0,/r/rust/z,,\"* `cargo` &amp; friends\",\n";
+    let trainer = RedditExportTrainer::from_csv(content).unwrap();
+
+    assert_eq!(
+      trainer.lines,
+      vec!["It really isn't, see the book.", "cargo & friends"]
+    );
+  }
+
+  #[test]
+  fn test_json() {
+    let content = r#"{"title": "Hello", "selftext": "first post"}
+                     {"body": "&gt; quoted\nanswer"}"#;
+    let trainer = RedditExportTrainer::from_json(content).unwrap();
+
+    assert_eq!(trainer.lines, vec!["Hello", "first post", "answer"]);
+  }
+}
@@ -0,0 +1,142 @@
+//! A [`Trainer`] that can learn from any line-based log, described by a regular expression.
+//!
+//! The regular expression is matched against every line, and must have a `message` named capture
+//! group, holding the text to learn. An `author` group, if any, allows to only learn the messages
+//! of a given author, or to learn every author at once with [`AuthorTrainer`]. For instance,
+//! `^\[[^\]]*\] <(?P<author>[^>]+)> (?P<message>.*)$` matches lines such as
+//! `[12:34] <nick> message`.
+//!
+//! Lines not matching the regular expression are ignored.
+
+use regex::Regex;
+use std::io::{BufReader, Read};
+use std::mem;
+
+use crate::persona::AuthorTrainer;
+use crate::trainers::lines::Lines;
+use crate::{Error, Trainer};
+
+/// Lines of a log matched by a regular expression.
+pub struct RegexTrainer {
+  content: Lines,
+  /// The author we are interested in
+  author: String,
+  regex: Regex,
+}
+
+impl RegexTrainer {
+  /// Create a trainer from the regular expression matching lines.
+  ///
+  /// The log is either loaded as a string or streamed with [`Lines::from_reader`]. Fails if the
+  /// regular expression is invalid or doesn’t have a `message` group.
+  pub fn new(
+    author: impl Into<String>,
+    regex: &str,
+    content: impl Into<Lines>,
+  ) -> Result<Self, Error> {
+    let regex = Regex::new(regex)
+      .map_err(|err| Error::InvalidParameters(format!("invalid regex: {}", err)))?;
+
+    if !regex.capture_names().any(|name| name == Some("message")) {
+      return Err(Error::InvalidParameters(
+        "the regex must have a message group".to_owned(),
+      ));
+    }
+
+    Ok(Self {
+      content: content.into(),
+      author: author.into(),
+      regex,
+    })
+  }
+
+  /// Stream a log from a reader, such as the standard input.
+  pub fn from_reader(
+    author: impl Into<String>,
+    regex: &str,
+    reader: impl Read + 'static,
+  ) -> Result<Self, Error> {
+    let content = Lines::from_reader(BufReader::new(reader));
+
+    Self::new(author, regex, content)
+  }
+
+  /// Author (empty if the regular expression has no `author` group) and message of a line.
+  fn message<'a>(&self, line: &'a str) -> Option<(&'a str, &'a str)> {
+    let captures = self.regex.captures(line)?;
+    let author = captures.name("author").map_or("", |author| author.as_str());
+    let message = captures.name("message")?.as_str().trim();
+
+    Some((author.trim(), message)).filter(|(_, message)| !message.is_empty())
+  }
+}
+
+impl Trainer for RegexTrainer {
+  fn for_each_line(&mut self, sink: &mut dyn FnMut(&str)) -> Result<(), Error> {
+    let has_author = self
+      .regex
+      .capture_names()
+      .any(|name| name == Some("author"));
+
+    let mut lines = mem::take(&mut self.content);
+
+    let result = lines.for_each(|line| {
+      if let Some((author, message)) = self.message(line.trim_end_matches('\r')) {
+        if !has_author || author == self.author {
+          sink(message);
+        }
+      }
+    });
+
+    self.content = lines;
+    result
+  }
+}
+
+impl AuthorTrainer for RegexTrainer {
+  fn for_each_message(&mut self, sink: &mut dyn FnMut(&str, &str)) -> Result<(), Error> {
+    let mut lines = mem::take(&mut self.content);
+
+    let result = lines.for_each(|line| {
+      if let Some((author, message)) = self.message(line.trim_end_matches('\r')) {
+        sink(author, message);
+      }
+    });
+
+    self.content = lines;
+    result
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::trainers::learned_lines;
+
+  #[test]
+  fn test_regex() {
+    let content = "[10:00] <alice> hello\n\
+                   [10:01] * bob joined\n\
+                   [10:02] <bob> hi alice\n\
+                   [10:03] <alice> how are you?\n";
+    let regex = r"^\[[^\]]*\] <(?P<author>[^>]+)> (?P<message>.*)$";
+
+    let lines = learned_lines(&mut RegexTrainer::new("alice", regex, content).unwrap());
+    assert_eq!(lines, vec!["hello", "how are you?"]);
+
+    let mut messages = Vec::new();
+    RegexTrainer::new("", regex, content)
+      .unwrap()
+      .for_each_message(&mut |author, message| messages.push(format!("{}: {}", author, message)))
+      .unwrap();
+    assert_eq!(
+      messages,
+      vec!["alice: hello", "bob: hi alice", "alice: how are you?"]
+    );
+
+    assert!(matches!(
+      RegexTrainer::new("alice", r"<(?P<author>\w+)>", content),
+      Err(Error::InvalidParameters(_))
+    ));
+  }
+}
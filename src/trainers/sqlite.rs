@@ -0,0 +1,81 @@
+//! A [`Trainer`] that can learn from an SQLite database.
+//!
+//! A user-supplied query is run and the first column of every row is learned, so that any bot
+//! database can be used. For instance, with macOS’ iMessage database (`chat.db`):
+//!
+//! ```sql
+//! SELECT text FROM message WHERE is_from_me = 1
+//! ```
+
+use rusqlite::types::ValueRef;
+use rusqlite::{Connection, OpenFlags};
+use std::path::Path;
+
+use crate::{Error, Trainer};
+
+/// The result of a query.
+pub struct SqliteTrainer {
+  lines: Vec<String>,
+}
+
+impl SqliteTrainer {
+  /// Open a database in read-only mode and run a query on it.
+  pub fn new(path: impl AsRef<Path>, query: impl AsRef<str>) -> Result<Self, Error> {
+    let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    Self::from_connection(&connection, query)
+  }
+
+  /// Run a query on an already opened database.
+  ///
+  /// Rows which first column is not text (`NULL`, numbers, blobs) are ignored.
+  pub fn from_connection(connection: &Connection, query: impl AsRef<str>) -> Result<Self, Error> {
+    let mut statement = connection.prepare(query.as_ref())?;
+    let mut rows = statement.query([])?;
+    let mut lines = Vec::new();
+
+    while let Some(row) = rows.next()? {
+      if let ValueRef::Text(text) = row.get_ref(0)? {
+        lines.extend(
+          String::from_utf8_lossy(text)
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_owned),
+        );
+      }
+    }
+
+    Ok(Self { lines })
+  }
+}
+
+impl Trainer for SqliteTrainer {
+  fn for_each_line(&mut self, sink: &mut dyn FnMut(&str)) -> Result<(), Error> {
+    for line in &self.lines {
+      sink(line);
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_query() {
+    let connection = Connection::open_in_memory().unwrap();
+    connection
+      .execute_batch(
+        "CREATE TABLE message (text TEXT, is_from_me INTEGER);
+         INSERT INTO message VALUES ('hello there', 1), ('not me', 0), (NULL, 1), ('bye\nnow', 1);",
+      )
+      .unwrap();
+
+    let trainer =
+      SqliteTrainer::from_connection(&connection, "SELECT text FROM message WHERE is_from_me = 1")
+        .unwrap();
+    assert_eq!(trainer.lines, vec!["hello there", "bye", "now"]);
+  }
+}
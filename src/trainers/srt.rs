@@ -0,0 +1,137 @@
+//! A [`Trainer`] that can learn from SubRip (`.srt`) subtitles.
+//!
+//! Subtitles are made of cues separated by empty lines:
+//!
+//! ```ignore
+//! 1
+//! 00:00:01,000 --> 00:00:04,000
+//! <i>This is a cue</i>
+//! spanning two lines.
+//!
+//! 2
+//! 00:00:05,000 --> 00:00:06,000
+//! - Two people talking.
+//! - In the same cue.
+//! ```
+//!
+//! Lines of a cue are joined, unless they start with a dash, which denotes different speakers.
+//! Formatting tags are removed.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::io::{BufReader, Read};
+use std::mem;
+
+use crate::trainers::lines::Lines;
+use crate::{Error, Trainer};
+
+lazy_static! {
+  static ref REGEX_TIMECODE: Regex =
+    Regex::new(r"^\d{2}:\d{2}:\d{2}[,.]\d{3}\s+-->\s+\d{2}:\d{2}:\d{2}[,.]\d{3}").unwrap();
+  static ref REGEX_TAG: Regex = Regex::new(r"</?[a-zA-Z][^>]*>|\{\\[^}]*\}").unwrap();
+}
+
+/// The content of a SubRip file.
+pub struct SubtitleTrainer {
+  content: Lines,
+}
+
+impl SubtitleTrainer {
+  /// Create a trainer from subtitles, either loaded as a string or streamed with
+  /// [`Lines::from_reader`].
+  pub fn new(content: impl Into<Lines>) -> Self {
+    let content = content.into();
+
+    Self { content }
+  }
+
+  /// Stream subtitles from a reader, such as the standard input.
+  pub fn from_reader(reader: impl Read + 'static) -> Result<Self, Error> {
+    let content = Lines::from_reader(BufReader::new(reader));
+
+    Ok(Self::new(content))
+  }
+}
+
+impl Trainer for SubtitleTrainer {
+  fn for_each_line(&mut self, sink: &mut dyn FnMut(&str)) -> Result<(), Error> {
+    let mut content = mem::take(&mut self.content);
+    let mut utterance = String::new();
+    // whether the next line starts a cue, hence is its index rather than text such as “1984”
+    let mut cue_start = true;
+
+    let result = content.for_each(|line| {
+      // a byte order mark may start the file
+      let line = line.trim_start_matches('\u{FEFF}').trim();
+      let is_index = cue_start && !line.is_empty() && line.chars().all(|c| c.is_ascii_digit());
+      cue_start = line.is_empty();
+
+      if is_index || REGEX_TIMECODE.is_match(line) {
+        return;
+      }
+
+      let line = REGEX_TAG.replace_all(line, "");
+      let line = line.trim();
+
+      // a dash starts the line of another speaker, and an empty line ends the cue
+      if (line.is_empty() || line.starts_with('-')) && !utterance.is_empty() {
+        sink(&utterance);
+        utterance.clear();
+      }
+
+      let line = line.trim_start_matches('-').trim_start();
+      if !line.is_empty() {
+        if !utterance.is_empty() {
+          utterance.push(' ');
+        }
+
+        utterance.push_str(line);
+      }
+    });
+
+    if !utterance.is_empty() {
+      sink(&utterance);
+    }
+
+    self.content = content;
+    result
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::trainers::learned_lines;
+  use std::io::Cursor;
+
+  #[test]
+  fn test_cues() {
+    let content = "\u{FEFF}1\r\n\
+                   00:00:01,000 --> 00:00:04,000\r\n\
+                   <i>This is a cue</i>\r\n\
+                   spanning two lines.\r\n\
+                   \r\n\
+                   2\r\n\
+                   00:00:05,000 --> 00:00:06,000\r\n\
+                   - Two people talking.\r\n\
+                   -{\\an8}In the same cue.\r\n\
+                   \r\n\
+                   3\r\n\
+                   00:00:07.000 --> 00:00:08.000 X1:0 X2:10\r\n\
+                   1984\r\n";
+
+    let expected = vec![
+      "This is a cue spanning two lines.",
+      "Two people talking.",
+      "In the same cue.",
+      "1984",
+    ];
+    assert_eq!(learned_lines(&mut SubtitleTrainer::new(content)), expected);
+
+    let reader = Cursor::new(content.as_bytes().to_vec());
+    assert_eq!(
+      learned_lines(&mut SubtitleTrainer::from_reader(reader).unwrap()),
+      expected
+    );
+  }
+}
@@ -0,0 +1,240 @@
+//! A [`Trainer`] that can learn from a Telegram Desktop JSON export.
+//!
+//! Both single chat exports and full account exports (`result.json`) are supported.
+
+use serde::Deserialize;
+
+use crate::{Error, Trainer};
+
+/// The author we are interested in.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TelegramAuthor {
+  /// Telegram user ID, with or without the `user` prefix used in exports.
+  Id(String),
+  /// Display name.
+  Name(String),
+}
+
+/// A full account export.
+#[derive(Debug, Deserialize)]
+struct AccountExport {
+  chats: ChatList,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatList {
+  list: Vec<ChatExport>,
+}
+
+/// A single chat export.
+#[derive(Debug, Deserialize)]
+struct ChatExport {
+  messages: Vec<Message>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Message {
+  #[serde(rename = "type")]
+  ty: String,
+  #[serde(default)]
+  from: Option<String>,
+  #[serde(default)]
+  from_id: Option<String>,
+  #[serde(default)]
+  text: Text,
+}
+
+impl Message {
+  fn is_from(&self, author: &TelegramAuthor) -> bool {
+    match author {
+      TelegramAuthor::Id(id) => self
+        .from_id
+        .as_ref()
+        .is_some_and(|from_id| from_id == id || from_id.strip_prefix("user") == Some(id)),
+      TelegramAuthor::Name(name) => self.from.as_ref() == Some(name),
+    }
+  }
+}
+
+/// Text of a message, either plain or split into formatted entities.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Text {
+  Plain(String),
+  Entities(Vec<TextEntity>),
+}
+
+impl Default for Text {
+  fn default() -> Self {
+    Text::Plain(String::new())
+  }
+}
+
+impl Text {
+  /// Flatten the text, dropping formatting.
+  fn flatten(self) -> String {
+    match self {
+      Text::Plain(text) => text,
+      Text::Entities(entities) => entities
+        .into_iter()
+        .map(|entity| match entity {
+          TextEntity::Plain(text) => text,
+          TextEntity::Formatted { text } => text,
+        })
+        .collect(),
+    }
+  }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum TextEntity {
+  Plain(String),
+  Formatted { text: String },
+}
+
+/// Any supported export.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Export {
+  Account(AccountExport),
+  Chat(ChatExport),
+}
+
+/// The content of a Telegram export.
+pub struct TelegramExportTrainer {
+  lines: Vec<String>,
+}
+
+impl TelegramExportTrainer {
+  /// Parse a Telegram export and keep the messages of `author`.
+  pub fn new(author: TelegramAuthor, content: impl AsRef<str>) -> Result<Self, Error> {
+    let chats = match serde_json::from_str(content.as_ref())? {
+      Export::Account(export) => export.chats.list,
+      Export::Chat(chat) => vec![chat],
+    };
+
+    let lines = chats
+      .into_iter()
+      .flat_map(|chat| chat.messages)
+      // service messages (joins, pins, calls, etc.) don’t have an author anyway
+      .filter(|msg| msg.ty == "message" && msg.is_from(&author))
+      .map(|msg| msg.text.flatten())
+      .flat_map(|text| {
+        text
+          .lines()
+          .map(|line| line.trim().to_owned())
+          .collect::<Vec<_>>()
+      })
+      // media messages without caption have an empty text
+      .filter(|line| !line.is_empty())
+      .collect();
+
+    Ok(Self { lines })
+  }
+}
+
+impl Trainer for TelegramExportTrainer {
+  fn for_each_line(&mut self, sink: &mut dyn FnMut(&str)) -> Result<(), Error> {
+    for line in &self.lines {
+      sink(line);
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::trainers::learned_lines;
+
+  #[test]
+  fn test_chat_export() {
+    let content = r#"{
+      "name": "friends",
+      "type": "private_group",
+      "id": 1,
+      "messages": [
+        {
+          "id": 10,
+          "type": "message",
+          "from": "Alice",
+          "from_id": "user20",
+          "text": "hello\n  everyone  "
+        },
+        {
+          "id": 11,
+          "type": "message",
+          "from": "Alice",
+          "from_id": "user20",
+          "text": ["look at ", { "type": "link", "text": "https://example.com" }, " now"]
+        },
+        {
+          "id": 12,
+          "type": "message",
+          "from": "Alice",
+          "from_id": "user20",
+          "photo": "photos/cat.jpg",
+          "text": ""
+        },
+        {
+          "id": 13,
+          "type": "service",
+          "actor": "Alice",
+          "actor_id": "user20",
+          "action": "pin_message",
+          "text": ""
+        },
+        {
+          "id": 14,
+          "type": "message",
+          "from": "Bob",
+          "from_id": "user21",
+          "text": "hi"
+        }
+      ]
+    }"#;
+
+    let expected = vec!["hello", "everyone", "look at https://example.com now"];
+    let trainer = TelegramExportTrainer::new(TelegramAuthor::Name("Alice".to_owned()), content);
+    assert_eq!(trainer.unwrap().lines, expected);
+
+    let trainer = TelegramExportTrainer::new(TelegramAuthor::Id("20".to_owned()), content);
+    assert_eq!(trainer.unwrap().lines, expected);
+
+    let trainer = TelegramExportTrainer::new(TelegramAuthor::Id("user21".to_owned()), content);
+    assert_eq!(trainer.unwrap().lines, vec!["hi"]);
+  }
+
+  #[test]
+  fn test_account_export() {
+    let content = r#"{
+      "about": "Telegram export",
+      "chats": {
+        "about": "chats",
+        "list": [
+          {
+            "name": "Alice",
+            "type": "personal_chat",
+            "id": 1,
+            "messages": [{ "id": 1, "type": "message", "from": "Bob", "from_id": "user21", "text": "first" }]
+          },
+          {
+            "name": "friends",
+            "type": "private_group",
+            "id": 2,
+            "messages": [{ "id": 2, "type": "message", "from": "Bob", "from_id": "user21", "text": "second" }]
+          }
+        ]
+      }
+    }"#;
+
+    let mut trainer =
+      TelegramExportTrainer::new(TelegramAuthor::Name("Bob".to_owned()), content).unwrap();
+    let lines = learned_lines(&mut trainer);
+    assert_eq!(lines, vec!["first", "second"]);
+
+    assert!(TelegramExportTrainer::new(TelegramAuthor::Name("Bob".to_owned()), "{}").is_err());
+  }
+}
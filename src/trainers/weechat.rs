@@ -1,91 +1,772 @@
 //! A [`Trainer`] that can learn from a Weechat log.
+//!
+//! The log can also be learned for all its authors at once, with [`AuthorTrainer`].
+//!
+//! Nick changes (`-- foo is now known as foo_`) are followed, so that the messages of a person are
+//! attributed to the same author whatever the nick they use. The nicks of the author are kept from
+//! a call of [`Trainer::for_each_line`] to the next, so that a log streamed in several parts, such
+//! as a log being written, is followed across them.
+//!
+//! Actions (` * nick does something`, written with `/me`) are ignored unless enabled with
+//! [`WeechatLogTrainer::actions`]. Messages can be cleaned up (formatting codes, URLs, mentions)
+//! with [`WeechatLogTrainer::cleanup`], and restricted to a period of time with
+//! [`WeechatLogTrainer::time_range`].
+//!
+//! Weechat logs each buffer in its own file, such as `irc.libera.#rust.weechatlog` or
+//! `irc.libera.bob.weechatlog` for private messages with `bob`. Given the name of its file with
+//! [`WeechatLogTrainer::file_name`], a log can be filtered by buffer with
+//! [`WeechatLogTrainer::channels`], for instance to keep private messages out of a public bot’s
+//! model. Logs concatenated with `tail -n +1 *.weechatlog` are split at the `==> file <==` headers
+//! `tail` writes, whose file names give the buffers of the following lines.
 
 use lazy_static::lazy_static;
 use regex::Regex;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::io::{BufReader, Read};
+use std::iter;
+use std::mem;
 
-use crate::{ChainError, LearningParameters, MarkovChainGenerator, Trainer};
+use crate::cleanup::Cleanup;
+use crate::persona::AuthorTrainer;
+use crate::trainers::lines::Lines;
+use crate::{Error, Trainer};
 
 lazy_static! {
   static ref REGEX_LINE: Regex =
-    Regex::new(r"(\d{4}-\d{2}-\d{2}\s+)?\d{2}:\d{2}:\d{2}\s+(.*)").unwrap();
+    Regex::new(r"^(\d{4}-\d{2}-\d{2}\s+)?\d{2}:\d{2}:\d{2}\s+(.*)").unwrap();
+  static ref REGEX_RENAME: Regex = Regex::new(r"^--\s+(\S+) is now known as (\S+)$").unwrap();
+  static ref REGEX_FILE_HEADER: Regex = Regex::new(r"^==> (.+) <==$").unwrap();
+  static ref REGEX_TIMESTAMP: Regex =
+    Regex::new(r"^\d{4}(-\d{2}(-\d{2}( \d{2}(:\d{2}(:\d{2})?)?)?)?)?$").unwrap();
+}
+
+/// Mode sigils prefixing nicks (owner, admin, operator, half-operator, voice).
+const MODE_SIGILS: [char; 6] = ['~', '&', '!', '@', '%', '+'];
+
+/// How to learn actions, written with `/me`.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Actions {
+  /// Don’t learn actions.
+  #[default]
+  Ignore,
+  /// Learn actions as regular messages: ` * nick does something` is learned as `does something`.
+  Plain,
+  /// Learn actions as `/me` commands: ` * nick does something` is learned as
+  /// `/me does something`, so that generated actions can be sent back as such.
+  Emote,
+}
+
+impl Actions {
+  /// Message to learn from the content of an action, if any.
+  fn message(self, content: &str) -> Option<Cow<'_, str>> {
+    match self {
+      Actions::Ignore => None,
+      Actions::Plain => Some(Cow::Borrowed(content)),
+      Actions::Emote => Some(Cow::Owned(format!("/me {}", content))),
+    }
+  }
+}
+
+/// A period of time, bounded by timestamps such as `2019`, `2019-06`, `2019-06-01` or
+/// `2019-06-01 12:00:00`.
+///
+/// Both bounds are inclusive and as precise as they are written: the range from `2019` to `2019`
+/// is the whole year 2019.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct TimeRange {
+  since: Option<String>,
+  until: Option<String>,
+}
+
+impl TimeRange {
+  /// Create a range from its bounds; [`None`] leaves a side open.
+  pub fn new(since: Option<&str>, until: Option<&str>) -> Result<Self, Error> {
+    let bound = |timestamp: Option<&str>| {
+      timestamp
+        .map(|timestamp| {
+          if REGEX_TIMESTAMP.is_match(timestamp) {
+            Ok(timestamp.to_owned())
+          } else {
+            Err(Error::InvalidParameters(format!(
+              "invalid timestamp {}: expected YYYY[-MM[-DD[ HH[:MM[:SS]]]]]",
+              timestamp
+            )))
+          }
+        })
+        .transpose()
+    };
+
+    Ok(Self {
+      since: bound(since)?,
+      until: bound(until)?,
+    })
+  }
+
+  /// Whether the range has no bound.
+  pub fn is_unbounded(&self) -> bool {
+    self.since.is_none() && self.until.is_none()
+  }
+
+  /// Whether a `YYYY-MM-DD HH:MM:SS` timestamp is in the range.
+  fn contains(&self, timestamp: &str) -> bool {
+    // timestamps are compared as strings, truncated to the precision of the bounds
+    let truncated = |bound: &str| &timestamp[..bound.len().min(timestamp.len())];
+
+    self
+      .since
+      .as_deref()
+      .is_none_or(|since| truncated(since) >= since)
+      && self
+        .until
+        .as_deref()
+        .is_none_or(|until| truncated(until) <= until)
+  }
+}
+
+/// Prefixes of IRC channel names; other buffers are private messages.
+const CHANNEL_PREFIXES: [char; 4] = ['#', '&', '!', '+'];
+
+/// Buffer of a Weechat log file name, such as `#rust` for `irc.libera.#rust.weechatlog`.
+///
+/// Directories and compression extensions are removed first.
+fn buffer(file_name: &str) -> &str {
+  let file_name = file_name.rsplit(['/', '\\']).next().unwrap_or(file_name);
+  let file_name = [".gz", ".xz", ".zst"]
+    .iter()
+    .find_map(|extension| file_name.strip_suffix(extension))
+    .unwrap_or(file_name);
+  let file_name = file_name.strip_suffix(".weechatlog").unwrap_or(file_name);
+
+  // IRC buffers are named after the server, then the channel or nick, which may contain dots
+  match file_name.strip_prefix("irc.") {
+    Some(rest) => rest.split_once('.').map_or(rest, |(_, buffer)| buffer),
+    None => file_name,
+  }
+}
+
+/// Which buffers (channels and private messages) to learn.
+///
+/// Buffer names are compared case-insensitively, as on IRC. Logs of unknown buffers, such as logs
+/// read from the standard input, are only learned if no channel is required with
+/// [`Channels::only`] and private messages are not skipped.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Channels {
+  /// Buffers to learn; all of them if [`None`].
+  only: Option<HashSet<String>>,
+  /// Buffers not to learn.
+  except: HashSet<String>,
+  /// Skip private messages.
+  skip_private: bool,
+}
+
+impl Channels {
+  /// Learn every buffer.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Only learn these buffers, such as `#rust`.
+  pub fn only<C>(mut self, channels: C) -> Self
+  where
+    C: IntoIterator,
+    C::Item: AsRef<str>,
+  {
+    let only = self.only.get_or_insert_with(HashSet::new);
+    only.extend(
+      channels
+        .into_iter()
+        .map(|channel| channel.as_ref().to_lowercase()),
+    );
+    self
+  }
+
+  /// Don’t learn these buffers.
+  pub fn except<C>(mut self, channels: C) -> Self
+  where
+    C: IntoIterator,
+    C::Item: AsRef<str>,
+  {
+    self.except.extend(
+      channels
+        .into_iter()
+        .map(|channel| channel.as_ref().to_lowercase()),
+    );
+    self
+  }
+
+  /// Don’t learn private messages, that is, buffers that aren’t channels.
+  pub fn skip_private(mut self, skip_private: bool) -> Self {
+    self.skip_private = skip_private;
+    self
+  }
+
+  /// Whether every buffer is learned.
+  pub fn is_unfiltered(&self) -> bool {
+    self.only.is_none() && self.except.is_empty() && !self.skip_private
+  }
+
+  /// Whether a buffer, if known, is learned.
+  fn allows(&self, buffer: Option<&str>) -> bool {
+    let buffer = match buffer {
+      Some(buffer) => buffer.to_lowercase(),
+      None => return self.only.is_none() && !self.skip_private,
+    };
+
+    self.only.as_ref().is_none_or(|only| only.contains(&buffer))
+      && !self.except.contains(&buffer)
+      && (!self.skip_private || buffer.starts_with(&CHANNEL_PREFIXES[..]))
+  }
 }
 
 /// The content of a weechat log.
 pub struct WeechatLogTrainer {
-  lines: Vec<String>,
-  /// The author we are interested in
+  content: Lines,
+  /// The author we are interested in; every author if empty.
   author: String,
+  /// Other nicks of the author.
+  aliases: HashSet<String>,
+  /// Lowercase nicks of the author, once the log started to be learned.
+  nicks: Option<HashSet<String>>,
+  /// Authors to learn with [`AuthorTrainer`]; all of them if [`None`].
+  authors: Option<HashSet<String>>,
+  /// How to learn actions.
+  actions: Actions,
+  /// Cleanup passes applied to messages.
+  cleanup: Cleanup,
+  /// Period of the messages to learn.
+  time_range: TimeRange,
+  /// Buffer of the log, if known.
+  buffer: Option<String>,
+  /// Buffers to learn.
+  channels: Channels,
 }
 
 impl WeechatLogTrainer {
-  pub fn new(author: impl Into<String>, content: impl Into<String>) -> Self {
-    let lines: Vec<_> = content
-      .into()
-      .split_terminator('\n')
-      .map(|line| line.to_owned())
-      .collect();
+  /// Create a trainer from a log, either loaded as a string or streamed with
+  /// [`Lines::from_reader`].
+  ///
+  /// An empty author learns the messages of every author.
+  pub fn new(author: impl Into<String>, content: impl Into<Lines>) -> Self {
+    let content = content.into();
     let author = author.into();
 
-    Self { lines, author }
+    Self {
+      content,
+      author,
+      aliases: HashSet::new(),
+      nicks: None,
+      authors: None,
+      actions: Actions::default(),
+      cleanup: Cleanup::default(),
+      time_range: TimeRange::default(),
+      buffer: None,
+      channels: Channels::default(),
+    }
   }
 
-  /// Filter the log by removing all the noise linked to Weechat.
-  fn filter_noise(&mut self) {
-    self.lines.retain(|line| {
-      if let Some(captures) = REGEX_LINE.captures(line) {
-        !(captures[2].starts_with("--")
-          || captures[2].starts_with("<--")
-          || captures[2].starts_with("-->"))
-      } else {
-        false
-      }
-    });
+  /// Clean up messages before learning them.
+  pub fn cleanup(mut self, cleanup: Cleanup) -> Self {
+    self.cleanup = cleanup;
+    self
+  }
+
+  /// Only learn the messages written during a period of time.
+  ///
+  /// Lines without a date are skipped when the range is bounded. Nick changes are followed
+  /// whenever they happen.
+  pub fn time_range(mut self, time_range: TimeRange) -> Self {
+    self.time_range = time_range;
+    self
+  }
+
+  /// Set the buffer of the log from the name of its file, such as `irc.libera.#rust.weechatlog`.
+  pub fn file_name(mut self, file_name: impl AsRef<str>) -> Self {
+    self.buffer = Some(buffer(file_name.as_ref()).to_owned());
+    self
+  }
+
+  /// Only learn the messages of some buffers.
+  pub fn channels(mut self, channels: Channels) -> Self {
+    self.channels = channels;
+    self
+  }
+
+  /// Change how actions are learned; they are ignored by default.
+  pub fn actions(mut self, actions: Actions) -> Self {
+    self.actions = actions;
+    self
+  }
+
+  /// Also learn the messages written with these nicks, such as `foo_` or `foo|afk`.
+  ///
+  /// Nick changes found in the log are followed anyway; aliases are useful when a log starts with
+  /// the author already using another nick.
+  pub fn aliases<A>(mut self, aliases: A) -> Self
+  where
+    A: IntoIterator,
+    A::Item: Into<String>,
+  {
+    self.aliases.extend(aliases.into_iter().map(Into::into));
+    self
+  }
+
+  /// Only learn the messages of these authors when used as an [`AuthorTrainer`], ignoring case.
+  ///
+  /// By default, the messages of every author are learned.
+  pub fn authors<A>(mut self, authors: A) -> Self
+  where
+    A: IntoIterator,
+    A::Item: Into<String>,
+  {
+    self.authors = Some(
+      authors
+        .into_iter()
+        .map(|author| author.into().to_lowercase())
+        .collect(),
+    );
+    self
+  }
+
+  /// Stream a log from a reader, such as the standard input.
+  pub fn from_reader(
+    author: impl Into<String>,
+    reader: impl Read + 'static,
+  ) -> Result<Self, Error> {
+    let content = Lines::from_reader(BufReader::new(reader));
+
+    Ok(Self::new(author, content))
   }
 
-  /// Clean up lines to remove dates and nicknames.
-  fn cleanup(&mut self) {
-    for line in &mut self.lines {
-      // remove the date
-      if let Some(captures) = REGEX_LINE.captures(line) {
-        let mut input = &captures[2];
+  /// Whether a line is noise linked to Weechat (joins, quits, network messages, etc.).
+  fn is_noise(input: &str) -> bool {
+    input.starts_with("--") || input.starts_with("<--") || input.starts_with("-->")
+  }
+
+  /// Whether the lines following a `==> file <==` header, starting the log of another buffer in
+  /// concatenated logs, are learned.
+  ///
+  /// Returns [`None`] if the line isn’t such a header.
+  fn file_header(&self, line: &str) -> Option<bool> {
+    let captures = REGEX_FILE_HEADER.captures(line)?;
+    Some(
+      self
+        .channels
+        .allows(Some(buffer(captures.get(1)?.as_str()))),
+    )
+  }
+
+  /// Old and new nick of a nick change line.
+  fn rename(line: &str) -> Option<(&str, &str)> {
+    let captures = REGEX_LINE.captures(line)?;
+    let captures = REGEX_RENAME.captures(captures.get(2)?.as_str())?;
 
-        if !input.is_empty() && input.as_bytes()[0] == b'@' {
-          input = &input[1..];
+    Some((captures.get(1)?.as_str(), captures.get(2)?.as_str()))
+  }
+
+  /// Apply the cleanup passes to a message.
+  ///
+  /// Returns [`None`] if nothing is left.
+  fn cleaned<'a>(&self, content: &'a str) -> Option<Cow<'a, str>> {
+    let content = match self.cleanup.apply(content) {
+      Cow::Borrowed(content) => Cow::Borrowed(content.trim()),
+      Cow::Owned(content) => Cow::Owned(content.trim().to_owned()),
+    };
+
+    Some(content).filter(|content| !content.is_empty())
+  }
+
+  /// Nickname of a prefix (the nick column), without its mode sigils (`@`, `+`, `%`, etc.).
+  fn nick(prefix: &str) -> &str {
+    prefix.trim().trim_start_matches(&MODE_SIGILS[..])
+  }
+
+  /// Whether a line, matched by [`REGEX_LINE`], was written during the time range.
+  fn in_time_range(&self, captures: &regex::Captures) -> bool {
+    if self.time_range.is_unbounded() {
+      return true;
+    }
+
+    let (line, date) = match (captures.get(0), captures.get(1)) {
+      (Some(line), Some(date)) => (line, date),
+      _ => return false,
+    };
+
+    // the date and time are separated by any whitespace, normalized to a single space
+    let time = &line.as_str()[date.end() - line.start()..][..8];
+    self
+      .time_range
+      .contains(&format!("{} {}", date.as_str().trim(), time))
+  }
+
+  /// Split a line into its nickname and message, removing its date.
+  ///
+  /// Returns [`None`] if the line is noise, an ignored action or out of the time range.
+  fn message<'a>(&self, line: &'a str) -> Option<(&'a str, Cow<'a, str>)> {
+    let captures = REGEX_LINE.captures(line)?;
+    let input = captures.get(2)?.as_str();
+
+    if Self::is_noise(input) || !self.in_time_range(&captures) {
+      return None;
+    }
+
+    // the nick column is delimited by a tab, or by a space in hand-edited logs
+    let (prefix, content) = input
+      .split_once('\t')
+      .or_else(|| input.split_once(char::is_whitespace))?;
+    let content = content.trim();
+
+    let (nick, content) = if prefix.trim() == "*" {
+      // actions: the nick is the first word of the message
+      let (nick, content) = content.split_once(char::is_whitespace)?;
+      let content = content.trim();
+      (Self::nick(nick), self.actions.message(content)?)
+    } else {
+      (Self::nick(prefix), Cow::Borrowed(content))
+    };
+
+    if nick.is_empty() || content.is_empty() {
+      None
+    } else {
+      Some((nick, content))
+    }
+  }
+}
+
+impl AuthorTrainer for WeechatLogTrainer {
+  fn for_each_message(&mut self, sink: &mut dyn FnMut(&str, &str)) -> Result<(), Error> {
+    // author of the messages written with a nick, keyed by the lowercase nick as nicks are
+    // case-insensitive on IRC; the author is named after the first nick seen
+    let mut authors = HashMap::<String, String>::new();
+    let mut lines = mem::take(&mut self.content);
+    let mut learned = self.channels.allows(self.buffer.as_deref());
+
+    let result = lines.for_each(|line| {
+      if let Some(allowed) = self.file_header(line) {
+        learned = allowed;
+      } else if let Some((old, new)) = Self::rename(line) {
+        // the old nick is released, and may be taken by someone else afterwards
+        let author = authors
+          .remove(&old.to_lowercase())
+          .unwrap_or_else(|| old.to_owned());
+        authors.insert(new.to_lowercase(), author);
+      } else if let Some((nick, content)) = self.message(line).filter(|_| learned) {
+        let author = authors
+          .entry(nick.to_lowercase())
+          .or_insert_with(|| nick.to_owned());
+        let wanted = self
+          .authors
+          .as_ref()
+          .is_none_or(|authors| authors.contains(&author.to_lowercase()));
+
+        if let Some(content) = self.cleaned(&content).filter(|_| wanted) {
+          sink(author, &content);
         }
+      }
+    });
+
+    self.content = lines;
+    result
+  }
+}
 
-        if input.starts_with(&self.author) {
-          // remove the nickname
-          let content = input[self.author.len()..].trim().to_owned();
-          eprintln!("{}", content);
+impl Trainer for WeechatLogTrainer {
+  fn for_each_line(&mut self, sink: &mut dyn FnMut(&str)) -> Result<(), Error> {
+    // an empty author learns every line, whoever wrote it
+    let every_author = self.author.is_empty();
+    // nicks are case-insensitive on IRC
+    let mut nicks = self.nicks.take().unwrap_or_else(|| {
+      iter::once(&self.author)
+        .chain(&self.aliases)
+        .map(|nick| nick.to_lowercase())
+        .collect()
+    });
 
-          *line = content;
+    let mut lines = mem::take(&mut self.content);
+    let mut learned = self.channels.allows(self.buffer.as_deref());
+
+    let result = lines.for_each(|line| {
+      if let Some(allowed) = self.file_header(line) {
+        trace!("switching to {}", line);
+        learned = allowed;
+      } else if let Some((old, new)) = Self::rename(line) {
+        // the old nick is released, and may be taken by someone else afterwards
+        if nicks.remove(&old.to_lowercase()) {
+          trace!("following nick change from {} to {}", old, new);
+          nicks.insert(new.to_lowercase());
+        }
+      } else if let Some((nick, content)) = self.message(line).filter(|_| learned) {
+        if let Some(content) = self
+          .cleaned(&content)
+          .filter(|_| every_author || nicks.contains(&nick.to_lowercase()))
+        {
+          trace!("learning {}", content);
+          sink(&content);
         } else {
-          // set the line to the empty line so that we drop it
-          eprintln!("\tignoring {}", input);
-          *line = String::new();
+          trace!("ignoring {}", line);
         }
       }
-    }
+    });
 
-    // remove empty lines
-    self.lines.retain(|line| !line.is_empty());
+    self.content = lines;
+    self.nicks = Some(nicks);
+    result
   }
 }
 
-impl Trainer for WeechatLogTrainer {
-  fn source_train(
-    &mut self,
-    markov_chain_generator: &mut MarkovChainGenerator,
-    learn_params: LearningParameters,
-  ) -> Result<(), ChainError> {
-    self.filter_noise();
-    self.cleanup();
-
-    eprintln!("learning from Weechat log ({} lines)", self.lines.len());
-    for line in &self.lines {
-      markov_chain_generator.train(&learn_params, line);
-    }
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::trainers::learned_lines;
+
+  #[test]
+  fn test_all_authors() {
+    let content = "2020-01-31 10:00:00\t-->\talice (~alice@host) has joined #rust\n\
+                   2020-01-31 10:00:01\t@alice\thello everyone\n\
+                   2020-01-31 10:00:02\tbob\thi alice\n\
+                   2020-01-31 10:00:03\t *\talice waves\n\
+                   2020-01-31 10:00:04\tcarol\they\n";
+    let mut messages = Vec::new();
+    WeechatLogTrainer::new("", content)
+      .for_each_message(&mut |nick, message| messages.push((nick.to_owned(), message.to_owned())))
+      .unwrap();
+
+    assert_eq!(
+      messages,
+      vec![
+        ("alice".to_owned(), "hello everyone".to_owned()),
+        ("bob".to_owned(), "hi alice".to_owned()),
+        ("carol".to_owned(), "hey".to_owned()),
+      ]
+    );
+
+    let mut nicks = Vec::new();
+    WeechatLogTrainer::new("", content)
+      .authors(vec!["alice", "carol"])
+      .for_each_message(&mut |nick, _| nicks.push(nick.to_owned()))
+      .unwrap();
+    assert_eq!(nicks, vec!["alice", "carol"]);
+
+    // nicks are case-insensitive, and nick changes are followed
+    let content = "2020-01-31 10:00:00\tFoo\thello\n\
+                   2020-01-31 10:00:01\t--\tFoo is now known as bar\n\
+                   2020-01-31 10:00:02\t--\tbar is now known as BAR\n\
+                   2020-01-31 10:00:03\tBAR\tagain\n\
+                   2020-01-31 10:00:04\tbar\tlowercase\n";
+    let mut messages = Vec::new();
+    WeechatLogTrainer::new("", content)
+      .authors(vec!["FOO"])
+      .for_each_message(&mut |nick, message| messages.push((nick.to_owned(), message.to_owned())))
+      .unwrap();
+    assert_eq!(
+      messages,
+      vec![
+        ("Foo".to_owned(), "hello".to_owned()),
+        ("Foo".to_owned(), "again".to_owned()),
+        ("Foo".to_owned(), "lowercase".to_owned()),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_nicks() {
+    let content = "2020-01-31 10:00:00\tal\tshort nick\n\
+                   2020-01-31 10:00:01\talice\tnot al\n\
+                   2020-01-31 10:00:02\t+al\tvoiced\n\
+                   2020-01-31 10:00:03\t%Al\thalf-op\n\
+                   2020-01-31 10:00:04\t~al\towner\n\
+                   2020-01-31 10:00:05\t@al_\tnot al either\n";
+
+    let lines = learned_lines(&mut WeechatLogTrainer::new("al", content));
+    assert_eq!(lines, vec!["short nick", "voiced", "half-op", "owner"]);
+
+    // without an author, every line is learned
+    let lines = learned_lines(&mut WeechatLogTrainer::new("", content));
+    assert_eq!(lines.len(), 6);
+  }
+
+  #[test]
+  fn test_cleanup() {
+    let content = "2020-01-31 10:00:00\talice\t\x02bob\x02: see https://example.com\n\
+                   2020-01-31 10:00:01\talice\thttps://example.com\n\
+                   not a log line, 2020-01-31 10:00:02\talice\tsee too\n";
+
+    let lines = learned_lines(
+      &mut WeechatLogTrainer::new("alice", content).cleanup(
+        Cleanup::new()
+          .strip_formatting(true)
+          .replace_urls("")
+          .strip_mentions(true),
+      ),
+    );
+    assert_eq!(lines, vec!["see"]);
+  }
+
+  #[test]
+  fn test_actions() {
+    let content = "2020-01-31 10:00:00\talice\thello\n\
+                   2020-01-31 10:00:01\t *\talice waves at everyone\n\
+                   2020-01-31 10:00:02\t *\tbob sighs\n";
+
+    let lines =
+      |actions| learned_lines(&mut WeechatLogTrainer::new("alice", content).actions(actions));
+    assert_eq!(lines(Actions::Ignore), vec!["hello"]);
+    assert_eq!(lines(Actions::Plain), vec!["hello", "waves at everyone"]);
+    assert_eq!(
+      lines(Actions::Emote),
+      vec!["hello", "/me waves at everyone"]
+    );
+
+    let mut messages = Vec::new();
+    WeechatLogTrainer::new("", content)
+      .actions(Actions::Plain)
+      .for_each_message(&mut |nick, message| messages.push(format!("{}: {}", nick, message)))
+      .unwrap();
+    assert_eq!(
+      messages,
+      vec!["alice: hello", "alice: waves at everyone", "bob: sighs"]
+    );
+  }
+
+  #[test]
+  fn test_time_range() {
+    let content = "2018-12-31 23:59:59\talice\tlast of 2018\n\
+                   2019-01-01 00:00:00\talice\tfirst of 2019\n\
+                   2019-06-15  12:00:00\talice\tmid 2019\n\
+                   12:30:00\talice\tno date\n\
+                   2020-01-01 00:00:00\talice\tfirst of 2020\n";
+
+    let lines = |since, until| {
+      learned_lines(
+        &mut WeechatLogTrainer::new("alice", content)
+          .time_range(TimeRange::new(since, until).unwrap()),
+      )
+    };
+    assert_eq!(lines(None, None).len(), 5);
+    assert_eq!(
+      lines(Some("2019"), Some("2019")),
+      vec!["first of 2019", "mid 2019"]
+    );
+    assert_eq!(
+      lines(Some("2019-06-15 12"), None),
+      vec!["mid 2019", "first of 2020"]
+    );
+    assert_eq!(
+      lines(None, Some("2019-01-01")),
+      vec!["last of 2018", "first of 2019"]
+    );
+
+    assert!(TimeRange::new(Some("june 2019"), None).is_err());
+    assert!(TimeRange::new(None, Some("2019-6")).is_err());
+  }
+
+  #[test]
+  fn test_channels() {
+    assert_eq!(buffer("irc.libera.#rust.weechatlog"), "#rust");
+    assert_eq!(buffer("logs/irc.oftc.#a.b.weechatlog.gz"), "#a.b");
+    assert_eq!(buffer("irc.libera.bob.weechatlog"), "bob");
+
+    let lines = |file_name: Option<&str>, content: &str, channels: Channels| {
+      let trainer = WeechatLogTrainer::new("alice", content).channels(channels);
+      let mut trainer = match file_name {
+        Some(file_name) => trainer.file_name(file_name),
+        None => trainer,
+      };
+      learned_lines(&mut trainer)
+    };
+
+    let content = "2020-01-31 10:00:00\talice\thello\n";
+    let public = Channels::new().skip_private(true);
+    assert_eq!(
+      lines(Some("irc.libera.#rust.weechatlog"), content, public.clone()),
+      vec!["hello"]
+    );
+    assert!(lines(Some("irc.libera.bob.weechatlog"), content, public.clone()).is_empty());
+    assert!(lines(None, content, public).is_empty());
+    assert_eq!(lines(None, content, Channels::new()), vec!["hello"]);
+
+    let concatenated = "==> irc.libera.#rust.weechatlog <==\n\
+                        2020-01-31 10:00:00\talice\tin #rust\n\
+                        \n\
+                        ==> irc.libera.#Offtopic.weechatlog <==\n\
+                        2020-01-31 10:00:01\talice\tin #offtopic\n\
+                        \n\
+                        ==> irc.libera.bob.weechatlog <==\n\
+                        2020-01-31 10:00:02\talice\tto bob\n";
+    assert_eq!(
+      lines(None, concatenated, Channels::new()),
+      vec!["in #rust", "in #offtopic", "to bob"]
+    );
+    assert_eq!(
+      lines(None, concatenated, Channels::new().skip_private(true)),
+      vec!["in #rust", "in #offtopic"]
+    );
+    assert_eq!(
+      lines(None, concatenated, Channels::new().only(vec!["#OFFTOPIC"])),
+      vec!["in #offtopic"]
+    );
+    assert_eq!(
+      lines(None, concatenated, Channels::new().except(vec!["#rust"])),
+      vec!["in #offtopic", "to bob"]
+    );
+
+    let mut messages = Vec::new();
+    WeechatLogTrainer::new("", concatenated)
+      .channels(Channels::new().only(vec!["#rust"]))
+      .for_each_message(&mut |nick, message| messages.push(format!("{}: {}", nick, message)))
+      .unwrap();
+    assert_eq!(messages, vec!["alice: in #rust"]);
+  }
+
+  #[test]
+  fn test_renames() {
+    let content = "2020-01-31 10:00:00\tbob\thello\n\
+                   2020-01-31 10:00:01\t--\tbob is now known as bob_\n\
+                   2020-01-31 10:00:02\tbob_\tstill me\n\
+                   2020-01-31 10:00:03\t--\tbob_ is now known as bob|afk\n\
+                   2020-01-31 10:00:04\tbob|afk\tbrb\n\
+                   2020-01-31 10:00:05\tcarol\they\n\
+                   2020-01-31 10:00:06\tcarol_\tnot carol\n";
+
+    let lines = learned_lines(&mut WeechatLogTrainer::new("bob", content));
+    assert_eq!(lines, vec!["hello", "still me", "brb"]);
+
+    let mut messages = Vec::new();
+    WeechatLogTrainer::new("", content)
+      .for_each_message(&mut |nick, message| messages.push(format!("{}: {}", nick, message)))
+      .unwrap();
+    assert_eq!(
+      messages,
+      vec![
+        "bob: hello",
+        "bob: still me",
+        "bob: brb",
+        "carol: hey",
+        "carol_: not carol"
+      ]
+    );
+
+    let lines =
+      learned_lines(&mut WeechatLogTrainer::new("carol", content).aliases(vec!["carol_"]));
+    assert_eq!(lines, vec!["hey", "not carol"]);
+
+    // a nick left by the author is no longer theirs
+    let content = "2020-01-31 10:00:00\tbob\thello\n\
+                   2020-01-31 10:00:01\t--\tbob is now known as bob_\n\
+                   2020-01-31 10:00:02\t--\tdave is now known as bob\n\
+                   2020-01-31 10:00:03\tbob\tnot bob\n\
+                   2020-01-31 10:00:04\tbob_\tstill me\n";
+
+    let lines = learned_lines(&mut WeechatLogTrainer::new("bob", content));
+    assert_eq!(lines, vec!["hello", "still me"]);
 
-    Ok(())
+    let mut messages = Vec::new();
+    WeechatLogTrainer::new("", content)
+      .for_each_message(&mut |nick, message| messages.push(format!("{}: {}", nick, message)))
+      .unwrap();
+    assert_eq!(
+      messages,
+      vec!["bob: hello", "dave: not bob", "bob: still me"]
+    );
   }
 }
@@ -0,0 +1,127 @@
+//! A [`Trainer`] that can learn from a ZNC log (`log` module).
+//!
+//! ZNC writes one file per channel and day, and only stores the time of day in the lines
+//! themselves:
+//!
+//! ```ignore
+//! [12:34:56] <nick> message
+//! [12:34:57] *** Joins: nick (ident@host)
+//! [12:34:58] * nick does something
+//! ```
+//!
+//! The date can be recovered from the name of the file, such as `#channel_20200131.log` or
+//! `2020-01-31.log`.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::io::{BufReader, Read};
+use std::mem;
+
+use crate::trainers::lines::Lines;
+use crate::{Error, Trainer};
+
+lazy_static! {
+  static ref REGEX_LINE: Regex = Regex::new(r"^\[\d{2}:\d{2}(:\d{2})?\]\s+(.*)").unwrap();
+  static ref REGEX_MESSAGE: Regex = Regex::new(r"^<([^>\s]+)>\s?(.*)").unwrap();
+  static ref REGEX_FILE_DATE: Regex = Regex::new(r"(\d{4})-?(\d{2})-?(\d{2})\.log$").unwrap();
+}
+
+/// The content of a ZNC log.
+pub struct ZncLogTrainer {
+  content: Lines,
+  /// The author we are interested in
+  author: String,
+  /// Date of the log, if known.
+  date: Option<String>,
+}
+
+impl ZncLogTrainer {
+  /// Create a trainer from a log, either loaded as a string or streamed with
+  /// [`Lines::from_reader`].
+  pub fn new(author: impl Into<String>, content: impl Into<Lines>) -> Self {
+    let content = content.into();
+    let author = author.into();
+
+    Self {
+      content,
+      author,
+      date: None,
+    }
+  }
+
+  /// Stream a log from a reader, such as the standard input.
+  pub fn from_reader(
+    author: impl Into<String>,
+    reader: impl Read + 'static,
+  ) -> Result<Self, Error> {
+    let content = Lines::from_reader(BufReader::new(reader));
+
+    Ok(Self::new(author, content))
+  }
+
+  /// Set the date of the log from the name of its file.
+  ///
+  /// If the file name doesn’t contain a date, the date is left unknown.
+  pub fn file_name(mut self, file_name: impl AsRef<str>) -> Self {
+    self.date = REGEX_FILE_DATE
+      .captures(file_name.as_ref())
+      .map(|captures| format!("{}-{}-{}", &captures[1], &captures[2], &captures[3]));
+    self
+  }
+
+  /// Date of the log (`YYYY-MM-DD`), if known.
+  pub fn date(&self) -> Option<&str> {
+    self.date.as_deref()
+  }
+
+  /// Remove the timestamp and nickname of a line, leaving only the content of the message.
+  ///
+  /// Returns [`None`] for noise (`***` joins, quits, mode changes, actions, notices) and messages
+  /// from other people.
+  fn cleanup<'a>(&self, line: &'a str) -> Option<&'a str> {
+    let captures = REGEX_LINE.captures(line)?;
+    let captures = REGEX_MESSAGE.captures(captures.get(2)?.as_str())?;
+
+    if captures.get(1)?.as_str() == self.author {
+      Some(captures.get(2)?.as_str().trim()).filter(|content| !content.is_empty())
+    } else {
+      None
+    }
+  }
+}
+
+impl Trainer for ZncLogTrainer {
+  fn for_each_line(&mut self, sink: &mut dyn FnMut(&str)) -> Result<(), Error> {
+    let mut lines = mem::take(&mut self.content);
+
+    let result = lines.for_each(|line| {
+      if let Some(content) = self.cleanup(line.trim_end_matches('\r')) {
+        sink(content);
+      }
+    });
+
+    self.content = lines;
+    result
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::trainers::learned_lines;
+
+  #[test]
+  fn test_cleanup() {
+    let content = "[10:00:00] *** Joins: alice (~alice@host)\r\n\
+                   [10:00:01] <alice> hello everyone\r\n\
+                   [10:00:02] <bob> hi alice\r\n\
+                   [10:00:03] * alice waves\r\n\
+                   [10:00:04] <alice_> not me\r\n\
+                   [10:00:05] <alice> how are you?\r\n";
+    let mut trainer = ZncLogTrainer::new("alice", content).file_name("#rust_20200131.log");
+    let lines = learned_lines(&mut trainer);
+
+    assert_eq!(lines, vec!["hello everyone", "how are you?"]);
+    assert_eq!(trainer.date(), Some("2020-01-31"));
+  }
+}
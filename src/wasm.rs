@@ -0,0 +1,134 @@
+//! JavaScript bindings, to train and generate fully client-side in web pages.
+//!
+//! With the `wasm` feature, the library compiles to `wasm32-unknown-unknown` as a `cdylib`, and
+//! the output can be processed by `wasm-bindgen`:
+//!
+//! ```text
+//! cargo rustc --lib --release --no-default-features --features wasm \
+//!   --target wasm32-unknown-unknown --crate-type cdylib
+//! wasm-bindgen --target web --out-dir pkg target/wasm32-unknown-unknown/release/impersonate.wasm
+//! ```
+//!
+//! ```js
+//! import init, { Generator } from "./pkg/impersonate.js";
+//!
+//! await init();
+//! const generator = new Generator(2);
+//! generator.train("hello, world!");
+//! console.log(generator.generate());
+//! ```
+//!
+//! Browsers have no thread-local random generator; a [`Generator`] owns a seedable one instead,
+//! seeded from the `crypto` API unless a seed is given.
+
+use rand::rngs::StdRng;
+use rand::SeedableRng as _;
+use wasm_bindgen::prelude::*;
+
+use crate::{ChainParameters, LearningParameters, MarkovChainGenerator};
+
+/// A generator, along with the parameters it learns with and its random generator.
+#[wasm_bindgen]
+pub struct Generator {
+  generator: MarkovChainGenerator,
+  learn_param: LearningParameters,
+  rng: StdRng,
+}
+
+#[wasm_bindgen]
+impl Generator {
+  /// Create an empty generator learning wordings of `wording_size` tokens.
+  #[wasm_bindgen(constructor)]
+  pub fn new(wording_size: usize) -> Result<Generator, JsError> {
+    Ok(Self {
+      generator: MarkovChainGenerator::new(),
+      learn_param: LearningParameters::new(wording_size)?,
+      rng: StdRng::from_entropy(),
+    })
+  }
+
+  /// Seed the random generator, for reproducible outputs.
+  pub fn seed(&mut self, seed: u64) {
+    self.rng = StdRng::seed_from_u64(seed);
+  }
+
+  /// Train the generator on a line.
+  pub fn train(&mut self, line: &str) {
+    self.generator.train(&self.learn_param, line);
+  }
+
+  /// Train the generator on every line of a text.
+  pub fn train_text(&mut self, text: &str) {
+    for line in text.lines() {
+      self.generator.train(&self.learn_param, line);
+    }
+  }
+
+  /// Generate a random string, going through `max_wordings` wordings at maximum.
+  pub fn generate(&mut self, max_wordings: Option<usize>) -> Result<String, JsError> {
+    let mut chain_param = ChainParameters::new();
+    chain_param.max_state_traversal = max_wordings;
+
+    let chain = self
+      .generator
+      .generate_chain_with_rng(&chain_param, &mut self.rng)?;
+    Ok(chain.to_string())
+  }
+
+  /// Generate a random string starting from a state matching `prompt`.
+  pub fn generate_from(&mut self, prompt: &str) -> Result<String, JsError> {
+    let chain_param = ChainParameters::new().prompt(prompt);
+    let chain = self
+      .generator
+      .generate_chain_with_rng(&chain_param, &mut self.rng)?;
+    Ok(chain.to_string())
+  }
+}
+
+#[cfg(feature = "persistence")]
+#[wasm_bindgen]
+impl Generator {
+  /// Load a generator saved with [`MarkovChainGenerator::save`], such as a model trained by the
+  /// command line.
+  pub fn load(json: &str) -> Result<Generator, JsError> {
+    let generator = MarkovChainGenerator::load(json.as_bytes())?;
+    let learn_param = match generator.learning_parameters() {
+      Some(learn_param) => learn_param.clone(),
+      None => LearningParameters::new(1)?,
+    };
+
+    Ok(Self {
+      generator,
+      learn_param,
+      rng: StdRng::from_entropy(),
+    })
+  }
+
+  /// Save the generator, to be loaded back with [`Generator::load`].
+  pub fn save(&self) -> Result<String, JsError> {
+    let mut json = Vec::new();
+    self.generator.save(&mut json)?;
+    Ok(String::from_utf8(json)?)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_wasm_generator() {
+    // errors are JavaScript objects, which only exist in a JavaScript runtime
+    let mut generator = Generator::new(1).ok().unwrap();
+    generator.train_text("hello world\nhello you");
+    generator.seed(0);
+    let first = generator.generate(None).ok().unwrap();
+    generator.seed(0);
+    assert_eq!(generator.generate(None).ok().unwrap(), first);
+    assert!(generator
+      .generate_from("world")
+      .ok()
+      .unwrap()
+      .starts_with("world"));
+  }
+}